@@ -0,0 +1,67 @@
+//! Compares building the project archive with and without zstd compression.
+//!
+//! The repository is generated at 100 MB by default; set `LITTERBOX_BENCH_MB` to change it.
+
+use std::fs;
+use std::path::Path;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use git2::{IndexAddOption, Repository, Signature};
+use litterbox::scm::{GitScm, Scm};
+
+const DEFAULT_REPO_MB: usize = 100;
+const FILE_BYTES: usize = 256 * 1024;
+
+/// Writes `total_bytes` of source-like files to a fresh repository and commits them.
+fn generate_repo(root: &Path, total_bytes: usize) {
+    let repo = Repository::init(root).expect("init repo");
+    for file in 0..total_bytes.div_ceil(FILE_BYTES) {
+        let dir = root.join(format!("module_{}", file % 32));
+        fs::create_dir_all(&dir).expect("create dir");
+        let mut content = String::with_capacity(FILE_BYTES);
+        let mut state = file as u64 + 1;
+        while content.len() < FILE_BYTES {
+            // A cheap LCG keeps the content only partly repetitive, like real source.
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let name = state >> 48;
+            content.push_str(&format!("pub fn item_{file}_{name}() -> u64 {{ {state} }}\n"));
+        }
+        fs::write(dir.join(format!("file_{file}.rs")), content).expect("write file");
+    }
+
+    let mut index = repo.index().expect("index");
+    index
+        .add_all(["*"].iter(), IndexAddOption::DEFAULT, None)
+        .expect("add files");
+    index.write().expect("write index");
+    let tree = repo.find_tree(index.write_tree().expect("write tree")).expect("tree");
+    let signature = Signature::now("bench", "bench@example.com").expect("signature");
+    repo.commit(Some("HEAD"), &signature, &signature, "bench", &tree, &[])
+        .expect("commit");
+}
+
+fn make_archive(c: &mut Criterion) {
+    let repo_mb = std::env::var("LITTERBOX_BENCH_MB")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_REPO_MB);
+    let total_bytes = repo_mb * 1024 * 1024;
+    let tempdir = tempfile::tempdir().expect("tempdir");
+    generate_repo(tempdir.path(), total_bytes);
+    let scm = GitScm::open(tempdir.path()).expect("open scm");
+
+    let mut group = c.benchmark_group(format!("make_archive_{repo_mb}mb"));
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(total_bytes as u64));
+    for (name, compressed) in [("uncompressed", false), ("zstd", true)] {
+        let size = scm.make_archive("HEAD", compressed).expect("archive").len();
+        eprintln!("{name}: {} MB archive", size / (1024 * 1024));
+        group.bench_function(name, |b| {
+            b.iter(|| scm.make_archive("HEAD", compressed).expect("archive"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, make_archive);
+criterion_main!(benches);
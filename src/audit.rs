@@ -0,0 +1,243 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::config::AuditConfig;
+
+/// Longest string argument, in bytes, copied verbatim into an audit record.
+const MAX_AUDIT_STRING_BYTES: usize = 120;
+const REDACTED: &str = "<redacted>";
+
+/// Append-only log of MCP tool calls, one JSON object per line.
+pub struct AuditLog {
+    sink: Mutex<AuditSink>,
+}
+
+enum AuditSink {
+    Stderr,
+    File(File),
+}
+
+#[derive(Debug, Serialize)]
+struct AuditRecord<'a> {
+    timestamp: u64,
+    tool: &'a str,
+    sandbox: Option<&'a str>,
+    args_summary: Map<String, Value>,
+}
+
+impl AuditLog {
+    pub fn stderr() -> Self {
+        Self {
+            sink: Mutex::new(AuditSink::Stderr),
+        }
+    }
+
+    /// Opens `path` for appending, creating it if needed.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            sink: Mutex::new(AuditSink::File(file)),
+        })
+    }
+
+    /// The audit log selected by `--audit-log` or, failing that, the `audit` config section.
+    ///
+    /// Returns `None` when auditing is off.
+    pub fn from_settings(
+        config: &AuditConfig,
+        path_override: Option<&Path>,
+    ) -> io::Result<Option<Self>> {
+        if let Some(path) = path_override {
+            return Self::open(path).map(Some);
+        }
+        if !config.enabled.unwrap_or(false) {
+            return Ok(None);
+        }
+        match config.path.as_deref() {
+            Some(path) => Self::open(path).map(Some),
+            None => Ok(Some(Self::stderr())),
+        }
+    }
+
+    /// Writes one record for a call to `tool` with the given JSON arguments.
+    ///
+    /// Failures to write are reported on stderr rather than failing the tool call.
+    pub fn record(&self, tool: &str, arguments: Option<&Map<String, Value>>) {
+        let line = audit_line(tool, arguments, unix_now());
+        let Ok(mut sink) = self.sink.lock() else {
+            return;
+        };
+        let result = match &mut *sink {
+            AuditSink::Stderr => writeln!(io::stderr(), "{line}"),
+            AuditSink::File(file) => writeln!(file, "{line}"),
+        };
+        if let Err(error) = result {
+            eprintln!("audit log write failed: {error}");
+        }
+    }
+}
+
+fn audit_line(tool: &str, arguments: Option<&Map<String, Value>>, timestamp: u64) -> String {
+    let sandbox = arguments
+        .and_then(|arguments| arguments.get("sandbox").or_else(|| arguments.get("name")))
+        .and_then(Value::as_str);
+    let args_summary = arguments
+        .map(|arguments| {
+            arguments
+                .iter()
+                .map(|(key, value)| {
+                    let value = match key.as_str() {
+                        "env" => redact_env(value),
+                        _ => summarize_value(value),
+                    };
+                    (key.clone(), value)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let record = AuditRecord {
+        timestamp,
+        tool,
+        sandbox,
+        args_summary,
+    };
+    serde_json::to_string(&record).unwrap_or_default()
+}
+
+/// Shortens long strings, such as file contents passed to `write`, so records stay one line
+/// of readable size.
+fn summarize_value(value: &Value) -> Value {
+    match value {
+        Value::String(text) if text.len() > MAX_AUDIT_STRING_BYTES => {
+            let mut end = MAX_AUDIT_STRING_BYTES;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            Value::String(format!("{}... ({} bytes)", &text[..end], text.len()))
+        }
+        Value::Array(items) => Value::Array(items.iter().map(summarize_value).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), summarize_value(value)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Keeps the names of environment variables passed to `bash` but hides their values, which
+/// are often credentials.
+fn redact_env(value: &Value) -> Value {
+    let redacted = || Value::String(REDACTED.to_string());
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .keys()
+                .map(|key| (key.clone(), redacted()))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| match item.as_str().and_then(|entry| entry.split_once('=')) {
+                    Some((key, _)) => Value::String(format!("{key}={REDACTED}")),
+                    None => redacted(),
+                })
+                .collect(),
+        ),
+        _ => redacted(),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn audit_line_names_sandbox_and_truncates_long_strings() {
+        let arguments = json!({
+            "sandbox": "feature",
+            "path": "src/main.rs",
+            "content": "é".repeat(100),
+        });
+        let line = audit_line("write", arguments.as_object(), 42);
+        let record: Value = serde_json::from_str(&line).expect("json");
+
+        assert_eq!(record["timestamp"], 42);
+        assert_eq!(record["tool"], "write");
+        assert_eq!(record["sandbox"], "feature");
+        assert_eq!(record["args_summary"]["path"], "src/main.rs");
+        let content = record["args_summary"]["content"].as_str().expect("content");
+        assert!(content.ends_with("... (200 bytes)"));
+        assert!(content.len() < 200);
+    }
+
+    #[test]
+    fn audit_line_redacts_bash_env_values() {
+        let arguments = json!({
+            "sandbox": "feature",
+            "command": "deploy",
+            "env": {"API_TOKEN": "hunter2"},
+        });
+        let line = audit_line("bash", arguments.as_object(), 1);
+        let record: Value = serde_json::from_str(&line).expect("json");
+
+        assert_eq!(record["args_summary"]["env"], json!({"API_TOKEN": "<redacted>"}));
+        assert!(!line.contains("hunter2"));
+
+        let arguments = json!({"env": ["API_TOKEN=hunter2"]});
+        let line = audit_line("bash", arguments.as_object(), 1);
+        let record: Value = serde_json::from_str(&line).expect("json");
+
+        assert_eq!(record["args_summary"]["env"], json!(["API_TOKEN=<redacted>"]));
+    }
+
+    #[test]
+    fn audit_line_without_arguments_has_no_sandbox() {
+        let line = audit_line("sandbox-list", None, 1);
+        let record: Value = serde_json::from_str(&line).expect("json");
+
+        assert_eq!(record["sandbox"], Value::Null);
+        assert_eq!(record["args_summary"], json!({}));
+    }
+
+    #[test]
+    fn disabled_audit_config_logs_nothing() {
+        let log = AuditLog::from_settings(&AuditConfig::default(), None).expect("settings");
+
+        assert!(log.is_none());
+    }
+
+    #[test]
+    fn audit_log_appends_to_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("audit.log");
+        let config = AuditConfig {
+            enabled: Some(true),
+            path: Some(path.clone()),
+        };
+        let log = AuditLog::from_settings(&config, None).expect("settings").expect("log");
+
+        log.record("sandbox-list", None);
+        log.record("sandbox-list", None);
+
+        let contents = std::fs::read_to_string(&path).expect("read");
+        assert_eq!(contents.lines().count(), 2);
+    }
+}
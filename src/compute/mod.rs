@@ -1,17 +1,31 @@
 use std::collections::HashMap;
+use std::env;
 use std::fs;
-use std::io::Cursor;
-use std::path::Path;
+use std::io::{Cursor, Write};
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
 use bollard::container::LogOutput;
 use bollard::exec::{CreateExecOptions, StartExecOptions, StartExecResults};
-use bollard::models::{ContainerCreateBody, HostConfig, PortBinding};
+use bollard::models::{
+    ContainerCpuStats, ContainerCreateBody, ContainerStateStatusEnum, ContainerStatsResponse,
+    ContainerSummary, ContainerSummaryStateEnum, CreateImageInfo, EndpointSettings, HostConfig,
+    NetworkConnectRequest, NetworkCreateRequest, PortBinding, RestartPolicyNameEnum,
+};
 use bollard::query_parameters::{
+    BuildImageOptionsBuilder,
+    CommitContainerOptionsBuilder,
     CreateContainerOptionsBuilder,
     CreateImageOptions,
     DownloadFromContainerOptionsBuilder,
+    KillContainerOptionsBuilder,
+    ListContainersOptionsBuilder,
+    LogsOptionsBuilder,
     RemoveContainerOptions,
+    RenameContainerOptionsBuilder,
+    StatsOptionsBuilder,
     UploadToContainerOptionsBuilder,
 };
 use bollard::body_full;
@@ -20,12 +34,29 @@ use tar::{Archive, Builder};
 use bollard::errors::Error as BollardError;
 use bollard::{Docker, API_DEFAULT_VERSION};
 use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
 use futures_util::StreamExt;
+use tokio::io::AsyncWriteExt;
+use tokio::time::sleep;
+
+use crate::config::ComputeBackend;
+use crate::domain::{ComputeError, ExecutionResult, RestartPolicy, SandboxError, SandboxStatus};
 
-use crate::domain::{ComputeError, ExecutionResult, SandboxError};
+#[cfg(unix)]
+mod podman;
+
+#[cfg(unix)]
+pub use podman::{PodmanCompute, podman_socket_path};
 
 pub trait Compute {
     fn ensure_image<'a>(&'a self, image: &'a str) -> BoxFuture<'a, Result<(), SandboxError>>;
+    /// Build the Dockerfile in `context_path` and tag the result as `tag`.
+    fn build_image<'a>(
+        &'a self,
+        context_path: &'a Path,
+        tag: &'a str,
+        build_args: &'a HashMap<String, String>,
+    ) -> BoxFuture<'a, Result<(), SandboxError>>;
     fn create_container<'a>(
         &'a self,
         spec: &'a ContainerSpec,
@@ -34,14 +65,41 @@ pub trait Compute {
         &'a self,
         container_id: &'a str,
     ) -> BoxFuture<'a, Result<ContainerInspection, SandboxError>>;
+    /// Sample the container's current CPU and memory usage.
+    fn container_stats<'a>(
+        &'a self,
+        container_id: &'a str,
+    ) -> BoxFuture<'a, Result<ContainerStats, SandboxError>>;
+    /// Save the container's filesystem as the image `image:tag`.
+    fn commit_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        image: &'a str,
+        tag: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>>;
     fn pause_container<'a>(&'a self, container_id: &'a str) -> BoxFuture<'a, Result<(), SandboxError>>;
     fn resume_container<'a>(&'a self, container_id: &'a str) -> BoxFuture<'a, Result<(), SandboxError>>;
     fn delete_container<'a>(&'a self, container_id: &'a str) -> BoxFuture<'a, Result<(), SandboxError>>;
+    /// Send `signal` (e.g. `SIGKILL`) to the container's main process.
+    fn kill_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        signal: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>>;
+    /// Run `command` in the container, retrying exec creation up to `retries` times (default
+    /// none) when the daemon reports a transient 500/503 error.
+    ///
+    /// Starting the exec is never retried, since the command may already have run.
+    ///
+    /// A `working_dir` missing from the container is dropped, with a warning prepended to
+    /// the result's stderr.
     fn exec<'a>(
         &'a self,
         container_id: &'a str,
         command: &'a [String],
         working_dir: Option<&'a str>,
+        options: Option<&'a ExecOptions>,
+        retries: Option<u32>,
     ) -> BoxFuture<'a, Result<ExecutionResult, SandboxError>>;
     /// Copy a host path into the container at `dest_path`.
     fn upload_path<'a>(
@@ -57,6 +115,17 @@ pub trait Compute {
         src_path: &'a str,
         dest_path: &'a Path,
     ) -> BoxFuture<'a, Result<(), SandboxError>>;
+    /// Return the id of the network `name`, creating a bridge network if there is none.
+    fn network_create<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<String, SandboxError>>;
+    /// Attach the container to a network, making it reachable there by its name and `aliases`.
+    ///
+    /// A container already on the network is left as it is.
+    fn network_connect<'a>(
+        &'a self,
+        network_id: &'a str,
+        container_id: &'a str,
+        aliases: &'a [String],
+    ) -> BoxFuture<'a, Result<(), SandboxError>>;
 }
 
 #[derive(Clone, Debug)]
@@ -67,6 +136,19 @@ pub struct ContainerSpec {
     pub working_dir: Option<String>,
     pub env: Vec<String>,
     pub port_bindings: HashMap<String, Vec<PortBinding>>,
+    pub labels: HashMap<String, String>,
+    pub restart_policy: Option<RestartPolicy>,
+}
+
+/// Per-exec overrides for the process identity and environment.
+#[derive(Clone, Debug, Default)]
+pub struct ExecOptions {
+    /// User to run as, in any form `docker exec --user` accepts.
+    pub user: Option<String>,
+    /// Extra `KEY=VALUE` environment variables.
+    pub env: Option<Vec<String>>,
+    /// Bytes fed to the process's standard input, which is closed once they are written.
+    pub stdin: Option<Vec<u8>>,
 }
 
 #[derive(Clone, Debug)]
@@ -79,15 +161,116 @@ pub struct PortBindingSpec {
 pub struct ContainerInspection {
     pub env: Vec<String>,
     pub port_bindings: HashMap<String, Vec<PortBindingSpec>>,
+    pub ip_address: Option<String>,
+    /// Content-addressed id of the image the container runs, e.g. `sha256:...`.
+    pub image_digest: Option<String>,
+    /// RFC 3339 time the container was last started.
+    pub started_at: Option<String>,
+    pub status: SandboxStatus,
+    /// Extra names the container answers to on its networks, sorted.
+    pub network_aliases: Vec<String>,
+    /// Directory commands run in by default: the sandbox workdir it was created with.
+    pub working_dir: Option<String>,
+}
+
+/// A container created by litterbox, as reported by the engine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LitterboxContainer {
+    pub container_id: String,
+    pub slug: String,
+    pub repo_prefix: String,
+    pub status: SandboxStatus,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerStats {
+    pub cpu_percent: f64,
+    pub memory_used_bytes: u64,
+    pub memory_limit_bytes: u64,
+}
+
+pub const DEFAULT_PULL_RETRIES: usize = 3;
+const PULL_BACKOFF_BASE_MS: u64 = 500;
+const MAX_EXEC_ATTEMPTS: u32 = 3;
+
+/// Label holding the repository prefix of the sandbox a container belongs to.
+pub const LABEL_REPO_PREFIX: &str = "com.litterbox.repo_prefix";
+/// Label holding the sandbox slug.
+pub const LABEL_SLUG: &str = "com.litterbox.slug";
+/// Label holding the git branch backing the sandbox.
+pub const LABEL_BRANCH: &str = "com.litterbox.branch";
+const CONTAINER_NAME_PREFIX: &str = "litterbox-";
+const EXEC_RETRY_BACKOFF_MS: u64 = 200;
+const UPLOAD_ZSTD_LEVEL: i32 = 3;
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+static GLOBAL_COMPUTE: OnceLock<DockerCompute> = OnceLock::new();
+
+/// Events emitted while pulling an image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PullEvent {
+    /// A pull attempt failed with a transient error and is about to be retried.
+    PullRetry { attempt: usize, error: String },
+}
+
+pub type PullProgressCallback = Box<dyn Fn(PullEvent) + Send + Sync>;
+
+/// A progress update for a single layer of an image pull.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PullProgress {
+    pub layer_id: String,
+    pub status: String,
+    pub progress_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+}
+
+impl From<CreateImageInfo> for PullProgress {
+    fn from(info: CreateImageInfo) -> Self {
+        let detail = info.progress_detail;
+        Self {
+            layer_id: info.id.unwrap_or_default(),
+            status: info.status.unwrap_or_default(),
+            progress_bytes: detail
+                .as_ref()
+                .and_then(|detail| detail.current)
+                .and_then(|value| u64::try_from(value).ok()),
+            total_bytes: detail
+                .as_ref()
+                .and_then(|detail| detail.total)
+                .and_then(|value| u64::try_from(value).ok()),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct DockerCompute {
     client: Docker,
+    pull_retries: usize,
+    compress_uploads: bool,
 }
 
 impl DockerCompute {
     pub fn new(client: Docker) -> Self {
-        Self { client }
+        Self {
+            client,
+            pull_retries: DEFAULT_PULL_RETRIES,
+            compress_uploads: false,
+        }
+    }
+
+    /// Sets how many times a failed image pull is retried on transient errors.
+    pub fn with_pull_retries(mut self, pull_retries: usize) -> Self {
+        self.pull_retries = pull_retries;
+        self
+    }
+
+    /// Sets whether tars uploaded to containers are zstd-compressed.
+    ///
+    /// The engine detects compression from the stream itself, so this needs a
+    /// daemon that understands zstd archives.
+    pub fn with_compressed_uploads(mut self, compress_uploads: bool) -> Self {
+        self.compress_uploads = compress_uploads;
+        self
     }
 
     pub fn client(&self) -> &Docker {
@@ -96,7 +279,37 @@ impl DockerCompute {
 
     pub fn connect() -> Result<Self, SandboxError> {
         let client = connect_docker_client()?;
-        Ok(Self { client })
+        Ok(Self::new(client))
+    }
+
+    /// Returns a process-wide Docker connection, connecting on first use.
+    ///
+    /// `Docker` clients share their connection pool across clones, so cloning
+    /// the result avoids a fresh socket handshake per call.
+    pub fn global() -> Result<&'static Self, SandboxError> {
+        if let Some(compute) = GLOBAL_COMPUTE.get() {
+            return Ok(compute);
+        }
+        let compute = Self::connect()?;
+        Ok(GLOBAL_COMPUTE.get_or_init(|| compute))
+    }
+
+    /// Connects to the container engine selected by `backend`.
+    pub fn connect_backend(backend: ComputeBackend) -> Result<Self, SandboxError> {
+        match backend {
+            ComputeBackend::Docker => Self::connect(),
+            #[cfg(unix)]
+            ComputeBackend::Podman => PodmanCompute::connect_podman().map(PodmanCompute::into_inner),
+            #[cfg(not(unix))]
+            ComputeBackend::Podman => Err(SandboxError::Compute(ComputeError::Connection {
+                source: BollardError::IOError {
+                    err: std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "podman is not supported on this platform",
+                    ),
+                },
+            })),
+        }
     }
 
     fn connect_with_defaults() -> Result<Docker, SandboxError> {
@@ -113,6 +326,60 @@ impl DockerCompute {
     }
 
     async fn pull_image(&self, image: &str) -> Result<(), SandboxError> {
+        self.pull_image_with_callback(image, &retry_warning()).await
+    }
+
+    /// Pull `image`, reporting per-layer progress to `callback` as the pull proceeds.
+    pub async fn pull_with_progress(
+        &self,
+        image: &str,
+        callback: Box<dyn Fn(PullProgress) + Send>,
+    ) -> Result<(), SandboxError> {
+        self.pull_with_retries(image, &retry_warning(), callback.as_ref())
+            .await
+    }
+
+    /// Pull `image`, retrying transient failures with exponential backoff.
+    pub async fn pull_image_with_callback(
+        &self,
+        image: &str,
+        callback: &PullProgressCallback,
+    ) -> Result<(), SandboxError> {
+        self.pull_with_retries(image, callback, &|_| {}).await
+    }
+
+    async fn pull_with_retries<P>(
+        &self,
+        image: &str,
+        callback: &PullProgressCallback,
+        progress: &P,
+    ) -> Result<(), SandboxError>
+    where
+        P: Fn(PullProgress) + ?Sized,
+    {
+        let mut attempt = 0;
+        loop {
+            match self.pull_image_once(image, progress).await {
+                Ok(()) => return Ok(()),
+                Err(error) if attempt < self.pull_retries && is_transient_pull_error(&error) => {
+                    callback(PullEvent::PullRetry {
+                        attempt: attempt + 1,
+                        error: error.to_string(),
+                    });
+                    sleep(pull_backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(source) => {
+                    return Err(SandboxError::Compute(ComputeError::ImagePull { source }));
+                }
+            }
+        }
+    }
+
+    async fn pull_image_once<P>(&self, image: &str, progress: &P) -> Result<(), BollardError>
+    where
+        P: Fn(PullProgress) + ?Sized,
+    {
         let options = Some(CreateImageOptions {
             from_image: Some(image.to_string()),
             ..Default::default()
@@ -120,12 +387,50 @@ impl DockerCompute {
         let mut stream = self.client.create_image(options, None, None);
 
         while let Some(item) = stream.next().await {
-            item.map_err(|source| SandboxError::Compute(ComputeError::ImagePull { source }))?;
+            progress(PullProgress::from(item?));
         }
 
         Ok(())
     }
 
+    /// Build an image from the Dockerfile in `context_path`, returning the build log.
+    pub async fn build_image(
+        &self,
+        context_path: &Path,
+        tag: &str,
+        build_args: &HashMap<String, String>,
+    ) -> Result<Vec<String>, SandboxError> {
+        let context = build_tar(context_path, false)?;
+        let options = BuildImageOptionsBuilder::default()
+            .dockerfile("Dockerfile")
+            .t(tag)
+            .buildargs(build_args)
+            .rm(true)
+            .build();
+        let mut stream = self
+            .client
+            .build_image(options, None, Some(body_full(Bytes::from(context))));
+
+        let mut log = Vec::new();
+        while let Some(item) = stream.next().await {
+            let info =
+                item.map_err(|source| SandboxError::Compute(ComputeError::ImageBuild { source }))?;
+            if let Some(detail) = info.error_detail.and_then(|detail| detail.message) {
+                log.push(detail);
+            }
+            if let Some(line) = info.stream.or(info.status) {
+                log.push(line.trim_end().to_string());
+            }
+        }
+
+        if let Some(line) = build_log_error(&log) {
+            return Err(SandboxError::Compute(ComputeError::ImageBuildOutput {
+                message: line.to_string(),
+            }));
+        }
+        Ok(log)
+    }
+
     pub async fn create_container(&self, spec: &ContainerSpec) -> Result<String, SandboxError> {
         let options = Some(
             CreateContainerOptionsBuilder::default()
@@ -156,8 +461,14 @@ impl DockerCompute {
             },
             working_dir: spec.working_dir.clone(),
             env,
+            labels: if spec.labels.is_empty() {
+                None
+            } else {
+                Some(spec.labels.clone())
+            },
             host_config: Some(HostConfig {
                 port_bindings,
+                restart_policy: spec.restart_policy.map(docker_restart_policy),
                 ..Default::default()
             }),
             ..Default::default()
@@ -185,11 +496,26 @@ impl DockerCompute {
             .client
             .inspect_container(container_id, None)
             .await
-            .map_err(|source| SandboxError::Compute(ComputeError::ContainerInspect { source }))?;
-        let env = inspect
-            .config
-            .and_then(|config| config.env)
+            .map_err(|source| {
+                container_error(container_id, source, |source| {
+                    ComputeError::ContainerInspect { source }
+                })
+            })?;
+        let config = inspect.config.unwrap_or_default();
+        let env = config.env.unwrap_or_default();
+        let working_dir = config.working_dir.filter(|working_dir| !working_dir.is_empty());
+        let image_digest = inspect.image;
+        let state = inspect.state.unwrap_or_default();
+        let status = sandbox_status_from_state(state.status);
+        let started_at = state
+            .started_at
+            .filter(|started_at| !started_at.starts_with("0001-"));
+        let networks = inspect
+            .network_settings
+            .and_then(|settings| settings.networks)
             .unwrap_or_default();
+        let ip_address = container_ip_address(&networks);
+        let network_aliases = container_network_aliases(&networks);
         let port_bindings = inspect
             .host_config
             .and_then(|config| config.port_bindings)
@@ -208,7 +534,16 @@ impl DockerCompute {
             })
             .collect();
 
-        Ok(ContainerInspection { env, port_bindings })
+        Ok(ContainerInspection {
+            env,
+            port_bindings,
+            ip_address,
+            image_digest,
+            started_at,
+            status,
+            network_aliases,
+            working_dir,
+        })
     }
 
     pub async fn pause_container(&self, container_id: &str) -> Result<(), SandboxError> {
@@ -217,7 +552,9 @@ impl DockerCompute {
             Err(bollard::errors::Error::DockerResponseServerError { status_code: 409, .. }) => {
                 Ok(())
             }
-            Err(source) => Err(SandboxError::Compute(ComputeError::ContainerPause { source })),
+            Err(source) => Err(container_error(container_id, source, |source| {
+                ComputeError::ContainerPause { source }
+            })),
         }
     }
 
@@ -227,7 +564,9 @@ impl DockerCompute {
             Err(bollard::errors::Error::DockerResponseServerError { status_code: 409, .. }) => {
                 Ok(())
             }
-            Err(source) => Err(SandboxError::Compute(ComputeError::ContainerResume { source })),
+            Err(source) => Err(container_error(container_id, source, |source| {
+                ComputeError::ContainerResume { source }
+            })),
         }
     }
 
@@ -251,37 +590,212 @@ impl DockerCompute {
         }
     }
 
+    /// List litterbox containers, optionally limited to one repository prefix.
+    ///
+    /// Containers are identified by their labels, falling back to the
+    /// `litterbox-<prefix>-<slug>` name for containers created before labels
+    /// were added (only resolvable when `repo_prefix` is given).
+    pub async fn list_litterbox_containers(
+        &self,
+        repo_prefix: Option<&str>,
+    ) -> Result<Vec<LitterboxContainer>, SandboxError> {
+        let filters = HashMap::from([("name", vec![CONTAINER_NAME_PREFIX])]);
+        let options = Some(
+            ListContainersOptionsBuilder::default()
+                .all(true)
+                .filters(&filters)
+                .build(),
+        );
+        let containers = self
+            .client
+            .list_containers(options)
+            .await
+            .map_err(|source| SandboxError::Compute(ComputeError::ContainerList { source }))?;
+        Ok(containers
+            .into_iter()
+            .filter_map(|summary| litterbox_container(summary, repo_prefix))
+            .collect())
+    }
+
+    pub async fn container_stats(&self, container_id: &str) -> Result<ContainerStats, SandboxError> {
+        // Without one-shot, the daemon waits for a second sample so `precpu_stats` is populated.
+        let options = Some(StatsOptionsBuilder::default().stream(false).one_shot(false).build());
+        let sample = self
+            .client
+            .stats(container_id, options)
+            .next()
+            .await
+            .ok_or_else(|| SandboxError::Config("Docker returned no stats sample".to_string()))?
+            .map_err(|source| {
+                container_error(container_id, source, |source| {
+                    ComputeError::ContainerStats { source }
+                })
+            })?;
+        Ok(container_stats_from_sample(&sample))
+    }
+
+    pub async fn commit_container(
+        &self,
+        container_id: &str,
+        image: &str,
+        tag: &str,
+    ) -> Result<(), SandboxError> {
+        let options = CommitContainerOptionsBuilder::default()
+            .container(container_id)
+            .repo(image)
+            .tag(tag)
+            .pause(true)
+            .build();
+        self.client
+            .commit_container(options, bollard::models::ContainerConfig::default())
+            .await
+            .map_err(|source| {
+                container_error(container_id, source, |source| {
+                    ComputeError::ContainerCommit { source }
+                })
+            })?;
+        Ok(())
+    }
+
+    pub async fn kill_container(&self, container_id: &str, signal: &str) -> Result<(), SandboxError> {
+        let options = Some(KillContainerOptionsBuilder::default().signal(signal).build());
+        match self.client.kill_container(container_id, options).await {
+            Ok(()) => Ok(()),
+            Err(bollard::errors::Error::DockerResponseServerError { status_code: 409, .. }) => {
+                Ok(())
+            }
+            Err(source) => Err(container_error(container_id, source, |source| {
+                ComputeError::ContainerKill { source }
+            })),
+        }
+    }
+
+    pub async fn rename_container(
+        &self,
+        container_id: &str,
+        new_name: &str,
+    ) -> Result<(), SandboxError> {
+        let options = RenameContainerOptionsBuilder::default().name(new_name).build();
+        self.client
+            .rename_container(container_id, options)
+            .await
+            .map_err(|source| {
+                container_error(container_id, source, |source| {
+                    ComputeError::ContainerRename { source }
+                })
+            })
+    }
+
+    pub async fn network_create(&self, name: &str) -> Result<String, SandboxError> {
+        if let Some(id) = self.network_id(name).await? {
+            return Ok(id);
+        }
+        let request = NetworkCreateRequest {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        match self.client.create_network(request).await {
+            Ok(response) => Ok(response.id),
+            // Another sandbox created the network since it was looked up.
+            Err(source @ BollardError::DockerResponseServerError { status_code: 409, .. }) => {
+                self.network_id(name).await?.ok_or_else(|| {
+                    SandboxError::Compute(ComputeError::NetworkCreate { source })
+                })
+            }
+            Err(source) => Err(SandboxError::Compute(ComputeError::NetworkCreate { source })),
+        }
+    }
+
+    async fn network_id(&self, name: &str) -> Result<Option<String>, SandboxError> {
+        match self.client.inspect_network(name, None).await {
+            Ok(network) => Ok(network.id),
+            Err(BollardError::DockerResponseServerError { status_code: 404, .. }) => Ok(None),
+            Err(source) => Err(SandboxError::Compute(ComputeError::NetworkCreate { source })),
+        }
+    }
+
+    pub async fn network_connect(
+        &self,
+        network_id: &str,
+        container_id: &str,
+        aliases: &[String],
+    ) -> Result<(), SandboxError> {
+        let request = NetworkConnectRequest {
+            container: container_id.to_string(),
+            endpoint_config: (!aliases.is_empty()).then(|| EndpointSettings {
+                aliases: Some(aliases.to_vec()),
+                ..Default::default()
+            }),
+        };
+        match self.client.connect_network(network_id, request).await {
+            Ok(()) => Ok(()),
+            Err(error) if is_already_connected_error(&error) => Ok(()),
+            Err(source) => Err(SandboxError::Compute(ComputeError::NetworkConnect { source })),
+        }
+    }
+
     pub async fn exec(
         &self,
         container_id: &str,
         command: &[String],
         working_dir: Option<&str>,
+        options: Option<&ExecOptions>,
+        retries: Option<u32>,
     ) -> Result<ExecutionResult, SandboxError> {
-        let command_args: Vec<&str> = command.iter().map(String::as_str).collect();
-        let exec_options = CreateExecOptions {
-            attach_stdout: Some(true),
-            attach_stderr: Some(true),
-            cmd: Some(command_args),
-            working_dir,
-            ..Default::default()
+        let max_attempts = retries.unwrap_or(0).saturating_add(1).min(MAX_EXEC_ATTEMPTS);
+        let mut attempt = 1;
+        let mut working_dir = working_dir;
+        let mut warning = None;
+        let started = Instant::now();
+        let (exec_id, results) = loop {
+            let created = self.create_exec(container_id, command, working_dir, options).await;
+            let exec_id = match created {
+                Ok(exec_id) => exec_id,
+                Err(error) if attempt < max_attempts && is_transient_exec_error(&error) => {
+                    sleep(Duration::from_millis(u64::from(attempt) * EXEC_RETRY_BACKOFF_MS)).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(source) => {
+                    return Err(container_error(container_id, source, |source| {
+                        ComputeError::ContainerExec { source }
+                    }));
+                }
+            };
+            match self.client.start_exec(&exec_id, None::<StartExecOptions>).await {
+                Ok(results) => break (exec_id, results),
+                // The engine reports a missing working directory as an opaque 500 before the
+                // process starts, so the command is rerun from the container's default
+                // directory with a warning.
+                Err(error) if working_dir.is_some() && is_missing_workdir_error(&error) => {
+                    warning = Some(format!(
+                        "warning: working directory '{}' does not exist; ran in the container's \
+                         default directory instead\n",
+                        working_dir.unwrap_or_default()
+                    ));
+                    working_dir = None;
+                }
+                Err(source) => {
+                    return Err(container_error(container_id, source, |source| {
+                        ComputeError::ContainerExec { source }
+                    }));
+                }
+            }
         };
 
-        let exec = self
-            .client
-            .create_exec(container_id, exec_options)
-            .await
-            .map_err(|source| SandboxError::Compute(ComputeError::ContainerExec { source }))?;
-
         let mut stdout = Vec::new();
-        let mut stderr = Vec::new();
-
-        let results = self
-            .client
-            .start_exec(&exec.id, None::<StartExecOptions>)
-            .await
-            .map_err(|source| SandboxError::Compute(ComputeError::ContainerExec { source }))?;
+        let mut stderr = warning.map(String::into_bytes).unwrap_or_default();
 
-        if let StartExecResults::Attached { mut output, .. } = results {
+        if let StartExecResults::Attached { mut output, mut input } = results {
+            // Feed stdin concurrently so a process that writes while reading cannot stall on
+            // a full output pipe. Write errors are ignored: the process may exit without
+            // consuming all of its input.
+            let writer = options.and_then(|options| options.stdin.clone()).map(|stdin| {
+                tokio::spawn(async move {
+                    let _ = input.write_all(&stdin).await;
+                    let _ = input.shutdown().await;
+                })
+            });
             while let Some(item) = output.next().await {
                 match item.map_err(|source| SandboxError::Compute(ComputeError::ContainerExec { source }))? {
                     LogOutput::StdOut { message } | LogOutput::Console { message } => {
@@ -291,11 +805,15 @@ impl DockerCompute {
                     LogOutput::StdIn { .. } => {}
                 }
             }
+            if let Some(writer) = writer {
+                writer.abort();
+            }
         }
+        let duration_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
 
         let inspect = self
             .client
-            .inspect_exec(&exec.id)
+            .inspect_exec(&exec_id)
             .await
             .map_err(|source| SandboxError::Compute(ComputeError::ContainerExec { source }))?;
         let exit_code = inspect
@@ -308,16 +826,70 @@ impl DockerCompute {
             exit_code,
             stdout: String::from_utf8_lossy(&stdout).to_string(),
             stderr: String::from_utf8_lossy(&stderr).to_string(),
+            duration_ms: Some(duration_ms),
+            // Exec inspection carries only the process configuration, not resource usage.
+            max_rss_bytes: None,
         })
     }
 
+    async fn create_exec(
+        &self,
+        container_id: &str,
+        command: &[String],
+        working_dir: Option<&str>,
+        options: Option<&ExecOptions>,
+    ) -> Result<String, BollardError> {
+        let command_args: Vec<&str> = command.iter().map(String::as_str).collect();
+        let exec_options = CreateExecOptions {
+            attach_stdin: Some(options.is_some_and(|options| options.stdin.is_some())),
+            attach_stdout: Some(true),
+            attach_stderr: Some(true),
+            cmd: Some(command_args),
+            working_dir,
+            user: options.and_then(|options| options.user.as_deref()),
+            env: options
+                .and_then(|options| options.env.as_ref())
+                .map(|env| env.iter().map(String::as_str).collect()),
+            ..Default::default()
+        };
+
+        let exec = self.client.create_exec(container_id, exec_options).await?;
+        Ok(exec.id)
+    }
+
+    /// Stream a container's stdout and stderr, starting from the last `tail` lines.
+    ///
+    /// With `follow`, the stream stays open and yields output as the container produces it.
+    pub fn container_logs<'a>(
+        &'a self,
+        container_id: &'a str,
+        tail: usize,
+        follow: bool,
+    ) -> BoxStream<'a, Result<String, SandboxError>> {
+        let options = Some(
+            LogsOptionsBuilder::default()
+                .follow(follow)
+                .stdout(true)
+                .stderr(true)
+                .tail(&tail.to_string())
+                .build(),
+        );
+        self.client
+            .logs(container_id, options)
+            .map(|item| {
+                item.map(|output| String::from_utf8_lossy(&output.into_bytes()).to_string())
+                    .map_err(|source| SandboxError::Compute(ComputeError::ContainerLogs { source }))
+            })
+            .boxed()
+    }
+
     pub async fn upload_path(
         &self,
         container_id: &str,
         src_path: &Path,
         dest_path: &str,
     ) -> Result<(), SandboxError> {
-        let tar = build_tar(src_path)?;
+        let tar = build_tar(src_path, self.compress_uploads)?;
         self.upload_tar(container_id, dest_path, &tar).await
     }
 
@@ -343,6 +915,8 @@ impl DockerCompute {
                 .path(dest_path)
                 .build(),
         );
+        // bollard always sends `application/x-tar`; the daemon sniffs the stream for
+        // gzip/bzip2/xz/zstd magic bytes, so compressed tars need no other header.
         let body = body_full(Bytes::from(tar.to_vec()));
         self.client
             .upload_to_container(container_id, options, body)
@@ -371,14 +945,175 @@ impl DockerCompute {
     }
 }
 
+/// Picks the container's address on the default `bridge` network, or on the
+/// first network that has one assigned.
+fn container_ip_address(networks: &HashMap<String, EndpointSettings>) -> Option<String> {
+    let address = |endpoint: &EndpointSettings| {
+        endpoint
+            .ip_address
+            .clone()
+            .filter(|address| !address.is_empty())
+    };
+    networks.get("bridge").and_then(address).or_else(|| {
+        let mut names: Vec<&String> = networks.keys().collect();
+        names.sort();
+        names.into_iter().find_map(|name| address(&networks[name]))
+    })
+}
+
+fn container_network_aliases(networks: &HashMap<String, EndpointSettings>) -> Vec<String> {
+    let mut aliases: Vec<String> = networks
+        .values()
+        .filter_map(|endpoint| endpoint.aliases.clone())
+        .flatten()
+        .collect();
+    aliases.sort();
+    aliases.dedup();
+    aliases
+}
+
+/// Converts a configured restart policy into the engine's form.
+fn docker_restart_policy(policy: RestartPolicy) -> bollard::models::RestartPolicy {
+    let (name, maximum_retry_count) = match policy {
+        RestartPolicy::No => (RestartPolicyNameEnum::NO, None),
+        RestartPolicy::OnFailure(retries) => {
+            (RestartPolicyNameEnum::ON_FAILURE, Some(i64::from(retries)))
+        }
+        RestartPolicy::Always => (RestartPolicyNameEnum::ALWAYS, None),
+        RestartPolicy::UnlessStopped => (RestartPolicyNameEnum::UNLESS_STOPPED, None),
+    };
+    bollard::models::RestartPolicy {
+        name: Some(name),
+        maximum_retry_count,
+    }
+}
+
+fn litterbox_container(
+    summary: ContainerSummary,
+    repo_prefix: Option<&str>,
+) -> Option<LitterboxContainer> {
+    let container_id = summary.id?;
+    let labels = summary.labels.unwrap_or_default();
+    let names = summary.names.unwrap_or_default();
+    let (prefix, slug) = match labels.get(LABEL_REPO_PREFIX).map(String::as_str).or(repo_prefix) {
+        Some(prefix) => {
+            // The name follows renames, while the slug label keeps the slug at creation.
+            let name_prefix = format!("/{CONTAINER_NAME_PREFIX}{prefix}-");
+            let slug = names
+                .iter()
+                .find_map(|name| name.strip_prefix(&name_prefix).map(str::to_string))
+                .or_else(|| labels.get(LABEL_SLUG).cloned())?;
+            (prefix.to_string(), slug)
+        }
+        // An unlabelled container of any repository: where its prefix ends is a guess.
+        None => names.iter().find_map(|name| {
+            let (prefix, slug) = name
+                .strip_prefix('/')?
+                .strip_prefix(CONTAINER_NAME_PREFIX)?
+                .rsplit_once('-')?;
+            Some((prefix.to_string(), slug.to_string()))
+        })?,
+    };
+    if repo_prefix.is_some_and(|repo_prefix| repo_prefix != prefix) {
+        return None;
+    }
+
+    let status = match summary.state {
+        Some(ContainerSummaryStateEnum::CREATED | ContainerSummaryStateEnum::RESTARTING) => {
+            SandboxStatus::Starting
+        }
+        Some(ContainerSummaryStateEnum::REMOVING) => SandboxStatus::Stopping,
+        Some(ContainerSummaryStateEnum::PAUSED) => SandboxStatus::Paused,
+        Some(ContainerSummaryStateEnum::RUNNING) => SandboxStatus::Active,
+        Some(
+            ContainerSummaryStateEnum::EXITED
+            | ContainerSummaryStateEnum::DEAD
+            | ContainerSummaryStateEnum::EMPTY,
+        )
+        | None => SandboxStatus::Error("not running".to_string()),
+    };
+
+    Some(LitterboxContainer {
+        container_id,
+        slug,
+        repo_prefix: prefix,
+        status,
+    })
+}
+
+fn sandbox_status_from_state(state: Option<ContainerStateStatusEnum>) -> SandboxStatus {
+    match state {
+        Some(ContainerStateStatusEnum::CREATED | ContainerStateStatusEnum::RESTARTING) => {
+            SandboxStatus::Starting
+        }
+        Some(ContainerStateStatusEnum::REMOVING) => SandboxStatus::Stopping,
+        Some(ContainerStateStatusEnum::PAUSED) => SandboxStatus::Paused,
+        Some(ContainerStateStatusEnum::RUNNING) => SandboxStatus::Active,
+        Some(
+            ContainerStateStatusEnum::EXITED
+            | ContainerStateStatusEnum::DEAD
+            | ContainerStateStatusEnum::EMPTY,
+        )
+        | None => SandboxStatus::Error("not running".to_string()),
+    }
+}
+
+/// Applies the CPU and memory formulas from the Docker Engine API stats documentation.
+fn container_stats_from_sample(sample: &ContainerStatsResponse) -> ContainerStats {
+    let total_usage = |stats: Option<&ContainerCpuStats>| {
+        stats
+            .and_then(|stats| stats.cpu_usage.as_ref())
+            .and_then(|usage| usage.total_usage)
+            .unwrap_or(0)
+    };
+    let system_usage = |stats: Option<&ContainerCpuStats>| {
+        stats.and_then(|stats| stats.system_cpu_usage).unwrap_or(0)
+    };
+
+    let cpu_stats = sample.cpu_stats.as_ref();
+    let precpu_stats = sample.precpu_stats.as_ref();
+    let cpu_delta = total_usage(cpu_stats).saturating_sub(total_usage(precpu_stats));
+    let system_delta = system_usage(cpu_stats).saturating_sub(system_usage(precpu_stats));
+    let online_cpus = cpu_stats
+        .and_then(|stats| stats.online_cpus)
+        .map(u64::from)
+        .or_else(|| {
+            cpu_stats
+                .and_then(|stats| stats.cpu_usage.as_ref())
+                .and_then(|usage| usage.percpu_usage.as_ref())
+                .map(|percpu| percpu.len() as u64)
+        })
+        .unwrap_or(1);
+    let cpu_percent = if system_delta > 0 {
+        (cpu_delta as f64 / system_delta as f64) * online_cpus as f64 * 100.0
+    } else {
+        0.0
+    };
+
+    let memory = sample.memory_stats.as_ref();
+    let usage = memory.and_then(|memory| memory.usage).unwrap_or(0);
+    // Page cache is reported as `cache` on cgroup v1 and `inactive_file` on cgroup v2.
+    let cache = memory
+        .and_then(|memory| memory.stats.as_ref())
+        .and_then(|stats| stats.get("cache").or_else(|| stats.get("inactive_file")))
+        .copied()
+        .unwrap_or(0);
+
+    ContainerStats {
+        cpu_percent,
+        memory_used_bytes: usage.saturating_sub(cache),
+        memory_limit_bytes: memory.and_then(|memory| memory.limit).unwrap_or(0),
+    }
+}
+
 fn connect_docker_client() -> Result<Docker, SandboxError> {
-    if let Some(host) = docker_host_from_context() {
-        return connect_with_host(&host);
+    if let Some(endpoint) = docker_endpoint_from_context() {
+        return connect_with_host(&endpoint.host, endpoint.tls_path.as_deref());
     }
     DockerCompute::connect_with_defaults()
 }
 
-fn connect_with_host(host: &str) -> Result<Docker, SandboxError> {
+fn connect_with_host(host: &str, context_tls_path: Option<&Path>) -> Result<Docker, SandboxError> {
     let (scheme, rest) = match host.split_once("://") {
         Some((scheme, rest)) => (scheme, rest),
         None => ("unix", host),
@@ -403,33 +1138,83 @@ fn connect_with_host(host: &str) -> Result<Docker, SandboxError> {
                 }))
             }
         }
-        "tcp" => {
-            let endpoint = format!("http://{}", rest);
-            Docker::connect_with_http(&endpoint, 120, API_DEFAULT_VERSION)
+        "tcp" | "tcp+tls" => match tls_cert_dir(scheme, context_tls_path) {
+            Some(cert_dir) => {
+                let endpoint = format!("tcp://{}", rest);
+                Docker::connect_with_ssl(
+                    &endpoint,
+                    &cert_dir.join("key.pem"),
+                    &cert_dir.join("cert.pem"),
+                    &cert_dir.join("ca.pem"),
+                    120,
+                    API_DEFAULT_VERSION,
+                )
                 .map_err(|source| SandboxError::Compute(ComputeError::Connection { source }))
-        }
+            }
+            None => {
+                let endpoint = format!("http://{}", rest);
+                Docker::connect_with_http(&endpoint, 120, API_DEFAULT_VERSION)
+                    .map_err(|source| SandboxError::Compute(ComputeError::Connection { source }))
+            }
+        },
         _ => DockerCompute::connect_with_defaults(),
     }
 }
 
-fn docker_host_from_context() -> Option<String> {
+/// Picks the directory holding `ca.pem`, `cert.pem` and `key.pem` for a TCP connection.
+///
+/// `DOCKER_TLS_VERIFY` and `DOCKER_CERT_PATH` take precedence, as in the Docker CLI,
+/// followed by TLS material stored with the active Docker context.
+fn tls_cert_dir(scheme: &str, context_tls_path: Option<&Path>) -> Option<PathBuf> {
+    let verify = env::var("DOCKER_TLS_VERIFY").ok();
+    if scheme == "tcp+tls" || tls_verify_enabled(verify.as_deref()) {
+        if let Some(cert_path) = env::var_os("DOCKER_CERT_PATH").filter(|path| !path.is_empty()) {
+            return Some(PathBuf::from(cert_path));
+        }
+        if let Some(context_dir) = context_tls_path {
+            return Some(context_dir.to_path_buf());
+        }
+        return env::var_os("HOME").map(|home| PathBuf::from(home).join(".docker"));
+    }
+    context_tls_path
+        .filter(|dir| dir.join("ca.pem").exists())
+        .map(Path::to_path_buf)
+}
+
+fn tls_verify_enabled(value: Option<&str>) -> bool {
+    matches!(value, Some(value) if !value.is_empty() && value != "0")
+}
+
+struct DockerEndpoint {
+    host: String,
+    tls_path: Option<PathBuf>,
+}
+
+fn docker_endpoint_from_context() -> Option<DockerEndpoint> {
     let output = Command::new("docker")
         .args([
             "context",
             "inspect",
             "-f",
-            "{{.Endpoints.docker.Host}}",
+            "{{.Endpoints.docker.Host}}\n{{.Storage.TLSPath}}",
         ])
         .output()
         .ok()?;
     if !output.status.success() {
         return None;
     }
-    let host = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if host.is_empty() {
-        return None;
-    }
-    Some(host)
+    parse_context_endpoint(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_context_endpoint(output: &str) -> Option<DockerEndpoint> {
+    let mut lines = output.lines().map(str::trim);
+    let host = lines.next().filter(|host| !host.is_empty())?.to_string();
+    // Context TLS material lives in a per-endpoint subdirectory of the storage path.
+    let tls_path = lines
+        .next()
+        .filter(|path| !path.is_empty() && *path != "<no value>")
+        .map(|path| Path::new(path).join("docker"));
+    Some(DockerEndpoint { host, tls_path })
 }
 
 impl Compute for DockerCompute {
@@ -437,6 +1222,18 @@ impl Compute for DockerCompute {
         Box::pin(async move { DockerCompute::ensure_image(self, image).await })
     }
 
+    fn build_image<'a>(
+        &'a self,
+        context_path: &'a Path,
+        tag: &'a str,
+        build_args: &'a HashMap<String, String>,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move {
+            DockerCompute::build_image(self, context_path, tag, build_args).await?;
+            Ok(())
+        })
+    }
+
     fn create_container<'a>(
         &'a self,
         spec: &'a ContainerSpec,
@@ -451,6 +1248,22 @@ impl Compute for DockerCompute {
         Box::pin(async move { DockerCompute::inspect_container(self, container_id).await })
     }
 
+    fn container_stats<'a>(
+        &'a self,
+        container_id: &'a str,
+    ) -> BoxFuture<'a, Result<ContainerStats, SandboxError>> {
+        Box::pin(async move { DockerCompute::container_stats(self, container_id).await })
+    }
+
+    fn commit_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        image: &'a str,
+        tag: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move { DockerCompute::commit_container(self, container_id, image, tag).await })
+    }
+
     fn pause_container<'a>(&'a self, container_id: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
         Box::pin(async move { DockerCompute::pause_container(self, container_id).await })
     }
@@ -466,13 +1279,25 @@ impl Compute for DockerCompute {
         Box::pin(async move { DockerCompute::delete_container(self, container_id).await })
     }
 
+    fn kill_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        signal: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move { DockerCompute::kill_container(self, container_id, signal).await })
+    }
+
     fn exec<'a>(
         &'a self,
         container_id: &'a str,
         command: &'a [String],
         working_dir: Option<&'a str>,
+        options: Option<&'a ExecOptions>,
+        retries: Option<u32>,
     ) -> BoxFuture<'a, Result<ExecutionResult, SandboxError>> {
-        Box::pin(async move { DockerCompute::exec(self, container_id, command, working_dir).await })
+        Box::pin(async move {
+            DockerCompute::exec(self, container_id, command, working_dir, options, retries).await
+        })
     }
 
     fn upload_path<'a>(
@@ -496,23 +1321,61 @@ impl Compute for DockerCompute {
             DockerCompute::download_path(self, container_id, src_path, dest_path).await
         })
     }
+
+    fn network_create<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<String, SandboxError>> {
+        Box::pin(async move { DockerCompute::network_create(self, name).await })
+    }
+
+    fn network_connect<'a>(
+        &'a self,
+        network_id: &'a str,
+        container_id: &'a str,
+        aliases: &'a [String],
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move {
+            DockerCompute::network_connect(self, network_id, container_id, aliases).await
+        })
+    }
 }
 
-fn build_tar(src_path: &Path) -> Result<Vec<u8>, SandboxError> {
+fn build_tar(src_path: &Path, compressed: bool) -> Result<Vec<u8>, SandboxError> {
+    if compressed {
+        let encoder = zstd::Encoder::new(Vec::new(), UPLOAD_ZSTD_LEVEL)?;
+        let mut builder = Builder::new(encoder);
+        append_path(&mut builder, src_path)?;
+        return Ok(builder.into_inner()?.finish()?);
+    }
+
     let mut builder = Builder::new(Vec::new());
+    append_path(&mut builder, src_path)?;
+    Ok(builder.into_inner()?)
+}
+
+fn append_path<W: Write>(builder: &mut Builder<W>, src_path: &Path) -> Result<(), SandboxError> {
     if src_path.is_dir() {
-        append_dir(&mut builder, src_path, src_path)?;
+        append_dir(builder, src_path, src_path)
     } else {
         let name = src_path
             .file_name()
             .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid path"))?;
         builder.append_path_with_name(src_path, name)?;
+        Ok(())
     }
-    builder.finish()?;
-    Ok(builder.into_inner()?)
 }
 
-fn append_dir(builder: &mut Builder<Vec<u8>>, root: &Path, dir: &Path) -> Result<(), SandboxError> {
+/// The first build log line that reports an error, if any.
+fn build_log_error(log: &[String]) -> Option<&str> {
+    log.iter()
+        .map(String::as_str)
+        .find(|line| line.to_ascii_lowercase().contains("error"))
+}
+
+/// Whether `data` starts with the zstd frame magic number.
+pub fn is_zstd_compressed(data: &[u8]) -> bool {
+    data.starts_with(&ZSTD_MAGIC)
+}
+
+fn append_dir<W: Write>(builder: &mut Builder<W>, root: &Path, dir: &Path) -> Result<(), SandboxError> {
     let entries = fs::read_dir(dir)?;
     let mut has_entries = false;
 
@@ -569,7 +1432,22 @@ fn extract_tar(dest_path: &Path, tar: &[u8]) -> Result<(), SandboxError> {
             continue;
         }
         
+        // `Path::starts_with` compares components lexically, so `..` must be rejected
+        // explicitly as well as checking the joined path stays under `dest_path`.
+        let escapes = stripped_path.components().any(|component| {
+            matches!(
+                component,
+                Component::ParentDir | Component::RootDir | Component::Prefix(_)
+            )
+        });
         let dest = dest_path.join(stripped_path);
+        if escapes || !dest.starts_with(dest_path) {
+            eprintln!(
+                "download warning: skipping archive entry outside destination: {}",
+                path.display()
+            );
+            continue;
+        }
         
         // Create parent directories if needed
         if let Some(parent) = dest.parent() {
@@ -587,10 +1465,460 @@ fn is_not_found(error: &BollardError) -> bool {
     matches!(error, BollardError::DockerResponseServerError { status_code: 404, .. })
 }
 
+fn retry_warning() -> PullProgressCallback {
+    Box::new(|event| match event {
+        PullEvent::PullRetry { attempt, error } => {
+            eprintln!("image pull warning: retry {attempt} after error: {error}")
+        }
+    })
+}
+
+/// Wraps a failed request about `container_id` in `variant`, or in `ContainerNotFound` when
+/// the container does not exist.
+fn container_error(
+    container_id: &str,
+    source: bollard::errors::Error,
+    variant: fn(bollard::errors::Error) -> ComputeError,
+) -> SandboxError {
+    let error = ComputeError::from_bollard_404(container_id, source).unwrap_or_else(variant);
+    SandboxError::Compute(error)
+}
+
+/// Whether an exec failure is the daemon being momentarily overloaded.
+fn is_transient_exec_error(error: &BollardError) -> bool {
+    matches!(
+        error,
+        BollardError::DockerResponseServerError {
+            status_code: 500 | 503,
+            ..
+        }
+    )
+}
+
+/// Whether an exec failed because its working directory does not exist in the container.
+fn is_missing_workdir_error(error: &BollardError) -> bool {
+    let BollardError::DockerResponseServerError { message, .. } = error else {
+        return false;
+    };
+    let message = message.to_ascii_lowercase();
+    message.contains("no such file or directory")
+        && (message.contains("chdir") || message.contains("cwd") || message.contains("working dir"))
+}
+
+/// Whether a network connect failed because the container is already on the network.
+fn is_already_connected_error(error: &BollardError) -> bool {
+    let BollardError::DockerResponseServerError {
+        status_code: 403 | 409,
+        message,
+    } = error
+    else {
+        return false;
+    };
+    let message = message.to_ascii_lowercase();
+    message.contains("already exists in network") || message.contains("already attached")
+}
+
+/// Whether a pull failure looks like a network problem worth retrying, rather than
+/// a definitive answer such as the image not existing.
+fn is_transient_pull_error(error: &BollardError) -> bool {
+    match error {
+        BollardError::DockerResponseServerError { status_code, .. } => *status_code >= 500,
+        BollardError::DockerStreamError { error } => {
+            let error = error.to_ascii_lowercase();
+            !(error.contains("not found")
+                || error.contains("manifest unknown")
+                || error.contains("unauthorized")
+                || error.contains("denied"))
+        }
+        BollardError::RequestTimeoutError
+        | BollardError::IOError { .. }
+        | BollardError::HyperResponseError { .. }
+        | BollardError::HyperLegacyError { .. } => true,
+        _ => false,
+    }
+}
+
+fn pull_backoff(attempt: usize) -> Duration {
+    let factor = 1u64.checked_shl(attempt as u32).unwrap_or(u64::MAX);
+    Duration::from_millis(PULL_BACKOFF_BASE_MS.saturating_mul(factor))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn pull_progress_parses_create_image_info() {
+        let progress = PullProgress::from(CreateImageInfo {
+            id: Some("abc123".to_string()),
+            status: Some("Downloading".to_string()),
+            progress_detail: Some(bollard::models::ProgressDetail {
+                current: Some(512),
+                total: Some(1024),
+            }),
+            ..Default::default()
+        });
+
+        assert_eq!(progress.layer_id, "abc123");
+        assert_eq!(progress.status, "Downloading");
+        assert_eq!(progress.progress_bytes, Some(512));
+        assert_eq!(progress.total_bytes, Some(1024));
+    }
+
+    #[test]
+    fn docker_restart_policy_maps_retry_count() {
+        let policy = docker_restart_policy(RestartPolicy::OnFailure(3));
+        assert_eq!(policy.name, Some(RestartPolicyNameEnum::ON_FAILURE));
+        assert_eq!(policy.maximum_retry_count, Some(3));
+
+        let policy = docker_restart_policy(RestartPolicy::UnlessStopped);
+        assert_eq!(policy.name, Some(RestartPolicyNameEnum::UNLESS_STOPPED));
+        assert_eq!(policy.maximum_retry_count, None);
+    }
+
+    #[test]
+    fn extract_tar_skips_parent_dir_entries() {
+        let root = tempfile::tempdir().expect("tempdir");
+        let dest = root.path().join("dest");
+
+        let mut builder = Builder::new(Vec::new());
+        let mut header = tar::Header::new_old();
+        let name = b"../escape.txt";
+        header.as_old_mut().name[..name.len()].copy_from_slice(name);
+        header.set_size(6);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, &b"escape"[..]).expect("append escape");
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "src/safe.txt", &b"safe"[..])
+            .expect("append safe");
+        let tar = builder.into_inner().expect("tar");
+
+        extract_tar(&dest, &tar).expect("extract");
+
+        assert!(!root.path().join("escape.txt").exists());
+        assert_eq!(fs::read_to_string(dest.join("safe.txt")).expect("read safe"), "safe");
+    }
+
+    #[test]
+    fn global_compute_is_shared() {
+        if std::env::var("LITTERBOX_DOCKER_TESTS").is_err() {
+            return;
+        }
+
+        let first = DockerCompute::global().expect("global compute");
+        let second = DockerCompute::global().expect("global compute");
+
+        assert!(std::ptr::eq(first, second));
+    }
+
+    #[test]
+    fn litterbox_container_prefers_labels() {
+        let summary = ContainerSummary {
+            id: Some("abc".to_string()),
+            names: Some(vec!["/renamed".to_string()]),
+            labels: Some(HashMap::from([
+                (LABEL_REPO_PREFIX.to_string(), "my-repo".to_string()),
+                (LABEL_SLUG.to_string(), "feature".to_string()),
+            ])),
+            state: Some(ContainerSummaryStateEnum::PAUSED),
+            ..Default::default()
+        };
+
+        let container = litterbox_container(summary.clone(), None).expect("container");
+        assert_eq!(container.container_id, "abc");
+        assert_eq!(container.repo_prefix, "my-repo");
+        assert_eq!(container.slug, "feature");
+        assert_eq!(container.status, SandboxStatus::Paused);
+        assert!(litterbox_container(summary, Some("other")).is_none());
+    }
+
+    #[test]
+    fn litterbox_container_takes_slug_from_renamed_container() {
+        let summary = ContainerSummary {
+            id: Some("abc".to_string()),
+            names: Some(vec!["/litterbox-my-repo-renamed".to_string()]),
+            labels: Some(HashMap::from([
+                (LABEL_REPO_PREFIX.to_string(), "my-repo".to_string()),
+                (LABEL_SLUG.to_string(), "feature".to_string()),
+            ])),
+            state: Some(ContainerSummaryStateEnum::RUNNING),
+            ..Default::default()
+        };
+
+        let container = litterbox_container(summary, Some("my-repo")).expect("container");
+        assert_eq!(container.slug, "renamed");
+    }
+
+    #[test]
+    fn litterbox_container_falls_back_to_name_with_prefix() {
+        let summary = ContainerSummary {
+            id: Some("abc".to_string()),
+            names: Some(vec!["/litterbox-my-repo-feature".to_string()]),
+            state: Some(ContainerSummaryStateEnum::RUNNING),
+            ..Default::default()
+        };
+
+        let container = litterbox_container(summary.clone(), Some("my-repo")).expect("container");
+        assert_eq!(container.slug, "feature");
+        assert_eq!(container.status, SandboxStatus::Active);
+        assert!(litterbox_container(summary.clone(), Some("other")).is_none());
+
+        // Without a repository to match, unlabelled containers are still found.
+        let container = litterbox_container(summary, None).expect("container");
+        assert_eq!(container.container_id, "abc");
+        assert_eq!(container.repo_prefix, "my-repo");
+    }
+
+    #[test]
+    fn inspected_state_maps_to_sandbox_status() {
+        assert_eq!(
+            sandbox_status_from_state(Some(ContainerStateStatusEnum::PAUSED)),
+            SandboxStatus::Paused
+        );
+        assert_eq!(
+            sandbox_status_from_state(Some(ContainerStateStatusEnum::RUNNING)),
+            SandboxStatus::Active
+        );
+        assert_eq!(
+            sandbox_status_from_state(Some(ContainerStateStatusEnum::EXITED)),
+            SandboxStatus::Error("not running".to_string())
+        );
+        assert_eq!(
+            sandbox_status_from_state(None),
+            SandboxStatus::Error("not running".to_string())
+        );
+    }
+
+    #[test]
+    fn exec_retries_only_on_overloaded_daemon() {
+        let server_error = |status_code| BollardError::DockerResponseServerError {
+            status_code,
+            message: "boom".to_string(),
+        };
+
+        assert!(is_transient_exec_error(&server_error(500)));
+        assert!(is_transient_exec_error(&server_error(503)));
+        assert!(!is_transient_exec_error(&server_error(404)));
+        assert!(!is_transient_exec_error(&server_error(409)));
+    }
+
+    #[test]
+    fn missing_workdir_error_is_recognised() {
+        let server_error = |message: &str| BollardError::DockerResponseServerError {
+            status_code: 500,
+            message: message.to_string(),
+        };
+
+        assert!(is_missing_workdir_error(&server_error(
+            "OCI runtime exec failed: exec failed: unable to start container process: chdir to \
+             cwd (\"/missing\") set in config.json failed: no such file or directory: unknown"
+        )));
+        assert!(!is_missing_workdir_error(&server_error(
+            "exec: \"missing\": executable file not found in $PATH: no such file or directory"
+        )));
+        assert!(!is_missing_workdir_error(&server_error("boom")));
+    }
+
+    #[test]
+    fn already_connected_error_is_recognised() {
+        let server_error = |status_code: u16, message: &str| {
+            BollardError::DockerResponseServerError {
+                status_code,
+                message: message.to_string(),
+            }
+        };
+
+        assert!(is_already_connected_error(&server_error(
+            403,
+            "endpoint with name litterbox-repo-api already exists in network litterbox-repo-net"
+        )));
+        assert!(!is_already_connected_error(&server_error(404, "network missing not found")));
+        assert!(!is_already_connected_error(&server_error(403, "operation not permitted")));
+    }
+
+    #[test]
+    fn container_network_aliases_are_sorted_and_unique() {
+        let endpoint = |aliases: &[&str]| EndpointSettings {
+            aliases: Some(aliases.iter().map(|alias| alias.to_string()).collect()),
+            ..Default::default()
+        };
+        let networks = HashMap::from([
+            ("bridge".to_string(), EndpointSettings::default()),
+            ("one".to_string(), endpoint(&["web"])),
+            ("two".to_string(), endpoint(&["web", "api"])),
+        ]);
+
+        assert_eq!(container_network_aliases(&networks), ["api", "web"]);
+    }
+
+    #[test]
+    fn build_log_error_matches_case_insensitively() {
+        let log = vec![
+            "Step 1/2 : FROM alpine".to_string(),
+            "ERROR: failed to solve".to_string(),
+        ];
+
+        assert_eq!(build_log_error(&log), Some("ERROR: failed to solve"));
+        assert_eq!(build_log_error(&log[..1]), None);
+    }
+
+    #[test]
+    fn build_tar_compresses_with_zstd() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        fs::write(dir.path().join("file.txt"), "hello ".repeat(1024)).expect("write file");
+
+        let plain = build_tar(dir.path(), false).expect("plain tar");
+        let compressed = build_tar(dir.path(), true).expect("compressed tar");
+
+        assert!(!is_zstd_compressed(&plain));
+        assert!(is_zstd_compressed(&compressed));
+        assert!(compressed.len() < plain.len());
+        let decoded = zstd::decode_all(Cursor::new(compressed)).expect("decode");
+        assert_eq!(decoded, plain);
+    }
+
+    #[test]
+    fn container_ip_address_prefers_bridge_network() {
+        let endpoint = |address: &str| EndpointSettings {
+            ip_address: Some(address.to_string()),
+            ..Default::default()
+        };
+        let networks = HashMap::from([
+            ("custom".to_string(), endpoint("10.1.0.5")),
+            ("bridge".to_string(), endpoint("172.17.0.2")),
+        ]);
+
+        assert_eq!(container_ip_address(&networks), Some("172.17.0.2".to_string()));
+    }
+
+    #[test]
+    fn container_ip_address_falls_back_to_other_networks() {
+        let networks = HashMap::from([
+            (
+                "bridge".to_string(),
+                EndpointSettings {
+                    ip_address: Some(String::new()),
+                    ..Default::default()
+                },
+            ),
+            (
+                "custom".to_string(),
+                EndpointSettings {
+                    ip_address: Some("10.1.0.5".to_string()),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        assert_eq!(container_ip_address(&networks), Some("10.1.0.5".to_string()));
+        assert_eq!(container_ip_address(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn container_stats_uses_docker_cpu_and_memory_formulas() {
+        let cpu = |total: u64, system: u64| ContainerCpuStats {
+            cpu_usage: Some(bollard::models::ContainerCpuUsage {
+                total_usage: Some(total),
+                ..Default::default()
+            }),
+            system_cpu_usage: Some(system),
+            online_cpus: Some(4),
+            ..Default::default()
+        };
+        let sample = ContainerStatsResponse {
+            cpu_stats: Some(cpu(300, 2_000)),
+            precpu_stats: Some(cpu(100, 1_000)),
+            memory_stats: Some(bollard::models::ContainerMemoryStats {
+                usage: Some(10_000),
+                limit: Some(50_000),
+                stats: Some(HashMap::from([("inactive_file".to_string(), 4_000)])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let stats = container_stats_from_sample(&sample);
+
+        assert!((stats.cpu_percent - 80.0).abs() < f64::EPSILON);
+        assert_eq!(stats.memory_used_bytes, 6_000);
+        assert_eq!(stats.memory_limit_bytes, 50_000);
+    }
+
+    #[test]
+    fn container_stats_handles_missing_precpu_sample() {
+        let stats = container_stats_from_sample(&ContainerStatsResponse::default());
+
+        assert_eq!(stats.cpu_percent, 0.0);
+        assert_eq!(stats.memory_used_bytes, 0);
+        assert_eq!(stats.memory_limit_bytes, 0);
+    }
+
+    #[test]
+    fn tls_verify_matches_docker_cli() {
+        assert!(tls_verify_enabled(Some("1")));
+        assert!(tls_verify_enabled(Some("true")));
+        assert!(!tls_verify_enabled(Some("0")));
+        assert!(!tls_verify_enabled(Some("")));
+        assert!(!tls_verify_enabled(None));
+    }
+
+    #[test]
+    fn context_endpoint_parses_host_and_tls_path() {
+        let endpoint = parse_context_endpoint("tcp://10.0.0.2:2376\n/home/me/.docker/contexts/tls/abc\n")
+            .expect("endpoint parses");
+
+        assert_eq!(endpoint.host, "tcp://10.0.0.2:2376");
+        assert_eq!(
+            endpoint.tls_path,
+            Some(PathBuf::from("/home/me/.docker/contexts/tls/abc/docker"))
+        );
+    }
+
+    #[test]
+    fn context_endpoint_without_tls_path() {
+        let endpoint = parse_context_endpoint("unix:///var/run/docker.sock\n\n").expect("endpoint parses");
+
+        assert_eq!(endpoint.host, "unix:///var/run/docker.sock");
+        assert!(endpoint.tls_path.is_none());
+        assert!(parse_context_endpoint("\n").is_none());
+    }
+
+    #[test]
+    fn pull_backoff_doubles_each_attempt() {
+        assert_eq!(pull_backoff(0), Duration::from_millis(500));
+        assert_eq!(pull_backoff(1), Duration::from_millis(1000));
+        assert_eq!(pull_backoff(2), Duration::from_millis(2000));
+    }
+
+    #[test]
+    fn transient_pull_errors_are_retried() {
+        assert!(is_transient_pull_error(&BollardError::RequestTimeoutError));
+        assert!(is_transient_pull_error(&BollardError::DockerResponseServerError {
+            status_code: 502,
+            message: "bad gateway".to_string(),
+        }));
+        assert!(is_transient_pull_error(&BollardError::DockerStreamError {
+            error: "connection reset by peer".to_string(),
+        }));
+    }
+
+    #[test]
+    fn missing_images_are_not_retried() {
+        assert!(!is_transient_pull_error(&BollardError::DockerResponseServerError {
+            status_code: 404,
+            message: "pull access denied".to_string(),
+        }));
+        assert!(!is_transient_pull_error(&BollardError::DockerStreamError {
+            error: "manifest unknown".to_string(),
+        }));
+    }
+
     #[tokio::test]
     async fn docker_connects_and_ensures_image() -> Result<(), Box<dyn std::error::Error>> {
         // Requires a running Docker daemon; opt in with LITTERBOX_DOCKER_TESTS.
@@ -602,4 +1930,39 @@ mod tests {
         compute.ensure_image("busybox:latest").await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn docker_exec_feeds_stdin() -> Result<(), Box<dyn std::error::Error>> {
+        // Requires a running Docker daemon; opt in with LITTERBOX_DOCKER_TESTS.
+        if std::env::var("LITTERBOX_DOCKER_TESTS").is_err() {
+            return Ok(());
+        }
+
+        let compute = DockerCompute::connect()?;
+        compute.ensure_image("busybox:latest").await?;
+        let spec = ContainerSpec {
+            name: format!("litterbox-stdin-test-{}", std::process::id()),
+            image: "busybox:latest".to_string(),
+            command: vec!["sleep".to_string(), "60".to_string()],
+            working_dir: None,
+            env: Vec::new(),
+            port_bindings: HashMap::new(),
+            labels: HashMap::new(),
+            restart_policy: None,
+        };
+        let container_id = compute.create_container(&spec).await?;
+        let options = ExecOptions {
+            stdin: Some(b"hello from stdin".to_vec()),
+            ..ExecOptions::default()
+        };
+        let result = compute
+            .exec(&container_id, &["cat".to_string()], None, Some(&options), None)
+            .await;
+        compute.delete_container(&container_id).await?;
+
+        let result = result?;
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(result.stdout, "hello from stdin");
+        Ok(())
+    }
 }
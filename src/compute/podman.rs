@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::env;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use bollard::{Docker, API_DEFAULT_VERSION};
+use futures_util::future::BoxFuture;
+
+use super::{
+    Compute, ContainerInspection, ContainerSpec, ContainerStats, DockerCompute, ExecOptions,
+};
+use crate::domain::{ComputeError, ExecutionResult, SandboxError};
+
+const PODMAN_SOCKET: &str = "podman/podman.sock";
+const ROOTFUL_PODMAN_SOCKET: &str = "/run/podman/podman.sock";
+
+/// Compute backend for Podman, driven through its Docker-compatible API.
+pub struct PodmanCompute {
+    inner: DockerCompute,
+}
+
+impl PodmanCompute {
+    pub fn new(client: Docker) -> Self {
+        Self {
+            inner: DockerCompute::new(client),
+        }
+    }
+
+    /// Connects to the Podman socket of the current user.
+    pub fn connect_podman() -> Result<Self, SandboxError> {
+        let socket = podman_socket_path();
+        let client = Docker::connect_with_unix(&socket.to_string_lossy(), 120, API_DEFAULT_VERSION)
+            .map_err(|source| SandboxError::Compute(ComputeError::Connection { source }))?;
+        Ok(Self::new(client))
+    }
+
+    pub fn with_pull_retries(self, pull_retries: usize) -> Self {
+        Self {
+            inner: self.inner.with_pull_retries(pull_retries),
+        }
+    }
+
+    pub fn client(&self) -> &Docker {
+        self.inner.client()
+    }
+
+    pub fn into_inner(self) -> DockerCompute {
+        self.inner
+    }
+}
+
+/// Resolves the Podman socket path from `$XDG_RUNTIME_DIR`, falling back to
+/// `/run/user/<uid>` and finally the rootful socket.
+pub fn podman_socket_path() -> PathBuf {
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| current_uid().map(|uid| PathBuf::from(format!("/run/user/{uid}"))));
+
+    match runtime_dir {
+        Some(dir) => dir.join(PODMAN_SOCKET),
+        None => PathBuf::from(ROOTFUL_PODMAN_SOCKET),
+    }
+}
+
+fn current_uid() -> Option<u32> {
+    Path::new("/proc/self").metadata().ok().map(|metadata| metadata.uid())
+}
+
+impl Compute for PodmanCompute {
+    fn ensure_image<'a>(&'a self, image: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Compute::ensure_image(&self.inner, image)
+    }
+
+    fn build_image<'a>(
+        &'a self,
+        context_path: &'a Path,
+        tag: &'a str,
+        build_args: &'a HashMap<String, String>,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Compute::build_image(&self.inner, context_path, tag, build_args)
+    }
+
+    fn create_container<'a>(
+        &'a self,
+        spec: &'a ContainerSpec,
+    ) -> BoxFuture<'a, Result<String, SandboxError>> {
+        Compute::create_container(&self.inner, spec)
+    }
+
+    fn inspect_container<'a>(
+        &'a self,
+        container_id: &'a str,
+    ) -> BoxFuture<'a, Result<ContainerInspection, SandboxError>> {
+        Compute::inspect_container(&self.inner, container_id)
+    }
+
+    fn container_stats<'a>(
+        &'a self,
+        container_id: &'a str,
+    ) -> BoxFuture<'a, Result<ContainerStats, SandboxError>> {
+        Compute::container_stats(&self.inner, container_id)
+    }
+
+    fn commit_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        image: &'a str,
+        tag: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Compute::commit_container(&self.inner, container_id, image, tag)
+    }
+
+    fn pause_container<'a>(&'a self, container_id: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Compute::pause_container(&self.inner, container_id)
+    }
+
+    fn resume_container<'a>(
+        &'a self,
+        container_id: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Compute::resume_container(&self.inner, container_id)
+    }
+
+    fn delete_container<'a>(&'a self, container_id: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Compute::delete_container(&self.inner, container_id)
+    }
+
+    fn kill_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        signal: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Compute::kill_container(&self.inner, container_id, signal)
+    }
+
+    fn exec<'a>(
+        &'a self,
+        container_id: &'a str,
+        command: &'a [String],
+        working_dir: Option<&'a str>,
+        options: Option<&'a ExecOptions>,
+        retries: Option<u32>,
+    ) -> BoxFuture<'a, Result<ExecutionResult, SandboxError>> {
+        Compute::exec(&self.inner, container_id, command, working_dir, options, retries)
+    }
+
+    fn upload_path<'a>(
+        &'a self,
+        container_id: &'a str,
+        src_path: &'a Path,
+        dest_path: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Compute::upload_path(&self.inner, container_id, src_path, dest_path)
+    }
+
+    fn download_path<'a>(
+        &'a self,
+        container_id: &'a str,
+        src_path: &'a str,
+        dest_path: &'a Path,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Compute::download_path(&self.inner, container_id, src_path, dest_path)
+    }
+
+    fn network_create<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<String, SandboxError>> {
+        Compute::network_create(&self.inner, name)
+    }
+
+    fn network_connect<'a>(
+        &'a self,
+        network_id: &'a str,
+        container_id: &'a str,
+        aliases: &'a [String],
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Compute::network_connect(&self.inner, network_id, container_id, aliases)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::podman_socket_path;
+
+    #[test]
+    fn podman_socket_path_uses_runtime_dir() {
+        let path = podman_socket_path();
+        match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+            Some(dir) => assert_eq!(path, PathBuf::from(dir).join("podman/podman.sock")),
+            None => assert!(path.ends_with("podman/podman.sock")),
+        }
+    }
+}
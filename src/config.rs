@@ -1,7 +1,14 @@
+use std::collections::{BTreeMap, HashSet};
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
+use crate::compute::DockerCompute;
+use crate::domain::{PortProtocol, RestartPolicy};
+
+pub const DEFAULT_PORT_RANGE_START: u16 = 3000;
+pub const DEFAULT_PORT_RANGE_END: u16 = 8000;
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
@@ -10,6 +17,78 @@ pub struct Config {
     pub docker: DockerConfig,
     #[serde(default)]
     pub ports: PortsConfig,
+    pub compute: Option<ComputeBackend>,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub templates: Vec<TemplateConfig>,
+}
+
+impl Config {
+    pub fn template(&self, name: &str) -> Option<&TemplateConfig> {
+        self.templates.iter().find(|template| template.name == name)
+    }
+
+    /// Checks that every `[[templates]]` entry has a unique, non-empty name and valid
+    /// `extra-env` variable names.
+    ///
+    /// Run by `config_loader::load_final`, so a sandbox never starts from a broken template.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut seen = HashSet::new();
+        for template in &self.templates {
+            if template.name.trim().is_empty() {
+                return Err(ConfigError::ParseError("Template name must not be empty".to_string()));
+            }
+            if !seen.insert(template.name.as_str()) {
+                return Err(ConfigError::ParseError(format!(
+                    "Duplicate template name: '{}'",
+                    template.name
+                )));
+            }
+            if let Some(key) = template.extra_env.keys().find(|key| !is_env_name(key)) {
+                return Err(ConfigError::ParseError(format!(
+                    "Invalid environment variable name '{key}' in template '{}'",
+                    template.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks settings that depend on the outside world, such as whether `docker.image` is
+    /// available locally or can be pulled.
+    ///
+    /// Not run by `config_loader::load_final`, since it needs the container engine and may
+    /// pull an image.
+    pub async fn validate_async(&self) -> Result<(), ConfigError> {
+        let Some(image) = self.docker.image.as_deref().filter(|image| !image.is_empty()) else {
+            return Ok(());
+        };
+        let unavailable = |error: crate::domain::SandboxError| ConfigError::ImageUnavailable {
+            image: image.to_string(),
+            message: error.to_string(),
+        };
+        let compute =
+            DockerCompute::connect_backend(self.compute.unwrap_or_default()).map_err(unavailable)?;
+        compute.ensure_image(image).await.map_err(unavailable)
+    }
+}
+
+fn is_env_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Container engine used to run sandboxes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ComputeBackend {
+    #[default]
+    Docker,
+    Podman,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -22,18 +101,106 @@ pub struct DockerConfig {
     pub image: Option<String>,
     #[serde(rename = "setup-command")]
     pub setup_command: Option<String>,
+    #[serde(rename = "setup-timeout")]
+    pub setup_timeout: Option<u64>,
+    #[serde(rename = "pull-retries")]
+    pub pull_retries: Option<usize>,
+    #[serde(rename = "exec-retries")]
+    pub exec_retries: Option<u32>,
+    #[serde(rename = "compress-uploads")]
+    pub compress_uploads: Option<bool>,
+    #[serde(rename = "ready-command")]
+    pub ready_command: Option<String>,
+    #[serde(rename = "restart-policy")]
+    pub restart_policy: Option<RestartPolicy>,
+    /// Absolute container directory the project is copied to; `/src` when unset.
+    pub workdir: Option<String>,
+}
+
+/// Named set of sandbox settings, picked per sandbox in place of the `docker` defaults.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateConfig {
+    pub name: String,
+    pub image: Option<String>,
+    #[serde(rename = "setup-command")]
+    pub setup_command: Option<String>,
+    /// Environment variables set in the container, on top of the forwarded port variables.
+    #[serde(rename = "extra-env", default)]
+    pub extra_env: BTreeMap<String, String>,
+}
+
+/// Logging of MCP tool calls.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditConfig {
+    pub enabled: Option<bool>,
+    /// File the audit log is appended to; stderr when unset.
+    pub path: Option<PathBuf>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ForwardedPort {
     pub name: String,
     pub target: u16,
+    #[serde(default)]
+    pub protocol: PortProtocol,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(transparent)]
+#[serde(from = "PortsConfigRepr")]
 pub struct PortsConfig {
     pub ports: Vec<ForwardedPort>,
+    /// First host port that may be allocated for forwarded ports.
+    #[serde(rename = "range-start", skip_serializing_if = "Option::is_none")]
+    pub range_start: Option<u16>,
+    /// Last host port (inclusive) that may be allocated for forwarded ports.
+    #[serde(rename = "range-end", skip_serializing_if = "Option::is_none")]
+    pub range_end: Option<u16>,
+}
+
+impl PortsConfig {
+    /// The configured host port range, with unset bounds taken from the defaults.
+    pub fn port_range(&self) -> (u16, u16) {
+        (
+            self.range_start.unwrap_or(DEFAULT_PORT_RANGE_START),
+            self.range_end.unwrap_or(DEFAULT_PORT_RANGE_END),
+        )
+    }
+}
+
+/// `ports` is either a bare `[[ports]]` array or a `[ports]` table carrying the
+/// allocation range alongside `[[ports.ports]]`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PortsConfigRepr {
+    List(Vec<ForwardedPort>),
+    Table {
+        #[serde(default)]
+        ports: Vec<ForwardedPort>,
+        #[serde(rename = "range-start")]
+        range_start: Option<u16>,
+        #[serde(rename = "range-end")]
+        range_end: Option<u16>,
+    },
+}
+
+impl From<PortsConfigRepr> for PortsConfig {
+    fn from(repr: PortsConfigRepr) -> Self {
+        match repr {
+            PortsConfigRepr::List(ports) => Self {
+                ports,
+                ..Self::default()
+            },
+            PortsConfigRepr::Table {
+                ports,
+                range_start,
+                range_end,
+            } => Self {
+                ports,
+                range_start,
+                range_end,
+            },
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
@@ -44,17 +211,20 @@ pub enum ConfigError {
     ParseError(String),
     #[error("Missing required key: {0}")]
     MissingRequiredKey(String),
+    #[error("Docker image '{image}' could not be found or pulled: {message}")]
+    ImageUnavailable { image: String, message: String },
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Config, ForwardedPort, PortsConfig};
+    use super::{ComputeBackend, Config, ForwardedPort, PortProtocol, PortsConfig, RestartPolicy};
 
     #[test]
     fn forwarded_port_instantiates() {
         let port = ForwardedPort {
             name: "backend".to_string(),
             target: 8080,
+            protocol: PortProtocol::Tcp,
         };
 
         assert_eq!(port.name, "backend");
@@ -67,7 +237,9 @@ mod tests {
             ports: vec![ForwardedPort {
                 name: "frontend".to_string(),
                 target: 8081,
+                protocol: PortProtocol::Tcp,
             }],
+            ..PortsConfig::default()
         };
 
         assert_eq!(ports.ports.len(), 1);
@@ -92,6 +264,68 @@ docker = { image = "image", setup-command = "setup" }
         assert!(config.ports.ports.is_empty());
     }
 
+    #[test]
+    fn config_deserializes_setup_timeout() {
+        let input = r#"
+docker = { image = "image", setup-command = "setup", setup-timeout = 300 }
+"#;
+        let config: Config = toml::from_str(input).expect("config parses");
+
+        assert_eq!(config.docker.setup_timeout, Some(300));
+    }
+
+    #[test]
+    fn config_deserializes_restart_policy() {
+        let config: Config =
+            toml::from_str("docker = { restart-policy = \"on-failure:3\" }").expect("parses");
+        assert_eq!(config.docker.restart_policy, Some(RestartPolicy::OnFailure(3)));
+
+        let config: Config =
+            toml::from_str("docker = { restart-policy = \"unless-stopped\" }").expect("parses");
+        assert_eq!(config.docker.restart_policy, Some(RestartPolicy::UnlessStopped));
+
+        let error = toml::from_str::<Config>("docker = { restart-policy = \"sometimes\" }")
+            .expect_err("invalid policy");
+        assert!(error.to_string().contains("invalid restart policy"));
+    }
+
+    #[test]
+    fn config_deserializes_compute_backend() {
+        let input = r#"
+compute = "podman"
+docker = { image = "image" }
+"#;
+        let config: Config = toml::from_str(input).expect("config parses");
+
+        assert_eq!(config.compute, Some(ComputeBackend::Podman));
+    }
+
+    #[tokio::test]
+    async fn validate_async_skips_unset_image() {
+        let config: Config = toml::from_str("[docker]\nsetup-command = \"setup\"\n")
+            .expect("config parses");
+
+        config.validate_async().await.expect("nothing to validate");
+    }
+
+    #[tokio::test]
+    async fn validate_async_ensures_image() {
+        // Requires a running Docker daemon; opt in with LITTERBOX_DOCKER_TESTS.
+        if std::env::var("LITTERBOX_DOCKER_TESTS").is_err() {
+            return;
+        }
+
+        let config: Config = toml::from_str("docker = { image = \"busybox:latest\" }\n")
+            .expect("config parses");
+        config.validate_async().await.expect("image available");
+
+        let config: Config =
+            toml::from_str("docker = { image = \"litterbox-missing/image:none\" }\n")
+                .expect("config parses");
+        let err = config.validate_async().await.expect_err("image missing");
+        assert!(err.to_string().contains("litterbox-missing/image:none"));
+    }
+
     #[test]
     fn config_deserializes_with_ports() {
         let input = r#"
@@ -113,4 +347,73 @@ target = 8081
         assert_eq!(config.ports.ports[1].name, "frontend");
         assert_eq!(config.ports.ports[1].target, 8081);
     }
+
+    #[test]
+    fn config_deserializes_port_range_table() {
+        let input = r#"
+docker = { image = "image", setup-command = "setup" }
+
+[ports]
+range-start = 10000
+range-end = 10100
+
+[[ports.ports]]
+name = "backend"
+target = 8080
+"#;
+        let config: Config = toml::from_str(input).expect("config parses");
+
+        assert_eq!(config.ports.range_start, Some(10000));
+        assert_eq!(config.ports.range_end, Some(10100));
+        assert_eq!(config.ports.ports.len(), 1);
+        assert_eq!(config.ports.ports[0].target, 8080);
+    }
+
+    #[test]
+    fn config_deserializes_port_protocol() {
+        let input = r#"
+docker = { image = "image", setup-command = "setup" }
+
+[[ports]]
+name = "dns"
+target = 53
+protocol = "udp"
+
+[[ports]]
+name = "web"
+target = 8080
+"#;
+        let config: Config = toml::from_str(input).expect("config parses");
+
+        assert_eq!(config.ports.ports[0].protocol, PortProtocol::Udp);
+        assert_eq!(config.ports.ports[1].protocol, PortProtocol::Tcp);
+    }
+
+    #[test]
+    fn validate_rejects_template_duplicates_and_bad_env_names() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [[templates]]
+            name = "ticket"
+            image = "node:20"
+            setup-command = "npm ci"
+            extra-env = { NODE_ENV = "development" }
+            "#,
+        )
+        .expect("parse");
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.template("ticket").and_then(|t| t.image.as_deref()),
+            Some("node:20")
+        );
+
+        config.templates.push(config.templates[0].clone());
+        let err = config.validate().expect_err("duplicate accepted");
+        assert!(err.to_string().contains("Duplicate template name"));
+
+        config.templates.pop();
+        config.templates[0].extra_env.insert("BAD-NAME".to_string(), String::new());
+        let err = config.validate().expect_err("bad env name accepted");
+        assert!(err.to_string().contains("BAD-NAME"));
+    }
 }
@@ -1,10 +1,17 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::ffi::OsString;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::config::{Config, ConfigError, PortsConfig};
+use crate::config::{AuditConfig, Config, ConfigError, PortsConfig};
 use crate::domain::slugify_name;
 
+/// Project config file names, in the order they are looked for in each directory.
+const PROJECT_CONFIG_FILES: [&str; 2] = [".litterbox.toml", "litterbox.toml"];
+const LOCAL_CONFIG_FILE: &str = ".litterbox.local.toml";
+const GLOBAL_CONFIG_FILE: &str = "litterbox/config.toml";
+
 /// Loads and parses a single TOML configuration file into a Config struct.
 pub fn load_file(path: &Path) -> Result<Config, ConfigError> {
     let contents = fs::read_to_string(path)
@@ -23,6 +30,13 @@ pub fn merge(base: Config, local: Config) -> Config {
         docker: crate::config::DockerConfig {
             image: local.docker.image.or(base.docker.image),
             setup_command: local.docker.setup_command.or(base.docker.setup_command),
+            setup_timeout: local.docker.setup_timeout.or(base.docker.setup_timeout),
+            pull_retries: local.docker.pull_retries.or(base.docker.pull_retries),
+            exec_retries: local.docker.exec_retries.or(base.docker.exec_retries),
+            compress_uploads: local.docker.compress_uploads.or(base.docker.compress_uploads),
+            ready_command: local.docker.ready_command.or(base.docker.ready_command),
+            restart_policy: local.docker.restart_policy.or(base.docker.restart_policy),
+            workdir: local.docker.workdir.or(base.docker.workdir),
         },
         ports: PortsConfig {
             ports: if local.ports.ports.is_empty() {
@@ -30,16 +44,193 @@ pub fn merge(base: Config, local: Config) -> Config {
             } else {
                 local.ports.ports
             },
+            range_start: local.ports.range_start.or(base.ports.range_start),
+            range_end: local.ports.range_end.or(base.ports.range_end),
+        },
+        compute: local.compute.or(base.compute),
+        audit: AuditConfig {
+            enabled: local.audit.enabled.or(base.audit.enabled),
+            path: local.audit.path.or(base.audit.path),
         },
+        templates: if local.templates.is_empty() {
+            base.templates
+        } else {
+            local.templates
+        },
+    }
+}
+
+/// Finds the project config file for `start` by walking up toward the filesystem root.
+///
+/// The search stops at the first directory holding a config file, or without a match at
+/// a directory containing `.git`, so a parent project's config is never picked up.
+pub fn find_config_file(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        if let Some(path) = PROJECT_CONFIG_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+        {
+            return Some(path);
+        }
+        if dir.join(".git").exists() {
+            return None;
+        }
     }
+    None
+}
+
+/// Path of the user-wide config, `$XDG_CONFIG_HOME/litterbox/config.toml` or
+/// `~/.config/litterbox/config.toml`.
+pub fn global_config_path() -> Option<PathBuf> {
+    global_config_path_from(env::var_os("XDG_CONFIG_HOME"), env::var_os("HOME"))
+}
+
+fn global_config_path_from(
+    xdg_config_home: Option<OsString>,
+    home: Option<OsString>,
+) -> Option<PathBuf> {
+    // The XDG spec says relative values are invalid and must be ignored.
+    let config_home = xdg_config_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            home.filter(|home| !home.is_empty())
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(config_home.join(GLOBAL_CONFIG_FILE))
+}
+
+/// Loads the global config and its path, if it exists.
+///
+/// `project.slug` only makes sense per project, so it is dropped from the global layer.
+fn load_global() -> Result<Option<(Config, PathBuf)>, ConfigError> {
+    let Some(path) = global_config_path().filter(|path| path.is_file()) else {
+        return Ok(None);
+    };
+    let mut config = load_file(&path)?;
+    config.project.slug = None;
+    Ok(Some((config, path)))
 }
 
-/// Creates a default configuration based on the current directory.
-fn default_config() -> Config {
-    let current_dir = std::env::current_dir().ok();
-    let project_slug = current_dir
-        .as_ref()
-        .and_then(|dir| dir.file_name())
+/// A config with every field unset.
+fn empty_config() -> Config {
+    Config {
+        project: crate::config::ProjectConfig { slug: None },
+        docker: crate::config::DockerConfig {
+            image: None,
+            setup_command: None,
+            setup_timeout: None,
+            pull_retries: None,
+            exec_retries: None,
+            compress_uploads: None,
+            ready_command: None,
+            restart_policy: None,
+            workdir: None,
+        },
+        ports: PortsConfig::default(),
+        compute: None,
+        audit: AuditConfig::default(),
+        templates: Vec::new(),
+    }
+}
+
+/// Builds a config holding only the fields set by `LITTERBOX_*` variables.
+fn env_layer_from(lookup: impl Fn(&str) -> Option<String>) -> Result<Config, ConfigError> {
+    let var = |name: &str| lookup(name).filter(|value| !value.is_empty());
+    let port = |name: &str| {
+        var(name)
+            .map(|value| {
+                value.parse::<u16>().map_err(|_| {
+                    ConfigError::ParseError(format!("Invalid port in {name}: {value}"))
+                })
+            })
+            .transpose()
+    };
+
+    let mut config = empty_config();
+    config.docker.image = var("LITTERBOX_IMAGE");
+    config.docker.setup_command = var("LITTERBOX_SETUP_COMMAND");
+    config.project.slug = var("LITTERBOX_PROJECT_SLUG");
+    config.ports.range_start = port("LITTERBOX_PORT_RANGE_START")?;
+    config.ports.range_end = port("LITTERBOX_PORT_RANGE_END")?;
+    Ok(config)
+}
+
+/// Where a resolved config value came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Derived by Litterbox, such as the slug from the project directory name.
+    Default,
+    File(PathBuf),
+    /// A `LITTERBOX_*` environment variable.
+    Environment,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(path) => write!(f, "{}", path.display()),
+            ConfigSource::Environment => write!(f, "environment"),
+        }
+    }
+}
+
+/// The merged config together with the layer each set key was taken from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedConfig {
+    pub config: Config,
+    /// Keys as written in TOML, such as `docker.setup-command`, in sorted order.
+    pub sources: BTreeMap<&'static str, ConfigSource>,
+}
+
+/// The keys `config` sets, as written in TOML.
+fn set_keys(config: &Config) -> Vec<&'static str> {
+    let docker = &config.docker;
+    let ports = &config.ports;
+    [
+        ("project.slug", config.project.slug.is_some()),
+        ("docker.image", docker.image.is_some()),
+        ("docker.setup-command", docker.setup_command.is_some()),
+        ("docker.setup-timeout", docker.setup_timeout.is_some()),
+        ("docker.pull-retries", docker.pull_retries.is_some()),
+        ("docker.exec-retries", docker.exec_retries.is_some()),
+        ("docker.compress-uploads", docker.compress_uploads.is_some()),
+        ("docker.ready-command", docker.ready_command.is_some()),
+        ("docker.restart-policy", docker.restart_policy.is_some()),
+        ("docker.workdir", docker.workdir.is_some()),
+        ("ports.ports", !ports.ports.is_empty()),
+        ("ports.range-start", ports.range_start.is_some()),
+        ("ports.range-end", ports.range_end.is_some()),
+        ("compute", config.compute.is_some()),
+        ("audit.enabled", config.audit.enabled.is_some()),
+        ("audit.path", config.audit.path.is_some()),
+        ("templates", !config.templates.is_empty()),
+    ]
+    .into_iter()
+    .filter_map(|(key, set)| set.then_some(key))
+    .collect()
+}
+
+/// Merges `layers` in order, later layers overriding earlier ones, recording which layer
+/// each key was last set by.
+fn resolve(layers: Vec<(Config, ConfigSource)>) -> ResolvedConfig {
+    let mut config = empty_config();
+    let mut sources = BTreeMap::new();
+    for (layer, source) in layers {
+        for key in set_keys(&layer) {
+            sources.insert(key, source.clone());
+        }
+        config = merge(config, layer);
+    }
+    ResolvedConfig { config, sources }
+}
+
+/// Creates a default configuration based on the project root directory.
+fn default_config(project_root: &Path) -> Config {
+    let project_slug = project_root
+        .file_name()
         .and_then(|name| name.to_str())
         .map(crate::domain::slugify)
         .filter(|slug| !slug.is_empty());
@@ -51,38 +242,64 @@ fn default_config() -> Config {
         docker: crate::config::DockerConfig {
             image: None,
             setup_command: None,
+            setup_timeout: None,
+            pull_retries: None,
+            exec_retries: None,
+            compress_uploads: None,
+            ready_command: None,
+            restart_policy: None,
+            workdir: None,
         },
         ports: PortsConfig::default(),
+        compute: None,
+        audit: AuditConfig::default(),
+        templates: Vec::new(),
     }
 }
 
-/// Loads the final merged configuration from defaults, .litterbox.toml, and .litterbox.local.toml.
+/// Loads the final merged configuration from defaults, the global config, .litterbox.toml,
+/// .litterbox.local.toml, and `LITTERBOX_*` environment variables, each layer overriding the
+/// ones before it.
+///
+/// The project config is found with [`find_config_file`] from the current directory; the
+/// local config is read from the same directory.
 pub fn load_final() -> Result<Config, ConfigError> {
+    load_final_with_sources().map(|resolved| resolved.config)
+}
+
+/// Like [`load_final`], but also reports where each set key came from.
+pub fn load_final_with_sources() -> Result<ResolvedConfig, ConfigError> {
+    let not_found = || ConfigError::FileNotFound(PathBuf::from(PROJECT_CONFIG_FILES[0]));
+    let current_dir = std::env::current_dir().map_err(|_| not_found())?;
+    let base_path = find_config_file(&current_dir).ok_or_else(not_found)?;
+    let project_root = base_path.parent().unwrap_or(&current_dir);
+
     // Start with defaults
-    let defaults = default_config();
+    let mut layers = vec![(default_config(project_root), ConfigSource::Default)];
+
+    // Load user-wide config
+    if let Some((global_config, global_path)) = load_global()? {
+        layers.push((global_config, ConfigSource::File(global_path)));
+    }
 
     // Load project config
-    let base_path = Path::new(".litterbox.toml");
-    let base_config = load_file(base_path)?;
+    layers.push((load_file(&base_path)?, ConfigSource::File(base_path.clone())));
 
     // Load local config if it exists
-    let local_path = Path::new(".litterbox.local.toml");
-    let local_config = if local_path.exists() {
-        load_file(local_path)?
-    } else {
-        // Empty config for merging
-        Config {
-            project: crate::config::ProjectConfig { slug: None },
-            docker: crate::config::DockerConfig {
-                image: None,
-                setup_command: None,
-            },
-            ports: PortsConfig::default(),
-        }
-    };
+    let local_path = project_root.join(LOCAL_CONFIG_FILE);
+    if local_path.exists() {
+        layers.push((load_file(&local_path)?, ConfigSource::File(local_path)));
+    }
 
-    // Merge: defaults <- project <- local
-    let merged = merge(merge(defaults, base_config), local_config);
+    // Environment variables override every file
+    layers.push((
+        env_layer_from(|name| env::var(name).ok())?,
+        ConfigSource::Environment,
+    ));
+
+    // Merge: defaults <- global <- project <- local <- environment
+    let resolved = resolve(layers);
+    let merged = &resolved.config;
 
     // Validate required keys
     if merged.docker.image.as_deref().unwrap_or("").is_empty() {
@@ -92,12 +309,42 @@ pub fn load_final() -> Result<Config, ConfigError> {
         return Err(ConfigError::MissingRequiredKey("docker.setup-command".to_string()));
     }
 
-    validate_ports(&merged)?;
+    validate_ports(merged)?;
+    validate_workdir(merged)?;
+    merged.validate()?;
 
-    Ok(merged)
+    Ok(resolved)
+}
+
+fn validate_workdir(config: &Config) -> Result<(), ConfigError> {
+    let Some(workdir) = config.docker.workdir.as_deref() else {
+        return Ok(());
+    };
+    let plain = workdir
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '/' | '.' | '_' | '-'));
+    if !plain || !workdir.starts_with('/') || workdir.trim_end_matches('/').is_empty() {
+        return Err(ConfigError::ParseError(format!(
+            "Invalid docker.workdir: '{workdir}' must be an absolute path other than / made of \
+             letters, digits, '.', '_', '-' and '/'"
+        )));
+    }
+    Ok(())
 }
 
 fn validate_ports(config: &Config) -> Result<(), ConfigError> {
+    // Port 0 asks the OS for any free port, so it cannot bound an allocation range.
+    if config.ports.range_start == Some(0) {
+        return Err(ConfigError::ParseError("Invalid forwarded port range start: 0".to_string()));
+    }
+    if let (Some(start), Some(end)) = (config.ports.range_start, config.ports.range_end)
+        && end < start
+    {
+        return Err(ConfigError::ParseError(format!(
+            "Invalid forwarded port range: {start}-{end}"
+        )));
+    }
+
     let mut seen = HashSet::new();
 
     for port in &config.ports.ports {
@@ -120,8 +367,17 @@ fn validate_ports(config: &Config) -> Result<(), ConfigError> {
 
 #[cfg(test)]
 mod tests {
-    use super::validate_ports;
+    use std::fs;
+
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    use super::{
+        ConfigSource, env_layer_from, find_config_file, global_config_path_from, merge, resolve,
+        validate_ports, validate_workdir,
+    };
     use crate::config::{Config, DockerConfig, PortsConfig, ProjectConfig, ForwardedPort};
+    use crate::domain::PortProtocol;
 
     fn base_config(ports: Vec<ForwardedPort>) -> Config {
         Config {
@@ -129,8 +385,21 @@ mod tests {
             docker: DockerConfig {
                 image: Some("image".to_string()),
                 setup_command: Some("setup".to_string()),
+                setup_timeout: None,
+                pull_retries: None,
+                exec_retries: None,
+                compress_uploads: None,
+                ready_command: None,
+                restart_policy: None,
+                workdir: None,
+            },
+            ports: PortsConfig {
+                ports,
+                ..PortsConfig::default()
             },
-            ports: PortsConfig { ports },
+            compute: None,
+            audit: Default::default(),
+            templates: Vec::new(),
         }
     }
 
@@ -140,10 +409,12 @@ mod tests {
             ForwardedPort {
                 name: "Backend".to_string(),
                 target: 8080,
+                protocol: PortProtocol::Tcp,
             },
             ForwardedPort {
                 name: "Frontend".to_string(),
                 target: 8081,
+                protocol: PortProtocol::Tcp,
             },
         ]);
 
@@ -156,10 +427,12 @@ mod tests {
             ForwardedPort {
                 name: "My Service".to_string(),
                 target: 8080,
+                protocol: PortProtocol::Tcp,
             },
             ForwardedPort {
                 name: "my-service".to_string(),
                 target: 8081,
+                protocol: PortProtocol::Tcp,
             },
         ]);
 
@@ -172,6 +445,7 @@ mod tests {
         let config = base_config(vec![ForwardedPort {
             name: "----".to_string(),
             target: 8080,
+            protocol: PortProtocol::Tcp,
         }]);
 
         let err = validate_ports(&config).expect_err("invalid slug rejected");
@@ -183,9 +457,169 @@ mod tests {
         let config = base_config(vec![ForwardedPort {
             name: "backend".to_string(),
             target: 0,
+            protocol: PortProtocol::Tcp,
         }]);
 
         let err = validate_ports(&config).expect_err("invalid target rejected");
         assert!(err.to_string().contains("Invalid forwarded port target"));
     }
+
+    #[test]
+    fn validate_ports_rejects_inverted_range() {
+        let mut config = base_config(Vec::new());
+        config.ports.range_start = Some(9000);
+        config.ports.range_end = Some(8000);
+
+        let err = validate_ports(&config).expect_err("inverted range rejected");
+        assert!(err.to_string().contains("Invalid forwarded port range"));
+    }
+
+    #[test]
+    fn validate_ports_rejects_zero_range_start() {
+        let mut config = base_config(Vec::new());
+        config.ports.range_start = Some(0);
+
+        let err = validate_ports(&config).expect_err("zero range start rejected");
+        assert!(err.to_string().contains("Invalid forwarded port range start: 0"));
+    }
+
+    #[test]
+    fn validate_workdir_requires_absolute_path() {
+        let mut config = base_config(Vec::new());
+        assert!(validate_workdir(&config).is_ok());
+
+        config.docker.workdir = Some("/workspace".to_string());
+        assert!(validate_workdir(&config).is_ok());
+
+        for workdir in ["workspace", "/", "", "/my project", "/a'b"] {
+            config.docker.workdir = Some(workdir.to_string());
+            let err = validate_workdir(&config).expect_err("invalid workdir accepted");
+            assert!(err.to_string().contains("Invalid docker.workdir"));
+        }
+    }
+
+    #[test]
+    fn find_config_file_walks_up_to_project_root() {
+        let root = tempfile::tempdir().expect("tempdir");
+        let nested = root.path().join("crates/app/src");
+        fs::create_dir_all(&nested).expect("create nested");
+        fs::write(root.path().join(".litterbox.toml"), "").expect("write config");
+
+        assert_eq!(find_config_file(&nested), Some(root.path().join(".litterbox.toml")));
+    }
+
+    #[test]
+    fn find_config_file_prefers_dotfile_and_accepts_plain_name() {
+        let root = tempfile::tempdir().expect("tempdir");
+        fs::write(root.path().join("litterbox.toml"), "").expect("write config");
+        assert_eq!(find_config_file(root.path()), Some(root.path().join("litterbox.toml")));
+
+        fs::write(root.path().join(".litterbox.toml"), "").expect("write config");
+        assert_eq!(find_config_file(root.path()), Some(root.path().join(".litterbox.toml")));
+    }
+
+    #[test]
+    fn find_config_file_stops_at_git_root() {
+        let outer = tempfile::tempdir().expect("tempdir");
+        fs::write(outer.path().join(".litterbox.toml"), "").expect("write config");
+        let repo = outer.path().join("repo");
+        let nested = repo.join("src");
+        fs::create_dir_all(repo.join(".git")).expect("create .git");
+        fs::create_dir_all(&nested).expect("create nested");
+
+        assert_eq!(find_config_file(&nested), None);
+    }
+
+    #[test]
+    fn global_config_path_prefers_xdg_config_home() {
+        let path = global_config_path_from(
+            Some(OsString::from("/xdg")),
+            Some(OsString::from("/home/user")),
+        );
+        assert_eq!(path, Some(PathBuf::from("/xdg/litterbox/config.toml")));
+    }
+
+    #[test]
+    fn global_config_path_falls_back_to_home() {
+        let expected = Some(PathBuf::from("/home/user/.config/litterbox/config.toml"));
+        assert_eq!(
+            global_config_path_from(None, Some(OsString::from("/home/user"))),
+            expected
+        );
+        assert_eq!(
+            global_config_path_from(
+                Some(OsString::from("relative")),
+                Some(OsString::from("/home/user"))
+            ),
+            expected
+        );
+        assert_eq!(global_config_path_from(None, None), None);
+    }
+
+    #[test]
+    fn env_override_replaces_file_values() {
+        let vars = [
+            ("LITTERBOX_IMAGE", "ci-image"),
+            ("LITTERBOX_SETUP_COMMAND", "make setup"),
+            ("LITTERBOX_PROJECT_SLUG", "ci-project"),
+            ("LITTERBOX_PORT_RANGE_START", "9000"),
+            ("LITTERBOX_PORT_RANGE_END", "9100"),
+        ];
+        let lookup = |name: &str| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        };
+
+        let layer = env_layer_from(lookup).expect("env layer");
+        let config = merge(base_config(Vec::new()), layer);
+
+        assert_eq!(config.docker.image.as_deref(), Some("ci-image"));
+        assert_eq!(config.docker.setup_command.as_deref(), Some("make setup"));
+        assert_eq!(config.project.slug.as_deref(), Some("ci-project"));
+        assert_eq!(config.ports.range_start, Some(9000));
+        assert_eq!(config.ports.range_end, Some(9100));
+    }
+
+    #[test]
+    fn env_override_keeps_values_when_unset_or_empty() {
+        let lookup = |name: &str| (name == "LITTERBOX_IMAGE").then(String::new);
+        let layer = env_layer_from(lookup).expect("env layer");
+        assert_eq!(merge(base_config(Vec::new()), layer), base_config(Vec::new()));
+    }
+
+    #[test]
+    fn env_override_rejects_invalid_ports() {
+        let lookup = |name: &str| (name == "LITTERBOX_PORT_RANGE_END").then(|| "99999".to_string());
+        let err = env_layer_from(lookup).expect_err("invalid port");
+        assert!(err.to_string().contains("LITTERBOX_PORT_RANGE_END"));
+    }
+
+    #[test]
+    fn resolve_records_last_layer_setting_each_key() {
+        let project_path = PathBuf::from("/repo/.litterbox.toml");
+        let mut defaults = base_config(Vec::new());
+        defaults.docker.image = None;
+        defaults.docker.setup_command = None;
+        defaults.project.slug = Some("repo".to_string());
+        let project = base_config(Vec::new());
+        let env = env_layer_from(|name| (name == "LITTERBOX_IMAGE").then(|| "ci".to_string()))
+            .expect("env layer");
+
+        let resolved = resolve(vec![
+            (defaults, ConfigSource::Default),
+            (project, ConfigSource::File(project_path.clone())),
+            (env, ConfigSource::Environment),
+        ]);
+
+        assert_eq!(resolved.config.docker.image.as_deref(), Some("ci"));
+        assert_eq!(resolved.config.docker.setup_command.as_deref(), Some("setup"));
+        assert_eq!(resolved.sources["project.slug"], ConfigSource::Default);
+        assert_eq!(resolved.sources["docker.image"], ConfigSource::Environment);
+        assert_eq!(
+            resolved.sources["docker.setup-command"],
+            ConfigSource::File(project_path)
+        );
+        assert!(!resolved.sources.contains_key("compute"));
+    }
 }
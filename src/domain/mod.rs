@@ -1,19 +1,121 @@
 use std::fmt;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct SandboxConfig {
     pub image: String,
     pub setup_command: Option<String>,
+    pub setup_timeout_secs: Option<u64>,
     pub forwarded_ports: Vec<ForwardedPort>,
+    /// Inclusive host port range for forwarded ports, defaulting to 3000-8000.
+    pub port_range: Option<(u16, u16)>,
+    /// Command polled after creation until it exits 0, signalling the sandbox is ready.
+    pub ready_command: Option<String>,
+    /// Git ref the sandbox branch and its files start from, instead of HEAD.
+    pub from_ref: Option<String>,
+    /// Whether the engine restarts the container after it exits.
+    pub restart_policy: Option<RestartPolicy>,
+    /// Extra `KEY=VALUE` environment variables set in the container.
+    pub extra_env: Vec<String>,
+    /// Docker network the container joins, created if missing, so sandboxes can reach
+    /// each other by container name.
+    pub network: Option<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct ForwardedPort {
     pub name: String,
     pub target: u16,
+    pub protocol: PortProtocol,
+}
+
+/// Transport protocol(s) a forwarded port is published for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortProtocol {
+    #[default]
+    Tcp,
+    Udp,
+    Both,
+}
+
+impl PortProtocol {
+    /// The Docker port-spec suffixes (`tcp`, `udp`) this protocol maps to.
+    pub fn docker_protocols(self) -> &'static [&'static str] {
+        match self {
+            PortProtocol::Tcp => &["tcp"],
+            PortProtocol::Udp => &["udp"],
+            PortProtocol::Both => &["tcp", "udp"],
+        }
+    }
+}
+
+/// When the container engine restarts a stopped sandbox container, written as Docker's
+/// `--restart` values: `no`, `on-failure[:max-retries]`, `always`, or `unless-stopped`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum RestartPolicy {
+    No,
+    /// Restart after a non-zero exit, at most this many times (0 means no limit).
+    OnFailure(u32),
+    Always,
+    UnlessStopped,
+}
+
+impl fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestartPolicy::No => write!(f, "no"),
+            RestartPolicy::OnFailure(0) => write!(f, "on-failure"),
+            RestartPolicy::OnFailure(retries) => write!(f, "on-failure:{retries}"),
+            RestartPolicy::Always => write!(f, "always"),
+            RestartPolicy::UnlessStopped => write!(f, "unless-stopped"),
+        }
+    }
+}
+
+impl std::str::FromStr for RestartPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split_once(':') {
+            None => match value {
+                "no" => Ok(RestartPolicy::No),
+                "on-failure" => Ok(RestartPolicy::OnFailure(0)),
+                "always" => Ok(RestartPolicy::Always),
+                "unless-stopped" => Ok(RestartPolicy::UnlessStopped),
+                _ => Err(format!(
+                    "invalid restart policy '{value}': expected no, on-failure[:N], always, \
+                     or unless-stopped"
+                )),
+            },
+            Some(("on-failure", retries)) => retries
+                .parse()
+                .map(RestartPolicy::OnFailure)
+                .map_err(|_| format!("invalid on-failure retry count '{retries}'")),
+            Some(_) => Err(format!(
+                "invalid restart policy '{value}': only on-failure takes a retry count"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for RestartPolicy {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<RestartPolicy> for String {
+    fn from(policy: RestartPolicy) -> Self {
+        policy.to_string()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
@@ -24,17 +126,92 @@ pub struct ForwardedPortMapping {
     pub env_var: String,
 }
 
+/// Which side of a sandbox is copied over the other by a sync.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncDirection {
+    ContainerToHost,
+    HostToContainer,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SyncResult {
+    pub files_transferred: usize,
+    pub bytes_transferred: u64,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct ExecutionResult {
     pub exit_code: i32,
     pub stdout: String,
     pub stderr: String,
+    /// Wall-clock time from starting the exec until its output stream closed.
+    pub duration_ms: Option<u64>,
+    /// Peak resident set size of the process, when the engine reports it.
+    /// Docker's exec inspect does not, so it is always `None` there.
+    pub max_rss_bytes: Option<u64>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SnapshotInfo {
+    pub oid: String,
+    pub timestamp: i64,
+    pub message: String,
+}
+
+/// Outcome of pruning a sandbox's snapshot history.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PruneResult {
+    /// Snapshots that no longer appear on the branch.
+    pub removed: usize,
+    /// Snapshots left on the branch, counting `squash`.
+    pub kept: usize,
+    /// The commit standing in for every removed snapshot when none were kept.
+    pub squash: Option<String>,
+}
+
+/// A sandbox as recorded in git, without consulting the container runtime.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SandboxEntry {
+    pub slug: String,
+    pub branch_name: String,
+    /// Latest snapshot on the sandbox branch, if any snapshot has been taken.
+    pub snapshot_oid: Option<git2::Oid>,
+    /// Commit time of the latest snapshot, in Unix seconds.
+    pub last_snapshot_time: Option<i64>,
+    /// Snapshots on the sandbox branch that are not reachable from HEAD.
+    pub snapshot_count: usize,
+}
+
+/// How a sandbox branch is folded into its target branch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeStrategy {
+    /// Move the target to the sandbox tip; fails if the target has diverged.
+    FastForward,
+    /// Record the sandbox's combined changes as one commit on the target.
+    Squash,
+    /// Create a merge commit with both tips as parents.
+    #[default]
+    Merge,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeResult {
+    /// The target's tip after the merge, or its unchanged tip when conflicts stopped it.
+    pub oid: git2::Oid,
+    pub had_conflicts: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SandboxStatus {
     Active,
     Paused,
+    /// The container has been created or is restarting but is not yet running.
+    Starting,
+    /// The container is being removed.
+    Stopping,
     Error(String),
 }
 
@@ -45,6 +222,18 @@ pub struct SandboxMetadata {
     pub container_id: String,
     pub status: SandboxStatus,
     pub forwarded_ports: Vec<ForwardedPortMapping>,
+    /// Unix timestamp of when the sandbox was created.
+    pub created_at: Option<u64>,
+    /// Unix timestamp of the latest snapshot on the sandbox branch.
+    pub last_active_at: Option<u64>,
+    /// Address of the container on its Docker network, reachable from other containers.
+    pub container_ip: Option<String>,
+    /// Names other containers on a shared network can reach this one by, such as its slug.
+    pub network_aliases: Vec<String>,
+    /// Content-addressed id of the image the container runs, e.g. `sha256:...`.
+    pub image_digest: Option<String>,
+    /// RFC 3339 time the container was last started.
+    pub started_at: Option<String>,
 }
 
 impl fmt::Display for SandboxConfig {
@@ -55,6 +244,10 @@ impl fmt::Display for SandboxConfig {
             None => write!(f, "setup_command=<none>"),
         }?;
 
+        if let Some(timeout) = self.setup_timeout_secs {
+            write!(f, ", setup_timeout_secs={timeout}")?;
+        }
+
         write!(f, ", forwarded_ports={}", self.forwarded_ports.len())
     }
 }
@@ -74,6 +267,8 @@ impl fmt::Display for SandboxStatus {
         match self {
             SandboxStatus::Active => write!(f, "active"),
             SandboxStatus::Paused => write!(f, "paused"),
+            SandboxStatus::Starting => write!(f, "starting"),
+            SandboxStatus::Stopping => write!(f, "stopping"),
             SandboxStatus::Error(message) => write!(f, "error: {}", message),
         }
     }
@@ -107,10 +302,18 @@ pub enum SandboxError {
     Compute(#[from] ComputeError),
     #[error("Setup command failed with exit code {exit_code}: {stderr}")]
     SetupCommandFailed { exit_code: i32, stderr: String },
+    #[error("Sandbox was not ready after {timeout_secs}s: '{command}' did not succeed")]
+    NotReady { command: String, timeout_secs: u64 },
+    #[error("Failed to restore snapshot '{reference}': {message}")]
+    SnapshotRestoreFailed { reference: String, message: String },
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
     #[error("Configuration error: {0}")]
     Config(String),
+    #[error("Interrupted by a shutdown signal")]
+    Interrupted,
+    #[error("Argument contains control character {character:?}, which cannot be passed to a shell")]
+    UnsafeShellArgument { character: char },
 }
 
 #[derive(Error, Debug)]
@@ -123,6 +326,8 @@ pub enum ScmError {
     BranchCreate { #[source] source: git2::Error },
     #[error("Git branch deletion failed: {source}")]
     BranchDelete { #[source] source: git2::Error },
+    #[error("Git branch rename failed: {source}")]
+    BranchRename { #[source] source: git2::Error },
     #[error("Git archive failed: {source}")]
     Archive { #[source] source: git2::Error },
     #[error("Git status failed: {source}")]
@@ -141,8 +346,26 @@ pub enum ScmError {
     Head { #[source] source: git2::Error },
     #[error("Git reference failed: {source}")]
     Reference { #[source] source: git2::Error },
+    #[error("Git log failed: {source}")]
+    Log { #[source] source: git2::Error },
+    #[error("Git diff failed: {source}")]
+    Diff { #[source] source: git2::Error },
+    #[error("Git push failed: {source}")]
+    Push { #[source] source: git2::Error },
+    #[error("Git merge failed: {source}")]
+    Merge { #[source] source: git2::Error },
+    #[error("Git cherry-pick failed: {source}")]
+    CherryPick { #[source] source: git2::Error },
+    #[error("Git stash failed: {source}")]
+    Stash { #[source] source: git2::Error },
+    #[error("Cannot fast-forward {target}: it has commits the sandbox branch does not")]
+    NotFastForward { target: String },
     #[error("failed to apply patch: {message}")]
     ApplyPatch { message: String },
+    #[error("Patch conflicts with: {}", .conflicted_files.join(", "))]
+    PatchConflict { conflicted_files: Vec<String> },
+    #[error("Cherry-pick of {commit} conflicts with: {}", .conflicted_files.join(", "))]
+    CherryPickConflict { commit: String, conflicted_files: Vec<String> },
 }
 
 #[derive(Error, Debug)]
@@ -153,8 +376,14 @@ pub enum ComputeError {
     ImageInspect { #[source] source: bollard::errors::Error },
     #[error("Docker image pull failed: {source}")]
     ImagePull { #[source] source: bollard::errors::Error },
+    #[error("Docker image build failed: {source}")]
+    ImageBuild { #[source] source: bollard::errors::Error },
+    #[error("Docker image build failed: {message}")]
+    ImageBuildOutput { message: String },
     #[error("Docker container provisioning failed: {source}")]
     ContainerProvision { #[source] source: bollard::errors::Error },
+    #[error("Docker container listing failed: {source}")]
+    ContainerList { #[source] source: bollard::errors::Error },
     #[error("Docker container inspection failed: {source}")]
     ContainerInspect { #[source] source: bollard::errors::Error },
     #[error("Docker pause failed: {source}")]
@@ -163,19 +392,133 @@ pub enum ComputeError {
     ContainerResume { #[source] source: bollard::errors::Error },
     #[error("Docker delete failed: {source}")]
     ContainerDelete { #[source] source: bollard::errors::Error },
+    #[error("Docker kill failed: {source}")]
+    ContainerKill { #[source] source: bollard::errors::Error },
+    #[error("Docker commit failed: {source}")]
+    ContainerCommit { #[source] source: bollard::errors::Error },
+    #[error("Docker rename failed: {source}")]
+    ContainerRename { #[source] source: bollard::errors::Error },
     #[error("Docker exec failed: {source}")]
     ContainerExec { #[source] source: bollard::errors::Error },
     #[error("Docker upload failed: {source}")]
     ContainerUpload { #[source] source: bollard::errors::Error },
     #[error("Docker download failed: {source}")]
     ContainerDownload { #[source] source: bollard::errors::Error },
+    #[error("Docker logs failed: {source}")]
+    ContainerLogs { #[source] source: bollard::errors::Error },
+    #[error("Docker stats failed: {source}")]
+    ContainerStats { #[source] source: bollard::errors::Error },
+    #[error("Docker network creation failed: {source}")]
+    NetworkCreate { #[source] source: bollard::errors::Error },
+    #[error("Docker network connect failed: {source}")]
+    NetworkConnect { #[source] source: bollard::errors::Error },
+    #[error("Docker container '{container_id}' not found")]
+    ContainerNotFound { container_id: String },
+}
+
+/// Stable numeric error classes, so MCP clients can tell failures apart without parsing
+/// messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[repr(u16)]
+pub enum LitterboxErrorCode {
+    Internal = 1000,
+    NotFound = 1001,
+    AlreadyExists = 1002,
+    SetupFailed = 1003,
+    Unauthorized = 1004,
+    InvalidName = 1005,
+    Conflict = 1006,
+    Config = 1007,
+    Interrupted = 1008,
+    InvalidArgument = 1009,
+}
+
+impl LitterboxErrorCode {
+    pub fn code(self) -> u16 {
+        self as u16
+    }
+}
+
+impl SandboxError {
+    pub fn error_code(&self) -> LitterboxErrorCode {
+        match self {
+            SandboxError::InvalidName { .. } => LitterboxErrorCode::InvalidName,
+            SandboxError::SandboxExists { .. } => LitterboxErrorCode::AlreadyExists,
+            SandboxError::SandboxNotFound { .. } => LitterboxErrorCode::NotFound,
+            SandboxError::SetupCommandFailed { .. } | SandboxError::NotReady { .. } => {
+                LitterboxErrorCode::SetupFailed
+            }
+            SandboxError::Config(_) => LitterboxErrorCode::Config,
+            SandboxError::Interrupted => LitterboxErrorCode::Interrupted,
+            SandboxError::UnsafeShellArgument { .. } => LitterboxErrorCode::InvalidArgument,
+            SandboxError::Scm(
+                ScmError::NotFastForward { .. }
+                | ScmError::ApplyPatch { .. }
+                | ScmError::PatchConflict { .. }
+                | ScmError::CherryPickConflict { .. },
+            ) => LitterboxErrorCode::Conflict,
+            SandboxError::Scm(ScmError::Push { source })
+                if source.code() == git2::ErrorCode::Auth =>
+            {
+                LitterboxErrorCode::Unauthorized
+            }
+            SandboxError::Scm(ScmError::Stash { source }) => match source.code() {
+                git2::ErrorCode::NotFound => LitterboxErrorCode::NotFound,
+                git2::ErrorCode::Conflict | git2::ErrorCode::MergeConflict => {
+                    LitterboxErrorCode::Conflict
+                }
+                _ => LitterboxErrorCode::Internal,
+            },
+            SandboxError::Compute(ComputeError::ContainerNotFound { .. }) => {
+                LitterboxErrorCode::NotFound
+            }
+            SandboxError::Compute(error) => match error.engine_status() {
+                Some(404) => LitterboxErrorCode::NotFound,
+                Some(401 | 403) => LitterboxErrorCode::Unauthorized,
+                _ => LitterboxErrorCode::Internal,
+            },
+            _ => LitterboxErrorCode::Internal,
+        }
+    }
+}
+
+impl ComputeError {
+    /// `ContainerNotFound` when `source` is the engine's 404 response for `container_id`;
+    /// otherwise `source` is handed back for the operation's own variant.
+    pub fn from_bollard_404(
+        container_id: &str,
+        source: bollard::errors::Error,
+    ) -> Result<Self, bollard::errors::Error> {
+        match source {
+            bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            } => Ok(ComputeError::ContainerNotFound {
+                container_id: container_id.to_string(),
+            }),
+            source => Err(source),
+        }
+    }
+
+    /// HTTP status of the engine's error response, when the failure was one.
+    fn engine_status(&self) -> Option<u16> {
+        let source = std::error::Error::source(self)?;
+        match source.downcast_ref::<bollard::errors::Error>()? {
+            bollard::errors::Error::DockerResponseServerError { status_code, .. } => {
+                Some(*status_code)
+            }
+            _ => None,
+        }
+    }
 }
 
 pub fn slugify(name: &str) -> String {
     let mut slug = String::new();
     let mut last_was_dash = false;
 
-    for ch in name.chars() {
+    // NFKD splits accented letters into a base letter plus combining marks, so
+    // dropping the marks leaves the ASCII base (e.g. `café` becomes `cafe`).
+    for ch in name.nfkd().filter(|ch| !is_combining_mark(*ch)) {
         let lower = ch.to_ascii_lowercase();
         if lower.is_ascii_alphanumeric() {
             slug.push(lower);
@@ -191,7 +534,31 @@ pub fn slugify(name: &str) -> String {
 
 const MAX_SLUG_LENGTH: usize = 63;
 
+/// Names that would collide with CLI subcommands, MCP tools, or common service names.
+pub const RESERVED_NAMES: &[&str] = &[
+    "api", "web", "all", "help", "init", "list", "status", "create", "stdio", "http", "pause",
+    "resume", "delete", "rename", "prune", "shell", "upload", "download", "logs", "diff",
+    "snapshot", "export", "import", "config", "docgen",
+];
+
+pub fn is_reserved_name(slug: &str) -> bool {
+    RESERVED_NAMES.contains(&slug)
+}
+
 pub fn validate_slug(original: &str, slug: &str) -> Result<(), SandboxError> {
+    validate_slug_format(original, slug)?;
+
+    if is_reserved_name(slug) {
+        return Err(SandboxError::InvalidName {
+            name: original.to_string(),
+            reason: format!("'{slug}' is a reserved name."),
+        });
+    }
+    Ok(())
+}
+
+/// Checks length and character set only, for identifiers that are not sandbox names.
+pub fn validate_slug_format(original: &str, slug: &str) -> Result<(), SandboxError> {
     // 63 keeps identifiers manageable and aligns with the spec requirement.
     let valid = !slug.is_empty()
         && slug.len() <= MAX_SLUG_LENGTH
@@ -210,7 +577,15 @@ pub fn validate_slug(original: &str, slug: &str) -> Result<(), SandboxError> {
     }
 }
 
+/// Slugifies the name of an existing sandbox, which may predate a reserved name.
 pub fn slugify_name(name: &str) -> Result<String, SandboxError> {
+    let slug = slugify(name);
+    validate_slug_format(name, &slug)?;
+    Ok(slug)
+}
+
+/// Slugifies the name of a sandbox about to be created, rejecting reserved names.
+pub fn slugify_new_name(name: &str) -> Result<String, SandboxError> {
     let slug = slugify(name);
     validate_slug(name, &slug)?;
     Ok(slug)
@@ -232,6 +607,46 @@ mod tests {
         assert_eq!(slug, "hello-world");
     }
 
+    fn assert_slug_charset(slug: &str) {
+        assert!(
+            slug.chars()
+                .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-'),
+            "unexpected characters in {slug:?}"
+        );
+    }
+
+    #[test]
+    fn slugify_transliterates_accented_latin() {
+        assert_eq!(slugify("Café Crème"), "cafe-creme");
+        assert_eq!(slugify("Ångström naïve"), "angstrom-naive");
+    }
+
+    #[test]
+    fn slugify_normalises_compatibility_characters() {
+        assert_eq!(slugify("ＡＢＣ①"), "abc1");
+    }
+
+    #[test]
+    fn slugify_arabic_stays_in_charset() {
+        let slug = slugify("ميزة feature");
+        assert_slug_charset(&slug);
+        assert_eq!(slug, "feature");
+    }
+
+    #[test]
+    fn slugify_cjk_stays_in_charset() {
+        let slug = slugify("功能 branch 機能");
+        assert_slug_charset(&slug);
+        assert_eq!(slug, "branch");
+    }
+
+    #[test]
+    fn slugify_emoji_stays_in_charset() {
+        let slug = slugify("fix 🐛 bug 🚀");
+        assert_slug_charset(&slug);
+        assert_eq!(slug, "fix-bug");
+    }
+
     #[test]
     fn slugify_name_rejects_empty_slug() {
         let err = slugify_name("----").expect_err("expected invalid name");
@@ -241,6 +656,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn slugify_new_name_rejects_reserved_names() {
+        for reserved in RESERVED_NAMES {
+            let err = slugify_new_name(reserved).expect_err("expected reserved name");
+            assert_eq!(
+                err.to_string(),
+                format!("Invalid sandbox name: '{reserved}'. '{reserved}' is a reserved name.")
+            );
+        }
+    }
+
+    #[test]
+    fn slugify_new_name_rejects_reserved_names_after_slugifying() {
+        assert!(slugify_new_name("  API ").is_err());
+        assert!(slugify_new_name("api-server").is_ok());
+    }
+
+    #[test]
+    fn slugify_name_allows_existing_reserved_names() {
+        assert_eq!(slugify_name("Status").expect("existing sandbox"), "status");
+    }
+
+    #[test]
+    fn validate_slug_format_allows_reserved_names() {
+        assert!(validate_slug_format("web", "web").is_ok());
+    }
+
+    #[test]
+    fn sandbox_status_serializes_lowercase() {
+        let active = serde_json::to_string(&SandboxStatus::Active).expect("serialize");
+        let error = serde_json::to_string(&SandboxStatus::Error("boom".to_string()))
+            .expect("serialize");
+
+        assert_eq!(active, "\"active\"");
+        assert_eq!(error, "{\"error\":\"boom\"}");
+    }
+
+    #[test]
+    fn transitional_statuses_serialize_and_display() {
+        let starting = serde_json::to_string(&SandboxStatus::Starting).expect("serialize");
+        let stopping = serde_json::to_string(&SandboxStatus::Stopping).expect("serialize");
+
+        assert_eq!(starting, "\"starting\"");
+        assert_eq!(stopping, "\"stopping\"");
+        assert_eq!(SandboxStatus::Starting.to_string(), "starting");
+        assert_eq!(SandboxStatus::Stopping.to_string(), "stopping");
+    }
+
     #[test]
     fn setup_command_failed_formats_error() {
         let err = SandboxError::SetupCommandFailed {
@@ -251,4 +714,57 @@ mod tests {
         assert!(message.contains("exit code 1"));
         assert!(message.contains("boom"));
     }
+
+    #[test]
+    fn error_codes_classify_sandbox_errors() {
+        let not_found = SandboxError::SandboxNotFound {
+            name: "feature".to_string(),
+        };
+        let missing_container = SandboxError::Compute(ComputeError::ContainerInspect {
+            source: bollard::errors::Error::DockerResponseServerError {
+                status_code: 404,
+                message: "no such container".to_string(),
+            },
+        });
+        let pull_denied = SandboxError::Compute(ComputeError::ImagePull {
+            source: bollard::errors::Error::DockerResponseServerError {
+                status_code: 401,
+                message: "unauthorized".to_string(),
+            },
+        });
+
+        assert_eq!(not_found.error_code(), LitterboxErrorCode::NotFound);
+        let container_not_found = SandboxError::Compute(ComputeError::ContainerNotFound {
+            container_id: "litterbox-repo-feature".to_string(),
+        });
+        assert_eq!(container_not_found.error_code(), LitterboxErrorCode::NotFound);
+        assert_eq!(missing_container.error_code(), LitterboxErrorCode::NotFound);
+        assert_eq!(pull_denied.error_code(), LitterboxErrorCode::Unauthorized);
+        assert_eq!(
+            SandboxError::Config("bad".to_string()).error_code().code(),
+            1007
+        );
+        assert_eq!(
+            SandboxError::Io(std::io::Error::other("boom")).error_code(),
+            LitterboxErrorCode::Internal
+        );
+    }
+
+    #[test]
+    fn from_bollard_404_only_claims_missing_containers() {
+        let missing = bollard::errors::Error::DockerResponseServerError {
+            status_code: 404,
+            message: "no such container".to_string(),
+        };
+        let conflict = bollard::errors::Error::DockerResponseServerError {
+            status_code: 409,
+            message: "container is paused".to_string(),
+        };
+
+        assert!(matches!(
+            ComputeError::from_bollard_404("abc", missing),
+            Ok(ComputeError::ContainerNotFound { container_id }) if container_id == "abc"
+        ));
+        assert!(ComputeError::from_bollard_404("abc", conflict).is_err());
+    }
 }
@@ -1,16 +1,42 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use bollard::query_parameters::ListContainersOptionsBuilder;
 use clap::{Arg, CommandFactory, Parser, Subcommand};
-use litterbox::compute::DockerCompute;
-use litterbox::domain::{ComputeError, SandboxError, SandboxMetadata, SandboxStatus, slugify_name};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use litterbox::audit::AuditLog;
+use litterbox::compute::{DEFAULT_PULL_RETRIES, DockerCompute, PullProgress};
+use litterbox::config::{AuditConfig, ComputeBackend, Config, ConfigError};
+use litterbox::config_loader;
+use litterbox::domain::{
+    ComputeError, ForwardedPort, SandboxConfig, SandboxError, SandboxMetadata, SandboxStatus,
+    SnapshotInfo, slugify_name, slugify_new_name,
+};
 use litterbox::mcp;
 use litterbox::sandbox::{
-    DockerSandboxProvider, SandboxProvider, branch_name_for_slug, container_name_for_slug,
+    DEFAULT_WORKDIR, DockerSandboxProvider, SandboxProvider, branch_name_for_slug,
+    container_name_for_slug, forwarded_ports_from_inspection, interrupt_creations,
+    restore_snapshot, shutdown_signal,
 };
 use litterbox::scm::{Scm, ThreadSafeScm};
 
+const DEFAULT_LOG_TAIL: usize = 100;
+const SHORT_OID_LENGTH: usize = 10;
+/// How many of the latest snapshots `status` prints.
+const STATUS_RECENT_SNAPSHOTS: usize = 5;
+const PROGRESS_BAR_WIDTH: u64 = 30;
+const EXPORT_MANIFEST: &str = "manifest.json";
+const EXPORT_SRC_DIR: &str = "src";
+const DEFAULT_HTTP_BIND: &str = "127.0.0.1:3000";
+const INIT_CONFIG_FILE: &str = ".litterbox.toml";
+
+static COMPUTE_BACKEND: OnceLock<Option<ComputeBackend>> = OnceLock::new();
+
 #[derive(Parser)]
 #[command(
     author,
@@ -20,24 +46,111 @@ use litterbox::scm::{Scm, ThreadSafeScm};
                   Each sandbox pairs a Git branch with a Docker container for safe, isolated development work."
 )]
 struct Cli {
+    /// Container engine to use, overriding the compute config key
+    #[arg(long, global = true, value_enum)]
+    compute: Option<ComputeBackend>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Create a starter .litterbox.toml
+    ///
+    /// Writes a commented project configuration to the current directory, prompting for
+    /// the image and setup command unless they are given as options.
+    Init {
+        /// Docker image to use for sandboxes
+        #[arg(long)]
+        image: Option<String>,
+
+        /// Command to run when a sandbox is created
+        #[arg(long)]
+        setup: Option<String>,
+
+        /// Overwrite an existing .litterbox.toml
+        #[arg(short, long)]
+        force: bool,
+    },
+
     /// List all sandboxes in the current repository
     ///
-    /// Shows all sandboxes with their current status (active, paused, missing, or error).
+    /// Shows all sandboxes with their current status (active, paused, starting, stopping,
+    /// missing, or error).
     /// Status information requires Docker to be available; otherwise statuses show as unknown.
-    List,
+    List {
+        /// Print sandboxes as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Also print the forwarded port mappings of active sandboxes
+        #[arg(long)]
+        ports: bool,
+    },
+
+    /// Show details of one sandbox
+    ///
+    /// Prints the sandbox's branch, container, live status, image digest, IP address,
+    /// forwarded ports, and most recent snapshots.
+    Status {
+        /// Name of the sandbox to inspect
+        name: String,
+
+        /// Print the details as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Create a new sandbox
+    ///
+    /// Creates a sandbox from the current repository HEAD using the project configuration.
+    /// The image and setup command may be overridden from the command line.
+    Create {
+        /// Name of the sandbox to create
+        name: String,
+
+        /// Docker image to use, overriding docker.image
+        #[arg(long)]
+        image: Option<String>,
+
+        /// Setup command to run, overriding docker.setup-command
+        #[arg(long)]
+        setup: Option<String>,
+
+        /// Commit, tag, or branch to start the sandbox from instead of HEAD
+        #[arg(long)]
+        from_ref: Option<String>,
+
+        /// Docker network to attach the sandbox to, created if it does not exist
+        #[arg(long)]
+        network: Option<String>,
+    },
     
     /// Run the MCP (Model Control Protocol) server over stdio
     ///
     /// Starts the Litterbox MCP server, enabling communication with AI agents and tools
     /// that support the Model Control Protocol. The server uses standard input/output
     /// for communication.
-    Stdio,
+    Stdio {
+        /// Append a JSON line for every tool call to this file
+        #[arg(long)]
+        audit_log: Option<PathBuf>,
+    },
+
+    /// Run the MCP (Model Control Protocol) server over HTTP
+    ///
+    /// Starts the Litterbox MCP server using the streamable HTTP transport with
+    /// Server-Sent Events. Clients connect to the `/mcp` path on the bound address.
+    Http {
+        /// Address to listen on
+        #[arg(long, default_value = DEFAULT_HTTP_BIND)]
+        bind: String,
+
+        /// Append a JSON line for every tool call to this file
+        #[arg(long)]
+        audit_log: Option<PathBuf>,
+    },
     
     /// Pause one or more sandboxes
     ///
@@ -89,6 +202,32 @@ enum Commands {
         force: bool,
     },
     
+    /// Rename a sandbox
+    ///
+    /// Renames both the sandbox's Git branch and container.
+    Rename {
+        /// Current name of the sandbox
+        old: String,
+
+        /// New name for the sandbox
+        new: String,
+    },
+    
+    /// Remove orphaned sandbox containers
+    ///
+    /// Removes Litterbox containers in the current repository that have no matching sandbox
+    /// branch. Sandbox branches are kept unless --branches is given, since a sandbox can be
+    /// recreated from its branch.
+    Prune {
+        /// Print what would be removed without removing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Also delete sandbox branches that have no matching container
+        #[arg(long)]
+        branches: bool,
+    },
+    
     /// Execute a shell command in a sandbox
     ///
     /// Runs the specified command inside the sandbox's container and returns the output.
@@ -102,6 +241,111 @@ enum Commands {
         command: Vec<String>,
     },
 
+    /// Copy a host path into a sandbox
+    ///
+    /// Uploads a file or directory from the host into the sandbox's container. Relative
+    /// destination paths are resolved against the sandbox's working directory.
+    Upload {
+        /// Name of the sandbox to upload into
+        name: String,
+
+        /// Host path to upload
+        src: PathBuf,
+
+        /// Destination directory inside the sandbox
+        dest: String,
+
+        /// Resume the sandbox first if it is paused
+        #[arg(long)]
+        resume: bool,
+    },
+
+    /// Copy a sandbox path to the host
+    ///
+    /// Downloads a file or directory from the sandbox's container into a host directory.
+    /// Relative source paths are resolved against the sandbox's working directory.
+    Download {
+        /// Name of the sandbox to download from
+        name: String,
+
+        /// Path inside the sandbox to download
+        src: String,
+
+        /// Host directory to download into
+        dest: PathBuf,
+
+        /// Resume the sandbox first if it is paused
+        #[arg(long)]
+        resume: bool,
+    },
+
+    /// Show a sandbox's container logs
+    ///
+    /// Prints the most recent output of the sandbox's container. With --follow, keeps
+    /// streaming new output until interrupted.
+    Logs {
+        /// Name of the sandbox to show logs for
+        name: String,
+
+        /// Number of lines to show from the end of the logs (default 100)
+        #[arg(long)]
+        tail: Option<usize>,
+
+        /// Keep streaming new output until interrupted
+        #[arg(short, long)]
+        follow: bool,
+    },
+
+    /// Show what changed in a sandbox
+    ///
+    /// Prints a unified diff between the repository HEAD and the sandbox's latest snapshot.
+    Diff {
+        /// Name of the sandbox to diff
+        name: String,
+
+        /// Limit the diff to this path
+        path: Option<String>,
+    },
+
+    /// Browse, restore, and prune sandbox snapshots
+    ///
+    /// Snapshots are the commits Litterbox records on a sandbox's branch as an agent works.
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommands,
+    },
+
+    /// Export a sandbox to an archive
+    ///
+    /// Writes a tar archive containing the sandbox's working tree and the tip of its
+    /// Git branch, which can later be restored with import.
+    Export {
+        /// Name of the sandbox to export
+        name: String,
+
+        /// Path of the archive to write
+        output: PathBuf,
+    },
+
+    /// Import a sandbox from an archive
+    ///
+    /// Creates a new sandbox from an archive written by export, restoring its working tree.
+    /// The sandbox branch starts where the exported one did, or at HEAD when this repository
+    /// does not have that commit.
+    Import {
+        /// Path of the archive to import
+        archive: PathBuf,
+
+        /// Name of the sandbox to create
+        name: String,
+    },
+
+    /// Inspect the project configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
     /// Generate reference documentation
     ///
     /// Prints docs to stdout.
@@ -112,6 +356,55 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum SnapshotCommands {
+    /// List a sandbox's snapshots, newest first
+    List {
+        /// Name of the sandbox to list snapshots for
+        name: String,
+
+        /// Maximum number of snapshots to show
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+
+    /// Restore a sandbox's working tree to a snapshot
+    Restore {
+        /// Name of the sandbox to restore
+        name: String,
+
+        /// Snapshot commit to restore
+        oid: String,
+    },
+
+    /// Drop old snapshots from a sandbox's history
+    ///
+    /// Keeps the newest snapshots within both limits and rewrites them onto the sandbox's
+    /// base, so the latest files survive. When no snapshot is kept, the history collapses
+    /// into a single squash commit.
+    Prune {
+        /// Name of the sandbox to prune
+        name: String,
+
+        /// Drop snapshots older than this many days
+        #[arg(long, required_unless_present = "max_count")]
+        max_age_days: Option<u64>,
+
+        /// Keep at most this many of the newest snapshots
+        #[arg(long)]
+        max_count: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Show the effective configuration after merging every layer
+    ///
+    /// Prints the resolved configuration as TOML, followed by the file (or default or
+    /// environment) each set key was taken from.
+    Show,
+}
+
 #[derive(Subcommand)]
 enum DocgenCommand {
     /// Generate CLI reference documentation
@@ -124,9 +417,20 @@ enum DocgenCommand {
 #[tokio::main]
 async fn main() -> ExitCode {
     let cli = Cli::parse();
+    COMPUTE_BACKEND.get_or_init(|| cli.compute);
     match cli.command {
-        Commands::List => handle_list().await,
-        Commands::Stdio => handle_stdio().await,
+        Commands::Init { image, setup, force } => handle_init(image, setup, force),
+        Commands::List { json, ports } => handle_list(json, ports).await,
+        Commands::Status { name, json } => handle_status(name, json).await,
+        Commands::Create {
+            name,
+            image,
+            setup,
+            from_ref,
+            network,
+        } => with_create_rollback(handle_create(name, image, setup, from_ref, network)).await,
+        Commands::Stdio { audit_log } => handle_stdio(audit_log, cli.compute).await,
+        Commands::Http { bind, audit_log } => handle_http(bind, audit_log, cli.compute).await,
         Commands::Pause {
             name,
             all_envs,
@@ -134,29 +438,182 @@ async fn main() -> ExitCode {
         } => handle_pause(name, all_envs, all_repos).await,
         Commands::Resume { name } => handle_resume(name).await,
         Commands::Delete { name, force } => handle_delete(name, force).await,
+        Commands::Rename { old, new } => handle_rename(old, new).await,
+        Commands::Prune { dry_run, branches } => handle_prune(dry_run, branches).await,
         Commands::Shell { name, command } => handle_shell(name, command).await,
+        Commands::Upload {
+            name,
+            src,
+            dest,
+            resume,
+        } => handle_upload(name, src, dest, resume).await,
+        Commands::Download {
+            name,
+            src,
+            dest,
+            resume,
+        } => handle_download(name, src, dest, resume).await,
+        Commands::Logs { name, tail, follow } => handle_logs(name, tail, follow).await,
+        Commands::Diff { name, path } => handle_diff(name, path),
+        Commands::Snapshot { command } => match command {
+            SnapshotCommands::List { name, limit } => handle_snapshot_list(name, limit),
+            SnapshotCommands::Restore { name, oid } => handle_snapshot_restore(name, oid).await,
+            SnapshotCommands::Prune {
+                name,
+                max_age_days,
+                max_count,
+            } => handle_snapshot_prune(name, max_age_days, max_count),
+        },
+        Commands::Export { name, output } => handle_export(name, output).await,
+        Commands::Import { archive, name } => {
+            with_create_rollback(handle_import(archive, name)).await
+        }
+        Commands::Config { command } => match command {
+            ConfigCommands::Show => handle_config_show(),
+        },
         Commands::Docgen { kind } => handle_docgen(kind),
     }
 }
 
-async fn handle_stdio() -> ExitCode {
+/// Runs a command that creates a sandbox with SIGINT/SIGTERM taken over, so an interrupted
+/// creation rolls back before the process exits with the signal's conventional status.
+async fn with_create_rollback(command: impl Future<Output = ExitCode>) -> ExitCode {
+    let handler = tokio::spawn(async {
+        let signal = shutdown_signal().await;
+        interrupt_creations().await;
+        std::process::exit(signal.exit_status());
+    });
+    let code = command.await;
+    handler.abort();
+    code
+}
+
+async fn handle_stdio(audit_log: Option<PathBuf>, compute: Option<ComputeBackend>) -> ExitCode {
     // Load and print config for debugging
-    match litterbox::config_loader::load_final() {
+    let audit_config = match litterbox::config_loader::load_final() {
         Ok(config) => {
             eprintln!("Loaded configuration: {:#?}", config);
+            if let Err(error) = config.validate_async().await {
+                eprintln!("Warning: {}", error);
+            }
+            config.audit
         }
         Err(error) => {
             eprintln!("Warning: Failed to load config: {}", error);
+            AuditConfig::default()
         }
-    }
+    };
+    let audit = match AuditLog::from_settings(&audit_config, audit_log.as_deref()) {
+        Ok(audit) => audit,
+        Err(error) => return report_error("stdio", format!("cannot open audit log: {error}")),
+    };
 
-    if let Err(error) = mcp::run_stdio().await {
+    if let Err(error) = mcp::run_stdio(audit, compute).await {
         return report_error("stdio", error);
     }
     ExitCode::from(0)
 }
 
-async fn handle_list() -> ExitCode {
+async fn handle_http(
+    bind: String,
+    audit_log: Option<PathBuf>,
+    compute: Option<ComputeBackend>,
+) -> ExitCode {
+    let bind_addr = match bind.parse::<SocketAddr>() {
+        Ok(bind_addr) => bind_addr,
+        Err(error) => return report_error("http", format!("invalid bind address {bind}: {error}")),
+    };
+    let audit_config = match litterbox::config_loader::load_final() {
+        Ok(config) => config.audit,
+        Err(error) => {
+            eprintln!("Warning: Failed to load config: {}", error);
+            AuditConfig::default()
+        }
+    };
+    let audit = match AuditLog::from_settings(&audit_config, audit_log.as_deref()) {
+        Ok(audit) => audit,
+        Err(error) => return report_error("http", format!("cannot open audit log: {error}")),
+    };
+
+    if let Err(error) = mcp::run_http(bind_addr, audit, compute).await {
+        return report_error("http", error);
+    }
+    ExitCode::from(0)
+}
+
+fn handle_init(image: Option<String>, setup: Option<String>, force: bool) -> ExitCode {
+    let path = Path::new(INIT_CONFIG_FILE);
+    if path.exists() && !force {
+        return report_error(
+            "init",
+            format!("{INIT_CONFIG_FILE} already exists; pass --force to overwrite it"),
+        );
+    }
+    let image = match image.map_or_else(|| prompt("Docker image (docker.image)"), Ok) {
+        Ok(image) => image,
+        Err(error) => return report_error("init", error),
+    };
+    let setup = match setup.map_or_else(|| prompt("Setup command (docker.setup-command)"), Ok) {
+        Ok(setup) => setup,
+        Err(error) => return report_error("init", error),
+    };
+    if let Err(error) = fs::write(path, render_init_config(&image, &setup)) {
+        return report_error("init", error);
+    }
+
+    println!("Wrote {INIT_CONFIG_FILE}");
+    println!("  docker.image = {image}");
+    println!("  docker.setup-command = {setup}");
+    ExitCode::from(0)
+}
+
+/// Asks for a value on stdin until a non-empty line is entered.
+fn prompt(label: &str) -> std::io::Result<String> {
+    let stdin = std::io::stdin();
+    loop {
+        eprint!("{label}: ");
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("no value given for {label}"),
+            ));
+        }
+        let value = line.trim();
+        if !value.is_empty() {
+            return Ok(value.to_string());
+        }
+    }
+}
+
+fn render_init_config(image: &str, setup: &str) -> String {
+    let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+    format!(
+        "# Litterbox project configuration.
+# See https://litterbox.throw.party/docs/reference/config for all options.
+
+# Optional: override the default project slug (derived from directory name)
+# [project]
+# slug = \"my-project\"
+
+[docker]
+# Image that sandbox containers are created from.
+image = {}
+# Command run inside each new sandbox, for example to install dependencies.
+setup-command = {}
+
+# Ports inside the sandbox to forward to the host.
+# [[ports]]
+# name = \"www\"
+# target = 8080
+",
+        quote(image),
+        quote(setup)
+    )
+}
+
+async fn handle_list(json: bool, ports: bool) -> ExitCode {
+    let config = config_loader::load_final().ok();
     let scm = match ThreadSafeScm::open(Path::new(".")) {
         Ok(scm) => scm,
         Err(error) => return report_error("list", error),
@@ -165,12 +622,12 @@ async fn handle_list() -> ExitCode {
         Ok(prefix) => prefix,
         Err(error) => return report_error("list", error),
     };
-    let slugs = match scm.list_sandboxes() {
-        Ok(slugs) => slugs,
+    let entries = match scm.list_sandboxes_with_metadata() {
+        Ok(entries) => entries,
         Err(error) => return report_error("list", error),
     };
 
-    let compute = match DockerCompute::connect() {
+    let compute = match connect_compute(config.as_ref()) {
         Ok(compute) => Some(compute),
         Err(_) => {
             eprintln!("list warning: docker unavailable; statuses shown as unknown");
@@ -178,50 +635,240 @@ async fn handle_list() -> ExitCode {
         }
     };
 
+    let statuses = match compute.as_ref() {
+        Some(compute) => match compute.list_litterbox_containers(Some(&repo_prefix)).await {
+            Ok(containers) => Some(
+                containers
+                    .into_iter()
+                    .map(|container| (container.slug, container.status))
+                    .collect::<HashMap<_, _>>(),
+            ),
+            Err(error) => return report_error("list", error),
+        },
+        None => None,
+    };
+
     let mut sandboxes = Vec::new();
-    for slug in slugs {
-        let status = match compute.as_ref() {
-            Some(compute) => {
-                let container = container_name_for_slug(&repo_prefix, &slug);
-                match compute.client().inspect_container(&container, None).await {
-                    Ok(info) => {
-                        let state = info.state.as_ref();
-                        let running = state.and_then(|state| state.running).unwrap_or(false);
-                        let paused = state.and_then(|state| state.paused).unwrap_or(false);
-                        if paused {
-                            SandboxStatus::Paused
-                        } else if running {
-                            SandboxStatus::Active
-                        } else {
-                            SandboxStatus::Error("not running".to_string())
-                        }
-                    }
-                    Err(bollard::errors::Error::DockerResponseServerError {
-                        status_code: 404,
-                        ..
-                    }) => SandboxStatus::Error("missing container".to_string()),
-                    Err(error) => return report_error("list", error),
-                }
-            }
+    let mut snapshot_counts = HashMap::new();
+    for entry in entries {
+        let slug = entry.slug;
+        let status = match statuses.as_ref() {
+            Some(statuses) => statuses
+                .get(&slug)
+                .cloned()
+                .unwrap_or_else(|| SandboxStatus::Error("missing container".to_string())),
             None => SandboxStatus::Error("docker unavailable".to_string()),
         };
-        sandboxes.push(metadata_for_slug(&repo_prefix, &slug, status));
+        let mut metadata = metadata_for_slug(&repo_prefix, &slug, status);
+        if let Ok((created_at, last_active_at)) = scm.branch_times(&slug) {
+            metadata.created_at = created_at;
+            metadata.last_active_at = last_active_at;
+        }
+        if ports
+            && metadata.status == SandboxStatus::Active
+            && let Some(compute) = compute.as_ref()
+        {
+            match compute.inspect_container(&metadata.container_id).await {
+                Ok(inspection) => {
+                    metadata.forwarded_ports = forwarded_ports_from_inspection(&inspection);
+                }
+                Err(error) => eprintln!("list warning: {slug}: {error}"),
+            }
+        }
+        snapshot_counts.insert(metadata.name.clone(), entry.snapshot_count);
+        sandboxes.push(metadata);
     }
 
     sandboxes.sort_by(|a, b| a.name.cmp(&b.name));
+    if json {
+        return match serde_json::to_string_pretty(&sandboxes) {
+            Ok(output) => {
+                println!("{output}");
+                ExitCode::from(0)
+            }
+            Err(error) => report_error("list", error),
+        };
+    }
+    let now = unix_now();
     for sandbox in sandboxes {
-        println!("{} {}", sandbox.name, status_label(&sandbox.status));
+        let snapshots = snapshot_counts.get(&sandbox.name).copied().unwrap_or_default();
+        let mut mappings = sandbox.forwarded_ports.clone();
+        mappings.sort_by(|a, b| a.name.cmp(&b.name));
+        let port_list: String = mappings
+            .iter()
+            .map(|port| format!(" {}:{}->{}", port.name, port.target, port.host_port))
+            .collect();
+        println!(
+            "{} {} (created {}, active {}, {} {}){}",
+            sandbox.name,
+            status_label(&sandbox.status),
+            format_relative_time(sandbox.created_at, now),
+            format_relative_time(sandbox.last_active_at, now),
+            snapshots,
+            if snapshots == 1 { "snapshot" } else { "snapshots" },
+            port_list
+        );
     }
 
     ExitCode::from(0)
 }
 
+async fn handle_status(name: String, json: bool) -> ExitCode {
+    let config = config_loader::load_final().ok();
+    let slug = match slugify_name(&name) {
+        Ok(slug) => slug,
+        Err(error) => return report_error("status", error),
+    };
+    let scm = match ThreadSafeScm::open(Path::new(".")) {
+        Ok(scm) => scm,
+        Err(error) => return report_error("status", error),
+    };
+    let repo_prefix = match scm.repo_prefix() {
+        Ok(prefix) => prefix,
+        Err(error) => return report_error("status", error),
+    };
+    if scm.branch_tip(&slug).is_err() {
+        return report_error("status", SandboxError::SandboxNotFound { name });
+    }
+    let recent_snapshots = match scm.list_snapshots(&slug, Some(STATUS_RECENT_SNAPSHOTS)) {
+        Ok(snapshots) => snapshots,
+        Err(error) => return report_error("status", error),
+    };
+
+    let mut metadata = metadata_for_slug(&repo_prefix, &slug, SandboxStatus::Active);
+    if let Ok((created_at, last_active_at)) = scm.branch_times(&slug) {
+        metadata.created_at = created_at;
+        metadata.last_active_at = last_active_at;
+    }
+    match build_provider(config.as_ref()) {
+        Ok(provider) => match provider.inspect(&metadata).await {
+            Ok(inspected) => metadata = inspected,
+            Err(error) if is_container_missing(&error) => {
+                metadata.status = SandboxStatus::Error("missing container".to_string());
+            }
+            Err(error) => {
+                eprintln!("status warning: {error}");
+                metadata.status = SandboxStatus::Error("docker unavailable".to_string());
+            }
+        },
+        Err(error) => {
+            eprintln!("status warning: {error}");
+            metadata.status = SandboxStatus::Error("docker unavailable".to_string());
+        }
+    }
+
+    let report = StatusReport {
+        metadata,
+        recent_snapshots,
+    };
+    if json {
+        return match serde_json::to_string_pretty(&report) {
+            Ok(output) => {
+                println!("{output}");
+                ExitCode::from(0)
+            }
+            Err(error) => report_error("status", error),
+        };
+    }
+    print!("{}", format_status_report(&report, unix_now()));
+    ExitCode::from(0)
+}
+
+#[derive(Serialize)]
+struct StatusReport {
+    metadata: SandboxMetadata,
+    recent_snapshots: Vec<SnapshotInfo>,
+}
+
+fn format_status_report(report: &StatusReport, now: u64) -> String {
+    let metadata = &report.metadata;
+    let unknown = || "unknown".to_string();
+    let mut ports = metadata.forwarded_ports.clone();
+    ports.sort_by(|a, b| a.name.cmp(&b.name));
+    let ports = if ports.is_empty() {
+        "none".to_string()
+    } else {
+        ports
+            .iter()
+            .map(|port| format!("{}:{}->{}", port.name, port.target, port.host_port))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut output = String::new();
+    let mut line = |label: &str, value: String| output.push_str(&format!("{label:<13}{value}\n"));
+    line("Name:", metadata.name.clone());
+    line("Branch:", metadata.branch_name.clone());
+    line("Container:", metadata.container_id.clone());
+    line("Status:", status_label(&metadata.status));
+    line("Image:", metadata.image_digest.clone().unwrap_or_else(unknown));
+    line("IP address:", metadata.container_ip.clone().unwrap_or_else(unknown));
+    if !metadata.network_aliases.is_empty() {
+        line("Aliases:", metadata.network_aliases.join(", "));
+    }
+    line("Started:", metadata.started_at.clone().unwrap_or_else(unknown));
+    line("Created:", format_relative_time(metadata.created_at, now));
+    line("Last active:", format_relative_time(metadata.last_active_at, now));
+    line("Ports:", ports);
+    if report.recent_snapshots.is_empty() {
+        line("Snapshots:", "none".to_string());
+    } else {
+        line("Snapshots:", String::new());
+        for snapshot in &report.recent_snapshots {
+            output.push_str(&format!(
+                "  {}  {}  {}\n",
+                &snapshot.oid[..snapshot.oid.len().min(SHORT_OID_LENGTH)],
+                format_timestamp(snapshot.timestamp),
+                snapshot.message
+            ));
+        }
+    }
+    output
+}
+
+async fn handle_create(
+    name: String,
+    image: Option<String>,
+    setup: Option<String>,
+    from_ref: Option<String>,
+    network: Option<String>,
+) -> ExitCode {
+    let config = match config_loader::load_final() {
+        Ok(config) => config,
+        Err(error) => return report_error("create", SandboxError::Config(error.to_string())),
+    };
+    let mut sandbox_config = match load_sandbox_config(&config, image, setup, from_ref) {
+        Ok(config) => config,
+        Err(error) => return report_error("create", error),
+    };
+    sandbox_config.network = network;
+    // Equivalent to `Config::validate_async` for the chosen image, but with pull progress.
+    if let Err(error) = pull_image_with_progress(&config, &sandbox_config.image).await {
+        let error = ConfigError::ImageUnavailable {
+            image: sandbox_config.image.clone(),
+            message: error.to_string(),
+        };
+        return report_error("create", error);
+    }
+    let provider = match build_provider(Some(&config)) {
+        Ok(provider) => provider,
+        Err(error) => return report_error("create", error),
+    };
+    let metadata = match provider.create(&name, &sandbox_config).await {
+        Ok(metadata) => metadata,
+        Err(error) => return report_error("create", error),
+    };
+    println!("Created {metadata}");
+    ExitCode::from(0)
+}
+
 async fn handle_pause(name: Option<String>, all_envs: bool, all_repos: bool) -> ExitCode {
+    let config = config_loader::load_final().ok();
     if all_repos {
-        return handle_pause_all_repos().await;
+        return handle_pause_all_repos(config.as_ref()).await;
     }
     if all_envs {
-        return handle_pause_all_envs().await;
+        return handle_pause_all_envs(config.as_ref()).await;
     }
 
     let Some(name) = name else {
@@ -236,7 +883,7 @@ async fn handle_pause(name: Option<String>, all_envs: bool, all_repos: bool) ->
         Err(error) => return report_error("pause", error),
     };
     let container = container_name_for_slug(&repo_prefix, &slug);
-    let provider = match build_provider() {
+    let provider = match build_provider(config.as_ref()) {
         Ok(provider) => provider,
         Err(error) => return report_error("pause", error),
     };
@@ -248,7 +895,7 @@ async fn handle_pause(name: Option<String>, all_envs: bool, all_repos: bool) ->
     ExitCode::from(0)
 }
 
-async fn handle_pause_all_envs() -> ExitCode {
+async fn handle_pause_all_envs(config: Option<&Config>) -> ExitCode {
     let scm = match ThreadSafeScm::open(Path::new(".")) {
         Ok(scm) => scm,
         Err(error) => return report_error("pause --all-envs", error),
@@ -257,7 +904,7 @@ async fn handle_pause_all_envs() -> ExitCode {
         Ok(prefix) => prefix,
         Err(error) => return report_error("pause --all-envs", error),
     };
-    let compute = match DockerCompute::connect() {
+    let compute = match connect_compute(config) {
         Ok(compute) => compute,
         Err(error) => return report_error("pause --all-envs", error),
     };
@@ -280,36 +927,22 @@ async fn handle_pause_all_envs() -> ExitCode {
     ExitCode::from(0)
 }
 
-async fn handle_pause_all_repos() -> ExitCode {
-    let compute = match DockerCompute::connect() {
+async fn handle_pause_all_repos(config: Option<&Config>) -> ExitCode {
+    let compute = match connect_compute(config) {
         Ok(compute) => compute,
         Err(error) => return report_error("pause --all-repos", error),
     };
-    let options = Some(ListContainersOptionsBuilder::default().all(true).build());
-    let containers = match compute.client().list_containers(options).await {
+    let containers = match compute.list_litterbox_containers(None).await {
         Ok(containers) => containers,
         Err(error) => return report_error("pause --all-repos", error),
     };
 
     let mut paused = 0usize;
     for container in containers {
-        let Some(names) = container.names.as_ref() else {
-            continue;
-        };
-        if !names.iter().any(|name| name.starts_with("/litterbox-")) {
+        if container.status != SandboxStatus::Active {
             continue;
         }
-        let Some(id) = container.id.as_ref() else {
-            continue;
-        };
-        let running = matches!(
-            container.state,
-            Some(bollard::models::ContainerSummaryStateEnum::RUNNING)
-        );
-        if !running {
-            continue;
-        }
-        match compute.pause_container(id).await {
+        match compute.pause_container(&container.container_id).await {
             Ok(()) => paused += 1,
             Err(error) if is_container_missing(&error) => {}
             Err(error) => return report_error("pause --all-repos", error),
@@ -321,6 +954,7 @@ async fn handle_pause_all_repos() -> ExitCode {
 }
 
 async fn handle_resume(name: String) -> ExitCode {
+    let config = config_loader::load_final().ok();
     let slug = match slugify_name(&name) {
         Ok(slug) => slug,
         Err(error) => return report_error("resume", error),
@@ -330,7 +964,7 @@ async fn handle_resume(name: String) -> ExitCode {
         Err(error) => return report_error("resume", error),
     };
     let container = container_name_for_slug(&repo_prefix, &slug);
-    let provider = match build_provider() {
+    let provider = match build_provider(config.as_ref()) {
         Ok(provider) => provider,
         Err(error) => return report_error("resume", error),
     };
@@ -343,6 +977,7 @@ async fn handle_resume(name: String) -> ExitCode {
 }
 
 async fn handle_delete(name: String, force: bool) -> ExitCode {
+    let config = config_loader::load_final().ok();
     let slug = match slugify_name(&name) {
         Ok(slug) => slug,
         Err(error) => return report_error("delete", error),
@@ -352,7 +987,7 @@ async fn handle_delete(name: String, force: bool) -> ExitCode {
         Err(error) => return report_error("delete", error),
     };
     let container = container_name_for_slug(&repo_prefix, &slug);
-    let compute = match DockerCompute::connect() {
+    let compute = match connect_compute(config.as_ref()) {
         Ok(compute) => compute,
         Err(error) => return report_error("delete", error),
     };
@@ -379,7 +1014,7 @@ async fn handle_delete(name: String, force: bool) -> ExitCode {
         Err(error) => return report_error("delete", error),
     }
 
-    let provider = match build_provider() {
+    let provider = match build_provider(config.as_ref()) {
         Ok(provider) => provider,
         Err(error) => return report_error("delete", error),
     };
@@ -392,22 +1027,116 @@ async fn handle_delete(name: String, force: bool) -> ExitCode {
     ExitCode::from(0)
 }
 
+async fn handle_rename(old: String, new: String) -> ExitCode {
+    let config = config_loader::load_final().ok();
+    let old_slug = match slugify_name(&old) {
+        Ok(slug) => slug,
+        Err(error) => return report_error("rename", error),
+    };
+    let new_slug = match slugify_new_name(&new) {
+        Ok(slug) => slug,
+        Err(error) => return report_error("rename", error),
+    };
+    let scm = match ThreadSafeScm::open(Path::new(".")) {
+        Ok(scm) => scm,
+        Err(error) => return report_error("rename", error),
+    };
+    let repo_prefix = match scm.repo_prefix() {
+        Ok(prefix) => prefix,
+        Err(error) => return report_error("rename", error),
+    };
+    let compute = match connect_compute(config.as_ref()) {
+        Ok(compute) => compute,
+        Err(error) => return report_error("rename", error),
+    };
+
+    if let Err(error) = scm.rename_branch(&old_slug, &new_slug) {
+        return report_error("rename", error);
+    }
+
+    let old_container = container_name_for_slug(&repo_prefix, &old_slug);
+    let new_container = container_name_for_slug(&repo_prefix, &new_slug);
+    match compute.rename_container(&old_container, &new_container).await {
+        Ok(()) => {}
+        // Branches are authoritative; a missing container can be recreated later.
+        Err(SandboxError::Compute(ComputeError::ContainerNotFound { .. })) => {}
+        Err(error) => {
+            let _ = scm.rename_branch(&new_slug, &old_slug);
+            return report_error("rename", error);
+        }
+    }
+
+    let metadata = metadata_for_slug(&repo_prefix, &new_slug, SandboxStatus::Active);
+    println!("Renamed {old} to {metadata}");
+    ExitCode::from(0)
+}
+
+async fn handle_prune(dry_run: bool, branches: bool) -> ExitCode {
+    let config = config_loader::load_final().ok();
+    let scm = match ThreadSafeScm::open(Path::new(".")) {
+        Ok(scm) => scm,
+        Err(error) => return report_error("prune", error),
+    };
+    let repo_prefix = match scm.repo_prefix() {
+        Ok(prefix) => prefix,
+        Err(error) => return report_error("prune", error),
+    };
+    let slugs = match scm.list_sandboxes() {
+        Ok(slugs) => slugs,
+        Err(error) => return report_error("prune", error),
+    };
+    let compute = match connect_compute(config.as_ref()) {
+        Ok(compute) => compute,
+        Err(error) => return report_error("prune", error),
+    };
+    let containers = match compute.list_litterbox_containers(Some(&repo_prefix)).await {
+        Ok(containers) => containers,
+        Err(error) => return report_error("prune", error),
+    };
+    let container_slugs: Vec<&String> =
+        containers.iter().map(|container| &container.slug).collect();
+
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    let mut removed = 0usize;
+    for container in containers.iter().filter(|container| !slugs.contains(&container.slug)) {
+        if !dry_run && let Err(error) = compute.delete_container(&container.container_id).await {
+            return report_error("prune", error);
+        }
+        println!(
+            "{verb} container {}",
+            container_name_for_slug(&repo_prefix, &container.slug)
+        );
+        removed += 1;
+    }
+    for slug in slugs.iter().filter(|slug| branches && !container_slugs.contains(slug)) {
+        if !dry_run && let Err(error) = scm.delete_branch(slug) {
+            return report_error("prune", error);
+        }
+        println!("{verb} branch {}", branch_name_for_slug(slug));
+        removed += 1;
+    }
+
+    println!("{verb} {removed} orphaned resource(s)");
+    ExitCode::from(0)
+}
+
 async fn handle_shell(name: String, command: Vec<String>) -> ExitCode {
+    let config = config_loader::load_final().ok();
     let slug = match slugify_name(&name) {
         Ok(slug) => slug,
         Err(error) => return report_error("shell", error),
     };
-    let provider = match build_provider() {
-        Ok(provider) => provider,
-        Err(error) => return report_error("shell", error),
-    };
     let repo_prefix = match repo_prefix() {
         Ok(prefix) => prefix,
         Err(error) => return report_error("shell", error),
     };
     let metadata = metadata_for_slug(&repo_prefix, &slug, SandboxStatus::Active);
+    let provider = match sandbox_provider(config.as_ref(), &metadata).await {
+        Ok(provider) => provider,
+        Err(error) => return report_error("shell", error),
+    };
 
-    let result = match provider.shell(&metadata, &command).await {
+    let result = match provider.shell(&metadata, &command, None).await {
         Ok(result) => result,
         Err(error) => return report_error("shell", error),
     };
@@ -431,6 +1160,495 @@ async fn handle_shell(name: String, command: Vec<String>) -> ExitCode {
     }
 }
 
+async fn handle_upload(name: String, src: PathBuf, dest: String, resume: bool) -> ExitCode {
+    let config = config_loader::load_final().ok();
+    let metadata = match prepare_transfer(config.as_ref(), &name, resume).await {
+        Ok(metadata) => metadata,
+        Err(error) => return report_error("upload", error),
+    };
+    let bytes = match path_size(&src) {
+        Ok(bytes) => bytes,
+        Err(error) => return report_error("upload", error),
+    };
+    let provider = match sandbox_provider(config.as_ref(), &metadata).await {
+        Ok(provider) => provider,
+        Err(error) => return report_error("upload", error),
+    };
+    let dest = mcp::resolve_container_path(provider.workdir(), &dest);
+    if let Err(error) = provider.upload_path(&metadata, &src, &dest).await {
+        return report_error("upload", error);
+    }
+
+    println!("Uploaded {bytes} bytes from {} to {name}:{dest}", src.display());
+    ExitCode::from(0)
+}
+
+async fn handle_download(name: String, src: String, dest: PathBuf, resume: bool) -> ExitCode {
+    let config = config_loader::load_final().ok();
+    let metadata = match prepare_transfer(config.as_ref(), &name, resume).await {
+        Ok(metadata) => metadata,
+        Err(error) => return report_error("download", error),
+    };
+    let provider = match sandbox_provider(config.as_ref(), &metadata).await {
+        Ok(provider) => provider,
+        Err(error) => return report_error("download", error),
+    };
+    let staging = match tempfile::TempDir::new() {
+        Ok(staging) => staging,
+        Err(error) => return report_error("download", error),
+    };
+    let src = mcp::resolve_container_path(provider.workdir(), &src);
+    if let Err(error) = provider.download_path(&metadata, &src, staging.path()).await {
+        return report_error("download", error);
+    }
+    let bytes = match path_size(staging.path()) {
+        Ok(bytes) => bytes,
+        Err(error) => return report_error("download", error),
+    };
+    if let Err(error) = copy_tree(staging.path(), &dest) {
+        return report_error("download", error);
+    }
+
+    println!("Downloaded {bytes} bytes from {name}:{src} to {}", dest.display());
+    ExitCode::from(0)
+}
+
+async fn handle_logs(name: String, tail: Option<usize>, follow: bool) -> ExitCode {
+    let config = config_loader::load_final().ok();
+    let slug = match slugify_name(&name) {
+        Ok(slug) => slug,
+        Err(error) => return report_error("logs", error),
+    };
+    let repo_prefix = match repo_prefix() {
+        Ok(prefix) => prefix,
+        Err(error) => return report_error("logs", error),
+    };
+    let container = container_name_for_slug(&repo_prefix, &slug);
+    let compute = match connect_compute(config.as_ref()) {
+        Ok(compute) => compute,
+        Err(error) => return report_error("logs", error),
+    };
+
+    let mut logs = compute.container_logs(&container, tail.unwrap_or(DEFAULT_LOG_TAIL), follow);
+    loop {
+        tokio::select! {
+            item = logs.next() => match item {
+                Some(Ok(chunk)) => print!("{chunk}"),
+                Some(Err(error)) => return report_error("logs", error),
+                None => break,
+            },
+            _ = tokio::signal::ctrl_c(), if follow => break,
+        }
+    }
+
+    ExitCode::from(0)
+}
+
+fn handle_diff(name: String, path: Option<String>) -> ExitCode {
+    let slug = match slugify_name(&name) {
+        Ok(slug) => slug,
+        Err(error) => return report_error("diff", error),
+    };
+    let scm = match ThreadSafeScm::open(Path::new(".")) {
+        Ok(scm) => scm,
+        Err(error) => return report_error("diff", error),
+    };
+    let branch_name = branch_name_for_slug(&slug);
+    match scm.diff_between("HEAD", &branch_name, path.as_deref()) {
+        Ok(Some(diff)) if !diff.is_empty() => print!("{diff}"),
+        Ok(_) => println!("No changes have been snapshotted for {name}"),
+        Err(error) => return report_error("diff", error),
+    }
+    ExitCode::from(0)
+}
+
+fn handle_snapshot_list(name: String, limit: Option<usize>) -> ExitCode {
+    let slug = match slugify_name(&name) {
+        Ok(slug) => slug,
+        Err(error) => return report_error("snapshot list", error),
+    };
+    let scm = match ThreadSafeScm::open(Path::new(".")) {
+        Ok(scm) => scm,
+        Err(error) => return report_error("snapshot list", error),
+    };
+    let snapshots = match scm.list_snapshots(&slug, limit) {
+        Ok(snapshots) => snapshots,
+        Err(error) => return report_error("snapshot list", error),
+    };
+
+    if snapshots.is_empty() {
+        println!("No snapshots for {name}");
+        return ExitCode::from(0);
+    }
+    for snapshot in snapshots {
+        println!(
+            "{}  {}  {}",
+            &snapshot.oid[..snapshot.oid.len().min(SHORT_OID_LENGTH)],
+            format_timestamp(snapshot.timestamp),
+            snapshot.message
+        );
+    }
+    ExitCode::from(0)
+}
+
+fn handle_snapshot_prune(
+    name: String,
+    max_age_days: Option<u64>,
+    max_count: Option<usize>,
+) -> ExitCode {
+    let slug = match slugify_name(&name) {
+        Ok(slug) => slug,
+        Err(error) => return report_error("snapshot prune", error),
+    };
+    let scm = match ThreadSafeScm::open(Path::new(".")) {
+        Ok(scm) => scm,
+        Err(error) => return report_error("snapshot prune", error),
+    };
+    let result = match scm.prune_snapshots(&slug, max_age_days, max_count) {
+        Ok(result) => result,
+        Err(error) => return report_error("snapshot prune", error),
+    };
+
+    if result.removed == 0 {
+        println!("Nothing to prune for {name}; {} snapshots kept", result.kept);
+    } else if let Some(squash) = result.squash {
+        println!(
+            "Squashed {} snapshots of {name} into {}",
+            result.removed,
+            &squash[..squash.len().min(SHORT_OID_LENGTH)]
+        );
+    } else {
+        println!(
+            "Pruned {} snapshots of {name}; {} kept",
+            result.removed, result.kept
+        );
+    }
+    ExitCode::from(0)
+}
+
+async fn handle_snapshot_restore(name: String, oid: String) -> ExitCode {
+    let config = config_loader::load_final().ok();
+    let slug = match slugify_name(&name) {
+        Ok(slug) => slug,
+        Err(error) => return report_error("snapshot restore", error),
+    };
+    let repo_prefix = match repo_prefix() {
+        Ok(prefix) => prefix,
+        Err(error) => return report_error("snapshot restore", error),
+    };
+    let metadata = metadata_for_slug(&repo_prefix, &slug, SandboxStatus::Active);
+    let provider = match sandbox_provider(config.as_ref(), &metadata).await {
+        Ok(provider) => provider,
+        Err(error) => return report_error("snapshot restore", error),
+    };
+    let scm = match ThreadSafeScm::for_sandbox(Path::new("."), None, &slug) {
+        Ok(scm) => scm,
+        Err(error) => return report_error("snapshot restore", error),
+    };
+    if let Err(error) = restore_snapshot(&provider, &scm, &metadata, &oid).await {
+        return report_error("snapshot restore", error);
+    }
+
+    println!("Restored {name} to {oid}");
+    ExitCode::from(0)
+}
+
+async fn handle_export(name: String, output: PathBuf) -> ExitCode {
+    let config = config_loader::load_final().ok();
+    let slug = match slugify_name(&name) {
+        Ok(slug) => slug,
+        Err(error) => return report_error("export", error),
+    };
+    let scm = match ThreadSafeScm::open(Path::new(".")) {
+        Ok(scm) => scm,
+        Err(error) => return report_error("export", error),
+    };
+    let repo_prefix = match scm.repo_prefix() {
+        Ok(prefix) => prefix,
+        Err(error) => return report_error("export", error),
+    };
+    let branch_tip = match scm.branch_tip(&slug) {
+        Ok(oid) => oid,
+        Err(error) => return report_error("export", error),
+    };
+    let base_commit = match scm.base_commit(&slug) {
+        Ok(oid) => oid,
+        Err(error) => return report_error("export", error),
+    };
+    let metadata = metadata_for_slug(&repo_prefix, &slug, SandboxStatus::Active);
+    let provider = match sandbox_provider(config.as_ref(), &metadata).await {
+        Ok(provider) => provider,
+        Err(error) => return report_error("export", error),
+    };
+    let staging = match tempfile::TempDir::new() {
+        Ok(staging) => staging,
+        Err(error) => return report_error("export", error),
+    };
+
+    let src_dir = staging.path().join(EXPORT_SRC_DIR);
+    if let Err(error) = provider.download_path(&metadata, provider.workdir(), &src_dir).await {
+        return report_error("export", error);
+    }
+
+    let manifest = ExportManifest {
+        name: slug,
+        branch_tip: branch_tip.to_string(),
+        base_commit: base_commit.map(|oid| oid.to_string()),
+    };
+    if let Err(error) = write_export_archive(&output, &manifest, &src_dir) {
+        return report_error("export", error);
+    }
+
+    println!("Exported {name} to {}", output.display());
+    ExitCode::from(0)
+}
+
+async fn handle_import(archive: PathBuf, name: String) -> ExitCode {
+    let staging = match tempfile::TempDir::new() {
+        Ok(staging) => staging,
+        Err(error) => return report_error("import", error),
+    };
+    let manifest = match read_export_archive(&archive, staging.path()) {
+        Ok(manifest) => manifest,
+        Err(error) => return report_error("import", error),
+    };
+    let from_ref = match ThreadSafeScm::open(Path::new("."))
+        .and_then(|scm| import_from_ref(&scm, &manifest))
+    {
+        Ok(from_ref) => from_ref,
+        Err(error) => return report_error("import", error),
+    };
+    let config = match config_loader::load_final() {
+        Ok(config) => config,
+        Err(error) => return report_error("import", SandboxError::Config(error.to_string())),
+    };
+    let sandbox_config = match load_sandbox_config(&config, None, None, from_ref) {
+        Ok(config) => config,
+        Err(error) => return report_error("import", error),
+    };
+    let provider = match build_provider(Some(&config)) {
+        Ok(provider) => provider,
+        Err(error) => return report_error("import", error),
+    };
+    let metadata = match provider.create(&name, &sandbox_config).await {
+        Ok(metadata) => metadata,
+        Err(error) => return report_error("import", error),
+    };
+
+    // Record the exported tree on the new branch, then restore the container from it.
+    let scm = match ThreadSafeScm::for_sandbox(Path::new("."), None, &metadata.name) {
+        Ok(scm) => scm,
+        Err(error) => return report_error("import", error),
+    };
+    let message = format!("import: {} at {}", manifest.name, manifest.branch_tip);
+    let src_dir = staging.path().join(EXPORT_SRC_DIR);
+    let reference = match scm.commit_snapshot_from_staging(&src_dir, &message, 1) {
+        Ok(Some(oid)) => oid.to_string(),
+        Ok(None) => metadata.branch_name.clone(),
+        Err(error) => return report_error("import", error),
+    };
+    if let Err(error) = restore_snapshot(&provider, &scm, &metadata, &reference).await {
+        return report_error("import", error);
+    }
+
+    println!("Imported {metadata}");
+    ExitCode::from(0)
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportManifest {
+    name: String,
+    /// The exported snapshot commit, which only exists in the exporting repository.
+    branch_tip: String,
+    /// The commit the exported sandbox branched from, usually shared with other clones.
+    #[serde(default)]
+    base_commit: Option<String>,
+}
+
+/// The ref an imported sandbox branches from: the exported sandbox's base commit when this
+/// repository has it, otherwise HEAD.
+fn import_from_ref(
+    scm: &ThreadSafeScm,
+    manifest: &ExportManifest,
+) -> Result<Option<String>, SandboxError> {
+    let Some(base) = &manifest.base_commit else {
+        return Ok(None);
+    };
+    if scm.has_commit(base)? {
+        return Ok(Some(base.clone()));
+    }
+    eprintln!("Warning: base commit {base} is not in this repository; branching from HEAD");
+    Ok(None)
+}
+
+fn write_export_archive(
+    output: &Path,
+    manifest: &ExportManifest,
+    src_dir: &Path,
+) -> Result<(), SandboxError> {
+    let manifest = serde_json::to_vec_pretty(manifest)
+        .map_err(|error| SandboxError::Config(error.to_string()))?;
+    let mut builder = tar::Builder::new(fs::File::create(output)?);
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, EXPORT_MANIFEST, manifest.as_slice())?;
+    builder.append_dir_all(EXPORT_SRC_DIR, src_dir)?;
+    builder.finish()?;
+    Ok(())
+}
+
+fn read_export_archive(archive: &Path, dest: &Path) -> Result<ExportManifest, SandboxError> {
+    tar::Archive::new(fs::File::open(archive)?).unpack(dest)?;
+    let manifest = fs::read(dest.join(EXPORT_MANIFEST))?;
+    let manifest: ExportManifest = serde_json::from_slice(&manifest)
+        .map_err(|error| SandboxError::Config(format!("invalid export manifest: {error}")))?;
+    fs::create_dir_all(dest.join(EXPORT_SRC_DIR))?;
+    Ok(manifest)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+fn format_relative_time(timestamp: Option<u64>, now: u64) -> String {
+    let Some(timestamp) = timestamp else {
+        return "unknown".to_string();
+    };
+    let elapsed = now.saturating_sub(timestamp);
+    let (count, unit) = match elapsed {
+        0..60 => return "just now".to_string(),
+        60..3_600 => (elapsed / 60, "minute"),
+        3_600..86_400 => (elapsed / 3_600, "hour"),
+        86_400..2_592_000 => (elapsed / 86_400, "day"),
+        2_592_000..31_536_000 => (elapsed / 2_592_000, "month"),
+        _ => (elapsed / 31_536_000, "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM:SS` in UTC.
+fn format_timestamp(seconds: i64) -> String {
+    let days = seconds.div_euclid(86_400);
+    let time = seconds.rem_euclid(86_400);
+
+    // Civil-from-days conversion, see https://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+/// Resolves a sandbox for file transfer, resuming it first when requested.
+async fn prepare_transfer(
+    config: Option<&Config>,
+    name: &str,
+    resume: bool,
+) -> Result<SandboxMetadata, String> {
+    let slug = slugify_name(name).map_err(|error| error.to_string())?;
+    let repo_prefix = repo_prefix().map_err(|error| error.to_string())?;
+    let container = container_name_for_slug(&repo_prefix, &slug);
+    let compute = connect_compute(config).map_err(|error| error.to_string())?;
+
+    match compute.client().inspect_container(&container, None).await {
+        Ok(info) => {
+            let paused = info
+                .state
+                .as_ref()
+                .and_then(|state| state.paused)
+                .unwrap_or(false);
+            if paused && !resume {
+                return Err("sandbox is paused; use --resume to resume it".to_string());
+            }
+            if paused {
+                compute
+                    .resume_container(&container)
+                    .await
+                    .map_err(|error| error.to_string())?;
+            }
+        }
+        Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 404, ..
+        }) => {
+            return Err(SandboxError::SandboxNotFound {
+                name: name.to_string(),
+            }
+            .to_string());
+        }
+        Err(error) => return Err(error.to_string()),
+    }
+
+    Ok(metadata_for_slug(&repo_prefix, &slug, SandboxStatus::Active))
+}
+
+/// Total size of the files under `path`. Symlinks are counted at their own size, not followed.
+fn path_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += path_size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+fn copy_tree(src: &Path, dest: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, target)?;
+        } else if file_type.is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+fn handle_config_show() -> ExitCode {
+    let resolved = match config_loader::load_final_with_sources() {
+        Ok(resolved) => resolved,
+        Err(error) => return report_error("config show", error),
+    };
+    let rendered = match toml::to_string_pretty(&resolved.config) {
+        Ok(rendered) => rendered,
+        Err(error) => return report_error("config show", error),
+    };
+
+    print!("{rendered}");
+    println!();
+    println!("# Sources:");
+    for (key, source) in &resolved.sources {
+        println!("#   {key}: {source}");
+    }
+    ExitCode::from(0)
+}
+
 fn handle_docgen(kind: DocgenCommand) -> ExitCode {
     let content = match kind {
         DocgenCommand::Cli => generate_cli_docs(),
@@ -533,10 +1751,121 @@ fn format_option_label(arg: &Arg) -> Option<String> {
     Some(label)
 }
 
-fn build_provider() -> Result<DockerSandboxProvider<ThreadSafeScm, DockerCompute>, SandboxError> {
+/// Pulls `image` if it is missing locally, drawing a progress line on stderr.
+async fn pull_image_with_progress(config: &Config, image: &str) -> Result<(), SandboxError> {
+    let compute = connect_compute(Some(config))?;
+    match compute.client().inspect_image(image).await {
+        Ok(_) => return Ok(()),
+        Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 404, ..
+        }) => {}
+        Err(source) => return Err(ComputeError::ImageInspect { source }.into()),
+    }
+
+    eprintln!("Pulling {image}");
+    compute
+        .pull_with_progress(
+            image,
+            Box::new(|progress| eprint!("\r\x1b[2K{}", format_pull_progress(&progress))),
+        )
+        .await?;
+    eprintln!();
+    Ok(())
+}
+
+fn format_pull_progress(progress: &PullProgress) -> String {
+    let mut line = if progress.layer_id.is_empty() {
+        progress.status.clone()
+    } else {
+        format!("{}: {}", progress.layer_id, progress.status)
+    };
+    if let (Some(current), Some(total)) = (progress.progress_bytes, progress.total_bytes)
+        && total > 0
+    {
+        let filled = (current.min(total) * PROGRESS_BAR_WIDTH / total) as usize;
+        let empty = PROGRESS_BAR_WIDTH as usize - filled;
+        line.push_str(&format!(
+            " [{}{}] {}%",
+            "#".repeat(filled),
+            " ".repeat(empty),
+            current.min(total) * 100 / total
+        ));
+    }
+    line
+}
+
+/// Builds a sandbox config from the project configuration, with optional CLI overrides.
+fn load_sandbox_config(
+    config: &Config,
+    image: Option<String>,
+    setup: Option<String>,
+    from_ref: Option<String>,
+) -> Result<SandboxConfig, SandboxError> {
+    let image = image
+        .or_else(|| config.docker.image.clone())
+        .ok_or_else(|| SandboxError::Config("missing docker.image".to_string()))?;
+    Ok(SandboxConfig {
+        image,
+        setup_command: setup.or_else(|| config.docker.setup_command.clone()),
+        setup_timeout_secs: config.docker.setup_timeout,
+        forwarded_ports: config
+            .ports
+            .ports
+            .iter()
+            .map(|port| ForwardedPort {
+                name: port.name.clone(),
+                target: port.target,
+                protocol: port.protocol,
+            })
+            .collect(),
+        port_range: Some(config.ports.port_range()),
+        ready_command: config.docker.ready_command.clone(),
+        from_ref,
+        restart_policy: config.docker.restart_policy,
+        extra_env: Vec::new(),
+        network: None,
+    })
+}
+
+/// Connects to the engine chosen by `--compute`, else by the `compute` key. Docker connections
+/// share one client for the whole command.
+fn connect_compute(config: Option<&Config>) -> Result<DockerCompute, SandboxError> {
+    let backend = COMPUTE_BACKEND
+        .get()
+        .copied()
+        .flatten()
+        .or_else(|| config.and_then(|config| config.compute))
+        .unwrap_or_default();
+    let compute = match backend {
+        ComputeBackend::Docker => DockerCompute::global().cloned()?,
+        backend => DockerCompute::connect_backend(backend)?,
+    };
+    let docker = config.map(|config| config.docker.clone()).unwrap_or_default();
+    Ok(compute
+        .with_pull_retries(docker.pull_retries.unwrap_or(DEFAULT_PULL_RETRIES))
+        .with_compressed_uploads(docker.compress_uploads.unwrap_or(false)))
+}
+
+fn build_provider(
+    config: Option<&Config>,
+) -> Result<DockerSandboxProvider<ThreadSafeScm, DockerCompute>, SandboxError> {
     let scm = ThreadSafeScm::open(Path::new("."))?;
-    let compute = DockerCompute::connect()?;
-    Ok(DockerSandboxProvider::new(scm, compute))
+    let compute = connect_compute(config)?;
+    let docker = config.map(|config| config.docker.clone()).unwrap_or_default();
+    let workdir = docker.workdir.unwrap_or_else(|| DEFAULT_WORKDIR.to_string());
+    Ok(DockerSandboxProvider::new(scm, compute)
+        .with_workdir(workdir)
+        .with_exec_retries(docker.exec_retries)
+        .with_compressed_archives(docker.compress_uploads.unwrap_or(false)))
+}
+
+/// [`build_provider`] with relative paths resolved against the workdir `metadata`'s sandbox
+/// was created with.
+async fn sandbox_provider(
+    config: Option<&Config>,
+    metadata: &SandboxMetadata,
+) -> Result<DockerSandboxProvider<ThreadSafeScm, DockerCompute>, SandboxError> {
+    build_provider(config)?.for_sandbox(metadata).await
 }
 
 fn metadata_for_slug(repo_prefix: &str, slug: &str, status: SandboxStatus) -> SandboxMetadata {
@@ -546,6 +1875,12 @@ fn metadata_for_slug(repo_prefix: &str, slug: &str, status: SandboxStatus) -> Sa
         container_id: container_name_for_slug(repo_prefix, slug),
         status,
         forwarded_ports: Vec::new(),
+        created_at: None,
+        last_active_at: None,
+        container_ip: None,
+        network_aliases: Vec::new(),
+        image_digest: None,
+        started_at: None,
     }
 }
 
@@ -562,6 +1897,8 @@ fn status_label(status: &SandboxStatus) -> String {
     match status {
         SandboxStatus::Active => "active".to_string(),
         SandboxStatus::Paused => "paused".to_string(),
+        SandboxStatus::Starting => "starting".to_string(),
+        SandboxStatus::Stopping => "stopping".to_string(),
         SandboxStatus::Error(message) if message == "missing container" => "missing".to_string(),
         SandboxStatus::Error(message) if message == "docker unavailable" => "unknown".to_string(),
         SandboxStatus::Error(message) => format!("error: {message}"),
@@ -571,11 +1908,93 @@ fn status_label(status: &SandboxStatus) -> String {
 fn is_container_missing(error: &SandboxError) -> bool {
     matches!(
         error,
-        SandboxError::Compute(ComputeError::ContainerPause {
-            source: bollard::errors::Error::DockerResponseServerError {
-                status_code: 404,
-                ..
-            }
-        })
+        SandboxError::Compute(ComputeError::ContainerNotFound { .. })
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use git2::{Repository, Signature};
+    use tempfile::TempDir;
+
+    fn repo_with_commit(content: &str) -> (TempDir, git2::Oid) {
+        let tempdir = TempDir::new().expect("tempdir");
+        let repo = Repository::init(tempdir.path()).expect("repo init");
+        fs::write(tempdir.path().join("README.md"), content).expect("write file");
+        let mut index = repo.index().expect("index");
+        index.add_path(Path::new("README.md")).expect("add path");
+        let tree = repo.find_tree(index.write_tree().expect("write tree")).expect("tree");
+        let signature = Signature::now("Litterbox", "noreply@example.com").expect("signature");
+        let oid = repo
+            .commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .expect("commit");
+        (tempdir, oid)
+    }
+
+    #[test]
+    fn copy_tree_recreates_symlinks_without_following_them() {
+        let src = TempDir::new().expect("src dir");
+        fs::create_dir(src.path().join("dir")).expect("create dir");
+        fs::write(src.path().join("dir/file.txt"), "contents").expect("write file");
+        std::os::unix::fs::symlink("..", src.path().join("dir/parent")).expect("loop link");
+        std::os::unix::fs::symlink("missing", src.path().join("dangling")).expect("dangling link");
+
+        // The file plus both links at their target lengths ("..", "missing").
+        assert_eq!(path_size(src.path()).expect("size"), 8 + 2 + 7);
+
+        let dest = TempDir::new().expect("dest dir");
+        let dest = dest.path().join("copy");
+        copy_tree(src.path(), &dest).expect("copy");
+        assert_eq!(
+            fs::read_to_string(dest.join("dir/file.txt")).expect("read file"),
+            "contents"
+        );
+        assert_eq!(fs::read_link(dest.join("dir/parent")).expect("link"), Path::new(".."));
+        assert_eq!(fs::read_link(dest.join("dangling")).expect("link"), Path::new("missing"));
+    }
+
+    #[test]
+    fn import_into_a_fresh_repo_branches_from_head() {
+        let (exporting, base) = repo_with_commit("exported");
+        let src_dir = TempDir::new().expect("src dir");
+        fs::write(src_dir.path().join("work.txt"), "work").expect("write work");
+        let archive = TempDir::new().expect("archive dir");
+        let archive = archive.path().join("sandbox.tar");
+        let manifest = ExportManifest {
+            name: "feature".to_string(),
+            branch_tip: "0123456789abcdef0123456789abcdef01234567".to_string(),
+            base_commit: Some(base.to_string()),
+        };
+        write_export_archive(&archive, &manifest, src_dir.path()).expect("export");
+
+        let (fresh, _) = repo_with_commit("unrelated");
+        let staging = TempDir::new().expect("staging");
+        let manifest = read_export_archive(&archive, staging.path()).expect("read archive");
+        assert_eq!(manifest.base_commit, Some(base.to_string()));
+        assert_eq!(
+            fs::read_to_string(staging.path().join(EXPORT_SRC_DIR).join("work.txt"))
+                .expect("read work"),
+            "work"
+        );
+
+        let fresh_scm = ThreadSafeScm::open(fresh.path()).expect("open fresh");
+        assert_eq!(import_from_ref(&fresh_scm, &manifest).expect("from ref"), None);
+        let exporting_scm = ThreadSafeScm::open(exporting.path()).expect("open exporting");
+        assert_eq!(
+            import_from_ref(&exporting_scm, &manifest).expect("from ref"),
+            Some(base.to_string())
+        );
+    }
+
+    #[test]
+    fn import_of_an_archive_without_a_base_branches_from_head() {
+        let manifest: ExportManifest =
+            serde_json::from_str(r#"{"name": "feature", "branch_tip": "abc"}"#).expect("parse");
+        let (repo, _) = repo_with_commit("repo");
+        let scm = ThreadSafeScm::open(repo.path()).expect("open");
+
+        assert_eq!(import_from_ref(&scm, &manifest).expect("from ref"), None);
+    }
+}
@@ -1,17 +1,30 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use glob::{MatchOptions, Pattern};
 use rmcp::{
-    ErrorData as McpError, ServerHandler, ServiceExt,
-    handler::server::tool::ToolRouter,
+    ErrorData as McpError, RoleServer, ServerHandler, ServiceExt,
+    handler::server::tool::{ToolCallContext, ToolRouter},
     handler::server::wrapper::Parameters,
-    model::{CallToolResult, Content, ServerCapabilities, ServerInfo},
-    tool, tool_handler, tool_router,
+    model::{
+        CallToolRequestParams, CallToolResult, Content, ListToolsResult, PaginatedRequestParams,
+        ServerCapabilities, ServerInfo,
+    },
+    service::RequestContext,
+    tool, tool_router,
     transport::stdio,
+    transport::streamable_http_server::{
+        StreamableHttpServerConfig, StreamableHttpService, session::local::LocalSessionManager,
+    },
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 use tempfile;
+use tokio::time::MissedTickBehavior;
 
 #[cfg(test)]
 use glob::glob as glob_paths;
@@ -19,23 +32,38 @@ use glob::glob as glob_paths;
 use std::fs;
 #[cfg(test)]
 use std::io;
-#[cfg(test)]
-use std::path::PathBuf;
 
-use crate::compute::{ContainerInspection, DockerCompute};
+use crate::audit::AuditLog;
+use crate::compute::{DEFAULT_PULL_RETRIES, DockerCompute, ExecOptions};
+use crate::config::ComputeBackend;
 use crate::config_loader;
 use crate::domain::{
-    ComputeError, ExecutionResult, ForwardedPort, ForwardedPortMapping, SandboxConfig,
-    SandboxError, SandboxMetadata, SandboxStatus, slugify_name,
+    ComputeError, ExecutionResult, ForwardedPort, ForwardedPortMapping, LitterboxErrorCode,
+    MergeStrategy, SandboxConfig, SandboxEntry, RESERVED_NAMES, SandboxError, SandboxMetadata,
+    SandboxStatus, ScmError, SnapshotInfo, is_reserved_name, slugify, slugify_name, validate_slug,
 };
 use crate::sandbox::{
-    DockerSandboxProvider, SandboxProvider, branch_name_for_slug, container_name_for_slug,
+    DEFAULT_WORKDIR, DockerSandboxProvider, SandboxProvider, branch_name_for_slug,
+    container_name_for_slug, forwarded_ports_from_inspection, interrupt_creations,
+    restore_snapshot, shutdown_signal,
 };
-use crate::scm::{Scm, ThreadSafeScm};
+use crate::scm::{DEFAULT_SNAPSHOT_DEDUP_DEPTH, Scm, ThreadSafeScm};
+use crate::watch::{DEFAULT_WATCH_INTERVAL_SECS, WatchState, Watches};
+
+const DEFAULT_CHECKPOINT_TAG: &str = "latest";
+/// How long `tail` with `follow` keeps collecting appended lines before returning.
+const TAIL_FOLLOW_SECS: u64 = 5;
+const DEFAULT_LS_PAGE_SIZE: usize = 200;
+/// How many of the latest snapshots `sandbox-status` reports.
+const STATUS_RECENT_SNAPSHOTS: usize = 5;
 
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct SandboxCreateArgs {
     pub name: String,
+    pub from_ref: Option<String>,
+    pub if_not_exists: Option<bool>,
+    pub template: Option<String>,
+    pub network: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
@@ -46,11 +74,69 @@ pub struct ReadArgs {
     pub limit: Option<usize>,
 }
 
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct HeadArgs {
+    pub sandbox: String,
+    pub path: String,
+    pub lines: usize,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct TailArgs {
+    pub sandbox: String,
+    pub path: String,
+    pub lines: usize,
+    pub follow: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct WcArgs {
+    pub sandbox: String,
+    pub path: String,
+}
+
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct WriteArgs {
     pub sandbox: String,
     pub path: String,
     pub content: String,
+    pub mode: Option<WriteMode>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct MultiWriteFile {
+    pub path: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct MultiWriteArgs {
+    pub sandbox: String,
+    pub files: Vec<MultiWriteFile>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct WriteBinaryArgs {
+    pub sandbox: String,
+    pub path: String,
+    pub content_base64: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteMode {
+    #[default]
+    Overwrite,
+    Append,
+    Prepend,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct LnArgs {
+    pub sandbox: String,
+    pub target: String,
+    pub link_path: String,
+    pub symbolic: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
@@ -66,6 +152,38 @@ pub struct BashArgs {
     pub command: String,
     pub workdir: Option<String>,
     pub timeout: Option<u64>,
+    pub user: Option<String>,
+    pub env: Option<BashEnv>,
+    pub stdin: Option<String>,
+}
+
+/// Extra environment variables for one `bash` call, either as a map of names to values or
+/// as `KEY=VALUE` strings.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum BashEnv {
+    Map(HashMap<String, String>),
+    List(Vec<String>),
+}
+
+impl BashEnv {
+    /// The variables as `KEY=VALUE` strings, sorted so that exec requests are reproducible.
+    fn assignments(&self) -> Vec<String> {
+        let mut assignments = match self {
+            BashEnv::Map(env) => env.iter().map(|(key, value)| format!("{key}={value}")).collect(),
+            BashEnv::List(env) => env.clone(),
+        };
+        assignments.sort();
+        assignments
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct GitArgs {
+    pub sandbox: String,
+    pub args: Vec<String>,
+    pub workdir: Option<String>,
+    pub allow_dangerous: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
@@ -73,6 +191,17 @@ pub struct LsArgs {
     pub sandbox: String,
     pub path: String,
     pub recursive: Option<bool>,
+    pub metadata: Option<bool>,
+    pub cursor: Option<String>,
+    pub page_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct TreeArgs {
+    pub sandbox: String,
+    pub path: String,
+    pub depth: Option<usize>,
+    pub show_hidden: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
@@ -80,6 +209,43 @@ pub struct GlobArgs {
     pub sandbox: String,
     pub pattern: String,
     pub path: Option<String>,
+    pub exclude: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct EnvArgs {
+    pub sandbox: String,
+    pub filter: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct PsArgs {
+    pub sandbox: String,
+    pub all: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct DfArgs {
+    pub sandbox: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct DuArgs {
+    pub sandbox: String,
+    pub path: String,
+    pub depth: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct FindArgs {
+    pub sandbox: String,
+    pub path: String,
+    pub name: Option<String>,
+    pub type_filter: Option<String>,
+    pub newer_than: Option<String>,
+    pub older_than: Option<String>,
+    pub min_size: Option<String>,
+    pub max_size: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
@@ -88,11 +254,26 @@ pub struct GrepArgs {
     pub pattern: String,
     pub path: String,
     pub include: Option<String>,
+    pub max_results: Option<usize>,
+    pub context_lines: Option<usize>,
+    pub fixed_string: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct SearchReplaceArgs {
+    pub sandbox: String,
+    pub pattern: String,
+    pub replacement: String,
+    pub path: String,
+    pub regex: Option<bool>,
+    pub case_insensitive: Option<bool>,
 }
 
 #[derive(Clone)]
 pub struct SandboxServer {
     tool_router: ToolRouter<Self>,
+    audit: Option<Arc<AuditLog>>,
+    watches: Watches,
 }
 
 impl Default for SandboxServer {
@@ -106,9 +287,23 @@ impl SandboxServer {
     pub fn new() -> Self {
         Self {
             tool_router: Self::tool_router(),
+            audit: None,
+            watches: Watches::default(),
         }
     }
 
+    /// Records every tool call to `audit` before it runs.
+    pub fn with_audit(mut self, audit: Option<Arc<AuditLog>>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Shares `watches` with other servers, so every session sees the same watched sandboxes.
+    pub fn with_watches(mut self, watches: Watches) -> Self {
+        self.watches = watches;
+        self
+    }
+
     #[tool(
         name = "sandbox-create",
         description = "Create a new sandbox based on the current repository HEAD"
@@ -117,39 +312,42 @@ impl SandboxServer {
         &self,
         Parameters(args): Parameters<SandboxCreateArgs>,
     ) -> Result<CallToolResult, McpError> {
+        validate_sandbox_name(&args.name)?;
         let config = config_loader::load_final()
             .map_err(|error| McpError::internal_error(error.to_string(), None))?;
-        let image =
-            config.docker.image.clone().ok_or_else(|| {
-                McpError::internal_error("missing docker.image".to_string(), None)
-            })?;
-        let forwarded_ports = config
-            .ports
-            .ports
-            .iter()
-            .map(|port| ForwardedPort {
-                name: port.name.clone(),
-                target: port.target,
-            })
-            .collect();
+        let sandbox_config = sandbox_config_for_create(&config, &args)?;
         let provider = build_provider_with_config(&config).map_err(map_error)?;
-        let sandbox_config = SandboxConfig {
-            image,
-            setup_command: config.docker.setup_command.clone(),
-            forwarded_ports,
+        let metadata = match provider.create(&args.name, &sandbox_config).await {
+            Ok(metadata) => metadata,
+            Err(SandboxError::SandboxExists { .. }) if args.if_not_exists.unwrap_or(false) => {
+                resolve_sandbox_metadata_inspected(&provider, &args.name)
+                    .await
+                    .map_err(map_error)?
+            }
+            Err(error) => return Err(map_error(error)),
         };
-        let metadata = provider
-            .create(&args.name, &sandbox_config)
-            .await
-            .map_err(map_error)?;
         let content = Content::json(metadata)
             .map_err(|error| McpError::internal_error(error.to_string(), None))?;
         Ok(CallToolResult::success(vec![content]))
     }
 
+    #[tool(
+        name = "sandbox-list",
+        description = "List sandboxes and their snapshot history without starting Docker"
+    )]
+    async fn sandbox_list(&self) -> Result<CallToolResult, McpError> {
+        let scm = ThreadSafeScm::open(Path::new(".")).map_err(map_error)?;
+        let mut entries = scm.list_sandboxes_with_metadata().map_err(map_error)?;
+        entries.sort_by(|a, b| a.slug.cmp(&b.slug));
+        let response: Vec<_> = entries.into_iter().map(SandboxListEntry::from).collect();
+        let content = Content::json(response)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
     #[tool(
         name = "sandbox-ports",
-        description = "Get forwarded ports for a sandbox"
+        description = "Get forwarded ports, the container IP address, image digest, and start time for a sandbox"
     )]
     async fn sandbox_ports(
         &self,
@@ -164,6 +362,9 @@ impl SandboxServer {
         let forwarded_ports = forwarded_ports_from_inspection(&inspection);
         let response = SandboxPortsResponse {
             name: args.sandbox,
+            container_ip: inspection.ip_address,
+            image_digest: inspection.image_digest,
+            started_at: inspection.started_at,
             forwarded_ports,
         };
         let content = Content::json(response)
@@ -171,170 +372,897 @@ impl SandboxServer {
         Ok(CallToolResult::success(vec![content]))
     }
 
-    #[tool(name = "read", description = "Read a file from the sandbox")]
-    async fn read(
+    #[tool(
+        name = "sandbox-status",
+        description = "Get a sandbox's status, forwarded ports, image, IP address, and recent snapshots"
+    )]
+    async fn sandbox_status(
         &self,
-        Parameters(args): Parameters<ReadArgs>,
+        Parameters(args): Parameters<SandboxStatusArgs>,
     ) -> Result<CallToolResult, McpError> {
         let provider = build_provider().map_err(map_error)?;
         let metadata = resolve_sandbox_metadata(&args.sandbox).map_err(map_error)?;
-        let content = read_in_sandbox(&provider, &metadata, &args.path, args.offset, args.limit)
-            .await
-            .map_err(|error| map_read_error(&args.sandbox, error))?;
-        let content = Content::text(content);
+        let slug = slugify_name(&args.sandbox).map_err(map_error)?;
+        let scm = sandbox_scm(&args.sandbox).map_err(map_error)?;
+        let mut metadata = match provider.inspect(&metadata).await {
+            Ok(inspected) => inspected,
+            Err(error) if is_container_missing(&error) => SandboxMetadata {
+                status: SandboxStatus::Error("missing container".to_string()),
+                ..metadata
+            },
+            Err(error) => return Err(map_sandbox_error(&args.sandbox, error)),
+        };
+        if let Ok((created_at, last_active_at)) = scm.branch_times(&slug) {
+            metadata.created_at = created_at;
+            metadata.last_active_at = last_active_at;
+        }
+        let recent_snapshots = scm
+            .list_snapshots(&slug, Some(STATUS_RECENT_SNAPSHOTS))
+            .map_err(map_error)?;
+        let response = SandboxStatusResponse {
+            metadata,
+            recent_snapshots,
+        };
+        let content = Content::json(response)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
         Ok(CallToolResult::success(vec![content]))
     }
 
-    #[tool(name = "write", description = "Write a file into the sandbox")]
-    async fn write(
+    #[tool(
+        name = "sandbox-link",
+        description = "Put two sandboxes on a shared Docker network so they can reach each other by slug, prefixed with the repository's prefix on a named network"
+    )]
+    async fn sandbox_link(
         &self,
-        Parameters(args): Parameters<WriteArgs>,
+        Parameters(args): Parameters<SandboxLinkArgs>,
     ) -> Result<CallToolResult, McpError> {
         let provider = build_provider().map_err(map_error)?;
-        let metadata = resolve_sandbox_metadata(&args.sandbox).map_err(map_error)?;
-        write_in_sandbox(&provider, &metadata, &args.path, &args.content)
-            .await
-            .map_err(|error| map_write_error(&args.sandbox, error))?;
-        snapshot_after(
-            &provider,
-            &metadata,
-            &args.sandbox,
-            SnapshotTrigger::Write { path: args.path },
-        )
-        .await
-        .map_err(map_error)?;
-        Ok(CallToolResult::success(Vec::new()))
+        let metadata_a = resolve_sandbox_metadata(&args.sandbox_a).map_err(map_error)?;
+        let metadata_b = resolve_sandbox_metadata(&args.sandbox_b).map_err(map_error)?;
+        // A caller-named network can be shared with other repositories' sandboxes, so bare
+        // slugs could collide there; the per-repository default network cannot.
+        let (network, alias_prefix) = match args.network_name {
+            Some(network) => (network, Some(link_repo_prefix().map_err(map_error)?)),
+            None => (default_link_network().map_err(map_error)?, None),
+        };
+        let members = [(args.sandbox_a, metadata_a), (args.sandbox_b, metadata_b)];
+        let response = link_sandboxes(&provider, members, network, alias_prefix.as_deref()).await?;
+        let content = Content::json(response)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
     }
 
     #[tool(
-        name = "patch",
-        description = "Apply a unified diff inside the sandbox"
+        name = "sandbox-stats",
+        description = "Get current CPU and memory usage for a sandbox"
     )]
-    async fn patch(
+    async fn sandbox_stats(
         &self,
-        Parameters(args): Parameters<PatchArgs>,
+        Parameters(args): Parameters<SandboxStatsArgs>,
     ) -> Result<CallToolResult, McpError> {
         let provider = build_provider().map_err(map_error)?;
         let metadata = resolve_sandbox_metadata(&args.sandbox).map_err(map_error)?;
-        patch_in_sandbox(&provider, &metadata, &args.path, &args.diff)
+        let stats = provider
+            .container_stats(&metadata.container_id)
             .await
-            .map_err(|error| map_patch_error(&args.sandbox, error))?;
-        snapshot_after(
-            &provider,
-            &metadata,
-            &args.sandbox,
-            SnapshotTrigger::Patch { path: args.path },
-        )
-        .await
-        .map_err(map_error)?;
-        Ok(CallToolResult::success(Vec::new()))
+            .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        let response = SandboxStatsResponse {
+            name: args.sandbox,
+            cpu_percent: stats.cpu_percent,
+            memory_used_bytes: stats.memory_used_bytes,
+            memory_limit_bytes: stats.memory_limit_bytes,
+        };
+        let content = Content::json(response)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
     }
 
     #[tool(
-        name = "bash",
-        description = "Execute a shell command inside the sandbox"
+        name = "sandbox-checkpoint",
+        description = "Save a sandbox container's current state as a reusable image"
     )]
-    async fn bash(
+    async fn sandbox_checkpoint(
         &self,
-        Parameters(args): Parameters<BashArgs>,
+        Parameters(args): Parameters<SandboxCheckpointArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let provider = build_provider().map_err(map_error)?;
-        let metadata = resolve_sandbox_metadata(&args.sandbox).map_err(map_error)?;
-        let result = bash_in_sandbox(
-            &provider,
-            &metadata,
-            &args.command,
-            args.workdir.as_deref(),
-            args.timeout,
-        )
-        .await
-        .map_err(|error| map_bash_error(&args.sandbox, error))?;
-        snapshot_after(
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let tag = args.tag.as_deref().unwrap_or(DEFAULT_CHECKPOINT_TAG);
+        provider
+            .commit_container(&metadata.container_id, &args.image, tag)
+            .await
+            .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Saved sandbox '{}' as image {}:{}.",
+            args.sandbox, args.image, tag
+        ))]))
+    }
+
+    #[tool(
+        name = "sandbox-snapshot",
+        description = "Record the sandbox's current files as a snapshot commit"
+    )]
+    async fn sandbox_snapshot(
+        &self,
+        Parameters(args): Parameters<SandboxSnapshotArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let oid = snapshot_after(
             &provider,
             &metadata,
             &args.sandbox,
-            SnapshotTrigger::Bash {
-                command: args.command.clone(),
+            SnapshotTrigger::Manual {
+                message: args.message,
             },
         )
         .await
-        .map_err(map_error)?;
-        let content = Content::json(result)
-            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
-        Ok(CallToolResult::success(vec![content]))
+        .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        let text = match oid {
+            Some(oid) => format!("Created snapshot {} for sandbox '{}'.", oid, args.sandbox),
+            None => format!(
+                "No changes in sandbox '{}' since the last snapshot.",
+                args.sandbox
+            ),
+        };
+        Ok(CallToolResult::success(vec![Content::text(text)]))
     }
 
-    #[tool(name = "ls", description = "List directory entries")]
-    async fn ls(&self, Parameters(args): Parameters<LsArgs>) -> Result<CallToolResult, McpError> {
-        let recursive = args.recursive.unwrap_or(false);
-        let provider = build_provider().map_err(map_error)?;
-        let metadata = resolve_sandbox_metadata(&args.sandbox).map_err(map_error)?;
-        let entries = ls_in_sandbox(&provider, &metadata, &args.path, recursive)
-            .await
-            .map_err(|error| map_ls_error(&args.sandbox, error))?;
-        let content = Content::json(entries)
+    #[tool(
+        name = "sandbox-watch",
+        description = "Snapshot a sandbox in the background every interval_secs seconds (default 30) while its files keep changing"
+    )]
+    async fn sandbox_watch(
+        &self,
+        Parameters(args): Parameters<SandboxWatchArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let interval_secs = args.interval_secs.unwrap_or(DEFAULT_WATCH_INTERVAL_SECS);
+        if interval_secs == 0 {
+            return Err(McpError::invalid_params(
+                "interval_secs must be at least 1".to_string(),
+                Some(error_data(LitterboxErrorCode::InvalidArgument)),
+            ));
+        }
+        resolve_sandbox_metadata(&args.sandbox).map_err(map_error)?;
+        let task = tokio::spawn(watch_sandbox(args.sandbox.clone(), interval_secs));
+        let previous = self
+            .watches
+            .lock()
+            .map_err(|_| McpError::internal_error("watch registry poisoned".to_string(), None))?
+            .insert(args.sandbox.clone(), WatchState::new(interval_secs, task));
+        let response = SandboxWatchResponse {
+            sandbox: args.sandbox,
+            interval_secs,
+            replaced: previous.is_some_and(|previous| !previous.is_finished()),
+        };
+        let content = Content::json(response)
             .map_err(|error| McpError::internal_error(error.to_string(), None))?;
         Ok(CallToolResult::success(vec![content]))
     }
 
-    #[tool(name = "glob", description = "Find files matching a glob pattern")]
-    async fn glob(
+    #[tool(
+        name = "sandbox-snapshot-prune",
+        description = "Drop a sandbox's old snapshots, keeping the newest within max_age_days and max_count"
+    )]
+    async fn sandbox_snapshot_prune(
         &self,
-        Parameters(args): Parameters<GlobArgs>,
+        Parameters(args): Parameters<SandboxSnapshotPruneArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let provider = build_provider().map_err(map_error)?;
-        let metadata = resolve_sandbox_metadata(&args.sandbox).map_err(map_error)?;
-        let matches = glob_in_sandbox(&provider, &metadata, &args.pattern, args.path.as_deref())
-            .await
-            .map_err(|error| map_glob_tool_error(&args.sandbox, error))?;
-        let content = Content::json(matches)
+        if args.max_age_days.is_none() && args.max_count.is_none() {
+            return Err(McpError::invalid_params(
+                "Pass max_age_days, max_count, or both".to_string(),
+                Some(error_data(LitterboxErrorCode::InvalidArgument)),
+            ));
+        }
+        let slug = slugify_name(&args.sandbox).map_err(map_error)?;
+        let scm = sandbox_scm(&args.sandbox).map_err(map_error)?;
+        let result = scm
+            .prune_snapshots(&slug, args.max_age_days, args.max_count)
+            .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        let content = Content::json(result)
             .map_err(|error| McpError::internal_error(error.to_string(), None))?;
         Ok(CallToolResult::success(vec![content]))
     }
 
-    #[tool(name = "grep", description = "Search file contents for a pattern")]
-    async fn grep(
+    #[tool(
+        name = "sandbox-stash",
+        description = "Set aside uncommitted changes in the host repository's working directory"
+    )]
+    async fn sandbox_stash(
         &self,
-        Parameters(args): Parameters<GrepArgs>,
+        Parameters(args): Parameters<SandboxStashArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let provider = build_provider().map_err(map_error)?;
-        let metadata = resolve_sandbox_metadata(&args.sandbox).map_err(map_error)?;
-        let matches = grep_in_sandbox(
-            &provider,
-            &metadata,
-            &args.pattern,
-            &args.path,
-            args.include.as_deref(),
-        )
-        .await
-        .map_err(|error| map_grep_error(&args.sandbox, error))?;
-        let content = Content::json(matches)
-            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
-        Ok(CallToolResult::success(vec![content]))
+        let scm = ThreadSafeScm::open(Path::new(".")).map_err(map_error)?;
+        let message = args.message.as_deref().unwrap_or("litterbox stash");
+        let text = match scm.stash(message) {
+            Ok(oid) => format!("Stashed local changes as {}.", oid),
+            Err(error) if error.error_code() == LitterboxErrorCode::NotFound => {
+                "No local changes to stash.".to_string()
+            }
+            Err(error) => return Err(map_error(error)),
+        };
+        Ok(CallToolResult::success(vec![Content::text(text)]))
     }
-}
 
-#[tool_handler(router = self.tool_router)]
-impl ServerHandler for SandboxServer {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            instructions: Some("Litterbox sandbox management".into()),
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
-            ..Default::default()
-        }
+    #[tool(
+        name = "sandbox-stash-pop",
+        description = "Reapply the most recent stash to the host repository's working directory"
+    )]
+    async fn sandbox_stash_pop(&self) -> Result<CallToolResult, McpError> {
+        let scm = ThreadSafeScm::open(Path::new(".")).map_err(map_error)?;
+        let text = match scm.stash_pop() {
+            Ok(()) => "Reapplied and dropped the most recent stash.".to_string(),
+            Err(error) if error.error_code() == LitterboxErrorCode::NotFound => {
+                "There is no stash to pop.".to_string()
+            }
+            Err(error) => return Err(map_error(error)),
+        };
+        Ok(CallToolResult::success(vec![Content::text(text)]))
     }
-}
-
-#[derive(Clone, Copy)]
-struct ToolDoc {
-    name: &'static str,
-    description: &'static str,
-    params: &'static [ParamDoc],
-}
 
-#[derive(Clone, Copy)]
-struct ParamDoc {
-    name: &'static str,
+    #[tool(
+        name = "sandbox-reset",
+        description = "Reset a sandbox's files to the repository HEAD, or preview the reset"
+    )]
+    async fn sandbox_reset(
+        &self,
+        Parameters(args): Parameters<SandboxResetArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let scm = sandbox_scm(&args.sandbox).map_err(map_error)?;
+
+        if !args.hard.unwrap_or(false) {
+            let diff = scm
+                .diff_between(&metadata.branch_name, "HEAD", None)
+                .map_err(map_error)?
+                .filter(|diff| !diff.is_empty());
+            let text = match diff {
+                Some(diff) => diff,
+                None => format!("Sandbox '{}' already matches HEAD.", args.sandbox),
+            };
+            return Ok(CallToolResult::success(vec![Content::text(text)]));
+        }
+
+        // Record the current files first so the reset can be undone from snapshot history.
+        snapshot_after(&provider, &metadata, &args.sandbox, SnapshotTrigger::Reset)
+            .await
+            .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        restore_snapshot(&provider, &scm, &metadata, "HEAD")
+            .await
+            .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Reset sandbox '{}' to HEAD.",
+            args.sandbox
+        ))]))
+    }
+
+    #[tool(
+        name = "sandbox-diff",
+        description = "Diff a sandbox's latest snapshot against HEAD or another reference"
+    )]
+    async fn sandbox_diff(
+        &self,
+        Parameters(args): Parameters<SandboxDiffArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let metadata = resolve_sandbox_metadata(&args.sandbox).map_err(map_error)?;
+        let scm = sandbox_scm(&args.sandbox).map_err(map_error)?;
+        let from = args.from.as_deref().unwrap_or("HEAD");
+        let diff = scm
+            .diff_between(from, &metadata.branch_name, args.path.as_deref())
+            .map_err(map_error)?
+            .filter(|diff| !diff.is_empty());
+        let text = match diff {
+            Some(diff) => diff,
+            None => format!("No changes in sandbox '{}' since {}.", args.sandbox, from),
+        };
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        name = "sandbox-branch",
+        description = "Create a named git branch at a sandbox's latest snapshot"
+    )]
+    async fn sandbox_branch(
+        &self,
+        Parameters(args): Parameters<SandboxBranchArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let slug = slugify_name(&args.sandbox).map_err(map_error)?;
+        let scm = sandbox_scm(&args.sandbox).map_err(map_error)?;
+        let branch_name = scm
+            .create_branch_from(&slug, &args.branch_name)
+            .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        if args.push.unwrap_or(false) {
+            scm.push_branch(&branch_name).map_err(map_error)?;
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Created and pushed branch {}.",
+                branch_name
+            ))]));
+        }
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Created branch {}.",
+            branch_name
+        ))]))
+    }
+
+    #[tool(
+        name = "sandbox-merge",
+        description = "Merge a sandbox's snapshots into a local branch of the host repository"
+    )]
+    async fn sandbox_merge(
+        &self,
+        Parameters(args): Parameters<SandboxMergeArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let slug = slugify_name(&args.sandbox).map_err(map_error)?;
+        let scm = sandbox_scm(&args.sandbox).map_err(map_error)?;
+        let target = args.target.as_deref().unwrap_or("HEAD");
+        let result = scm
+            .merge_branch(&slug, target, args.strategy.unwrap_or_default())
+            .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        let text = if result.had_conflicts {
+            format!(
+                "Merging sandbox '{}' into {} conflicts; {} was left unchanged.",
+                args.sandbox, target, target
+            )
+        } else {
+            format!("Merged sandbox '{}' into {} at {}.", args.sandbox, target, result.oid)
+        };
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        name = "sandbox-cherry-pick",
+        description = "Apply the changes of a single commit to a sandbox"
+    )]
+    async fn sandbox_cherry_pick(
+        &self,
+        Parameters(args): Parameters<SandboxCherryPickArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let scm = sandbox_scm(&args.sandbox).map_err(map_error)?;
+
+        // Capture unsnapshotted edits so the pick builds on the sandbox's current files.
+        let trigger = SnapshotTrigger::CherryPick {
+            commit: args.commit.clone(),
+        };
+        snapshot_after(&provider, &metadata, &args.sandbox, trigger)
+            .await
+            .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        let Some(oid) = scm.cherry_pick(&args.commit).map_err(map_error)? else {
+            return Ok(CallToolResult::success(vec![Content::text(format!(
+                "Sandbox '{}' already contains {}.",
+                args.sandbox, args.commit
+            ))]));
+        };
+        restore_snapshot(&provider, &scm, &metadata, &oid.to_string())
+            .await
+            .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Cherry-picked {} into sandbox '{}' as {}.",
+            args.commit, args.sandbox, oid
+        ))]))
+    }
+
+    #[tool(name = "read", description = "Read a file from the sandbox")]
+    async fn read(
+        &self,
+        Parameters(args): Parameters<ReadArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let content =
+            read_content_in_sandbox(&provider, &metadata, &args.path, args.offset, args.limit)
+                .await
+                .map_err(|error| map_read_error(&args.sandbox, error))?;
+        let content = match content {
+            ReadContent::Text(text) => Content::text(text),
+            ReadContent::Binary(bytes) => Content::json(ReadBinaryResult {
+                content_base64: BASE64.encode(&bytes),
+                encoding: "base64",
+                size: bytes.len(),
+            })
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?,
+        };
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(name = "head", description = "Read the first lines of a file in the sandbox")]
+    async fn head(
+        &self,
+        Parameters(args): Parameters<HeadArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let content = head_in_sandbox(&provider, &metadata, &args.path, args.lines)
+            .await
+            .map_err(|error| map_read_error(&args.sandbox, error))?;
+        Ok(CallToolResult::success(vec![Content::text(content)]))
+    }
+
+    #[tool(name = "tail", description = "Read the last lines of a file in the sandbox")]
+    async fn tail(
+        &self,
+        Parameters(args): Parameters<TailArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let content = tail_in_sandbox(
+            &provider,
+            &metadata,
+            &args.path,
+            args.lines,
+            args.follow.unwrap_or(false),
+        )
+        .await
+        .map_err(|error| map_read_error(&args.sandbox, error))?;
+        Ok(CallToolResult::success(vec![Content::text(content)]))
+    }
+
+    #[tool(name = "wc", description = "Count lines, words, and bytes in a sandbox file")]
+    async fn wc(&self, Parameters(args): Parameters<WcArgs>) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let counts = wc_in_sandbox(&provider, &metadata, &args.path)
+            .await
+            .map_err(|error| map_read_error(&args.sandbox, error))?;
+        let content = Content::json(counts)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(name = "write", description = "Write a file into the sandbox")]
+    async fn write(
+        &self,
+        Parameters(args): Parameters<WriteArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let mode = args.mode.unwrap_or_default();
+        write_in_sandbox(&provider, &metadata, &args.path, &args.content, mode)
+            .await
+            .map_err(|error| map_write_error(&args.sandbox, error))?;
+        snapshot_after(
+            &provider,
+            &metadata,
+            &args.sandbox,
+            SnapshotTrigger::Write {
+                path: args.path,
+                mode,
+            },
+        )
+        .await
+        .map_err(map_error)?;
+        Ok(CallToolResult::success(Vec::new()))
+    }
+
+    #[tool(name = "ln", description = "Create a link inside the sandbox")]
+    async fn ln(&self, Parameters(args): Parameters<LnArgs>) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        ln_in_sandbox(
+            &provider,
+            &metadata,
+            &args.target,
+            &args.link_path,
+            args.symbolic.unwrap_or(true),
+        )
+        .await
+        .map_err(|error| map_ln_error(&args.sandbox, error))?;
+        Ok(CallToolResult::success(Vec::new()))
+    }
+
+    #[tool(
+        name = "write-binary",
+        description = "Write base64-encoded binary content to a file in the sandbox"
+    )]
+    async fn write_binary(
+        &self,
+        Parameters(args): Parameters<WriteBinaryArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        write_binary_in_sandbox(&provider, &metadata, &args.path, &args.content_base64)
+            .await
+            .map_err(|error| map_write_error(&args.sandbox, error))?;
+        snapshot_after(
+            &provider,
+            &metadata,
+            &args.sandbox,
+            SnapshotTrigger::Write {
+                path: args.path,
+                mode: WriteMode::Overwrite,
+            },
+        )
+        .await
+        .map_err(map_error)?;
+        Ok(CallToolResult::success(Vec::new()))
+    }
+
+    #[tool(
+        name = "multi-write",
+        description = "Write several files into the sandbox in a single upload"
+    )]
+    async fn multi_write(
+        &self,
+        Parameters(args): Parameters<MultiWriteArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        if args.files.is_empty() {
+            return Err(McpError::invalid_params(
+                "files must not be empty".to_string(),
+                None,
+            ));
+        }
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        multi_write_in_sandbox(&provider, &metadata, &args.files)
+            .await
+            .map_err(|error| map_write_error(&args.sandbox, error))?;
+        snapshot_after(
+            &provider,
+            &metadata,
+            &args.sandbox,
+            SnapshotTrigger::MultiWrite {
+                count: args.files.len(),
+            },
+        )
+        .await
+        .map_err(map_error)?;
+        Ok(CallToolResult::success(Vec::new()))
+    }
+
+    #[tool(
+        name = "patch",
+        description = "Apply a unified diff inside the sandbox"
+    )]
+    async fn patch(
+        &self,
+        Parameters(args): Parameters<PatchArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        patch_in_sandbox(&provider, &metadata, &args.path, &args.diff)
+            .await
+            .map_err(|error| map_patch_error(&args.sandbox, error))?;
+        snapshot_after(
+            &provider,
+            &metadata,
+            &args.sandbox,
+            SnapshotTrigger::Patch { path: args.path },
+        )
+        .await
+        .map_err(map_error)?;
+        Ok(CallToolResult::success(Vec::new()))
+    }
+
+    #[tool(
+        name = "patch-check",
+        description = "Check that a unified diff applies cleanly inside the sandbox without changing files"
+    )]
+    async fn patch_check(
+        &self,
+        Parameters(args): Parameters<PatchArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, _) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let path = workdir_relative_path(provider.workdir(), &args.path).ok_or_else(|| {
+            McpError::invalid_params(
+                format!("{} is outside the sandbox's project directory", args.path),
+                Some(error_data(LitterboxErrorCode::InvalidArgument)),
+            )
+        })?;
+        let diff = git_diff_for_path(path, &args.diff).ok_or_else(|| {
+            McpError::invalid_params("invalid patch: no hunks found".to_string(), None)
+        })?;
+        // Every tool snapshots the sandbox after changing it, so the patch is checked against
+        // the sandbox branch rather than the container.
+        let scm = sandbox_scm(&args.sandbox).map_err(map_error)?;
+        crate::scm::AsyncScm::apply_patch_dry_run(&scm, &diff).await.map_err(map_error)?;
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "Patch applies cleanly to {}.",
+            args.path
+        ))]))
+    }
+
+    #[tool(
+        name = "bash",
+        description = "Execute a shell command inside the sandbox"
+    )]
+    async fn bash(
+        &self,
+        Parameters(args): Parameters<BashArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let env = args.env.as_ref().map(BashEnv::assignments);
+        if let Some(env) = &env {
+            validate_env_assignments(env)?;
+        }
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let result = bash_in_sandbox(
+            &provider,
+            &metadata,
+            &args.command,
+            args.workdir.as_deref(),
+            args.timeout,
+            &ExecOptions {
+                user: args.user.clone(),
+                env,
+                stdin: args.stdin.clone().map(String::into_bytes),
+            },
+        )
+        .await
+        .map_err(|error| map_bash_error(&args.sandbox, error))?;
+        snapshot_after(
+            &provider,
+            &metadata,
+            &args.sandbox,
+            SnapshotTrigger::Bash {
+                command: args.command.clone(),
+            },
+        )
+        .await
+        .map_err(map_error)?;
+        let content = Content::json(result)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(
+        name = "git",
+        description = "Run a git command in the sandbox's project directory"
+    )]
+    async fn git(&self, Parameters(args): Parameters<GitArgs>) -> Result<CallToolResult, McpError> {
+        if !args.allow_dangerous.unwrap_or(false)
+            && let Some(operation) = dangerous_git_operation(&args.args)
+        {
+            return Err(McpError::invalid_params(
+                format!("git {operation} is blocked; pass allow_dangerous: true to run it"),
+                Some(error_data(LitterboxErrorCode::InvalidArgument)),
+            ));
+        }
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let result = git_in_sandbox(&provider, &metadata, &args.args, args.workdir.as_deref())
+            .await
+            .map_err(|error| map_sandbox_error(&args.sandbox, error))?;
+        snapshot_after(
+            &provider,
+            &metadata,
+            &args.sandbox,
+            SnapshotTrigger::Git {
+                args: args.args.clone(),
+            },
+        )
+        .await
+        .map_err(map_error)?;
+        let content = Content::json(result)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(name = "ls", description = "List directory entries")]
+    async fn ls(&self, Parameters(args): Parameters<LsArgs>) -> Result<CallToolResult, McpError> {
+        let recursive = args.recursive.unwrap_or(false);
+        let with_metadata = args.metadata.unwrap_or(false);
+        if recursive && with_metadata {
+            return Err(McpError::invalid_params(
+                "metadata cannot be combined with recursive".to_string(),
+                None,
+            ));
+        }
+        let page_size = args.page_size.unwrap_or(DEFAULT_LS_PAGE_SIZE);
+        if page_size == 0 {
+            return Err(McpError::invalid_params(
+                "page_size must be greater than zero".to_string(),
+                None,
+            ));
+        }
+        let after = args.cursor.as_deref().map(decode_ls_cursor).transpose()?;
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let content = if with_metadata {
+            let mut entries = ls_metadata_in_sandbox(&provider, &metadata, &args.path)
+                .await
+                .map_err(|error| map_ls_error(&args.sandbox, error))?;
+            entries.sort_by(|left, right| left.name.cmp(&right.name));
+            let page = paginate_entries(entries, |entry| &entry.name, after.as_deref(), page_size);
+            Content::json(page)
+        } else {
+            let entries = ls_in_sandbox(&provider, &metadata, &args.path, recursive)
+                .await
+                .map_err(|error| map_ls_error(&args.sandbox, error))?;
+            Content::json(paginate_entries(entries, String::as_str, after.as_deref(), page_size))
+        }
+        .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(name = "tree", description = "Show a directory as an indented tree")]
+    async fn tree(
+        &self,
+        Parameters(args): Parameters<TreeArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let tree = tree_in_sandbox(
+            &provider,
+            &metadata,
+            &args.path,
+            args.depth,
+            args.show_hidden.unwrap_or(false),
+        )
+        .await
+        .map_err(|error| map_ls_error(&args.sandbox, error))?;
+        Ok(CallToolResult::success(vec![Content::text(tree)]))
+    }
+
+    #[tool(name = "glob", description = "Find files matching a glob pattern")]
+    async fn glob(
+        &self,
+        Parameters(args): Parameters<GlobArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let matches = glob_in_sandbox(
+            &provider,
+            &metadata,
+            &args.pattern,
+            args.path.as_deref(),
+            args.exclude.as_deref().unwrap_or_default(),
+        )
+        .await
+        .map_err(|error| map_glob_tool_error(&args.sandbox, error))?;
+        let content = Content::json(matches)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(name = "env", description = "List environment variables inside the sandbox")]
+    async fn env(&self, Parameters(args): Parameters<EnvArgs>) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let entries = env_in_sandbox(&provider, &metadata, args.filter.as_deref())
+            .await
+            .map_err(|error| map_env_error(&args.sandbox, error))?;
+        let content = Content::json(entries)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(name = "ps", description = "List processes running inside the sandbox")]
+    async fn ps(&self, Parameters(args): Parameters<PsArgs>) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let processes = ps_in_sandbox(&provider, &metadata, args.all.unwrap_or(false))
+            .await
+            .map_err(|error| map_ps_error(&args.sandbox, error))?;
+        let content = Content::json(processes)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(name = "df", description = "Report disk space on the sandbox root filesystem")]
+    async fn df(&self, Parameters(args): Parameters<DfArgs>) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let usage = df_in_sandbox(&provider, &metadata)
+            .await
+            .map_err(|error| map_disk_usage_error(&args.sandbox, error))?;
+        let content = Content::json(usage)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(name = "du", description = "Report disk usage of a path in the sandbox")]
+    async fn du(&self, Parameters(args): Parameters<DuArgs>) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let entries = du_in_sandbox(&provider, &metadata, &args.path, args.depth)
+            .await
+            .map_err(|error| map_disk_usage_error(&args.sandbox, error))?;
+        let content = Content::json(entries)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(
+        name = "find",
+        description = "Find paths by name, type, modification time, or size"
+    )]
+    async fn find(
+        &self,
+        Parameters(args): Parameters<FindArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let matches = find_in_sandbox(&provider, &metadata, &args)
+            .await
+            .map_err(|error| map_find_error(&args.sandbox, error))?;
+        let content = Content::json(matches)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(name = "grep", description = "Search file contents for a pattern")]
+    async fn grep(
+        &self,
+        Parameters(args): Parameters<GrepArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let options = GrepOptions {
+            include: args.include.clone(),
+            max_results: args.max_results,
+            context_lines: args.context_lines,
+            fixed_string: args.fixed_string.unwrap_or(false),
+        };
+        let matches = grep_in_sandbox(&provider, &metadata, &args.pattern, &args.path, &options)
+            .await
+        .map_err(|error| map_grep_error(&args.sandbox, error))?;
+        let content = Content::json(matches)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    #[tool(
+        name = "search-replace",
+        description = "Replace every occurrence of a pattern in files under a path"
+    )]
+    async fn search_replace(
+        &self,
+        Parameters(args): Parameters<SearchReplaceArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (provider, metadata) = sandbox_provider(&args.sandbox).await.map_err(map_error)?;
+        let result = search_replace_in_sandbox(
+            &provider,
+            &metadata,
+            &args.pattern,
+            &args.replacement,
+            &args.path,
+            args.regex.unwrap_or(false),
+            args.case_insensitive.unwrap_or(false),
+        )
+        .await
+        .map_err(|error| map_search_replace_error(&args.sandbox, error))?;
+        snapshot_after(
+            &provider,
+            &metadata,
+            &args.sandbox,
+            SnapshotTrigger::SearchReplace {
+                pattern: args.pattern,
+                replacement: args.replacement,
+                path: args.path,
+            },
+        )
+        .await
+        .map_err(map_error)?;
+        let content = Content::json(result)
+            .map_err(|error| McpError::internal_error(error.to_string(), None))?;
+        Ok(CallToolResult::success(vec![content]))
+    }
+}
+
+// Written out rather than generated by `#[tool_handler]` so every call passes through the
+// audit log in one place.
+impl ServerHandler for SandboxServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            instructions: Some("Litterbox sandbox management".into()),
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            ..Default::default()
+        }
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if let Some(audit) = &self.audit {
+            audit.record(&request.name, request.arguments.as_ref());
+        }
+        let context = ToolCallContext::new(self, request, context);
+        self.tool_router.call(context).await
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult {
+            tools: self.tool_router.list_all(),
+            meta: None,
+            next_cursor: None,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ToolDoc {
+    name: &'static str,
+    description: &'static str,
+    params: &'static [ParamDoc],
+}
+
+#[derive(Clone, Copy)]
+struct ParamDoc {
+    name: &'static str,
     type_name: &'static str,
     required: bool,
     description: &'static str,
@@ -356,123 +1284,637 @@ const PATH_PARAM: ParamDoc = ParamDoc {
 
 const TOOL_DOCS: &[ToolDoc] = &[
     ToolDoc {
-        name: "sandbox-create",
-        description: "Create a new sandbox based on the current repository HEAD.",
-        params: &[ParamDoc {
-            name: "name",
-            type_name: "string",
-            required: true,
-            description: "Sandbox name.",
-        }],
+        name: "sandbox-create",
+        description: "Create a new sandbox based on the current repository HEAD, or on another git ref.",
+        params: &[
+            ParamDoc {
+                name: "name",
+                type_name: "string",
+                required: true,
+                description: "Sandbox name.",
+            },
+            ParamDoc {
+                name: "from_ref",
+                type_name: "string",
+                required: false,
+                description: "Commit, tag, or branch to start from (default: HEAD).",
+            },
+            ParamDoc {
+                name: "if_not_exists",
+                type_name: "boolean",
+                required: false,
+                description: "Return the existing sandbox instead of failing when the name is taken (default: false).",
+            },
+            ParamDoc {
+                name: "template",
+                type_name: "string",
+                required: false,
+                description: "Name of a `[[templates]]` entry whose image, setup command, and environment replace the `docker` defaults.",
+            },
+            ParamDoc {
+                name: "network",
+                type_name: "string",
+                required: false,
+                description: "Docker network to attach the sandbox to, created if missing. Sandboxes on the same network reach each other by container name.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "sandbox-list",
+        description: "List the sandboxes recorded in the repository with their latest snapshot and snapshot count. Reads git only, so it works while Docker is unavailable.",
+        params: &[],
+    },
+    ToolDoc {
+        name: "sandbox-ports",
+        description: "Get forwarded ports, the container IP address, image digest, and start time for a sandbox.",
+        params: &[SANDBOX_NAME_PARAM],
+    },
+    ToolDoc {
+        name: "sandbox-status",
+        description: "Get a sandbox's status, forwarded ports, image digest, IP address, start time, and its five most recent snapshots.",
+        params: &[SANDBOX_NAME_PARAM],
+    },
+    ToolDoc {
+        name: "sandbox-link",
+        description: "Attach two sandboxes to a shared Docker network, creating it if needed, so each can reach the other by its slug. On a `network_name` the alias is `<repo prefix>-<slug>` instead, since that network may hold other repositories' sandboxes. Linking is idempotent. Returns the network name and each sandbox's alias on it.",
+        params: &[
+            ParamDoc {
+                name: "sandbox_a",
+                type_name: "string",
+                required: true,
+                description: "First sandbox name.",
+            },
+            ParamDoc {
+                name: "sandbox_b",
+                type_name: "string",
+                required: true,
+                description: "Second sandbox name.",
+            },
+            ParamDoc {
+                name: "network_name",
+                type_name: "string",
+                required: false,
+                description: "Docker network to use (default: one network per repository, `litterbox-<repo prefix>`).",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "sandbox-stats",
+        description: "Get current CPU and memory usage for a sandbox.",
+        params: &[SANDBOX_NAME_PARAM],
+    },
+    ToolDoc {
+        name: "sandbox-checkpoint",
+        description: "Save a sandbox container's current state as a reusable image.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "image",
+                type_name: "string",
+                required: true,
+                description: "Image repository name to save as.",
+            },
+            ParamDoc {
+                name: "tag",
+                type_name: "string",
+                required: false,
+                description: "Image tag (default: latest).",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "sandbox-snapshot",
+        description: "Record the sandbox's current files as a snapshot commit. Reports the new commit id, or that nothing changed since the last snapshot.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "message",
+                type_name: "string",
+                required: false,
+                description: "Description recorded in the snapshot commit message.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "sandbox-watch",
+        description: "Snapshot a sandbox in the background on a fixed interval, so changes made outside the MCP tools are recorded too. A snapshot is only committed when the files differ from the last one. Calling it again for the same sandbox replaces the interval; the watch ends when the server stops or the sandbox is gone.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "interval_secs",
+                type_name: "integer",
+                required: false,
+                description: "Seconds between snapshots (default: 30).",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "sandbox-snapshot-prune",
+        description: "Drop a sandbox's old snapshots. Keeps the newest snapshots that satisfy both limits and rewrites them onto the sandbox's base, so the latest files are unchanged; when none are kept, the history collapses into one squash commit. Returns the number of snapshots removed and kept, and the squash commit id if one was made.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "max_age_days",
+                type_name: "integer",
+                required: false,
+                description: "Drop snapshots older than this many days.",
+            },
+            ParamDoc {
+                name: "max_count",
+                type_name: "integer",
+                required: false,
+                description: "Keep at most this many of the newest snapshots. At least one of the two limits is required.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "sandbox-stash",
+        description: "Set aside uncommitted changes to tracked files in the host repository's working directory, leaving it at HEAD. Sandbox files are not touched. Reports the stash commit id, or that there was nothing to stash.",
+        params: &[ParamDoc {
+            name: "message",
+            type_name: "string",
+            required: false,
+            description: "Description recorded with the stash (default: litterbox stash).",
+        }],
+    },
+    ToolDoc {
+        name: "sandbox-stash-pop",
+        description: "Reapply the most recent stash to the host repository's working directory and drop it. Fails without dropping the stash when it conflicts with local changes.",
+        params: &[],
+    },
+    ToolDoc {
+        name: "sandbox-reset",
+        description: "Reset a sandbox's files to the repository HEAD. Without `hard`, returns the diff the reset would apply and changes nothing.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "hard",
+                type_name: "boolean",
+                required: false,
+                description: "Apply the reset, snapshotting the current files first (default: false).",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "sandbox-diff",
+        description: "Show a unified diff of a sandbox's latest snapshot against HEAD or another reference.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "from",
+                type_name: "string",
+                required: false,
+                description: "Snapshot commit id or branch to compare from (default: HEAD).",
+            },
+            ParamDoc {
+                name: "path",
+                type_name: "string",
+                required: false,
+                description: "Only include changes under this path.",
+            },
+        ],
     },
     ToolDoc {
-        name: "sandbox-ports",
-        description: "Get forwarded ports for a sandbox.",
-        params: &[SANDBOX_NAME_PARAM],
+        name: "sandbox-branch",
+        description: "Create a named git branch at a sandbox's latest snapshot, for example to promote finished work to a feature branch.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "branch_name",
+                type_name: "string",
+                required: true,
+                description: "Name of the branch to create.",
+            },
+            ParamDoc {
+                name: "push",
+                type_name: "boolean",
+                required: false,
+                description: "Also push the branch to origin (default: false).",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "sandbox-merge",
+        description: "Merge a sandbox's latest snapshot into a local branch of the host repository. A checked-out target also has its working tree updated. Conflicting merges leave the target unchanged.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "target",
+                type_name: "string",
+                required: false,
+                description: "Branch to merge into (default: the checked-out branch).",
+            },
+            ParamDoc {
+                name: "strategy",
+                type_name: "string",
+                required: false,
+                description: "fast-forward, squash, or merge (default: merge).",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "sandbox-cherry-pick",
+        description: "Apply the changes of one commit, such as a fix snapshotted in another sandbox, to a sandbox's files and snapshot history.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "commit",
+                type_name: "string",
+                required: true,
+                description: "Commit id or other git revision to apply.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "read",
+        description: "Read a file from the sandbox. Binary files (containing NUL bytes) are returned as JSON with `content_base64`, `encoding`, and `size`, ignoring offset and limit.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            PATH_PARAM,
+            ParamDoc {
+                name: "offset",
+                type_name: "integer",
+                required: false,
+                description: "Line offset (0-based).",
+            },
+            ParamDoc {
+                name: "limit",
+                type_name: "integer",
+                required: false,
+                description: "Maximum number of lines to read.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "head",
+        description: "Read the first lines of a file in the sandbox.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            PATH_PARAM,
+            ParamDoc {
+                name: "lines",
+                type_name: "integer",
+                required: true,
+                description: "Number of lines to read.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "tail",
+        description: "Read the last lines of a file in the sandbox.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            PATH_PARAM,
+            ParamDoc {
+                name: "lines",
+                type_name: "integer",
+                required: true,
+                description: "Number of lines to read.",
+            },
+            ParamDoc {
+                name: "follow",
+                type_name: "boolean",
+                required: false,
+                description: "Keep reading lines appended to the file for 5 seconds before returning.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "wc",
+        description: "Count lines, words, and bytes in a file in the sandbox.",
+        params: &[SANDBOX_NAME_PARAM, PATH_PARAM],
+    },
+    ToolDoc {
+        name: "write",
+        description: "Write a file into the sandbox.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            PATH_PARAM,
+            ParamDoc {
+                name: "content",
+                type_name: "string",
+                required: true,
+                description: "Content to write.",
+            },
+            ParamDoc {
+                name: "mode",
+                type_name: "string",
+                required: false,
+                description: "overwrite, append, or prepend (default: overwrite).",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "ln",
+        description: "Create a symbolic or hard link inside the sandbox.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "target",
+                type_name: "string",
+                required: true,
+                description: "Path the link points to. Symbolic link targets are stored as given.",
+            },
+            ParamDoc {
+                name: "link_path",
+                type_name: "string",
+                required: true,
+                description: "Path of the link to create inside the sandbox.",
+            },
+            ParamDoc {
+                name: "symbolic",
+                type_name: "boolean",
+                required: false,
+                description: "Create a symbolic link rather than a hard link (default: true).",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "write-binary",
+        description: "Write binary content into the sandbox without shell quoting, replacing the file.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            PATH_PARAM,
+            ParamDoc {
+                name: "content_base64",
+                type_name: "string",
+                required: true,
+                description: "File contents encoded as standard base64.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "multi-write",
+        description: "Write several files into the sandbox in a single upload, so other operations never see only some of them. Existing files are replaced.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "files",
+                type_name: "array",
+                required: true,
+                description: "Files to write, each an object with `path` and `content`.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "patch",
+        description: "Apply a unified diff inside the sandbox.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            PATH_PARAM,
+            ParamDoc {
+                name: "diff",
+                type_name: "string",
+                required: true,
+                description: "Unified diff to apply.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "patch-check",
+        description: "Check that a unified diff applies cleanly inside the sandbox without changing files.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            PATH_PARAM,
+            ParamDoc {
+                name: "diff",
+                type_name: "string",
+                required: true,
+                description: "Unified diff to check.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "bash",
+        description: "Execute a shell command inside the sandbox.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "command",
+                type_name: "string",
+                required: true,
+                description: "Shell command to execute.",
+            },
+            ParamDoc {
+                name: "workdir",
+                type_name: "string",
+                required: false,
+                description: "Working directory inside the sandbox.",
+            },
+            ParamDoc {
+                name: "timeout",
+                type_name: "integer",
+                required: false,
+                description: "Timeout in seconds.",
+            },
+            ParamDoc {
+                name: "user",
+                type_name: "string",
+                required: false,
+                description: "User to run the command as.",
+            },
+            ParamDoc {
+                name: "env",
+                type_name: "object",
+                required: false,
+                description: "Extra environment variables for this command only, as a map of names to values or an array of KEY=VALUE strings. They are set on the exec, not in the container.",
+            },
+            ParamDoc {
+                name: "stdin",
+                type_name: "string",
+                required: false,
+                description: "Text written to the command's standard input.",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "git",
+        description: "Run `git <args>` inside the sandbox, in its project directory unless workdir is given. The arguments are passed to git directly, without a shell. Cloning, adding remotes, force pushes, and defining aliases are refused unless allow_dangerous is set. Sandboxes hold their branch's files without its `.git`, so commands that need a repository fail with an error until one is created with `git init`. Returns the exit code, stdout, and stderr, and records a snapshot if files changed.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "args",
+                type_name: "array",
+                required: true,
+                description: "Arguments after `git`, e.g. [\"rebase\", \"main\"].",
+            },
+            ParamDoc {
+                name: "workdir",
+                type_name: "string",
+                required: false,
+                description: "Directory to run git in, relative to the project directory.",
+            },
+            ParamDoc {
+                name: "allow_dangerous",
+                type_name: "boolean",
+                required: false,
+                description: "Allow `clone`, `remote add`, force pushes, and alias definitions (default: false).",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "ls",
+        description: "List directory entries.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            PATH_PARAM,
+            ParamDoc {
+                name: "recursive",
+                type_name: "boolean",
+                required: false,
+                description: "Recursively list all descendants.",
+            },
+            ParamDoc {
+                name: "metadata",
+                type_name: "boolean",
+                required: false,
+                description: "Return name, size, mode, modified, is_dir, and is_symlink for each entry instead of bare names. Cannot be combined with recursive.",
+            },
+            ParamDoc {
+                name: "cursor",
+                type_name: "string",
+                required: false,
+                description: "Opaque next_cursor value from a previous page.",
+            },
+            ParamDoc {
+                name: "page_size",
+                type_name: "integer",
+                required: false,
+                description: "Maximum number of entries per page (default 200).",
+            },
+        ],
     },
     ToolDoc {
-        name: "read",
-        description: "Read a file from the sandbox.",
+        name: "tree",
+        description: "Show a directory and its descendants as an indented tree.",
         params: &[
             SANDBOX_NAME_PARAM,
             PATH_PARAM,
             ParamDoc {
-                name: "offset",
+                name: "depth",
                 type_name: "integer",
                 required: false,
-                description: "Line offset (0-based).",
+                description: "Maximum depth to descend (default: unlimited).",
             },
             ParamDoc {
-                name: "limit",
-                type_name: "integer",
+                name: "show_hidden",
+                type_name: "boolean",
                 required: false,
-                description: "Maximum number of lines to read.",
+                description: "Include entries whose names start with a dot (default: false).",
             },
         ],
     },
     ToolDoc {
-        name: "write",
-        description: "Write a file into the sandbox.",
+        name: "glob",
+        description: "Find files matching a glob pattern.",
         params: &[
             SANDBOX_NAME_PARAM,
-            PATH_PARAM,
             ParamDoc {
-                name: "content",
+                name: "pattern",
                 type_name: "string",
                 required: true,
-                description: "Full file contents to write.",
+                description: "Glob pattern to match.",
+            },
+            ParamDoc {
+                name: "path",
+                type_name: "string",
+                required: false,
+                description: "Base path for matching.",
+            },
+            ParamDoc {
+                name: "exclude",
+                type_name: "array",
+                required: false,
+                description: "Glob patterns for paths to leave out (e.g., target/**).",
             },
         ],
     },
     ToolDoc {
-        name: "patch",
-        description: "Apply a unified diff inside the sandbox.",
+        name: "env",
+        description: "List environment variables inside the sandbox.",
         params: &[
             SANDBOX_NAME_PARAM,
-            PATH_PARAM,
             ParamDoc {
-                name: "diff",
+                name: "filter",
                 type_name: "string",
-                required: true,
-                description: "Unified diff to apply.",
+                required: false,
+                description: "Only return variables whose KEY=VALUE line matches this grep pattern.",
             },
         ],
     },
     ToolDoc {
-        name: "bash",
-        description: "Execute a shell command inside the sandbox.",
+        name: "ps",
+        description: "List processes running inside the sandbox. CPU and memory columns are empty on images whose ps does not report them (e.g., BusyBox).",
         params: &[
             SANDBOX_NAME_PARAM,
             ParamDoc {
-                name: "command",
-                type_name: "string",
-                required: true,
-                description: "Shell command to execute.",
-            },
-            ParamDoc {
-                name: "workdir",
-                type_name: "string",
-                required: false,
-                description: "Working directory inside the sandbox.",
-            },
-            ParamDoc {
-                name: "timeout",
-                type_name: "integer",
+                name: "all",
+                type_name: "boolean",
                 required: false,
-                description: "Timeout in seconds.",
+                description: "Include processes of all users, not just the exec user (default: false).",
             },
         ],
     },
     ToolDoc {
-        name: "ls",
-        description: "List directory entries.",
+        name: "df",
+        description: "Report size, used, and available space on the sandbox root filesystem.",
+        params: &[SANDBOX_NAME_PARAM],
+    },
+    ToolDoc {
+        name: "du",
+        description: "Report disk usage of a path in the sandbox.",
         params: &[
             SANDBOX_NAME_PARAM,
             PATH_PARAM,
             ParamDoc {
-                name: "recursive",
-                type_name: "boolean",
+                name: "depth",
+                type_name: "integer",
                 required: false,
-                description: "Recursively list all descendants.",
+                description: "Also report subdirectories down to this depth (default: path total only).",
             },
         ],
     },
     ToolDoc {
-        name: "glob",
-        description: "Find files matching a glob pattern.",
+        name: "find",
+        description: "Find paths under a directory by name, type, modification time, or size.",
         params: &[
             SANDBOX_NAME_PARAM,
+            PATH_PARAM,
             ParamDoc {
-                name: "pattern",
+                name: "name",
                 type_name: "string",
-                required: true,
-                description: "Glob pattern to match.",
+                required: false,
+                description: "Shell pattern matched against the file name (e.g., *.rs).",
             },
             ParamDoc {
-                name: "path",
+                name: "type_filter",
                 type_name: "string",
                 required: false,
-                description: "Base path for matching.",
+                description: "Entry type: f (file), d (directory), or l (symlink).",
+            },
+            ParamDoc {
+                name: "newer_than",
+                type_name: "string",
+                required: false,
+                description: "Only entries modified within this age (e.g., 30m, 2h, 7d, 1w).",
+            },
+            ParamDoc {
+                name: "older_than",
+                type_name: "string",
+                required: false,
+                description: "Only entries last modified longer ago than this age.",
+            },
+            ParamDoc {
+                name: "min_size",
+                type_name: "string",
+                required: false,
+                description: "Minimum size in bytes, or with a k, M, or G suffix.",
+            },
+            ParamDoc {
+                name: "max_size",
+                type_name: "string",
+                required: false,
+                description: "Maximum size in bytes, or with a k, M, or G suffix.",
             },
         ],
     },
@@ -494,6 +1936,56 @@ const TOOL_DOCS: &[ToolDoc] = &[
                 required: false,
                 description: "Glob include filter (e.g., *.rs).",
             },
+            ParamDoc {
+                name: "max_results",
+                type_name: "integer",
+                required: false,
+                description: "Stop after this many matching lines in each file.",
+            },
+            ParamDoc {
+                name: "context_lines",
+                type_name: "integer",
+                required: false,
+                description: "Lines of context to include around each match.",
+            },
+            ParamDoc {
+                name: "fixed_string",
+                type_name: "boolean",
+                required: false,
+                description: "Match the pattern literally rather than as a regular expression (default: false).",
+            },
+        ],
+    },
+    ToolDoc {
+        name: "search-replace",
+        description: "Replace every occurrence of a pattern in files under a path and report how many files and occurrences changed.",
+        params: &[
+            SANDBOX_NAME_PARAM,
+            ParamDoc {
+                name: "pattern",
+                type_name: "string",
+                required: true,
+                description: "Text to search for.",
+            },
+            ParamDoc {
+                name: "replacement",
+                type_name: "string",
+                required: true,
+                description: "Replacement text.",
+            },
+            PATH_PARAM,
+            ParamDoc {
+                name: "regex",
+                type_name: "boolean",
+                required: false,
+                description: "Treat the pattern as an extended regular expression; the replacement may use \\1 and & (default: false).",
+            },
+            ParamDoc {
+                name: "case_insensitive",
+                type_name: "boolean",
+                required: false,
+                description: "Match case-insensitively (default: false).",
+            },
         ],
     },
 ];
@@ -528,11 +2020,60 @@ pub fn generate_mcp_docs() -> String {
     output
 }
 
-pub async fn run_stdio() -> Result<(), Box<dyn std::error::Error>> {
-    let service = SandboxServer::new().serve(stdio()).await.inspect_err(|e| {
+/// The container engine the server was started with, overriding the `compute` config key.
+static COMPUTE_BACKEND: OnceLock<ComputeBackend> = OnceLock::new();
+
+pub async fn run_stdio(
+    audit: Option<AuditLog>,
+    compute: Option<ComputeBackend>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(backend) = compute {
+        COMPUTE_BACKEND.get_or_init(|| backend);
+    }
+    let server = SandboxServer::new().with_audit(audit.map(Arc::new));
+    let service = server.serve(stdio()).await.inspect_err(|e| {
+        eprintln!("Error starting MCP server: {e}");
+    })?;
+    tokio::select! {
+        result = service.waiting() => {
+            result?;
+        }
+        _ = shutdown_signal() => interrupt_creations().await,
+    }
+    Ok(())
+}
+
+/// Serves the MCP protocol over streamable HTTP with Server-Sent Events at `/mcp`.
+pub async fn run_http(
+    bind_addr: SocketAddr,
+    audit: Option<AuditLog>,
+    compute: Option<ComputeBackend>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(backend) = compute {
+        COMPUTE_BACKEND.get_or_init(|| backend);
+    }
+    let audit = audit.map(Arc::new);
+    let watches = Watches::default();
+    let service = StreamableHttpService::new(
+        move || {
+            Ok(SandboxServer::new()
+                .with_audit(audit.clone())
+                .with_watches(watches.clone()))
+        },
+        LocalSessionManager::default().into(),
+        StreamableHttpServerConfig::default(),
+    );
+    let router = axum::Router::new().nest_service("/mcp", service);
+    let listener = tokio::net::TcpListener::bind(bind_addr).await.inspect_err(|e| {
         eprintln!("Error starting MCP server: {e}");
     })?;
-    service.waiting().await?;
+    eprintln!("MCP server listening on http://{bind_addr}/mcp");
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            shutdown_signal().await;
+            interrupt_creations().await;
+        })
+        .await?;
     Ok(())
 }
 
@@ -541,126 +2082,435 @@ fn build_provider() -> Result<DockerSandboxProvider<ThreadSafeScm, DockerCompute
     build_provider_with_config(&config)
 }
 
-fn build_provider_with_config(
-    config: &crate::config::Config,
-) -> Result<DockerSandboxProvider<ThreadSafeScm, DockerCompute>, SandboxError> {
-    let scm =
-        ThreadSafeScm::open_with_prefix(std::path::Path::new("."), config.project.slug.clone())?;
-    let compute = DockerCompute::connect()?;
-    Ok(DockerSandboxProvider::new(scm, compute))
+/// The provider and metadata for the existing sandbox `name`, with relative paths resolved
+/// against the workdir the sandbox was created with.
+async fn sandbox_provider(
+    name: &str,
+) -> Result<(DockerSandboxProvider<ThreadSafeScm, DockerCompute>, SandboxMetadata), SandboxError> {
+    let metadata = resolve_sandbox_metadata(name)?;
+    let provider = build_provider()?.for_sandbox(&metadata).await?;
+    Ok((provider, metadata))
+}
+
+/// The settings for a new sandbox: the `docker` section, overlaid by the requested template,
+/// overlaid by the call's own arguments.
+fn sandbox_config_for_create(
+    config: &crate::config::Config,
+    args: &SandboxCreateArgs,
+) -> Result<SandboxConfig, McpError> {
+    let template = match args.template.as_deref() {
+        Some(name) => Some(config.template(name).ok_or_else(|| {
+            let known: Vec<&str> = config.templates.iter().map(|t| t.name.as_str()).collect();
+            McpError::invalid_params(
+                format!(
+                    "Unknown template '{name}'. Configured templates: {}.",
+                    if known.is_empty() { "none".to_string() } else { known.join(", ") }
+                ),
+                Some(error_data(LitterboxErrorCode::Config)),
+            )
+        })?),
+        None => None,
+    };
+    let image = template
+        .and_then(|template| template.image.clone())
+        .or_else(|| config.docker.image.clone())
+        .ok_or_else(|| McpError::internal_error("missing docker.image".to_string(), None))?;
+    let forwarded_ports = config
+        .ports
+        .ports
+        .iter()
+        .map(|port| ForwardedPort {
+            name: port.name.clone(),
+            target: port.target,
+            protocol: port.protocol,
+        })
+        .collect();
+    Ok(SandboxConfig {
+        image,
+        setup_command: template
+            .and_then(|template| template.setup_command.clone())
+            .or_else(|| config.docker.setup_command.clone()),
+        setup_timeout_secs: config.docker.setup_timeout,
+        forwarded_ports,
+        port_range: Some(config.ports.port_range()),
+        ready_command: config.docker.ready_command.clone(),
+        from_ref: args.from_ref.clone(),
+        restart_policy: config.docker.restart_policy,
+        extra_env: template
+            .map(|template| {
+                template
+                    .extra_env
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        network: args.network.clone(),
+    })
+}
+
+/// Connects to the engine chosen by the server's `--compute` flag, else by the `compute` key.
+fn connect_compute(config: &crate::config::Config) -> Result<DockerCompute, SandboxError> {
+    match COMPUTE_BACKEND.get().copied().or(config.compute).unwrap_or_default() {
+        ComputeBackend::Docker => DockerCompute::global().cloned(),
+        backend => DockerCompute::connect_backend(backend),
+    }
+}
+
+fn build_provider_with_config(
+    config: &crate::config::Config,
+) -> Result<DockerSandboxProvider<ThreadSafeScm, DockerCompute>, SandboxError> {
+    let scm =
+        ThreadSafeScm::open_with_prefix(std::path::Path::new("."), config.project.slug.clone())?;
+    let compress_uploads = config.docker.compress_uploads.unwrap_or(false);
+    let compute = connect_compute(config)?
+        .with_pull_retries(config.docker.pull_retries.unwrap_or(DEFAULT_PULL_RETRIES))
+        .with_compressed_uploads(compress_uploads);
+    let workdir = config.docker.workdir.as_deref().unwrap_or(DEFAULT_WORKDIR);
+    Ok(DockerSandboxProvider::new(scm, compute)
+        .with_workdir(workdir)
+        .with_exec_retries(config.docker.exec_retries)
+        .with_compressed_archives(compress_uploads))
+}
+
+fn map_error(error: SandboxError) -> McpError {
+    let data = Some(error_data(error.error_code()));
+    match error {
+        SandboxError::InvalidName { .. } => McpError::invalid_params(error.to_string(), data),
+        SandboxError::SandboxExists { .. } => McpError::invalid_params(error.to_string(), data),
+        SandboxError::SandboxNotFound { .. } => McpError::invalid_params(error.to_string(), data),
+        SandboxError::UnsafeShellArgument { .. } => {
+            McpError::invalid_params(error.to_string(), data)
+        }
+        _ => McpError::internal_error(error.to_string(), data),
+    }
+}
+
+/// The `data` of an MCP error: `{"code": 1001, "kind": "not-found"}`.
+fn error_data(code: LitterboxErrorCode) -> serde_json::Value {
+    serde_json::json!({ "code": code.code(), "kind": code })
+}
+
+fn validate_sandbox_name(name: &str) -> Result<(), McpError> {
+    let slug = slugify(name);
+    if is_reserved_name(&slug) {
+        return Err(McpError::invalid_params(
+            format!(
+                "Sandbox name '{name}' is reserved. Reserved names: {}.",
+                RESERVED_NAMES.join(", ")
+            ),
+            Some(error_data(LitterboxErrorCode::InvalidName)),
+        ));
+    }
+    validate_slug(name, &slug).map_err(map_error)
+}
+
+fn map_sandbox_error(name: &str, error: SandboxError) -> McpError {
+    if is_container_missing(&error) {
+        return McpError::invalid_params(
+            format!("Sandbox '{}' not found.", name),
+            Some(error_data(LitterboxErrorCode::NotFound)),
+        );
+    }
+    map_error(error)
+}
+
+/// Builds the metadata of an existing sandbox by name, without contacting the container engine.
+///
+/// The forwarded ports, container IP and network aliases are left empty; tools that return
+/// metadata use [`resolve_sandbox_metadata_inspected`] instead.
+fn resolve_sandbox_metadata(name: &str) -> Result<SandboxMetadata, SandboxError> {
+    let slug = slugify_name(name)?;
+    let config = config_loader::load_final().map_err(|e| SandboxError::Config(e.to_string()))?;
+    let scm = ThreadSafeScm::open_with_prefix(Path::new("."), config.project.slug)?;
+    let repo_prefix = scm.repo_prefix()?;
+    Ok(SandboxMetadata {
+        name: name.to_string(),
+        branch_name: branch_name_for_slug(&slug),
+        container_id: container_name_for_slug(&repo_prefix, &slug),
+        status: SandboxStatus::Active,
+        forwarded_ports: Vec::new(),
+        created_at: None,
+        last_active_at: None,
+        container_ip: None,
+        network_aliases: Vec::new(),
+        image_digest: None,
+        started_at: None,
+    })
+}
+
+/// Like [`resolve_sandbox_metadata`], filled in with what the engine reports about the
+/// container.
+async fn resolve_sandbox_metadata_inspected<P: SandboxProvider + Sync>(
+    provider: &P,
+    name: &str,
+) -> Result<SandboxMetadata, SandboxError> {
+    existing_sandbox_metadata(provider, resolve_sandbox_metadata(name)?).await
+}
+
+/// The network `sandbox-link` uses when none is named. There is one per repository, so linking
+/// several pairs of sandboxes joins them all.
+fn default_link_network() -> Result<String, SandboxError> {
+    Ok(format!("litterbox-{}", link_repo_prefix()?))
+}
+
+fn link_repo_prefix() -> Result<String, SandboxError> {
+    let config = config_loader::load_final().map_err(|e| SandboxError::Config(e.to_string()))?;
+    let scm = ThreadSafeScm::open_with_prefix(Path::new("."), config.project.slug)?;
+    scm.repo_prefix()
+}
+
+/// Joins both sandboxes to `network`, aliased by slug, or by `<alias_prefix>-<slug>` if given.
+async fn link_sandboxes<P: SandboxProvider>(
+    provider: &P,
+    members: [(String, SandboxMetadata); 2],
+    network: String,
+    alias_prefix: Option<&str>,
+) -> Result<SandboxLinkResponse, McpError> {
+    let [(_, metadata_a), (_, metadata_b)] = &members;
+    if metadata_a.container_id == metadata_b.container_id {
+        return Err(McpError::invalid_params(
+            "sandbox_a and sandbox_b must name different sandboxes".to_string(),
+            Some(error_data(LitterboxErrorCode::InvalidArgument)),
+        ));
+    }
+    let mut sandboxes = Vec::with_capacity(members.len());
+    for (sandbox, metadata) in members {
+        let slug = slugify(&metadata.name);
+        let alias = match alias_prefix {
+            Some(prefix) => format!("{prefix}-{slug}"),
+            None => slug,
+        };
+        provider
+            .join_network(&metadata, &network, &alias)
+            .await
+            .map_err(|error| map_sandbox_error(&sandbox, error))?;
+        sandboxes.push(LinkedSandbox { sandbox, alias });
+    }
+    Ok(SandboxLinkResponse { network, sandboxes })
+}
+
+fn is_container_missing(error: &SandboxError) -> bool {
+    matches!(
+        error,
+        SandboxError::Compute(ComputeError::ContainerNotFound { .. })
+    )
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxPortsArgs {
+    pub sandbox: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SandboxPortsResponse {
+    pub name: String,
+    pub container_ip: Option<String>,
+    pub image_digest: Option<String>,
+    pub started_at: Option<String>,
+    pub forwarded_ports: Vec<ForwardedPortMapping>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxLinkArgs {
+    pub sandbox_a: String,
+    pub sandbox_b: String,
+    pub network_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SandboxLinkResponse {
+    pub network: String,
+    pub sandboxes: Vec<LinkedSandbox>,
+}
+
+#[derive(Debug, Serialize)]
+struct LinkedSandbox {
+    pub sandbox: String,
+    /// Host name the other sandbox reaches this one by on `network`.
+    pub alias: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxCheckpointArgs {
+    pub sandbox: String,
+    pub image: String,
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxSnapshotArgs {
+    pub sandbox: String,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxSnapshotPruneArgs {
+    pub sandbox: String,
+    pub max_age_days: Option<u64>,
+    pub max_count: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxStashArgs {
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxWatchArgs {
+    pub sandbox: String,
+    pub interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct SandboxWatchResponse {
+    pub sandbox: String,
+    pub interval_secs: u64,
+    /// Whether an earlier watch of the sandbox was stopped in favour of this one.
+    pub replaced: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxResetArgs {
+    pub sandbox: String,
+    pub hard: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxDiffArgs {
+    pub sandbox: String,
+    pub from: Option<String>,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxBranchArgs {
+    pub sandbox: String,
+    pub branch_name: String,
+    pub push: Option<bool>,
 }
 
-fn map_error(error: SandboxError) -> McpError {
-    match error {
-        SandboxError::InvalidName { .. } => McpError::invalid_params(error.to_string(), None),
-        SandboxError::SandboxExists { .. } => McpError::invalid_params(error.to_string(), None),
-        SandboxError::SandboxNotFound { .. } => McpError::invalid_params(error.to_string(), None),
-        _ => McpError::internal_error(error.to_string(), None),
-    }
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxMergeArgs {
+    pub sandbox: String,
+    pub target: Option<String>,
+    pub strategy: Option<MergeStrategy>,
 }
 
-fn map_sandbox_error(name: &str, error: SandboxError) -> McpError {
-    if is_container_missing(&error) {
-        return McpError::invalid_params(format!("Sandbox '{}' not found.", name), None);
-    }
-    map_error(error)
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxCherryPickArgs {
+    pub sandbox: String,
+    pub commit: String,
 }
 
-fn resolve_sandbox_metadata(name: &str) -> Result<SandboxMetadata, SandboxError> {
-    let slug = slugify_name(name)?;
-    let config = config_loader::load_final().map_err(|e| SandboxError::Config(e.to_string()))?;
-    let scm = ThreadSafeScm::open_with_prefix(Path::new("."), config.project.slug)?;
-    let repo_prefix = scm.repo_prefix()?;
-    Ok(SandboxMetadata {
-        name: name.to_string(),
-        branch_name: branch_name_for_slug(&slug),
-        container_id: container_name_for_slug(&repo_prefix, &slug),
-        status: SandboxStatus::Active,
-        forwarded_ports: Vec::new(),
-    })
+#[derive(Debug, Deserialize, JsonSchema)]
+struct SandboxStatusArgs {
+    pub sandbox: String,
 }
 
-fn is_container_missing(error: &SandboxError) -> bool {
-    matches!(
-        error,
-        SandboxError::Compute(ComputeError::ContainerExec {
-            source: bollard::errors::Error::DockerResponseServerError {
-                status_code: 404,
-                ..
-            }
-        })
-            | SandboxError::Compute(ComputeError::ContainerInspect {
-                source: bollard::errors::Error::DockerResponseServerError {
-                    status_code: 404,
-                    ..
-                }
-            })
-    )
+#[derive(Debug, Serialize)]
+struct SandboxStatusResponse {
+    pub metadata: SandboxMetadata,
+    pub recent_snapshots: Vec<SnapshotInfo>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
-struct SandboxPortsArgs {
+struct SandboxStatsArgs {
     pub sandbox: String,
 }
 
 #[derive(Debug, Serialize)]
-struct SandboxPortsResponse {
+struct SandboxListEntry {
     pub name: String,
-    pub forwarded_ports: Vec<ForwardedPortMapping>,
+    pub branch_name: String,
+    pub snapshot_oid: Option<String>,
+    pub last_snapshot_time: Option<i64>,
+    pub snapshot_count: usize,
 }
 
-fn forwarded_ports_from_inspection(inspection: &ContainerInspection) -> Vec<ForwardedPortMapping> {
-    let mut env_map: HashMap<u16, String> = HashMap::new();
-    for entry in &inspection.env {
-        if let Some((key, value)) = entry.split_once('=')
-            && key.starts_with("LITTERBOX_FWD_PORT_")
-            && let Ok(port) = value.parse::<u16>()
-        {
-            env_map.insert(port, key.to_string());
+impl From<SandboxEntry> for SandboxListEntry {
+    fn from(entry: SandboxEntry) -> Self {
+        Self {
+            name: entry.slug,
+            branch_name: entry.branch_name,
+            snapshot_oid: entry.snapshot_oid.map(|oid| oid.to_string()),
+            last_snapshot_time: entry.last_snapshot_time,
+            snapshot_count: entry.snapshot_count,
         }
     }
+}
 
-    let mut mappings = Vec::new();
-    for (container_port, bindings) in &inspection.port_bindings {
-        let target = container_port
-            .split('/')
-            .next()
-            .and_then(|value| value.parse::<u16>().ok());
-        let target = match target {
-            Some(target) => target,
-            None => continue,
-        };
-
-        for binding in bindings {
-            let host_port = binding
-                .host_port
-                .as_ref()
-                .and_then(|value| value.parse::<u16>().ok());
-            let host_port = match host_port {
-                Some(host_port) => host_port,
-                None => continue,
-            };
+#[derive(Debug, Serialize)]
+struct SandboxStatsResponse {
+    pub name: String,
+    pub cpu_percent: f64,
+    pub memory_used_bytes: u64,
+    pub memory_limit_bytes: u64,
+}
 
-            let env_var = match env_map.get(&host_port) {
-                Some(env) => env.clone(),
-                None => continue,
-            };
-            let name = env_var
-                .strip_prefix("LITTERBOX_FWD_PORT_")
-                .unwrap_or("")
-                .to_ascii_lowercase()
-                .replace('_', "-");
+#[derive(Debug, Serialize)]
+struct ReadBinaryResult {
+    pub content_base64: String,
+    pub encoding: &'static str,
+    pub size: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct WcResult {
+    pub lines: u64,
+    pub words: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct EnvEntry {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct ProcessEntry {
+    pub pid: u32,
+    pub user: String,
+    pub cpu: String,
+    pub mem: String,
+    pub command: String,
+}
 
-            mappings.push(ForwardedPortMapping {
-                name,
-                target,
-                host_port,
-                env_var,
-            });
+#[derive(Debug, PartialEq, Serialize)]
+struct DfResult {
+    pub filesystem: String,
+    pub size: String,
+    pub used: String,
+    pub available: String,
+    pub use_percent: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct DuEntry {
+    pub size: String,
+    pub path: String,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct SearchReplaceResult {
+    pub files_modified: usize,
+    pub total_replacements: usize,
+}
+
+/// Fills in what the container engine knows about an existing sandbox's container.
+async fn existing_sandbox_metadata<P: SandboxProvider + Sync>(
+    provider: &P,
+    mut metadata: SandboxMetadata,
+) -> Result<SandboxMetadata, SandboxError> {
+    match provider.inspect(&metadata).await {
+        Ok(inspected) => Ok(inspected),
+        Err(error) if is_container_missing(&error) => {
+            metadata.status = SandboxStatus::Error("missing container".to_string());
+            Ok(metadata)
         }
+        Err(error) => Err(error),
     }
-
-    mappings
 }
 
 #[derive(Debug)]
@@ -692,18 +2542,19 @@ async fn ls_in_sandbox<P: SandboxProvider>(
     path: &str,
     recursive: bool,
 ) -> Result<Vec<String>, LsError> {
-    let container_path = resolve_container_path(path);
+    let container_path = resolve_container_path(provider.workdir(), path);
+    let escaped_path = shell_escape(&container_path).map_err(LsError::Sandbox)?;
     let command = if recursive {
         vec![
             "sh".to_string(),
             "-c".to_string(),
-            format!("find {} -mindepth 1 -print", shell_escape(&container_path)),
+            format!("find {} -mindepth 1 -print", escaped_path),
         ]
     } else {
         vec![
             "sh".to_string(),
             "-c".to_string(),
-            format!("ls -1A {}", shell_escape(&container_path)),
+            format!("ls -1A {}", escaped_path),
         ]
     };
     let result = exec_in_sandbox(provider, metadata, command)
@@ -740,6 +2591,45 @@ fn classify_ls_failure(path: &str, result: &ExecutionResult) -> LsError {
     }
 }
 
+/// One page of a sorted directory listing.
+#[derive(Debug, PartialEq, Serialize)]
+struct LsPage<T> {
+    entries: Vec<T>,
+    next_cursor: Option<String>,
+}
+
+/// Returns up to `page_size` entries sorting after `after`, with a cursor pointing past the
+/// last one when more remain. Keying on the name rather than an offset keeps the cursor
+/// stable when entries are added or removed between calls.
+fn paginate_entries<T>(
+    entries: Vec<T>,
+    name: impl Fn(&T) -> &str,
+    after: Option<&str>,
+    page_size: usize,
+) -> LsPage<T> {
+    let mut remaining = entries
+        .into_iter()
+        .filter(|entry| after.is_none_or(|after| name(entry) > after))
+        .peekable();
+    let entries: Vec<T> = remaining.by_ref().take(page_size).collect();
+    let next_cursor = match (remaining.peek(), entries.last()) {
+        (Some(_), Some(last)) => Some(BASE64.encode(name(last))),
+        _ => None,
+    };
+    LsPage {
+        entries,
+        next_cursor,
+    }
+}
+
+fn decode_ls_cursor(cursor: &str) -> Result<String, McpError> {
+    BASE64
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(|| McpError::invalid_params(format!("invalid cursor: {}", cursor), None))
+}
+
 fn parse_ls_output(output: &str, base: &str, recursive: bool) -> Vec<String> {
     let mut entries: Vec<String> = output
         .lines()
@@ -761,6 +2651,148 @@ fn parse_ls_output(output: &str, base: &str, recursive: bool) -> Vec<String> {
     entries
 }
 
+async fn tree_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    path: &str,
+    depth: Option<usize>,
+    show_hidden: bool,
+) -> Result<String, LsError> {
+    let container_path = resolve_container_path(provider.workdir(), path);
+    let escaped_path = shell_escape(&container_path).map_err(LsError::Sandbox)?;
+    let mut find = format!("find {} -mindepth 1", escaped_path);
+    if let Some(depth) = depth {
+        find.push_str(&format!(" -maxdepth {}", depth));
+    }
+    // Pruning hidden directories keeps large trees such as `.git` from being walked at all.
+    if !show_hidden {
+        find.push_str(" -name '.*' -prune -o");
+    }
+    find.push_str(" -print");
+    let command = vec!["sh".to_string(), "-c".to_string(), find];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(LsError::Sandbox)?;
+    if result.exit_code != 0 {
+        return Err(classify_ls_failure(&container_path, &result));
+    }
+    let paths: Vec<String> = parse_find_output(&result.stdout)
+        .iter()
+        .map(|entry| strip_base_prefix(entry, &container_path))
+        .collect();
+    Ok(format_tree(&container_path, &paths))
+}
+
+#[derive(Default)]
+struct TreeNode {
+    children: BTreeMap<String, TreeNode>,
+}
+
+/// Renders relative paths below `root` in the style of the `tree` command, sorted by name.
+fn format_tree(root: &str, paths: &[String]) -> String {
+    let mut tree = TreeNode::default();
+    for path in paths {
+        let mut node = &mut tree;
+        for component in path.split('/').filter(|component| !component.is_empty()) {
+            node = node.children.entry(component.to_string()).or_default();
+        }
+    }
+
+    let mut output = format!("{}\n", root);
+    render_tree_children(&tree, "", &mut output);
+    output
+}
+
+fn render_tree_children(node: &TreeNode, prefix: &str, output: &mut String) {
+    let count = node.children.len();
+    for (index, (name, child)) in node.children.iter().enumerate() {
+        let last = index + 1 == count;
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        output.push_str(&format!("{}{}{}\n", prefix, branch, name));
+        render_tree_children(child, &format!("{}{}", prefix, indent), output);
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct DirEntry {
+    pub name: String,
+    pub size: u64,
+    pub mode: String,
+    pub modified: String,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+}
+
+async fn ls_metadata_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    path: &str,
+) -> Result<Vec<DirEntry>, LsError> {
+    let container_path = resolve_container_path(provider.workdir(), path);
+    let command = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        format!("ls -la {}", shell_escape(&container_path).map_err(LsError::Sandbox)?),
+    ];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(LsError::Sandbox)?;
+    if result.exit_code != 0 {
+        return Err(classify_ls_failure(&container_path, &result));
+    }
+    Ok(parse_ls_long_output(&result.stdout))
+}
+
+/// Parses `ls -la` lines of the form `mode links owner group size month day time name`.
+/// Device files report `major, minor` in place of a size and are listed with size 0.
+fn parse_ls_long_output(output: &str) -> Vec<DirEntry> {
+    let mut entries = Vec::new();
+    for line in output.lines().map(|line| line.trim_end_matches('\r')) {
+        let fields = split_columns(line, 9);
+        if fields.len() < 9 {
+            continue;
+        }
+        let (size, fields) = if fields[4].ends_with(',') {
+            let fields = split_columns(line, 10);
+            if fields.len() < 10 {
+                continue;
+            }
+            (0, fields)
+        } else {
+            let Ok(size) = fields[4].parse() else {
+                continue;
+            };
+            (size, fields)
+        };
+        let mode = fields[0];
+        let [month, day, time, name] = fields[fields.len() - 4..] else {
+            continue;
+        };
+        let is_symlink = mode.starts_with('l');
+        let name = if is_symlink {
+            name.split(" -> ").next().unwrap_or(name)
+        } else {
+            name
+        };
+        if name == "." || name == ".." {
+            continue;
+        }
+        entries.push(DirEntry {
+            name: name.to_string(),
+            size,
+            mode: mode.to_string(),
+            modified: format!("{} {} {}", month, day, time),
+            is_dir: mode.starts_with('d'),
+            is_symlink,
+        });
+    }
+    entries
+}
+
 #[derive(Debug)]
 enum ReadError {
     Sandbox(SandboxError),
@@ -769,12 +2801,20 @@ enum ReadError {
     Failed { path: String, message: String },
 }
 
+#[derive(Debug, PartialEq)]
+enum ReadContent {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
 #[derive(Debug)]
 enum WriteError {
     Sandbox(SandboxError),
     NotFound { path: String },
     PermissionDenied { path: String },
     Failed { path: String, message: String },
+    InvalidBase64 { message: String },
+    InvalidPath { path: String, message: String },
 }
 
 #[derive(Debug)]
@@ -796,6 +2836,15 @@ enum PatchError {
     },
 }
 
+#[derive(Debug)]
+enum LnError {
+    Sandbox(SandboxError),
+    AlreadyExists { path: String },
+    NotFound { path: String },
+    PermissionDenied { path: String },
+    Failed { path: String, message: String },
+}
+
 #[derive(Debug)]
 enum BashError {
     Sandbox(SandboxError),
@@ -803,9 +2852,54 @@ enum BashError {
 
 #[derive(Debug, Clone)]
 enum SnapshotTrigger {
-    Write { path: String },
+    Write { path: String, mode: WriteMode },
     Patch { path: String },
     Bash { command: String },
+    SearchReplace {
+        pattern: String,
+        replacement: String,
+        path: String,
+    },
+    Manual { message: Option<String> },
+    Reset,
+    MultiWrite { count: usize },
+    CherryPick { commit: String },
+    Watch,
+    Git { args: Vec<String> },
+}
+
+impl SnapshotTrigger {
+    /// The repository-relative path the triggering tool changed, or `None` when it may have
+    /// changed anything, as a shell command can.
+    fn touched_path(&self, workdir: &str) -> Option<&str> {
+        let path = match self {
+            SnapshotTrigger::Write { path, .. }
+            | SnapshotTrigger::Patch { path }
+            | SnapshotTrigger::SearchReplace { path, .. } => path.as_str(),
+            _ => return None,
+        };
+        workdir_relative_path(workdir, path)
+    }
+}
+
+/// `path` relative to the sandbox `workdir`, or `None` when it lies outside it.
+fn workdir_relative_path<'a>(workdir: &str, path: &'a str) -> Option<&'a str> {
+    match path.strip_prefix(workdir).and_then(|path| path.strip_prefix('/')) {
+        Some(relative) => Some(relative),
+        None if path.starts_with('/') => None,
+        None => Some(path.strip_prefix("./").unwrap_or(path)),
+    }
+}
+
+/// Rewrites a unified diff of the single file `path` with the `diff --git` header libgit2
+/// expects, keeping only its hunks. Returns `None` when the diff has no hunks.
+fn git_diff_for_path(path: &str, diff: &str) -> Option<String> {
+    let hunks = if diff.starts_with("@@") {
+        diff
+    } else {
+        &diff[diff.find("\n@@")? + 1..]
+    };
+    Some(format!("diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n{hunks}"))
 }
 
 fn map_read_error(sandbox: &str, error: ReadError) -> McpError {
@@ -835,6 +2929,12 @@ fn map_write_error(sandbox: &str, error: WriteError) -> McpError {
         WriteError::Failed { path, message } => {
             McpError::internal_error(format!("failed to write {}: {}", path, message), None)
         }
+        WriteError::InvalidBase64 { message } => {
+            McpError::invalid_params(format!("invalid base64 content: {}", message), None)
+        }
+        WriteError::InvalidPath { path, message } => {
+            McpError::invalid_params(format!("invalid path {}: {}", path, message), None)
+        }
     }
 }
 
@@ -851,10 +2951,33 @@ fn map_patch_error(_sandbox: &str, error: PatchError) -> McpError {
             format!("failed to write patched file {}: {:?}", path, source),
             None,
         ),
-        PatchError::ApplyFailed { path, source } => McpError::internal_error(
-            format!("failed to apply patch to {}: {}", path, source),
-            None,
-        ),
+        PatchError::ApplyFailed { path, source } => {
+            let conflict = ScmError::PatchConflict {
+                conflicted_files: vec![path],
+            };
+            McpError::internal_error(
+                format!("{conflict} ({source})"),
+                Some(error_data(LitterboxErrorCode::Conflict)),
+            )
+        }
+    }
+}
+
+fn map_ln_error(sandbox: &str, error: LnError) -> McpError {
+    match error {
+        LnError::Sandbox(error) => map_sandbox_error(sandbox, error),
+        LnError::AlreadyExists { path } => {
+            McpError::invalid_params(format!("path already exists: {}", path), None)
+        }
+        LnError::NotFound { path } => {
+            McpError::invalid_params(format!("path not found: {}", path), None)
+        }
+        LnError::PermissionDenied { path } => {
+            McpError::invalid_params(format!("permission denied: {}", path), None)
+        }
+        LnError::Failed { path, message } => {
+            McpError::internal_error(format!("failed to link {}: {}", path, message), None)
+        }
     }
 }
 
@@ -869,53 +2992,249 @@ async fn snapshot_after<P: SandboxProvider>(
     metadata: &SandboxMetadata,
     sandbox: &str,
     trigger: SnapshotTrigger,
-) -> Result<(), SandboxError> {
-    let config = config_loader::load_final().map_err(|e| SandboxError::Config(e.to_string()))?;
-    let scm = ThreadSafeScm::for_sandbox(Path::new("."), config.project.slug.clone(), sandbox)?;
+) -> Result<Option<git2::Oid>, SandboxError> {
+    let scm = sandbox_scm(sandbox)?;
 
-    // Download container /src to temp staging directory
+    // Download the container workdir to a temp staging directory
     let staging_dir = tempfile::tempdir()
         .map_err(|e| SandboxError::Config(format!("Failed to create temp dir: {}", e)))?;
     provider
-        .download_path(metadata, "/src", staging_dir.path())
+        .download_path(metadata, provider.workdir(), staging_dir.path())
         .await?;
 
-    // Commit from staging directory to snapshot branch
-    let _ = scm.commit_snapshot_from_staging(staging_dir.path(), &snapshot_message(&trigger))?;
+    // Commit from staging directory to snapshot branch. A tool that changed one path only
+    // records that path, so unrelated changes made alongside it are not attributed to it.
+    let message = snapshot_message(&trigger);
+    let depth = DEFAULT_SNAPSHOT_DEDUP_DEPTH;
+    match trigger.touched_path(provider.workdir()) {
+        Some(path) => {
+            scm.commit_snapshot_paths_from_staging_async(
+                staging_dir.path(),
+                &[path],
+                &message,
+                depth,
+            )
+            .await
+        }
+        None => {
+            scm.commit_snapshot_from_staging_async(staging_dir.path(), &message, depth).await
+        }
+    }
+}
 
-    Ok(())
+/// Snapshots `sandbox` every `interval_secs` until its container or branch is gone.
+async fn watch_sandbox(sandbox: String, interval_secs: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let result = async {
+            let (provider, metadata) = sandbox_provider(&sandbox).await?;
+            snapshot_after(&provider, &metadata, &sandbox, SnapshotTrigger::Watch).await
+        }
+        .await;
+        match result {
+            Ok(_) => {}
+            Err(error)
+                if matches!(error, SandboxError::SandboxNotFound { .. })
+                    || is_container_missing(&error) =>
+            {
+                eprintln!("sandbox-watch: stopped watching '{sandbox}': {error}");
+                return;
+            }
+            Err(error) => eprintln!("sandbox-watch: snapshot of '{sandbox}' failed: {error}"),
+        }
+    }
+}
+
+/// Opens the host repository with snapshots directed at `sandbox`'s branch.
+fn sandbox_scm(sandbox: &str) -> Result<ThreadSafeScm, SandboxError> {
+    let config = config_loader::load_final().map_err(|e| SandboxError::Config(e.to_string()))?;
+    ThreadSafeScm::for_sandbox(Path::new("."), config.project.slug, sandbox)
 }
 
 fn snapshot_message(trigger: &SnapshotTrigger) -> String {
     match trigger {
-        SnapshotTrigger::Write { path } => format!("write: {}", path),
+        SnapshotTrigger::Write { path, mode } => match mode {
+            WriteMode::Overwrite => format!("write: {}", path),
+            WriteMode::Append => format!("write (append): {}", path),
+            WriteMode::Prepend => format!("write (prepend): {}", path),
+        },
         SnapshotTrigger::Patch { path } => format!("patch: {}", path),
         SnapshotTrigger::Bash { command } => format!("bash: {}", command),
+        SnapshotTrigger::SearchReplace {
+            pattern,
+            replacement,
+            path,
+        } => format!("search-replace: {} -> {} in {}", pattern, replacement, path),
+        SnapshotTrigger::Manual { message } => match message {
+            Some(message) => format!("manual: {}", message),
+            None => "manual snapshot".to_string(),
+        },
+        SnapshotTrigger::Reset => "reset: before restoring HEAD".to_string(),
+        SnapshotTrigger::MultiWrite { count } => format!("multi-write: {} files", count),
+        SnapshotTrigger::CherryPick { commit } => {
+            format!("cherry-pick: before applying {}", commit)
+        }
+        SnapshotTrigger::Watch => "watch: files changed".to_string(),
+        SnapshotTrigger::Git { args } => format!("git: {}", args.join(" ")),
+    }
+}
+
+#[allow(unused)]
+fn snapshot_after_with_scm<S: Scm>(
+    scm: &S,
+    workdir: &str,
+    trigger: SnapshotTrigger,
+) -> Result<(), SandboxError> {
+    let message = snapshot_message(&trigger);
+    match trigger.touched_path(workdir) {
+        Some(path) if scm.has_changes_in(&[path])? => {
+            scm.commit_snapshot_paths(&[path], &message)?;
+        }
+        Some(_) => {}
+        None if scm.has_changes()? => {
+            scm.stage_all()?;
+            scm.commit_snapshot(&message)?;
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+async fn read_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    path: &str,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<String, ReadError> {
+    let container_path = resolve_container_path(provider.workdir(), path);
+    let content = cat_in_sandbox(provider, metadata, &container_path).await?;
+    Ok(slice_content(&content, offset, limit))
+}
+
+/// Like `read_in_sandbox`, but files detected as binary are returned as their exact bytes.
+///
+/// Exec output is decoded lossily, so binary files are fetched again through
+/// `download_path` rather than re-encoding the text that `cat` produced.
+async fn read_content_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    path: &str,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<ReadContent, ReadError> {
+    let container_path = resolve_container_path(provider.workdir(), path);
+    let content = cat_in_sandbox(provider, metadata, &container_path).await?;
+    if !is_binary_content(&content) {
+        return Ok(ReadContent::Text(slice_content(&content, offset, limit)));
+    }
+
+    let failed = |message: String| ReadError::Failed {
+        path: container_path.clone(),
+        message,
+    };
+    let staging_dir = tempfile::tempdir().map_err(|error| failed(error.to_string()))?;
+    provider
+        .download_path(metadata, &container_path, staging_dir.path())
+        .await
+        .map_err(ReadError::Sandbox)?;
+    let file_name = Path::new(&container_path)
+        .file_name()
+        .ok_or_else(|| failed("path must name a file".to_string()))?;
+    let bytes = std::fs::read(staging_dir.path().join(file_name))
+        .map_err(|error| failed(error.to_string()))?;
+    Ok(ReadContent::Binary(bytes))
+}
+
+async fn cat_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    container_path: &str,
+) -> Result<String, ReadError> {
+    let command = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        format!("cat -- {}", shell_escape(container_path).map_err(ReadError::Sandbox)?),
+    ];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(ReadError::Sandbox)?;
+    if result.exit_code != 0 {
+        return Err(classify_read_failure(container_path, &result));
+    }
+    Ok(result.stdout)
+}
+
+/// Git treats content as binary when a NUL byte appears in its first 8000 bytes.
+fn is_binary_content(content: &str) -> bool {
+    content.as_bytes().iter().take(8000).any(|byte| *byte == 0)
+}
+
+async fn head_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    path: &str,
+    lines: usize,
+) -> Result<String, ReadError> {
+    let container_path = resolve_container_path(provider.workdir(), path);
+    let escaped_path = shell_escape(&container_path).map_err(ReadError::Sandbox)?;
+    let command = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        format!("head -n {} -- {}", lines, escaped_path),
+    ];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(ReadError::Sandbox)?;
+    if result.exit_code != 0 {
+        return Err(classify_read_failure(&container_path, &result));
     }
+    Ok(result.stdout)
 }
 
-#[allow(unused)]
-fn snapshot_after_with_scm<S: Scm>(scm: &S, trigger: SnapshotTrigger) -> Result<(), SandboxError> {
-    if !scm.has_changes()? {
-        return Ok(());
+/// With `follow`, `tail -f` is stopped after `TAIL_FOLLOW_SECS`; the resulting timeout exit
+/// status is expected and not treated as a failure.
+async fn tail_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    path: &str,
+    lines: usize,
+    follow: bool,
+) -> Result<String, ReadError> {
+    let container_path = resolve_container_path(provider.workdir(), path);
+    let escaped_path = shell_escape(&container_path).map_err(ReadError::Sandbox)?;
+    let tail = if follow {
+        format!(
+            "timeout {}s tail -n {} -f -- {}",
+            TAIL_FOLLOW_SECS, lines, escaped_path
+        )
+    } else {
+        format!("tail -n {} -- {}", lines, escaped_path)
+    };
+    let command = vec!["sh".to_string(), "-c".to_string(), tail];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(ReadError::Sandbox)?;
+    // GNU timeout exits 124; BusyBox reports the SIGTERM status instead.
+    let timed_out = follow && matches!(result.exit_code, 124 | 143);
+    if result.exit_code != 0 && !timed_out {
+        return Err(classify_read_failure(&container_path, &result));
     }
-    scm.stage_all()?;
-    scm.commit_snapshot(&snapshot_message(&trigger))?;
-    Ok(())
+    Ok(result.stdout)
 }
 
-async fn read_in_sandbox<P: SandboxProvider>(
+async fn wc_in_sandbox<P: SandboxProvider>(
     provider: &P,
     metadata: &SandboxMetadata,
     path: &str,
-    offset: Option<usize>,
-    limit: Option<usize>,
-) -> Result<String, ReadError> {
-    let container_path = resolve_container_path(path);
+) -> Result<WcResult, ReadError> {
+    let container_path = resolve_container_path(provider.workdir(), path);
     let command = vec![
         "sh".to_string(),
         "-c".to_string(),
-        format!("cat -- {}", shell_escape(&container_path)),
+        format!("wc -lwc -- {}", shell_escape(&container_path).map_err(ReadError::Sandbox)?),
     ];
     let result = exec_in_sandbox(provider, metadata, command)
         .await
@@ -923,7 +3242,20 @@ async fn read_in_sandbox<P: SandboxProvider>(
     if result.exit_code != 0 {
         return Err(classify_read_failure(&container_path, &result));
     }
-    Ok(slice_content(&result.stdout, offset, limit))
+    parse_wc_output(&result.stdout).ok_or_else(|| ReadError::Failed {
+        path: container_path,
+        message: format!("unexpected wc output: {}", result.stdout.trim()),
+    })
+}
+
+/// Parses `wc -lwc` output, which lists lines, words, and bytes before the file name.
+fn parse_wc_output(output: &str) -> Option<WcResult> {
+    let mut counts = output.split_whitespace();
+    Some(WcResult {
+        lines: counts.next()?.parse().ok()?,
+        words: counts.next()?.parse().ok()?,
+        bytes: counts.next()?.parse().ok()?,
+    })
 }
 
 async fn write_in_sandbox<P: SandboxProvider>(
@@ -931,18 +3263,37 @@ async fn write_in_sandbox<P: SandboxProvider>(
     metadata: &SandboxMetadata,
     path: &str,
     content: &str,
+    mode: WriteMode,
 ) -> Result<(), WriteError> {
-    let container_path = resolve_container_path(path);
+    let container_path = resolve_container_path(provider.workdir(), path);
+    let (redirect, content) = match mode {
+        WriteMode::Overwrite => (">", content.to_string()),
+        WriteMode::Append => (">>", content.to_string()),
+        WriteMode::Prepend => {
+            let existing = match read_in_sandbox(provider, metadata, path, None, None).await {
+                Ok(existing) => existing,
+                Err(ReadError::NotFound { .. }) => String::new(),
+                Err(error) => return Err(write_error_from_read(error)),
+            };
+            (">", format!("{}{}", content, existing))
+        }
+    };
+    // The content goes over stdin rather than into the script, so it may hold any character.
     let command = vec![
         "sh".to_string(),
         "-c".to_string(),
         format!(
-            "printf %s {} > {}",
-            shell_escape(content),
-            shell_escape(&container_path)
+            "cat {} {}",
+            redirect,
+            shell_escape(&container_path).map_err(WriteError::Sandbox)?
         ),
     ];
-    let result = exec_in_sandbox(provider, metadata, command)
+    let options = ExecOptions {
+        stdin: Some(content.into_bytes()),
+        ..ExecOptions::default()
+    };
+    let result = provider
+        .shell(metadata, &command, Some(&options))
         .await
         .map_err(WriteError::Sandbox)?;
     if result.exit_code != 0 {
@@ -951,12 +3302,224 @@ async fn write_in_sandbox<P: SandboxProvider>(
     Ok(())
 }
 
+/// Symbolic link targets are stored verbatim so relative links resolve from the link's
+/// directory; hard link targets must name an existing file and are resolved like other paths.
+async fn ln_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    target: &str,
+    link_path: &str,
+    symbolic: bool,
+) -> Result<(), LnError> {
+    let link_path = resolve_container_path(provider.workdir(), link_path);
+    let (flag, target) = if symbolic {
+        ("-s ", target.to_string())
+    } else {
+        ("", resolve_container_path(provider.workdir(), target))
+    };
+    let command = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        format!(
+            "ln {}-- {} {}",
+            flag,
+            shell_escape(&target).map_err(LnError::Sandbox)?,
+            shell_escape(&link_path).map_err(LnError::Sandbox)?
+        ),
+    ];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(LnError::Sandbox)?;
+    if result.exit_code != 0 {
+        return Err(classify_ln_failure(&link_path, &result));
+    }
+    Ok(())
+}
+
+fn classify_ln_failure(path: &str, result: &ExecutionResult) -> LnError {
+    let stderr = result.stderr.trim();
+    let stdout = result.stdout.trim();
+    let message = if !stderr.is_empty() { stderr } else { stdout };
+    if message.contains("File exists") {
+        LnError::AlreadyExists {
+            path: path.to_string(),
+        }
+    } else if message.contains("No such file or directory") {
+        LnError::NotFound {
+            path: path.to_string(),
+        }
+    } else if message.contains("Permission denied") {
+        LnError::PermissionDenied {
+            path: path.to_string(),
+        }
+    } else if message.is_empty() {
+        LnError::Failed {
+            path: path.to_string(),
+            message: format!("exit code {}", result.exit_code),
+        }
+    } else {
+        LnError::Failed {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Uploads the decoded bytes as a tar entry instead of passing them through a shell command,
+/// so NUL and non-UTF-8 bytes survive unchanged.
+async fn write_binary_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    path: &str,
+    content_base64: &str,
+) -> Result<(), WriteError> {
+    let bytes = BASE64
+        .decode(content_base64.trim())
+        .map_err(|error| WriteError::InvalidBase64 {
+            message: error.to_string(),
+        })?;
+    let container_path = resolve_container_path(provider.workdir(), path);
+    let (staging_dir, staged_file, dest_dir) = stage_binary_file(&container_path, &bytes)?;
+    provider
+        .upload_path(metadata, &staged_file, &dest_dir)
+        .await
+        .map_err(WriteError::Sandbox)?;
+    drop(staging_dir);
+    Ok(())
+}
+
+/// Writes `bytes` to a temp file named after `container_path` and returns the temp dir guard,
+/// the staged file, and the container directory to upload it into.
+fn stage_binary_file(
+    container_path: &str,
+    bytes: &[u8],
+) -> Result<(tempfile::TempDir, std::path::PathBuf, String), WriteError> {
+    let invalid_path = || WriteError::Failed {
+        path: container_path.to_string(),
+        message: "path must name a file".to_string(),
+    };
+    let target = Path::new(container_path);
+    let file_name = target.file_name().ok_or_else(invalid_path)?;
+    let dest_dir = target
+        .parent()
+        .and_then(|parent| parent.to_str())
+        .filter(|parent| !parent.is_empty())
+        .ok_or_else(invalid_path)?
+        .to_string();
+    let failed = |error: std::io::Error| WriteError::Failed {
+        path: container_path.to_string(),
+        message: error.to_string(),
+    };
+    let staging_dir = tempfile::tempdir().map_err(failed)?;
+    let staged_file = staging_dir.path().join(file_name);
+    std::fs::write(&staged_file, bytes).map_err(failed)?;
+    Ok((staging_dir, staged_file, dest_dir))
+}
+
+async fn multi_write_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    files: &[MultiWriteFile],
+) -> Result<(), WriteError> {
+    let (staging_dir, dest_dir) = stage_files(provider.workdir(), files)?;
+    provider
+        .upload_path(metadata, staging_dir.path(), &dest_dir)
+        .await
+        .map_err(WriteError::Sandbox)
+}
+
+/// Lays `files` out in a temp dir relative to the workdir, or to the deepest directory holding
+/// both it and every file, returning the temp dir guard and that directory, so one upload
+/// writes them all. The upload creates missing parent directories, as the workdir exists.
+fn stage_files(
+    workdir: &str,
+    files: &[MultiWriteFile],
+) -> Result<(tempfile::TempDir, String), WriteError> {
+    let invalid_path = |path: &str, message: &str| WriteError::InvalidPath {
+        path: path.to_string(),
+        message: message.to_string(),
+    };
+    let mut targets = Vec::with_capacity(files.len());
+    for file in files {
+        let container_path = PathBuf::from(resolve_container_path(workdir, &file.path));
+        if container_path
+            .components()
+            .any(|component| matches!(component, Component::ParentDir | Component::CurDir))
+        {
+            return Err(invalid_path(&file.path, "must not contain . or .. components"));
+        }
+        if container_path.file_name().is_none() || file.path.ends_with('/') {
+            return Err(invalid_path(&file.path, "must name a file"));
+        }
+        if targets.iter().any(|(target, _)| *target == container_path) {
+            return Err(invalid_path(&file.path, "listed more than once"));
+        }
+        targets.push((container_path, file.content.as_str()));
+    }
+
+    let mut dest_dir = PathBuf::from(workdir);
+    for (target, _) in &targets {
+        while !target.starts_with(&dest_dir) {
+            dest_dir.pop();
+        }
+    }
+
+    let staging_dir = tempfile::tempdir().map_err(|error| WriteError::Failed {
+        path: dest_dir.display().to_string(),
+        message: error.to_string(),
+    })?;
+    for (target, content) in &targets {
+        let failed = |error: std::io::Error| WriteError::Failed {
+            path: target.display().to_string(),
+            message: error.to_string(),
+        };
+        let relative = target
+            .strip_prefix(&dest_dir)
+            .expect("common directory is a prefix of every target");
+        let staged = staging_dir.path().join(relative);
+        if let Some(parent) = staged.parent() {
+            std::fs::create_dir_all(parent).map_err(failed)?;
+        }
+        std::fs::write(&staged, content).map_err(failed)?;
+    }
+    Ok((staging_dir, dest_dir.display().to_string()))
+}
+
+fn write_error_from_read(error: ReadError) -> WriteError {
+    match error {
+        ReadError::Sandbox(error) => WriteError::Sandbox(error),
+        ReadError::NotFound { path } => WriteError::NotFound { path },
+        ReadError::PermissionDenied { path } => WriteError::PermissionDenied { path },
+        ReadError::Failed { path, message } => WriteError::Failed { path, message },
+    }
+}
+
 async fn patch_in_sandbox<P: SandboxProvider>(
     provider: &P,
     metadata: &SandboxMetadata,
     path: &str,
     diff: &str,
 ) -> Result<(), PatchError> {
+    let patched_content = patched_content_in_sandbox(provider, metadata, path, diff).await?;
+
+    // Write patched content back
+    write_in_sandbox(provider, metadata, path, &patched_content, WriteMode::Overwrite)
+        .await
+        .map_err(|e| PatchError::WriteFile {
+            path: path.to_string(),
+            source: Box::new(e),
+        })?;
+
+    Ok(())
+}
+
+/// Applies `diff` to the sandbox file in memory and returns the result without writing it.
+async fn patched_content_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    path: &str,
+    diff: &str,
+) -> Result<String, PatchError> {
     // Read current file content
     let original_content = read_in_sandbox(provider, metadata, path, None, None)
         .await
@@ -970,21 +3533,10 @@ async fn patch_in_sandbox<P: SandboxProvider>(
         source: e.to_string(),
     })?;
 
-    let patched_content =
-        diffy::apply(&original_content, &patch).map_err(|e| PatchError::ApplyFailed {
-            path: path.to_string(),
-            source: e.to_string(),
-        })?;
-
-    // Write patched content back
-    write_in_sandbox(provider, metadata, path, &patched_content)
-        .await
-        .map_err(|e| PatchError::WriteFile {
-            path: path.to_string(),
-            source: Box::new(e),
-        })?;
-
-    Ok(())
+    diffy::apply(&original_content, &patch).map_err(|e| PatchError::ApplyFailed {
+        path: path.to_string(),
+        source: e.to_string(),
+    })
 }
 
 async fn bash_in_sandbox<P: SandboxProvider>(
@@ -993,10 +3545,14 @@ async fn bash_in_sandbox<P: SandboxProvider>(
     command: &str,
     workdir: Option<&str>,
     timeout: Option<u64>,
+    options: &ExecOptions,
 ) -> Result<ExecutionResult, BashError> {
-    let command = build_bash_command(command, workdir, timeout);
+    let workdir = workdir.map(|workdir| resolve_container_path(provider.workdir(), workdir));
+    let command = build_bash_command(command, workdir.as_deref(), timeout)
+        .map_err(BashError::Sandbox)?;
     let command = vec!["sh".to_string(), "-c".to_string(), command];
-    exec_in_sandbox(provider, metadata, command)
+    provider
+        .shell(metadata, &command, Some(options))
         .await
         .map_err(BashError::Sandbox)
 }
@@ -1051,18 +3607,125 @@ fn classify_write_failure(path: &str, result: &ExecutionResult) -> WriteError {
     }
 }
 
-fn build_bash_command(command: &str, workdir: Option<&str>, timeout: Option<u64>) -> String {
+/// Rejects entries without `=` and names that are not valid shell variable names.
+fn validate_env_assignments(env: &[String]) -> Result<(), McpError> {
+    for assignment in env {
+        let key = assignment.split_once('=').map_or("", |(key, _)| key);
+        let mut chars = key.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+            && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+        if !valid {
+            return Err(McpError::invalid_params(
+                format!("invalid environment variable: {}", assignment),
+                None,
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn build_bash_command(
+    command: &str,
+    workdir: Option<&str>,
+    timeout: Option<u64>,
+) -> Result<String, SandboxError> {
     let command = if let Some(workdir) = workdir {
-        let container_path = resolve_container_path(workdir);
-        format!("cd {} && {}", shell_escape(&container_path), command)
+        format!("cd {} && {}", shell_escape(workdir)?, command)
     } else {
         command.to_string()
     };
 
     if let Some(timeout) = timeout {
-        format!("timeout {}s sh -c {}", timeout, shell_escape(&command))
+        Ok(format!("timeout {}s sh -c {}", timeout, shell_escape(&command)?))
     } else {
-        command
+        Ok(command)
+    }
+}
+
+/// Runs `git <args>` in `workdir`, or in the sandbox's project directory when unset.
+async fn git_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    args: &[String],
+    workdir: Option<&str>,
+) -> Result<ExecutionResult, SandboxError> {
+    let workdir = match workdir {
+        Some(workdir) => resolve_container_path(provider.workdir(), workdir),
+        None => provider.workdir().to_string(),
+    };
+    let mut command = vec!["git".to_string(), "-C".to_string(), workdir.clone()];
+    command.extend(args.iter().cloned());
+    let result = exec_in_sandbox(provider, metadata, command).await?;
+    if result.exit_code == 127 {
+        return Err(SandboxError::Config(
+            "git is not installed in the sandbox image".to_string(),
+        ));
+    }
+    // Sandboxes receive the branch's files but not its `.git`; their history lives in the
+    // host repository, so point the caller there instead of passing on git's message.
+    if result.exit_code != 0 && result.stderr.contains("not a git repository") {
+        return Err(SandboxError::Config(format!(
+            "{workdir} in the sandbox is not a git repository: sandboxes hold a copy of their \
+             branch without its history, which is kept in the host repository; use the \
+             snapshot and diff tools, or run `git init` in the sandbox first"
+        )));
+    }
+    Ok(result)
+}
+
+/// The operation in a `git` tool call that needs `allow_dangerous`: `clone`, `remote add`, a
+/// push that can overwrite remote history, or an alias definition, which could hide any of these.
+fn dangerous_git_operation(args: &[String]) -> Option<&'static str> {
+    let mut args = args.iter().map(String::as_str);
+    let subcommand = loop {
+        match args.next()? {
+            "-c" | "--config-env" => {
+                if args.next().is_some_and(is_alias_key) {
+                    return Some("alias definition");
+                }
+            }
+            option if option.strip_prefix("--config-env=").is_some_and(is_alias_key) => {
+                return Some("alias definition");
+            }
+            // Global options that take the next argument as their value.
+            "-C" | "--git-dir" | "--work-tree" | "--namespace" => {
+                args.next();
+            }
+            option if option.starts_with('-') => {}
+            subcommand => break subcommand,
+        }
+    };
+    let mut rest = args;
+    match subcommand {
+        "clone" => Some("clone"),
+        "remote" if rest.find(|arg| !arg.starts_with('-')) == Some("add") => Some("remote add"),
+        "push" if rest.any(is_force_push_arg) => Some("push --force"),
+        "config" => {
+            // Both `git config alias.x value` and `git config set alias.x value`.
+            let mut operands = rest.filter(|arg| !arg.starts_with('-'));
+            let key = operands.next().filter(|operand| *operand != "set").or(operands.next());
+            key.is_some_and(is_alias_key).then_some("alias definition")
+        }
+        _ => None,
+    }
+}
+
+/// Whether a config key, optionally followed by `=value`, names an alias. Keys are
+/// case-insensitive in their section name.
+fn is_alias_key(arg: &str) -> bool {
+    arg.get(..6).is_some_and(|section| section.eq_ignore_ascii_case("alias."))
+}
+
+fn is_force_push_arg(arg: &str) -> bool {
+    match arg.strip_prefix("--") {
+        Some(long) => long == "force" || long.starts_with("force-with-lease") || long == "mirror",
+        None => match arg.strip_prefix('-') {
+            Some(short) => short.contains('f'),
+            // A `+` refspec forces that ref.
+            None => arg.starts_with('+'),
+        },
     }
 }
 
@@ -1071,18 +3734,30 @@ async fn exec_in_sandbox<P: SandboxProvider>(
     metadata: &SandboxMetadata,
     command: Vec<String>,
 ) -> Result<ExecutionResult, SandboxError> {
-    provider.shell(metadata, &command).await
+    provider.shell(metadata, &command, None).await
 }
 
-fn resolve_container_path(path: &str) -> String {
+/// Resolves `path` against the sandbox `workdir` unless it is already absolute.
+pub fn resolve_container_path(workdir: &str, path: &str) -> String {
     if path.starts_with('/') {
         path.to_string()
     } else {
-        format!("/src/{}", path)
+        format!("{}/{}", workdir, path)
     }
 }
 
-fn shell_escape(value: &str) -> String {
+/// Single-quotes `value` for `sh`.
+///
+/// Control characters other than tab, line feed, and carriage return are refused: a NUL byte
+/// cannot be passed to a process at all, and the rest are more likely injected terminal
+/// sequences than intended input.
+fn shell_escape(value: &str) -> Result<String, SandboxError> {
+    if let Some(character) = value
+        .chars()
+        .find(|ch| ch.is_ascii_control() && !matches!(ch, '\t' | '\n' | '\r'))
+    {
+        return Err(SandboxError::UnsafeShellArgument { character });
+    }
     let mut escaped = String::from("'");
     for ch in value.chars() {
         if ch == '\'' {
@@ -1092,7 +3767,7 @@ fn shell_escape(value: &str) -> String {
         }
     }
     escaped.push('\'');
-    escaped
+    Ok(escaped)
 }
 
 #[cfg(test)]
@@ -1202,6 +3877,33 @@ enum GrepError {
     Failed { path: String, message: String },
 }
 
+#[derive(Debug)]
+enum EnvError {
+    Sandbox(SandboxError),
+    Failed { message: String },
+}
+
+#[derive(Debug)]
+enum PsError {
+    Sandbox(SandboxError),
+    Failed { message: String },
+}
+
+#[derive(Debug)]
+enum DiskUsageError {
+    Sandbox(SandboxError),
+    NotFound { path: String },
+    Failed { path: String, message: String },
+}
+
+#[derive(Debug)]
+enum SearchReplaceError {
+    Sandbox(SandboxError),
+    InvalidPattern { pattern: String, message: String },
+    NotFound { path: String },
+    Failed { path: String, message: String },
+}
+
 fn map_glob_tool_error(sandbox: &str, error: GlobError) -> McpError {
     match error {
         GlobError::Sandbox(error) => map_sandbox_error(sandbox, error),
@@ -1226,14 +3928,15 @@ async fn glob_in_sandbox<P: SandboxProvider>(
     metadata: &SandboxMetadata,
     pattern: &str,
     base_path: Option<&str>,
+    exclude: &[String],
 ) -> Result<Vec<String>, GlobError> {
     let base = base_path
-        .map(resolve_container_path)
-        .unwrap_or_else(|| "/src".to_string());
+        .map(|path| resolve_container_path(provider.workdir(), path))
+        .unwrap_or_else(|| provider.workdir().to_string());
     let command = vec![
         "sh".to_string(),
         "-c".to_string(),
-        format!("find {} -mindepth 1 -print", shell_escape(&base)),
+        format!("find {} -mindepth 1 -print", shell_escape(&base).map_err(GlobError::Sandbox)?),
     ];
     let result = exec_in_sandbox(provider, metadata, command)
         .await
@@ -1243,10 +3946,14 @@ async fn glob_in_sandbox<P: SandboxProvider>(
     }
 
     let pattern_is_absolute = Path::new(pattern).is_absolute();
-    let pattern = Pattern::new(pattern).map_err(|error| GlobError::InvalidPattern {
-        pattern: pattern.to_string(),
-        message: error.to_string(),
-    })?;
+    let pattern = compile_glob_pattern(pattern)?;
+    let exclude = exclude
+        .iter()
+        .map(|exclusion| {
+            let is_absolute = Path::new(exclusion).is_absolute();
+            compile_glob_pattern(exclusion).map(|exclusion| (exclusion, is_absolute))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
     let options = MatchOptions {
         case_sensitive: true,
         require_literal_separator: true,
@@ -1261,7 +3968,11 @@ async fn glob_in_sandbox<P: SandboxProvider>(
         } else {
             relative.as_str()
         };
-        if pattern.matches_with(candidate, options) {
+        let excluded = exclude.iter().any(|(exclusion, is_absolute)| {
+            let candidate = if *is_absolute { &entry } else { &relative };
+            exclusion.matches_with(candidate, options)
+        });
+        if pattern.matches_with(candidate, options) && !excluded {
             let display = if pattern_is_absolute { entry } else { relative };
             entries.push(display);
         }
@@ -1271,28 +3982,167 @@ async fn glob_in_sandbox<P: SandboxProvider>(
     Ok(entries)
 }
 
-fn classify_glob_failure(base: &str, result: &ExecutionResult) -> GlobError {
-    let stderr = result.stderr.trim();
-    let stdout = result.stdout.trim();
-    let message = if !stderr.is_empty() { stderr } else { stdout };
-    if message.contains("No such file or directory") {
-        GlobError::NotFound {
-            path: base.to_string(),
-        }
-    } else if message.contains("Permission denied") {
-        GlobError::PermissionDenied {
-            path: base.to_string(),
-        }
-    } else if message.is_empty() {
-        GlobError::Failed {
-            path: base.to_string(),
-            message: format!("exit code {}", result.exit_code),
-        }
-    } else {
-        GlobError::Failed {
-            path: base.to_string(),
-            message: message.to_string(),
-        }
+fn compile_glob_pattern(pattern: &str) -> Result<Pattern, GlobError> {
+    Pattern::new(pattern).map_err(|error| GlobError::InvalidPattern {
+        pattern: pattern.to_string(),
+        message: error.to_string(),
+    })
+}
+
+fn classify_glob_failure(base: &str, result: &ExecutionResult) -> GlobError {
+    let stderr = result.stderr.trim();
+    let stdout = result.stdout.trim();
+    let message = if !stderr.is_empty() { stderr } else { stdout };
+    if message.contains("No such file or directory") {
+        GlobError::NotFound {
+            path: base.to_string(),
+        }
+    } else if message.contains("Permission denied") {
+        GlobError::PermissionDenied {
+            path: base.to_string(),
+        }
+    } else if message.is_empty() {
+        GlobError::Failed {
+            path: base.to_string(),
+            message: format!("exit code {}", result.exit_code),
+        }
+    } else {
+        GlobError::Failed {
+            path: base.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+fn map_find_error(sandbox: &str, error: GlobError) -> McpError {
+    match error {
+        GlobError::Sandbox(error) => map_sandbox_error(sandbox, error),
+        GlobError::InvalidPattern { pattern, message } => McpError::invalid_params(
+            format!("invalid find filter '{}': {}", pattern, message),
+            None,
+        ),
+        GlobError::NotFound { path } => {
+            McpError::invalid_params(format!("path not found: {}", path), None)
+        }
+        GlobError::PermissionDenied { path } => {
+            McpError::invalid_params(format!("permission denied: {}", path), None)
+        }
+        GlobError::Failed { path, message } => {
+            McpError::internal_error(format!("find failed for {}: {}", path, message), None)
+        }
+    }
+}
+
+async fn find_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    args: &FindArgs,
+) -> Result<Vec<String>, GlobError> {
+    let base = resolve_container_path(provider.workdir(), &args.path);
+    let command = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        build_find_command(&base, args)?,
+    ];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(GlobError::Sandbox)?;
+    if result.exit_code != 0 {
+        return Err(classify_glob_failure(&base, &result));
+    }
+
+    let mut entries: Vec<String> = parse_find_output(&result.stdout)
+        .iter()
+        .map(|entry| strip_base_prefix(entry, &base))
+        .collect();
+    entries.sort();
+    Ok(entries)
+}
+
+fn build_find_command(base: &str, args: &FindArgs) -> Result<String, GlobError> {
+    let mut parts = vec![
+        "find".to_string(),
+        shell_escape(base).map_err(GlobError::Sandbox)?,
+        "-mindepth".to_string(),
+        "1".to_string(),
+    ];
+    if let Some(name) = &args.name {
+        parts.push("-name".to_string());
+        parts.push(shell_escape(name).map_err(GlobError::Sandbox)?);
+    }
+    if let Some(type_filter) = &args.type_filter {
+        let find_type = match type_filter.as_str() {
+            "f" | "file" => "f",
+            "d" | "dir" | "directory" => "d",
+            "l" | "symlink" => "l",
+            _ => {
+                return Err(invalid_find_filter(
+                    type_filter,
+                    "expected f (file), d (directory), or l (symlink)",
+                ));
+            }
+        };
+        parts.push("-type".to_string());
+        parts.push(find_type.to_string());
+    }
+    if let Some(newer_than) = &args.newer_than {
+        parts.push("-mmin".to_string());
+        parts.push(format!("-{}", parse_find_age_minutes(newer_than)?));
+    }
+    if let Some(older_than) = &args.older_than {
+        parts.push("-mmin".to_string());
+        parts.push(format!("+{}", parse_find_age_minutes(older_than)?));
+    }
+    // `-size` rounds up to whole units, so sizes are compared in bytes to stay exact.
+    if let Some(min_size) = &args.min_size {
+        let bytes = parse_find_size_bytes(min_size)?;
+        parts.push("-size".to_string());
+        parts.push(format!("+{}c", bytes.saturating_sub(1)));
+    }
+    if let Some(max_size) = &args.max_size {
+        let bytes = parse_find_size_bytes(max_size)?;
+        parts.push("-size".to_string());
+        parts.push(format!("-{}c", bytes.saturating_add(1)));
+    }
+    parts.push("-print".to_string());
+    Ok(parts.join(" "))
+}
+
+/// Parses an age such as `30m`, `2h`, `7d`, or `1w` into minutes; a bare number is minutes.
+fn parse_find_age_minutes(value: &str) -> Result<u64, GlobError> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((index, 'm')) => (&value[..index], 1),
+        Some((index, 'h')) => (&value[..index], 60),
+        Some((index, 'd')) => (&value[..index], 60 * 24),
+        Some((index, 'w')) => (&value[..index], 60 * 24 * 7),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| invalid_find_filter(value, "expected an age such as 30m, 2h, 7d, or 1w"))
+}
+
+/// Parses a size such as `512`, `10k`, `5M`, or `1G` into bytes.
+fn parse_find_size_bytes(value: &str) -> Result<u64, GlobError> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&value[..index], 1024),
+        Some((index, 'm' | 'M')) => (&value[..index], 1024 * 1024),
+        Some((index, 'g' | 'G')) => (&value[..index], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| invalid_find_filter(value, "expected a size such as 512, 10k, 5M, or 1G"))
+}
+
+fn invalid_find_filter(value: &str, message: &str) -> GlobError {
+    GlobError::InvalidPattern {
+        pattern: value.to_string(),
+        message: message.to_string(),
     }
 }
 
@@ -1332,18 +4182,26 @@ fn map_grep_error(sandbox: &str, error: GrepError) -> McpError {
     }
 }
 
+#[derive(Debug, Default)]
+struct GrepOptions {
+    include: Option<String>,
+    max_results: Option<usize>,
+    context_lines: Option<usize>,
+    fixed_string: bool,
+}
+
 async fn grep_in_sandbox<P: SandboxProvider>(
     provider: &P,
     metadata: &SandboxMetadata,
     pattern: &str,
     path: &str,
-    include: Option<&str>,
+    options: &GrepOptions,
 ) -> Result<Vec<String>, GrepError> {
-    let container_path = resolve_container_path(path);
+    let container_path = resolve_container_path(provider.workdir(), path);
     let command = vec![
         "sh".to_string(),
         "-c".to_string(),
-        build_grep_command(pattern, &container_path, include),
+        build_grep_command(pattern, &container_path, options).map_err(GrepError::Sandbox)?,
     ];
     let result = exec_in_sandbox(provider, metadata, command)
         .await
@@ -1357,15 +4215,28 @@ async fn grep_in_sandbox<P: SandboxProvider>(
     Err(classify_grep_failure(&container_path, pattern, &result))
 }
 
-fn build_grep_command(pattern: &str, path: &str, include: Option<&str>) -> String {
+fn build_grep_command(
+    pattern: &str,
+    path: &str,
+    options: &GrepOptions,
+) -> Result<String, SandboxError> {
     let mut parts = vec!["grep".to_string(), "-R".to_string(), "-n".to_string()];
-    if let Some(include) = include {
-        parts.push(format!("--include={}", shell_escape(include)));
+    if options.fixed_string {
+        parts.push("-F".to_string());
+    }
+    if let Some(max_results) = options.max_results {
+        parts.push(format!("-m {}", max_results));
+    }
+    if let Some(context_lines) = options.context_lines {
+        parts.push(format!("-C {}", context_lines));
+    }
+    if let Some(include) = &options.include {
+        parts.push(format!("--include={}", shell_escape(include)?));
     }
     parts.push("--".to_string());
-    parts.push(shell_escape(pattern));
-    parts.push(shell_escape(path));
-    parts.join(" ")
+    parts.push(shell_escape(pattern)?);
+    parts.push(shell_escape(path)?);
+    Ok(parts.join(" "))
 }
 
 fn classify_grep_failure(path: &str, pattern: &str, result: &ExecutionResult) -> GrepError {
@@ -1398,19 +4269,458 @@ fn classify_grep_failure(path: &str, pattern: &str, result: &ExecutionResult) ->
     }
 }
 
+/// Context output separates groups of lines with a bare `--`, which is dropped.
 fn parse_grep_output(output: &str) -> Vec<String> {
     output
         .lines()
         .map(|line| line.trim_end_matches('\r'))
-        .filter(|line| !line.is_empty())
+        .filter(|line| !line.is_empty() && *line != "--")
         .map(|line| line.to_string())
         .collect()
 }
 
+fn map_env_error(sandbox: &str, error: EnvError) -> McpError {
+    match error {
+        EnvError::Sandbox(error) => map_sandbox_error(sandbox, error),
+        EnvError::Failed { message } => {
+            McpError::internal_error(format!("env failed: {}", message), None)
+        }
+    }
+}
+
+async fn env_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    filter: Option<&str>,
+) -> Result<Vec<EnvEntry>, EnvError> {
+    let script = match filter {
+        Some(filter) => {
+            let filter = shell_escape(filter).map_err(EnvError::Sandbox)?;
+            format!("env | grep -- {}", filter)
+        }
+        None => "env".to_string(),
+    };
+    let command = vec!["sh".to_string(), "-c".to_string(), script];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(EnvError::Sandbox)?;
+    if result.exit_code == 0 {
+        return Ok(parse_env_output(&result.stdout));
+    }
+    if filter.is_some() && result.exit_code == 1 && result.stderr.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let message = result.stderr.trim();
+    Err(EnvError::Failed {
+        message: if message.is_empty() {
+            format!("exit code {}", result.exit_code)
+        } else {
+            message.to_string()
+        },
+    })
+}
+
+/// Parses `env` output; lines without `=` continue the previous multi-line value.
+fn parse_env_output(output: &str) -> Vec<EnvEntry> {
+    let mut entries: Vec<EnvEntry> = Vec::new();
+    for line in output.lines() {
+        match line.split_once('=') {
+            Some((key, value)) if !key.is_empty() => entries.push(EnvEntry {
+                key: key.to_string(),
+                value: value.to_string(),
+            }),
+            _ => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.value.push('\n');
+                    entry.value.push_str(line);
+                }
+            }
+        }
+    }
+    entries
+}
+
+fn map_ps_error(sandbox: &str, error: PsError) -> McpError {
+    match error {
+        PsError::Sandbox(error) => map_sandbox_error(sandbox, error),
+        PsError::Failed { message } => {
+            McpError::internal_error(format!("ps failed: {}", message), None)
+        }
+    }
+}
+
+async fn ps_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    all: bool,
+) -> Result<Vec<ProcessEntry>, PsError> {
+    // BusyBox ps rejects BSD-style options but always lists every process.
+    let options = if all { "aux" } else { "ux" };
+    let command = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        format!("ps {} 2>/dev/null || ps", options),
+    ];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(PsError::Sandbox)?;
+    if result.exit_code != 0 {
+        let message = result.stderr.trim();
+        return Err(PsError::Failed {
+            message: if message.is_empty() {
+                format!("exit code {}", result.exit_code)
+            } else {
+                message.to_string()
+            },
+        });
+    }
+    parse_ps_output(&result.stdout).ok_or_else(|| PsError::Failed {
+        message: format!("unexpected ps output: {}", result.stdout.trim()),
+    })
+}
+
+/// Parses `ps` output by its header, so both procps `ps aux` and BusyBox `ps` columns work.
+/// The command is always the last column and may contain spaces.
+fn parse_ps_output(output: &str) -> Option<Vec<ProcessEntry>> {
+    let mut lines = output.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<&str> = lines.next()?.split_whitespace().collect();
+    let column = |names: &[&str]| header.iter().position(|name| names.contains(name));
+    let pid_column = column(&["PID"])?;
+    let user_column = column(&["USER", "UID"]);
+    let cpu_column = column(&["%CPU"]);
+    let mem_column = column(&["%MEM"]);
+
+    let mut processes = Vec::new();
+    for line in lines {
+        let fields = split_columns(line, header.len());
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|index| fields.get(index))
+                .map(|value| value.to_string())
+                .unwrap_or_default()
+        };
+        let Some(pid) = fields.get(pid_column).and_then(|pid| pid.parse().ok()) else {
+            continue;
+        };
+        processes.push(ProcessEntry {
+            pid,
+            user: field(user_column),
+            cpu: field(cpu_column),
+            mem: field(mem_column),
+            command: field(Some(header.len() - 1)),
+        });
+    }
+    Some(processes)
+}
+
+/// Splits `line` on whitespace into at most `count` fields, keeping the remainder intact.
+fn split_columns(line: &str, count: usize) -> Vec<&str> {
+    let mut fields = Vec::with_capacity(count);
+    let mut rest = line.trim_start();
+    while fields.len() + 1 < count && !rest.is_empty() {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    if !rest.is_empty() {
+        fields.push(rest.trim_end());
+    }
+    fields
+}
+
+fn map_disk_usage_error(sandbox: &str, error: DiskUsageError) -> McpError {
+    match error {
+        DiskUsageError::Sandbox(error) => map_sandbox_error(sandbox, error),
+        DiskUsageError::NotFound { path } => {
+            McpError::invalid_params(format!("path not found: {}", path), None)
+        }
+        DiskUsageError::Failed { path, message } => McpError::internal_error(
+            format!("disk usage failed for {}: {}", path, message),
+            None,
+        ),
+    }
+}
+
+async fn df_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+) -> Result<DfResult, DiskUsageError> {
+    // POSIX output keeps each filesystem on one line even when its name is long.
+    let command = vec!["sh".to_string(), "-c".to_string(), "df -hP /".to_string()];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(DiskUsageError::Sandbox)?;
+    if result.exit_code != 0 {
+        return Err(classify_disk_usage_failure("/", &result));
+    }
+    parse_df_output(&result.stdout).ok_or_else(|| DiskUsageError::Failed {
+        path: "/".to_string(),
+        message: format!("unexpected df output: {}", result.stdout.trim()),
+    })
+}
+
+fn parse_df_output(output: &str) -> Option<DfResult> {
+    let line = output.lines().skip(1).find(|line| !line.trim().is_empty())?;
+    let mut fields = line.split_whitespace();
+    Some(DfResult {
+        filesystem: fields.next()?.to_string(),
+        size: fields.next()?.to_string(),
+        used: fields.next()?.to_string(),
+        available: fields.next()?.to_string(),
+        use_percent: fields.next()?.to_string(),
+    })
+}
+
+async fn du_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    path: &str,
+    depth: Option<usize>,
+) -> Result<Vec<DuEntry>, DiskUsageError> {
+    let container_path = resolve_container_path(provider.workdir(), path);
+    // `-s` and a depth limit are mutually exclusive in GNU du, so depth 0 stands in for `-s`.
+    let command = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        format!(
+            "du -h -d {} -- {}",
+            depth.unwrap_or(0),
+            shell_escape(&container_path).map_err(DiskUsageError::Sandbox)?
+        ),
+    ];
+    let result = exec_in_sandbox(provider, metadata, command)
+        .await
+        .map_err(DiskUsageError::Sandbox)?;
+    if result.exit_code != 0 {
+        return Err(classify_disk_usage_failure(&container_path, &result));
+    }
+    Ok(parse_du_output(&result.stdout))
+}
+
+fn parse_du_output(output: &str) -> Vec<DuEntry> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .map(|(size, path)| DuEntry {
+            size: size.to_string(),
+            path: path.trim_start().to_string(),
+        })
+        .collect()
+}
+
+fn classify_disk_usage_failure(path: &str, result: &ExecutionResult) -> DiskUsageError {
+    let stderr = result.stderr.trim();
+    let stdout = result.stdout.trim();
+    let message = if !stderr.is_empty() { stderr } else { stdout };
+    if message.contains("No such file or directory") {
+        DiskUsageError::NotFound {
+            path: path.to_string(),
+        }
+    } else if message.is_empty() {
+        DiskUsageError::Failed {
+            path: path.to_string(),
+            message: format!("exit code {}", result.exit_code),
+        }
+    } else {
+        DiskUsageError::Failed {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+fn map_search_replace_error(sandbox: &str, error: SearchReplaceError) -> McpError {
+    match error {
+        SearchReplaceError::Sandbox(error) => map_sandbox_error(sandbox, error),
+        SearchReplaceError::InvalidPattern { pattern, message } => McpError::invalid_params(
+            format!("invalid search pattern '{}': {}", pattern, message),
+            None,
+        ),
+        SearchReplaceError::NotFound { path } => {
+            McpError::invalid_params(format!("path not found: {}", path), None)
+        }
+        SearchReplaceError::Failed { path, message } => McpError::internal_error(
+            format!("search-replace failed for {}: {}", path, message),
+            None,
+        ),
+    }
+}
+
+async fn search_replace_in_sandbox<P: SandboxProvider>(
+    provider: &P,
+    metadata: &SandboxMetadata,
+    pattern: &str,
+    replacement: &str,
+    path: &str,
+    regex: bool,
+    case_insensitive: bool,
+) -> Result<SearchReplaceResult, SearchReplaceError> {
+    if pattern.is_empty() || pattern.contains('\n') {
+        return Err(SearchReplaceError::InvalidPattern {
+            pattern: pattern.to_string(),
+            message: "pattern must be a single non-empty line".to_string(),
+        });
+    }
+    let container_path = resolve_container_path(provider.workdir(), path);
+    let command = build_search_replace_command(
+        pattern,
+        replacement,
+        &container_path,
+        regex,
+        case_insensitive,
+    )
+    .map_err(SearchReplaceError::Sandbox)?;
+    let result = bash_in_sandbox(
+        provider,
+        metadata,
+        &command,
+        None,
+        None,
+        &ExecOptions::default(),
+    )
+    .await
+    .map_err(|BashError::Sandbox(error)| SearchReplaceError::Sandbox(error))?;
+    if result.exit_code != 0 {
+        return Err(classify_search_replace_failure(&container_path, pattern, &result));
+    }
+    parse_search_replace_output(&result.stdout).ok_or_else(|| SearchReplaceError::Failed {
+        path: container_path,
+        message: format!("unexpected output: {}", result.stdout.trim()),
+    })
+}
+
+/// Builds a script that lists matching files with `grep -rl`, counts the occurrences in each
+/// with `grep -o`, rewrites it with `sed -i`, and finally prints `<files> <replacements>`.
+fn build_search_replace_command(
+    pattern: &str,
+    replacement: &str,
+    path: &str,
+    regex: bool,
+    case_insensitive: bool,
+) -> Result<String, SandboxError> {
+    let mut grep_flags = vec![if regex { "-E" } else { "-F" }];
+    let mut sed_flags = "g";
+    if case_insensitive {
+        grep_flags.push("-i");
+        sed_flags = "gI";
+    }
+    let grep_flags = grep_flags.join(" ");
+    let expression = if regex {
+        format!(
+            "s/{}/{}/{}",
+            escape_sed_delimiter(pattern),
+            escape_sed_delimiter(replacement).replace('\n', "\\\n"),
+            sed_flags
+        )
+    } else {
+        format!(
+            "s/{}/{}/{}",
+            escape_sed_literal_pattern(pattern),
+            escape_sed_literal_replacement(replacement),
+            sed_flags
+        )
+    };
+    let sed = if regex { "sed -i -E" } else { "sed -i" };
+    let pattern = shell_escape(pattern)?;
+    let path = shell_escape(path)?;
+    let expression = shell_escape(&expression)?;
+
+    Ok(format!(
+        "[ -e {path} ] || {{ echo {path}: No such file or directory >&2; exit 2; }}; \
+         grep -q {grep_flags} -e {pattern} /dev/null; [ $? -le 1 ] || exit 2; \
+         grep -rlI {grep_flags} -e {pattern} -- {path} | {{ files=0; total=0; \
+         while IFS= read -r file; do \
+         count=$(grep -o {grep_flags} -e {pattern} -- \"$file\" | wc -l); \
+         {sed} -e {expression} -- \"$file\" || exit 1; \
+         files=$((files + 1)); total=$((total + count)); \
+         done; echo \"$files $total\"; }}"
+    ))
+}
+
+/// Escapes a literal string for use as a sed basic regular expression.
+fn escape_sed_literal_pattern(value: &str) -> String {
+    let mut escaped = String::new();
+    for ch in value.chars() {
+        if matches!(ch, '\\' | '/' | '.' | '*' | '[' | ']' | '^' | '$') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// Escapes a literal string for use as a sed replacement.
+fn escape_sed_literal_replacement(value: &str) -> String {
+    let mut escaped = String::new();
+    for ch in value.chars() {
+        if matches!(ch, '\\' | '/' | '&' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// Escapes unescaped `/` so a user-supplied regex or replacement can sit inside `s/.../.../`.
+fn escape_sed_delimiter(value: &str) -> String {
+    let mut escaped = String::new();
+    let mut backslash = false;
+    for ch in value.chars() {
+        if ch == '/' && !backslash {
+            escaped.push('\\');
+        }
+        backslash = ch == '\\' && !backslash;
+        escaped.push(ch);
+    }
+    escaped
+}
+
+fn classify_search_replace_failure(
+    path: &str,
+    pattern: &str,
+    result: &ExecutionResult,
+) -> SearchReplaceError {
+    let stderr = result.stderr.trim();
+    let stdout = result.stdout.trim();
+    let message = if !stderr.is_empty() { stderr } else { stdout };
+    if message.contains("No such file or directory") {
+        SearchReplaceError::NotFound {
+            path: path.to_string(),
+        }
+    } else if result.exit_code == 2 {
+        SearchReplaceError::InvalidPattern {
+            pattern: pattern.to_string(),
+            message: message.to_string(),
+        }
+    } else if message.is_empty() {
+        SearchReplaceError::Failed {
+            path: path.to_string(),
+            message: format!("exit code {}", result.exit_code),
+        }
+    } else {
+        SearchReplaceError::Failed {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+fn parse_search_replace_output(output: &str) -> Option<SearchReplaceResult> {
+    let line = output.lines().rev().find(|line| !line.trim().is_empty())?;
+    let mut counts = line.split_whitespace();
+    let files_modified = counts.next()?.parse().ok()?;
+    let total_replacements = counts.next()?.parse().ok()?;
+    Some(SearchReplaceResult {
+        files_modified,
+        total_replacements,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::compute::{ContainerInspection, PortBindingSpec};
+    use crate::compute::{ContainerInspection, ContainerStats, ExecOptions};
+    use crate::domain::{MergeResult, SyncDirection, SyncResult};
     use futures_util::future::BoxFuture;
     use git2::{ErrorCode, Oid, Repository, Signature};
     use std::fs;
@@ -1434,26 +4744,11 @@ mod tests {
         }
     }
 
-    #[test]
-    fn forwarded_ports_from_inspection_builds_mapping() {
-        let inspection = ContainerInspection {
-            env: vec!["LITTERBOX_FWD_PORT_WEB=3001".to_string()],
-            port_bindings: HashMap::from([(
-                "8080/tcp".to_string(),
-                vec![PortBindingSpec {
-                    host_ip: Some("0.0.0.0".to_string()),
-                    host_port: Some("3001".to_string()),
-                }],
-            )]),
-        };
-
-        let mappings = forwarded_ports_from_inspection(&inspection);
-
-        assert_eq!(mappings.len(), 1);
-        assert_eq!(mappings[0].name, "web");
-        assert_eq!(mappings[0].target, 8080);
-        assert_eq!(mappings[0].host_port, 3001);
-        assert_eq!(mappings[0].env_var, "LITTERBOX_FWD_PORT_WEB");
+    #[test]
+    fn validate_sandbox_name_rejects_reserved_names() {
+        let error = validate_sandbox_name("Delete").expect_err("reserved name");
+        assert!(error.message.contains("Reserved names: api, web"));
+        assert!(validate_sandbox_name("feature-one").is_ok());
     }
 
     impl Scm for TestScm {
@@ -1461,11 +4756,39 @@ mod tests {
             Ok("branch".to_string())
         }
 
+        fn create_branch_at(&self, _slug: &str, _from_ref: &str) -> Result<String, SandboxError> {
+            Ok("branch".to_string())
+        }
+
         fn delete_branch(&self, _slug: &str) -> Result<(), SandboxError> {
             Ok(())
         }
 
-        fn make_archive(&self, _reference: &str) -> Result<Vec<u8>, SandboxError> {
+        fn rename_branch(&self, _old_slug: &str, new_slug: &str) -> Result<String, SandboxError> {
+            Ok(format!("litterbox/{new_slug}"))
+        }
+
+        fn create_branch_from(&self, _slug: &str, branch_name: &str) -> Result<String, SandboxError> {
+            Ok(branch_name.to_string())
+        }
+
+        fn push_branch(&self, _branch_name: &str) -> Result<(), SandboxError> {
+            Ok(())
+        }
+
+        fn merge_branch(
+            &self,
+            _source_slug: &str,
+            _target_ref: &str,
+            _strategy: MergeStrategy,
+        ) -> Result<MergeResult, SandboxError> {
+            Ok(MergeResult {
+                oid: git2::Oid::zero(),
+                had_conflicts: false,
+            })
+        }
+
+        fn make_archive(&self, _reference: &str, _compressed: bool) -> Result<Vec<u8>, SandboxError> {
             Ok(Vec::new())
         }
 
@@ -1473,6 +4796,10 @@ mod tests {
             Ok(Vec::new())
         }
 
+        fn list_sandboxes_with_metadata(&self) -> Result<Vec<SandboxEntry>, SandboxError> {
+            Ok(Vec::new())
+        }
+
         fn repo_prefix(&self) -> Result<String, SandboxError> {
             Ok("repo".to_string())
         }
@@ -1481,10 +4808,18 @@ mod tests {
             Ok(self.has_changes)
         }
 
+        fn has_changes_in(&self, _paths: &[&str]) -> Result<bool, SandboxError> {
+            Ok(self.has_changes)
+        }
+
         fn stage_all(&self) -> Result<(), SandboxError> {
             Ok(())
         }
 
+        fn stage_all_except(&self, _exclude: &[&str]) -> Result<(), SandboxError> {
+            Ok(())
+        }
+
         fn commit_snapshot(&self, message: &str) -> Result<Option<Oid>, SandboxError> {
             self.committed_messages
                 .lock()
@@ -1493,9 +4828,50 @@ mod tests {
             Ok(Some(Oid::zero()))
         }
 
+        fn commit_snapshot_paths(
+            &self,
+            _paths: &[&str],
+            message: &str,
+        ) -> Result<Option<Oid>, SandboxError> {
+            self.commit_snapshot(message)
+        }
+
         fn apply_patch(&self, _diff: &str) -> Result<(), SandboxError> {
             Ok(())
         }
+
+        fn cherry_pick(&self, _commit_oid: &str) -> Result<Option<git2::Oid>, SandboxError> {
+            Ok(None)
+        }
+
+        fn apply_patch_dry_run(&self, _diff: &str) -> Result<(), SandboxError> {
+            Ok(())
+        }
+
+        fn diff_between(
+            &self,
+            _from: &str,
+            _to: &str,
+            _path_filter: Option<&str>,
+        ) -> Result<Option<String>, SandboxError> {
+            Ok(None)
+        }
+
+        fn list_snapshots(
+            &self,
+            _slug: &str,
+            _limit: Option<usize>,
+        ) -> Result<Vec<crate::domain::SnapshotInfo>, SandboxError> {
+            Ok(Vec::new())
+        }
+
+        fn stash(&self, _message: &str) -> Result<Oid, SandboxError> {
+            Ok(Oid::zero())
+        }
+
+        fn stash_pop(&self) -> Result<(), SandboxError> {
+            Ok(())
+        }
     }
 
     fn init_repo() -> (TempDir, Repository) {
@@ -1517,6 +4893,9 @@ mod tests {
     struct TestProvider {
         shell_result: Mutex<Option<Result<ExecutionResult, SandboxError>>>,
         last_command: Arc<Mutex<Option<Vec<String>>>>,
+        last_stdin: Arc<Mutex<Option<Vec<u8>>>>,
+        /// `(container_id, network, alias)` for each `join_network` call.
+        joined: Mutex<Vec<(String, String, String)>>,
     }
 
     impl TestProvider {
@@ -1527,6 +4906,8 @@ mod tests {
             Self {
                 shell_result: Mutex::new(Some(result)),
                 last_command,
+                last_stdin: Arc::new(Mutex::new(None)),
+                joined: Mutex::new(Vec::new()),
             }
         }
     }
@@ -1573,6 +4954,30 @@ impl SandboxProvider for MultiResultProvider {
         })
     }
 
+    fn container_stats<'a>(
+        &'a self,
+        _container_id: &'a str,
+    ) -> BoxFuture<'a, Result<ContainerStats, SandboxError>> {
+        Box::pin(async move {
+            Err(SandboxError::SandboxNotFound {
+                name: "unused".to_string(),
+            })
+        })
+    }
+
+    fn commit_container<'a>(
+        &'a self,
+        _container_id: &'a str,
+        _image: &'a str,
+        _tag: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move {
+            Err(SandboxError::SandboxNotFound {
+                name: "unused".to_string(),
+            })
+        })
+    }
+
         fn resume<'a>(&'a self, _container_id: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
             Box::pin(async move {
                 Err(SandboxError::SandboxNotFound {
@@ -1592,10 +4997,20 @@ impl SandboxProvider for MultiResultProvider {
             })
         }
 
+        fn join_network<'a>(
+            &'a self,
+            _metadata: &'a SandboxMetadata,
+            _network: &'a str,
+            _alias: &'a str,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async move { Ok(()) })
+        }
+
         fn shell<'a>(
             &'a self,
             _metadata: &'a SandboxMetadata,
             _command: &'a [String],
+            _options: Option<&'a ExecOptions>,
         ) -> BoxFuture<'a, Result<ExecutionResult, SandboxError>> {
             let results = Arc::clone(&self.results);
             Box::pin(async move {
@@ -1609,6 +5024,24 @@ impl SandboxProvider for MultiResultProvider {
             })
         }
 
+        fn sync<'a>(
+            &'a self,
+            _metadata: &'a SandboxMetadata,
+            _direction: SyncDirection,
+        ) -> BoxFuture<'a, Result<SyncResult, SandboxError>> {
+            Box::pin(async move { Ok(SyncResult::default()) })
+        }
+
+        fn wait_for_ready<'a>(
+            &'a self,
+            _metadata: &'a SandboxMetadata,
+            _command: &'a str,
+            _timeout_secs: u64,
+            _poll_interval_ms: u64,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async move { Ok(()) })
+        }
+
         fn upload_path<'a>(
             &'a self,
             _metadata: &'a SandboxMetadata,
@@ -1668,6 +5101,30 @@ impl SandboxProvider for TestProvider {
         })
     }
 
+    fn container_stats<'a>(
+        &'a self,
+        _container_id: &'a str,
+    ) -> BoxFuture<'a, Result<ContainerStats, SandboxError>> {
+        Box::pin(async move {
+            Err(SandboxError::SandboxNotFound {
+                name: "unused".to_string(),
+            })
+        })
+    }
+
+    fn commit_container<'a>(
+        &'a self,
+        _container_id: &'a str,
+        _image: &'a str,
+        _tag: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move {
+            Err(SandboxError::SandboxNotFound {
+                name: "unused".to_string(),
+            })
+        })
+    }
+
         fn resume<'a>(&'a self, _container_id: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
             Box::pin(async move {
                 Err(SandboxError::SandboxNotFound {
@@ -1687,10 +5144,25 @@ impl SandboxProvider for TestProvider {
             })
         }
 
+        fn join_network<'a>(
+            &'a self,
+            metadata: &'a SandboxMetadata,
+            network: &'a str,
+            alias: &'a str,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            self.joined.lock().expect("joined lock").push((
+                metadata.container_id.clone(),
+                network.to_string(),
+                alias.to_string(),
+            ));
+            Box::pin(async move { Ok(()) })
+        }
+
         fn shell<'a>(
             &'a self,
             _metadata: &'a SandboxMetadata,
             command: &'a [String],
+            options: Option<&'a ExecOptions>,
         ) -> BoxFuture<'a, Result<ExecutionResult, SandboxError>> {
             let result = self
                 .shell_result
@@ -1704,12 +5176,32 @@ impl SandboxProvider for TestProvider {
                 });
             let last_command = Arc::clone(&self.last_command);
             let command = command.to_vec();
+            *self.last_stdin.lock().expect("stdin lock") =
+                options.and_then(|options| options.stdin.clone());
             Box::pin(async move {
                 *last_command.lock().expect("command lock") = Some(command);
                 result
             })
         }
 
+        fn sync<'a>(
+            &'a self,
+            _metadata: &'a SandboxMetadata,
+            _direction: SyncDirection,
+        ) -> BoxFuture<'a, Result<SyncResult, SandboxError>> {
+            Box::pin(async move { Ok(SyncResult::default()) })
+        }
+
+        fn wait_for_ready<'a>(
+            &'a self,
+            _metadata: &'a SandboxMetadata,
+            _command: &'a str,
+            _timeout_secs: u64,
+            _poll_interval_ms: u64,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async move { Ok(()) })
+        }
+
         fn upload_path<'a>(
             &'a self,
             _metadata: &'a SandboxMetadata,
@@ -1744,6 +5236,12 @@ impl SandboxProvider for TestProvider {
             container_id: "container".to_string(),
             status: SandboxStatus::Active,
             forwarded_ports: Vec::new(),
+            created_at: None,
+            last_active_at: None,
+            container_ip: None,
+            network_aliases: Vec::new(),
+            image_digest: None,
+            started_at: None,
         }
     }
 
@@ -1777,6 +5275,8 @@ impl SandboxProvider for TestProvider {
             exit_code: 0,
             stdout: "one\ntwo\n".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let last_command = Arc::new(Mutex::new(None));
         let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
@@ -1799,6 +5299,8 @@ impl SandboxProvider for TestProvider {
             exit_code: 0,
             stdout: "one\ntwo\nthree\n".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let last_command = Arc::new(Mutex::new(None));
         let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
@@ -1809,12 +5311,40 @@ impl SandboxProvider for TestProvider {
         assert_eq!(content, "two\n");
     }
 
+    #[tokio::test]
+    async fn read_content_in_sandbox_returns_text() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "one\ntwo\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let content =
+            read_content_in_sandbox(&provider, &stub_metadata(), "README.md", Some(1), None)
+                .await
+                .expect("read");
+
+        assert_eq!(content, ReadContent::Text("two\n".to_string()));
+    }
+
+    #[test]
+    fn is_binary_content_detects_nul_bytes() {
+        assert!(is_binary_content("PNG\0\u{1}\u{2}"));
+        assert!(!is_binary_content("plain text\n"));
+        let late_nul = format!("{}\0", "a".repeat(8000));
+        assert!(!is_binary_content(&late_nul));
+    }
+
     #[tokio::test]
     async fn read_in_sandbox_missing_file_returns_not_found() {
         let result = ExecutionResult {
             exit_code: 1,
             stdout: String::new(),
             stderr: "cat: /src/missing: No such file or directory".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
         let error = read_in_sandbox(&provider, &stub_metadata(), "missing", None, None)
@@ -1826,24 +5356,346 @@ impl SandboxProvider for TestProvider {
         }
     }
 
+    #[tokio::test]
+    async fn head_in_sandbox_runs_head() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "one\ntwo\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let content = head_in_sandbox(&provider, &stub_metadata(), "README.md", 2)
+            .await
+            .expect("head");
+
+        assert_eq!(content, "one\ntwo\n");
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "head -n 2 -- '/src/README.md'");
+    }
+
+    #[tokio::test]
+    async fn tail_in_sandbox_missing_file_returns_not_found() {
+        let result = ExecutionResult {
+            exit_code: 1,
+            stdout: String::new(),
+            stderr: "tail: cannot open '/src/missing' for reading: No such file or directory"
+                .to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let error = tail_in_sandbox(&provider, &stub_metadata(), "missing", 3, false)
+            .await
+            .expect_err("missing file");
+        match error {
+            ReadError::NotFound { path } => assert_eq!(path, "/src/missing"),
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn tail_in_sandbox_follow_accepts_timeout() {
+        let result = ExecutionResult {
+            exit_code: 124,
+            stdout: "last\nappended\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let content = tail_in_sandbox(&provider, &stub_metadata(), "app.log", 1, true)
+            .await
+            .expect("tail");
+
+        assert_eq!(content, "last\nappended\n");
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "timeout 5s tail -n 1 -f -- '/src/app.log'");
+    }
+
+    #[tokio::test]
+    async fn wc_in_sandbox_parses_counts() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "  3  10  55 /src/README.md\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let counts = wc_in_sandbox(&provider, &stub_metadata(), "README.md")
+            .await
+            .expect("wc");
+
+        assert_eq!(
+            counts,
+            WcResult {
+                lines: 3,
+                words: 10,
+                bytes: 55,
+            }
+        );
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "wc -lwc -- '/src/README.md'");
+    }
+
+    #[tokio::test]
+    async fn wc_in_sandbox_missing_file_returns_not_found() {
+        let result = ExecutionResult {
+            exit_code: 1,
+            stdout: String::new(),
+            stderr: "wc: /src/missing: No such file or directory".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let error = wc_in_sandbox(&provider, &stub_metadata(), "missing")
+            .await
+            .expect_err("missing file");
+        match error {
+            ReadError::NotFound { path } => assert_eq!(path, "/src/missing"),
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ln_in_sandbox_creates_symbolic_link() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        ln_in_sandbox(&provider, &stub_metadata(), "../lib/cli.js", "bin/cli", true)
+            .await
+            .expect("ln");
+
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "ln -s -- '../lib/cli.js' '/src/bin/cli'");
+    }
+
+    #[tokio::test]
+    async fn ln_in_sandbox_hard_link_resolves_target() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        ln_in_sandbox(&provider, &stub_metadata(), "a.txt", "b.txt", false)
+            .await
+            .expect("ln");
+
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "ln -- '/src/a.txt' '/src/b.txt'");
+    }
+
+    #[tokio::test]
+    async fn ln_in_sandbox_existing_link_returns_already_exists() {
+        let result = ExecutionResult {
+            exit_code: 1,
+            stdout: String::new(),
+            stderr: "ln: failed to create symbolic link '/src/bin/cli': File exists".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let error = ln_in_sandbox(&provider, &stub_metadata(), "cli.js", "bin/cli", true)
+            .await
+            .expect_err("existing link");
+        match error {
+            LnError::AlreadyExists { path } => assert_eq!(path, "/src/bin/cli"),
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
     #[tokio::test]
     async fn write_in_sandbox_success() {
         let result = ExecutionResult {
             exit_code: 0,
             stdout: String::new(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        write_in_sandbox(&provider, &stub_metadata(), "file.txt", "hello", WriteMode::Overwrite)
+            .await
+            .expect("write");
+
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "cat > '/src/file.txt'");
+        let stdin = provider.last_stdin.lock().expect("stdin lock");
+        assert_eq!(stdin.as_deref(), Some(&b"hello"[..]));
+    }
+
+    #[tokio::test]
+    async fn write_in_sandbox_accepts_control_characters_in_content() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let content = "\x1b[1mbold\x1b[0m\x0cpage";
+        write_in_sandbox(&provider, &stub_metadata(), "file.txt", content, WriteMode::Overwrite)
+            .await
+            .expect("write");
+
+        let stdin = provider.last_stdin.lock().expect("stdin lock");
+        assert_eq!(stdin.as_deref(), Some(content.as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn write_in_sandbox_append_redirects() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let last_command = Arc::new(Mutex::new(None));
         let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
-        write_in_sandbox(&provider, &stub_metadata(), "file.txt", "hello")
+        write_in_sandbox(&provider, &stub_metadata(), "file.txt", "more", WriteMode::Append)
             .await
-            .expect("write");
+            .expect("append");
 
         let command = last_command.lock().expect("command lock");
         let command = command.as_ref().expect("command captured");
-        assert!(command[2].contains("printf %s"));
-        assert!(command[2].contains("'hello'"));
-        assert!(command[2].contains("/src/file.txt"));
+        assert_eq!(command[2], "cat >> '/src/file.txt'");
+    }
+
+    #[tokio::test]
+    async fn write_in_sandbox_prepend_reads_existing_content() {
+        let read_result = ExecutionResult {
+            exit_code: 0,
+            stdout: "body\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let write_result = ExecutionResult {
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let results = Arc::new(Mutex::new(vec![Ok(read_result), Ok(write_result)]));
+        let provider = MultiResultProvider::new(Arc::clone(&results));
+        write_in_sandbox(&provider, &stub_metadata(), "file.txt", "header\n", WriteMode::Prepend)
+            .await
+            .expect("prepend");
+
+        assert!(results.lock().expect("results lock").is_empty());
+    }
+
+    #[test]
+    fn stage_binary_file_round_trips_nul_and_high_bytes() {
+        let bytes = vec![0x00, 0xff, 0x80, b'a', 0x00, 0xc3, 0x28, 0xfe];
+        let encoded = BASE64.encode(&bytes);
+        let decoded = BASE64.decode(encoded).expect("decode");
+
+        let (_staging_dir, staged_file, dest_dir) =
+            stage_binary_file("/src/assets/logo.png", &decoded).expect("stage");
+
+        assert_eq!(fs::read(&staged_file).expect("read staged"), bytes);
+        assert_eq!(staged_file.file_name().expect("file name"), "logo.png");
+        assert_eq!(dest_dir, "/src/assets");
+    }
+
+    fn multi_write_file(path: &str, content: &str) -> MultiWriteFile {
+        MultiWriteFile {
+            path: path.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn stage_files_uses_common_directory() {
+        let files = vec![
+            multi_write_file("src/module.rs", "pub fn f() {}"),
+            multi_write_file("src/module/tests.rs", "#[test] fn t() {}"),
+            multi_write_file("/src/README.md", "readme"),
+        ];
+
+        let (staging_dir, dest_dir) = stage_files(DEFAULT_WORKDIR, &files).expect("stage");
+
+        assert_eq!(dest_dir, "/src");
+        let read = |relative: &str| {
+            fs::read_to_string(staging_dir.path().join(relative)).expect("read")
+        };
+        assert_eq!(read("src/module.rs"), "pub fn f() {}");
+        assert_eq!(read("src/module/tests.rs"), "#[test] fn t() {}");
+        assert_eq!(read("README.md"), "readme");
+    }
+
+    #[test]
+    fn stage_files_single_file_stages_from_the_workdir() {
+        let files = vec![multi_write_file("new/dir/guide.md", "guide")];
+        let (staging_dir, dest_dir) = stage_files(DEFAULT_WORKDIR, &files).expect("stage");
+        assert_eq!(dest_dir, "/src");
+        assert!(staging_dir.path().join("new/dir/guide.md").is_file());
+    }
+
+    #[test]
+    fn stage_files_outside_the_workdir_uses_common_ancestor() {
+        let files = vec![multi_write_file("/tmp/notes.txt", "notes")];
+        let (staging_dir, dest_dir) = stage_files(DEFAULT_WORKDIR, &files).expect("stage");
+        assert_eq!(dest_dir, "/");
+        assert!(staging_dir.path().join("tmp/notes.txt").is_file());
+    }
+
+    #[test]
+    fn stage_files_rejects_invalid_paths() {
+        for files in [
+            vec![multi_write_file("../escape.txt", "x")],
+            vec![multi_write_file("dir/", "x")],
+            vec![multi_write_file("a.txt", "1"), multi_write_file("/src/a.txt", "2")],
+        ] {
+            match stage_files(DEFAULT_WORKDIR, &files) {
+                Err(WriteError::InvalidPath { .. }) => {}
+                other => panic!("unexpected result: {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn write_binary_in_sandbox_rejects_invalid_base64() {
+        let provider = TestProvider::new(
+            Err(SandboxError::SandboxNotFound {
+                name: "unused".to_string(),
+            }),
+            Arc::new(Mutex::new(None)),
+        );
+        let error = write_binary_in_sandbox(&provider, &stub_metadata(), "file.bin", "not base64!")
+            .await
+            .expect_err("invalid base64");
+        match error {
+            WriteError::InvalidBase64 { .. } => {}
+            other => panic!("unexpected error: {other:?}"),
+        }
     }
 
     #[tokio::test]
@@ -1852,9 +5704,11 @@ impl SandboxProvider for TestProvider {
             exit_code: 1,
             stdout: String::new(),
             stderr: "/src/file.txt: Permission denied".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
-        let error = write_in_sandbox(&provider, &stub_metadata(), "file.txt", "hello")
+        let error = write_in_sandbox(&provider, &stub_metadata(), "file.txt", "hello", WriteMode::Overwrite)
             .await
             .expect_err("permission denied");
         match error {
@@ -1869,9 +5723,17 @@ impl SandboxProvider for TestProvider {
             exit_code: 1,
             stdout: String::new(),
             stderr: "sh: /src/missing/file.txt: No such file or directory".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
-        let error = write_in_sandbox(&provider, &stub_metadata(), "missing/file.txt", "hello")
+        let error = write_in_sandbox(
+            &provider,
+            &stub_metadata(),
+            "missing/file.txt",
+            "hello",
+            WriteMode::Overwrite,
+        )
             .await
             .expect_err("missing path");
         match error {
@@ -1887,12 +5749,16 @@ impl SandboxProvider for TestProvider {
             exit_code: 0,
             stdout: "original\n".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         // Mock write succeeding
         let write_result = ExecutionResult {
             exit_code: 0,
             stdout: String::new(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
 
         let results = Arc::new(Mutex::new(vec![Ok(read_result), Ok(write_result)]));
@@ -1903,6 +5769,27 @@ impl SandboxProvider for TestProvider {
             .expect("patch");
     }
 
+    #[tokio::test]
+    async fn patched_content_in_sandbox_does_not_write() {
+        let read_result = ExecutionResult {
+            exit_code: 0,
+            stdout: "original\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+
+        // Only the read is queued, so any write would fail the check.
+        let results = Arc::new(Mutex::new(vec![Ok(read_result)]));
+        let provider = MultiResultProvider::new(results);
+        let diff = "--- a/file.txt\n+++ b/file.txt\n@@ -1 +1 @@\n-original\n+patched\n";
+        let patched = patched_content_in_sandbox(&provider, &stub_metadata(), "file.txt", diff)
+            .await
+            .expect("patch check");
+
+        assert_eq!(patched, "patched\n");
+    }
+
     #[tokio::test]
     async fn patch_in_sandbox_invalid_diff() {
         // Mock read returning content
@@ -1910,6 +5797,8 @@ impl SandboxProvider for TestProvider {
             exit_code: 0,
             stdout: "line1\nline2\n".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
 
         // The patch will fail to apply because it tries to replace text that doesn't exist
@@ -1936,6 +5825,8 @@ impl SandboxProvider for TestProvider {
             exit_code: 1,
             stdout: String::new(),
             stderr: "cat: /src/missing.txt: No such file or directory".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
 
         let results = Arc::new(Mutex::new(vec![Ok(read_result)]));
@@ -1961,6 +5852,8 @@ impl SandboxProvider for TestProvider {
             exit_code: 0,
             stdout: "file.txt\nsubdir\n".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let last_command = Arc::new(Mutex::new(None));
         let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
@@ -1981,6 +5874,8 @@ impl SandboxProvider for TestProvider {
             exit_code: 0,
             stdout: "/src/dir/subdir\n/src/dir/subdir/child.txt\n/src/dir/file.txt\n".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let last_command = Arc::new(Mutex::new(None));
         let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
@@ -1996,12 +5891,46 @@ impl SandboxProvider for TestProvider {
         assert!(command[2].contains("/src/dir"));
     }
 
+    #[test]
+    fn paginate_entries_walks_pages_with_cursor() {
+        let entries: Vec<String> = ["a", "b", "c", "d", "e"].map(String::from).to_vec();
+
+        let first = paginate_entries(entries.clone(), String::as_str, None, 2);
+        assert_eq!(first.entries, vec!["a", "b"]);
+        let cursor = first.next_cursor.expect("next cursor");
+        let after = decode_ls_cursor(&cursor).expect("decode cursor");
+        assert_eq!(after, "b");
+
+        let second = paginate_entries(entries.clone(), String::as_str, Some(&after), 2);
+        assert_eq!(second.entries, vec!["c", "d"]);
+        let after = decode_ls_cursor(&second.next_cursor.expect("next cursor")).expect("decode");
+
+        let last = paginate_entries(entries, String::as_str, Some(&after), 2);
+        assert_eq!(last.entries, vec!["e"]);
+        assert!(last.next_cursor.is_none());
+    }
+
+    #[test]
+    fn paginate_entries_exact_page_has_no_cursor() {
+        let entries: Vec<String> = ["a", "b"].map(String::from).to_vec();
+        let page = paginate_entries(entries, String::as_str, None, 2);
+        assert_eq!(page.entries, vec!["a", "b"]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn decode_ls_cursor_rejects_garbage() {
+        assert!(decode_ls_cursor("not base64!").is_err());
+    }
+
     #[tokio::test]
     async fn ls_in_sandbox_empty_directory() {
         let result = ExecutionResult {
             exit_code: 0,
             stdout: String::new(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
         let entries = ls_in_sandbox(&provider, &stub_metadata(), "empty", false)
@@ -2011,12 +5940,114 @@ impl SandboxProvider for TestProvider {
         assert!(entries.is_empty());
     }
 
+    #[tokio::test]
+    async fn ls_metadata_in_sandbox_parses_long_listing() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "total 16\n\
+                     drwxr-xr-x 3 root root 4096 Jan  2 10:00 .\n\
+                     drwxr-xr-x 5 root root 4096 Jan  2 09:00 ..\n\
+                     -rw-r--r-- 1 root root  120 Dec 31  2024 notes file.txt\n\
+                     drwxr-xr-x 2 root root 4096 Jan  2 10:00 subdir\n\
+                     lrwxrwxrwx 1 root root    8 Jan  2 10:01 link -> subdir\n\
+                     crw-rw-rw- 1 root root 1, 3 Jan  2 10:02 null\n"
+                .to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let entries = ls_metadata_in_sandbox(&provider, &stub_metadata(), "dir")
+            .await
+            .expect("list");
+
+        assert_eq!(
+            entries,
+            vec![
+                DirEntry {
+                    name: "notes file.txt".to_string(),
+                    size: 120,
+                    mode: "-rw-r--r--".to_string(),
+                    modified: "Dec 31 2024".to_string(),
+                    is_dir: false,
+                    is_symlink: false,
+                },
+                DirEntry {
+                    name: "subdir".to_string(),
+                    size: 4096,
+                    mode: "drwxr-xr-x".to_string(),
+                    modified: "Jan 2 10:00".to_string(),
+                    is_dir: true,
+                    is_symlink: false,
+                },
+                DirEntry {
+                    name: "link".to_string(),
+                    size: 8,
+                    mode: "lrwxrwxrwx".to_string(),
+                    modified: "Jan 2 10:01".to_string(),
+                    is_dir: false,
+                    is_symlink: true,
+                },
+                DirEntry {
+                    name: "null".to_string(),
+                    size: 0,
+                    mode: "crw-rw-rw-".to_string(),
+                    modified: "Jan 2 10:02".to_string(),
+                    is_dir: false,
+                    is_symlink: false,
+                },
+            ]
+        );
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "ls -la '/src/dir'");
+    }
+
+    #[tokio::test]
+    async fn tree_in_sandbox_formats_nested_paths() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "/src/dir/src\n/src/dir/src/main.rs\n/src/dir/Cargo.toml\n/src/dir/src/lib\n\
+                     /src/dir/src/lib/mod.rs\n"
+                .to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let tree = tree_in_sandbox(&provider, &stub_metadata(), "dir", Some(2), false)
+            .await
+            .expect("tree");
+
+        let expected = [
+            "/src/dir",
+            "├── Cargo.toml",
+            "└── src",
+            "    ├── lib",
+            "    │   └── mod.rs",
+            "    └── main.rs",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(tree, expected);
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(
+            command[2],
+            "find '/src/dir' -mindepth 1 -maxdepth 2 -name '.*' -prune -o -print"
+        );
+    }
+
     #[test]
     fn classify_ls_failure_permission_denied() {
         let result = ExecutionResult {
             exit_code: 1,
             stdout: String::new(),
             stderr: "ls: /src/secret: Permission denied".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let error = classify_ls_failure("/src/secret", &result);
         match error {
@@ -2031,6 +6062,8 @@ impl SandboxProvider for TestProvider {
             exit_code: 1,
             stdout: String::new(),
             stderr: "ls: /src/missing: No such file or directory".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let error = classify_ls_failure("/src/missing", &result);
         match error {
@@ -2056,10 +6089,12 @@ impl SandboxProvider for TestProvider {
             exit_code: 0,
             stdout: "/src/dir/subdir\n/src/dir/subdir/child.txt\n/src/dir/root.txt\n".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let last_command = Arc::new(Mutex::new(None));
         let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
-        let entries = glob_in_sandbox(&provider, &stub_metadata(), "**/*.txt", Some("dir"))
+        let entries = glob_in_sandbox(&provider, &stub_metadata(), "**/*.txt", Some("dir"), &[])
             .await
             .expect("glob");
 
@@ -2070,15 +6105,35 @@ impl SandboxProvider for TestProvider {
         assert!(command[2].contains("/src/dir"));
     }
 
+    #[tokio::test]
+    async fn glob_in_sandbox_applies_exclusions() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "/src/src/main.rs\n/src/target/debug/build.rs\n/src/tests/it.rs\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let exclude = vec!["target/**".to_string(), "/src/tests/*".to_string()];
+        let entries = glob_in_sandbox(&provider, &stub_metadata(), "**/*.rs", None, &exclude)
+            .await
+            .expect("glob");
+
+        assert_eq!(entries, vec!["src/main.rs"]);
+    }
+
     #[tokio::test]
     async fn glob_in_sandbox_no_matches() {
         let result = ExecutionResult {
             exit_code: 0,
             stdout: "/src/root.txt\n".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
-        let entries = glob_in_sandbox(&provider, &stub_metadata(), "*.md", None)
+        let entries = glob_in_sandbox(&provider, &stub_metadata(), "*.md", None, &[])
             .await
             .expect("glob");
 
@@ -2086,32 +6141,332 @@ impl SandboxProvider for TestProvider {
     }
 
     #[tokio::test]
-    async fn glob_in_sandbox_invalid_pattern() {
+    async fn glob_in_sandbox_invalid_pattern() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let error = glob_in_sandbox(&provider, &stub_metadata(), "[[", None, &[])
+            .await
+            .expect_err("invalid pattern");
+        match error {
+            GlobError::InvalidPattern { .. } => {}
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn env_in_sandbox_parses_entries() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "PATH=/usr/bin:/bin\nMULTI=one\ntwo\nEMPTY=\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let entries = env_in_sandbox(&provider, &stub_metadata(), None)
+            .await
+            .expect("env");
+
+        assert_eq!(
+            entries,
+            vec![
+                EnvEntry {
+                    key: "PATH".to_string(),
+                    value: "/usr/bin:/bin".to_string(),
+                },
+                EnvEntry {
+                    key: "MULTI".to_string(),
+                    value: "one\ntwo".to_string(),
+                },
+                EnvEntry {
+                    key: "EMPTY".to_string(),
+                    value: String::new(),
+                },
+            ]
+        );
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "env");
+    }
+
+    #[tokio::test]
+    async fn env_in_sandbox_filter_without_matches() {
+        let result = ExecutionResult {
+            exit_code: 1,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let entries = env_in_sandbox(&provider, &stub_metadata(), Some("CARGO"))
+            .await
+            .expect("env");
+
+        assert!(entries.is_empty());
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "env | grep -- 'CARGO'");
+    }
+
+    #[tokio::test]
+    async fn ps_in_sandbox_parses_procps_output() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "USER  PID %CPU %MEM    VSZ   RSS TTY STAT START TIME COMMAND\n\
+                     root    1  0.0  0.1   4364  3456 ?   Ss   10:00 0:00 sleep infinity\n\
+                     app    42  1.5  2.0 104364 23456 ?   Sl   10:01 0:03 node server.js --port 3000\n"
+                .to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let processes = ps_in_sandbox(&provider, &stub_metadata(), true)
+            .await
+            .expect("ps");
+
+        assert_eq!(
+            processes,
+            vec![
+                ProcessEntry {
+                    pid: 1,
+                    user: "root".to_string(),
+                    cpu: "0.0".to_string(),
+                    mem: "0.1".to_string(),
+                    command: "sleep infinity".to_string(),
+                },
+                ProcessEntry {
+                    pid: 42,
+                    user: "app".to_string(),
+                    cpu: "1.5".to_string(),
+                    mem: "2.0".to_string(),
+                    command: "node server.js --port 3000".to_string(),
+                },
+            ]
+        );
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "ps aux 2>/dev/null || ps");
+    }
+
+    #[test]
+    fn parse_ps_output_busybox_columns() {
+        let output = "PID   USER     TIME  COMMAND\n    1 root      0:00 sleep infinity\n";
+        let processes = parse_ps_output(output).expect("parse ps");
+
+        assert_eq!(
+            processes,
+            vec![ProcessEntry {
+                pid: 1,
+                user: "root".to_string(),
+                cpu: String::new(),
+                mem: String::new(),
+                command: "sleep infinity".to_string(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn ps_in_sandbox_non_zero_exit_fails() {
+        let result = ExecutionResult {
+            exit_code: 127,
+            stdout: String::new(),
+            stderr: "sh: ps: not found".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let error = ps_in_sandbox(&provider, &stub_metadata(), false)
+            .await
+            .expect_err("ps missing");
+        match error {
+            PsError::Failed { message } => assert_eq!(message, "sh: ps: not found"),
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn df_in_sandbox_parses_root_filesystem() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "Filesystem      Size  Used Avail Use% Mounted on\n\
+                     overlay          59G   21G   36G  37% /\n"
+                .to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let usage = df_in_sandbox(&provider, &stub_metadata())
+            .await
+            .expect("df");
+
+        assert_eq!(
+            usage,
+            DfResult {
+                filesystem: "overlay".to_string(),
+                size: "59G".to_string(),
+                used: "21G".to_string(),
+                available: "36G".to_string(),
+                use_percent: "37%".to_string(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn du_in_sandbox_parses_entries() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "12K\t/src/dir/sub dir\n20K\t/src/dir\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let entries = du_in_sandbox(&provider, &stub_metadata(), "dir", Some(1))
+            .await
+            .expect("du");
+
+        assert_eq!(
+            entries,
+            vec![
+                DuEntry {
+                    size: "12K".to_string(),
+                    path: "/src/dir/sub dir".to_string(),
+                },
+                DuEntry {
+                    size: "20K".to_string(),
+                    path: "/src/dir".to_string(),
+                },
+            ]
+        );
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command[2], "du -h -d 1 -- '/src/dir'");
+    }
+
+    #[tokio::test]
+    async fn du_in_sandbox_missing_path() {
+        let result = ExecutionResult {
+            exit_code: 1,
+            stdout: String::new(),
+            stderr: "du: cannot access '/src/missing': No such file or directory".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let error = du_in_sandbox(&provider, &stub_metadata(), "missing", None)
+            .await
+            .expect_err("missing path");
+        match error {
+            DiskUsageError::NotFound { path } => assert_eq!(path, "/src/missing"),
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn find_in_sandbox_applies_filters() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "/src/dir/b.rs\n/src/dir/a.rs\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let args = FindArgs {
+            sandbox: "sandbox".to_string(),
+            path: "dir".to_string(),
+            name: Some("*.rs".to_string()),
+            type_filter: Some("file".to_string()),
+            newer_than: Some("2h".to_string()),
+            older_than: Some("30".to_string()),
+            min_size: Some("1k".to_string()),
+            max_size: Some("2M".to_string()),
+        };
+        let entries = find_in_sandbox(&provider, &stub_metadata(), &args)
+            .await
+            .expect("find");
+
+        assert_eq!(entries, vec!["a.rs", "b.rs"]);
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(
+            command[2],
+            "find '/src/dir' -mindepth 1 -name '*.rs' -type f -mmin -120 -mmin +30 \
+             -size +1023c -size -2097153c -print"
+        );
+    }
+
+    #[tokio::test]
+    async fn find_in_sandbox_missing_path() {
         let result = ExecutionResult {
-            exit_code: 0,
+            exit_code: 1,
             stdout: String::new(),
-            stderr: String::new(),
+            stderr: "find: '/src/missing': No such file or directory".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
-        let error = glob_in_sandbox(&provider, &stub_metadata(), "[[", None)
+        let args = FindArgs {
+            path: "missing".to_string(),
+            ..FindArgs::default()
+        };
+        let error = find_in_sandbox(&provider, &stub_metadata(), &args)
             .await
-            .expect_err("invalid pattern");
+            .expect_err("missing path");
         match error {
-            GlobError::InvalidPattern { .. } => {}
+            GlobError::NotFound { path } => assert_eq!(path, "/src/missing"),
             other => panic!("unexpected error: {other:?}"),
         }
     }
 
+    #[test]
+    fn build_find_command_rejects_invalid_filters() {
+        let args = FindArgs {
+            type_filter: Some("socket".to_string()),
+            ..FindArgs::default()
+        };
+        assert!(matches!(
+            build_find_command("/src", &args),
+            Err(GlobError::InvalidPattern { .. })
+        ));
+
+        let args = FindArgs {
+            min_size: Some("big".to_string()),
+            ..FindArgs::default()
+        };
+        assert!(matches!(
+            build_find_command("/src", &args),
+            Err(GlobError::InvalidPattern { .. })
+        ));
+    }
+
     #[tokio::test]
     async fn grep_in_sandbox_matches() {
         let result = ExecutionResult {
             exit_code: 0,
             stdout: "/src/dir/file.txt:1:hello\n/src/dir/sub/file.rs:2:hello\n".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let last_command = Arc::new(Mutex::new(None));
         let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
-        let entries = grep_in_sandbox(&provider, &stub_metadata(), "hello", "dir", None)
+        let options = GrepOptions::default();
+        let entries = grep_in_sandbox(&provider, &stub_metadata(), "hello", "dir", &options)
             .await
             .expect("grep");
 
@@ -2133,10 +6488,16 @@ impl SandboxProvider for TestProvider {
             exit_code: 0,
             stdout: "/src/dir/main.rs:1:hello\n".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let last_command = Arc::new(Mutex::new(None));
         let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
-        let entries = grep_in_sandbox(&provider, &stub_metadata(), "hello", "dir", Some("*.rs"))
+        let options = GrepOptions {
+            include: Some("*.rs".to_string()),
+            ..GrepOptions::default()
+        };
+        let entries = grep_in_sandbox(&provider, &stub_metadata(), "hello", "dir", &options)
             .await
             .expect("grep");
 
@@ -2147,15 +6508,56 @@ impl SandboxProvider for TestProvider {
         assert!(command[2].contains("*.rs"));
     }
 
+    #[tokio::test]
+    async fn grep_in_sandbox_limits_and_context() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "/src/dir/a.rs-1-before\n/src/dir/a.rs:2:a.b\n--\n/src/dir/a.rs:9:a.b\n"
+                .to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let options = GrepOptions {
+            max_results: Some(2),
+            context_lines: Some(1),
+            fixed_string: true,
+            ..GrepOptions::default()
+        };
+        let entries = grep_in_sandbox(&provider, &stub_metadata(), "a.b", "dir", &options)
+            .await
+            .expect("grep");
+
+        assert_eq!(
+            entries,
+            vec![
+                "/src/dir/a.rs-1-before",
+                "/src/dir/a.rs:2:a.b",
+                "/src/dir/a.rs:9:a.b"
+            ]
+        );
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(
+            command[2],
+            "grep -R -n -F -m 2 -C 1 -- 'a.b' '/src/dir'"
+        );
+    }
+
     #[tokio::test]
     async fn grep_in_sandbox_no_matches() {
         let result = ExecutionResult {
             exit_code: 1,
             stdout: String::new(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
-        let entries = grep_in_sandbox(&provider, &stub_metadata(), "hello", "dir", None)
+        let options = GrepOptions::default();
+        let entries = grep_in_sandbox(&provider, &stub_metadata(), "hello", "dir", &options)
             .await
             .expect("grep");
 
@@ -2168,9 +6570,12 @@ impl SandboxProvider for TestProvider {
             exit_code: 2,
             stdout: String::new(),
             stderr: "grep: Unmatched [".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
-        let error = grep_in_sandbox(&provider, &stub_metadata(), "[", "dir", None)
+        let options = GrepOptions::default();
+        let error = grep_in_sandbox(&provider, &stub_metadata(), "[", "dir", &options)
             .await
             .expect_err("invalid pattern");
         match error {
@@ -2185,9 +6590,12 @@ impl SandboxProvider for TestProvider {
             exit_code: 2,
             stdout: String::new(),
             stderr: "grep: /src/dir: No such file or directory".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
-        let error = grep_in_sandbox(&provider, &stub_metadata(), "hello", "dir", None)
+        let options = GrepOptions::default();
+        let error = grep_in_sandbox(&provider, &stub_metadata(), "hello", "dir", &options)
             .await
             .expect_err("missing path");
         match error {
@@ -2224,6 +6632,8 @@ impl SandboxProvider for TestProvider {
             exit_code: 1,
             stdout: String::new(),
             stderr: "cat: /src/secret: Permission denied".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let error = classify_read_failure("/src/secret", &result);
         match error {
@@ -2238,6 +6648,8 @@ impl SandboxProvider for TestProvider {
             exit_code: 1,
             stdout: String::new(),
             stderr: "cat: /src/dir: Is a directory".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let error = classify_read_failure("/src/dir", &result);
         match error {
@@ -2289,10 +6701,12 @@ impl SandboxProvider for TestProvider {
             exit_code: 0,
             stdout: "ok".to_string(),
             stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let last_command = Arc::new(Mutex::new(None));
         let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
-        let output = bash_in_sandbox(&provider, &stub_metadata(), "echo ok", None, None)
+        let output = bash_in_sandbox(&provider, &stub_metadata(), "echo ok", None, None, &ExecOptions::default())
             .await
             .expect("bash");
 
@@ -2304,15 +6718,137 @@ impl SandboxProvider for TestProvider {
         assert!(command[2].contains("echo ok"));
     }
 
+    #[tokio::test]
+    async fn git_in_sandbox_runs_git_in_the_workdir() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "main".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let args = ["branch".to_string(), "--show-current".to_string()];
+
+        let output = git_in_sandbox(&provider, &stub_metadata(), &args, Some("web"))
+            .await
+            .expect("git");
+
+        assert_eq!(output.stdout, "main");
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert_eq!(command, &["git", "-C", "/src/web", "branch", "--show-current"]);
+    }
+
+    #[tokio::test]
+    async fn git_in_sandbox_reports_missing_repository() {
+        let result = ExecutionResult {
+            exit_code: 128,
+            stdout: String::new(),
+            stderr: "fatal: not a git repository (or any of the parent directories): .git\n"
+                .to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let args = ["log".to_string()];
+
+        let error = git_in_sandbox(&provider, &stub_metadata(), &args, None)
+            .await
+            .expect_err("no repository");
+
+        assert!(error.to_string().contains("/src in the sandbox is not a git repository"));
+    }
+
+    #[tokio::test]
+    async fn git_in_sandbox_reports_missing_repository_in_container()
+    -> Result<(), Box<dyn std::error::Error>> {
+        if std::env::var("LITTERBOX_DOCKER_TESTS").is_err() {
+            return Ok(());
+        }
+
+        let (tempdir, _repo) = init_repo();
+        let scm = ThreadSafeScm::open(tempdir.path())?;
+        let provider = DockerSandboxProvider::new(scm, DockerCompute::connect()?);
+        let name = format!("git-{}", std::process::id());
+        let metadata = provider
+            .create(
+                &name,
+                &SandboxConfig {
+                    image: "alpine:latest".to_string(),
+                    setup_command: Some("apk add --no-cache git".to_string()),
+                    setup_timeout_secs: None,
+                    forwarded_ports: Vec::new(),
+                    port_range: None,
+                    ready_command: None,
+                    from_ref: None,
+                    restart_policy: None,
+                    extra_env: Vec::new(),
+                    network: None,
+                },
+            )
+            .await?;
+
+        let result = git_in_sandbox(&provider, &metadata, &["status".to_string()], None).await;
+        let _ = provider.delete(&metadata).await;
+
+        let error = result.expect_err("sandbox has no repository");
+        assert!(error.to_string().contains("is not a git repository"));
+        Ok(())
+    }
+
+    #[test]
+    fn dangerous_git_operations_are_detected() {
+        let operation = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            dangerous_git_operation(&args)
+        };
+
+        assert_eq!(operation(&["push", "--force"]), Some("push --force"));
+        assert_eq!(operation(&["push", "-uf", "origin", "main"]), Some("push --force"));
+        assert_eq!(operation(&["push", "--force-with-lease=main"]), Some("push --force"));
+        assert_eq!(operation(&["push", "origin", "+main"]), Some("push --force"));
+        assert_eq!(operation(&["-C", "push", "clone", "url"]), Some("clone"));
+        assert_eq!(operation(&["remote", "add", "up", "url"]), Some("remote add"));
+        assert_eq!(operation(&["push", "origin", "main"]), None);
+        assert_eq!(operation(&["remote", "-v"]), None);
+        assert_eq!(operation(&["-c", "user.name=clone", "commit", "-m", "add"]), None);
+        assert_eq!(operation(&[]), None);
+
+        // Aliases could otherwise run any of the above under another name.
+        assert_eq!(
+            operation(&["-c", "alias.x=clone", "x", "url"]),
+            Some("alias definition")
+        );
+        assert_eq!(
+            operation(&["-c", "Alias.p=push --force", "p"]),
+            Some("alias definition")
+        );
+        assert_eq!(operation(&["--config-env", "alias.p=CMD", "p"]), Some("alias definition"));
+        assert_eq!(operation(&["--config-env=alias.p=CMD", "p"]), Some("alias definition"));
+        assert_eq!(
+            operation(&["config", "--local", "alias.p", "push --force"]),
+            Some("alias definition")
+        );
+        assert_eq!(
+            operation(&["config", "set", "alias.p", "push --force"]),
+            Some("alias definition")
+        );
+        assert_eq!(operation(&["config", "user.name", "alias.x"]), None);
+    }
+
     #[tokio::test]
     async fn bash_in_sandbox_non_zero_exit() {
         let result = ExecutionResult {
             exit_code: 2,
             stdout: String::new(),
             stderr: "fail".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
         };
         let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
-        let output = bash_in_sandbox(&provider, &stub_metadata(), "false", None, None)
+        let output = bash_in_sandbox(&provider, &stub_metadata(), "false", None, None, &ExecOptions::default())
             .await
             .expect("bash");
 
@@ -2320,37 +6856,212 @@ impl SandboxProvider for TestProvider {
         assert_eq!(output.stderr, "fail");
     }
 
+    #[tokio::test]
+    async fn bash_in_sandbox_passes_stdin() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "hello\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let options = ExecOptions {
+            stdin: Some(b"hello\n".to_vec()),
+            ..ExecOptions::default()
+        };
+        let output = bash_in_sandbox(&provider, &stub_metadata(), "cat", None, None, &options)
+            .await
+            .expect("bash");
+
+        assert_eq!(output.stdout, "hello\n");
+        let command = last_command.lock().expect("command lock");
+        assert_eq!(command.as_ref().expect("command captured")[2], "cat");
+        let stdin = provider.last_stdin.lock().expect("stdin lock");
+        assert_eq!(stdin.as_deref(), Some(&b"hello\n"[..]));
+    }
+
     #[test]
     fn build_bash_command_with_workdir() {
-        let command = build_bash_command("ls", Some("dir"), None);
+        let command = build_bash_command("ls", Some("/src/dir"), None).expect("command");
         assert!(command.contains("cd '/src/dir'"));
         assert!(command.contains("&& ls"));
     }
 
     #[test]
     fn build_bash_command_with_timeout() {
-        let command = build_bash_command("sleep 5", None, Some(3));
+        let command = build_bash_command("sleep 5", None, Some(3)).expect("command");
         assert!(command.starts_with("timeout 3s sh -c"));
         assert!(command.contains("sleep 5"));
     }
 
     #[test]
     fn build_bash_command_with_workdir_and_timeout() {
-        let command = build_bash_command("ls -la", Some("dir"), Some(5));
+        let command = build_bash_command("ls -la", Some("/src/dir"), Some(5)).expect("command");
         assert!(command.starts_with("timeout 5s sh -c"));
         assert!(command.contains("/src/dir"));
         assert!(command.contains("ls -la"));
     }
 
+    #[test]
+    fn bash_env_accepts_map_and_list() {
+        let map: BashArgs = serde_json::from_value(serde_json::json!({
+            "sandbox": "feature",
+            "command": "env",
+            "env": {"GREETING": "it's me", "A": "1"},
+        }))
+        .expect("map");
+        let list: BashArgs = serde_json::from_value(serde_json::json!({
+            "sandbox": "feature",
+            "command": "env",
+            "env": ["GREETING=it's me", "A=1"],
+        }))
+        .expect("list");
+
+        let expected = ["A=1", "GREETING=it's me"];
+        assert_eq!(map.env.expect("env").assignments(), expected);
+        assert_eq!(list.env.expect("env").assignments(), expected);
+    }
+
+    #[test]
+    fn validate_env_assignments_rejects_invalid_names() {
+        assert!(validate_env_assignments(&["_PATH2=".to_string()]).is_ok());
+        for assignment in ["=1", "1ABC=1", "A-B=1", "A B=1", "A;rm=1", "NOVALUE"] {
+            let env = [assignment.to_string()];
+            assert!(validate_env_assignments(&env).is_err(), "{assignment} accepted");
+        }
+    }
+
     #[test]
     fn resolve_container_path_relative() {
-        assert_eq!(resolve_container_path("README.md"), "/src/README.md");
-        assert_eq!(resolve_container_path("/etc/hosts"), "/etc/hosts");
+        assert_eq!(resolve_container_path(DEFAULT_WORKDIR, "README.md"), "/src/README.md");
+        assert_eq!(resolve_container_path(DEFAULT_WORKDIR, "/etc/hosts"), "/etc/hosts");
+        assert_eq!(resolve_container_path("/workspace", "README.md"), "/workspace/README.md");
     }
 
     #[test]
     fn shell_escape_handles_quotes() {
-        assert_eq!(shell_escape("a'b"), "'a'\"'\"'b'");
+        assert_eq!(shell_escape("a'b").expect("escape"), "'a'\"'\"'b'");
+    }
+
+    #[test]
+    fn shell_escape_rejects_control_characters() {
+        for value in ["a\0b", "\u{1b}[31mred", "bell\u{7}", "del\u{7f}"] {
+            let error = shell_escape(value).expect_err("control character accepted");
+            assert!(matches!(error, SandboxError::UnsafeShellArgument { .. }));
+        }
+    }
+
+    #[test]
+    fn shell_escape_allows_whitespace_and_unicode() {
+        assert_eq!(shell_escape("a\tb\r\nc").expect("escape"), "'a\tb\r\nc'");
+        assert_eq!(shell_escape("héllo 日本 🚀").expect("escape"), "'héllo 日本 🚀'");
+    }
+
+    #[tokio::test]
+    async fn search_replace_in_sandbox_reports_counts() {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: "2 5\n".to_string(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let last_command = Arc::new(Mutex::new(None));
+        let provider = TestProvider::new(Ok(result), Arc::clone(&last_command));
+        let result = search_replace_in_sandbox(
+            &provider,
+            &stub_metadata(),
+            "old_name",
+            "new_name",
+            "src",
+            false,
+            false,
+        )
+        .await
+        .expect("search-replace");
+
+        assert_eq!(
+            result,
+            SearchReplaceResult {
+                files_modified: 2,
+                total_replacements: 5,
+            }
+        );
+        let command = last_command.lock().expect("command lock");
+        let command = command.as_ref().expect("command captured");
+        assert!(command[2].contains("grep -rlI -F -e 'old_name' -- '/src/src'"));
+        assert!(command[2].contains("sed -i -e 's/old_name/new_name/g'"));
+    }
+
+    #[tokio::test]
+    async fn search_replace_in_sandbox_missing_path() {
+        let result = ExecutionResult {
+            exit_code: 2,
+            stdout: String::new(),
+            stderr: "/src/missing: No such file or directory".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let error = search_replace_in_sandbox(
+            &provider,
+            &stub_metadata(),
+            "a",
+            "b",
+            "missing",
+            false,
+            false,
+        )
+        .await
+        .expect_err("missing path");
+        match error {
+            SearchReplaceError::NotFound { path } => assert_eq!(path, "/src/missing"),
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn search_replace_in_sandbox_invalid_pattern() {
+        let result = ExecutionResult {
+            exit_code: 2,
+            stdout: String::new(),
+            stderr: "grep: Unmatched ( or \\(".to_string(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        let provider = TestProvider::new(Ok(result), Arc::new(Mutex::new(None)));
+        let error = search_replace_in_sandbox(
+            &provider,
+            &stub_metadata(),
+            "(",
+            "b",
+            "src",
+            true,
+            false,
+        )
+        .await
+        .expect_err("invalid pattern");
+        match error {
+            SearchReplaceError::InvalidPattern { .. } => {}
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn build_search_replace_command_regex_case_insensitive() {
+        let command = build_search_replace_command("fo+/(x)", "\\1/y", "/src", true, true)
+            .expect("command");
+        assert!(command.contains("grep -rlI -E -i -e 'fo+/(x)'"));
+        assert!(command.contains("sed -i -E -e 's/fo+\\/(x)/\\1\\/y/gI'"));
+    }
+
+    #[test]
+    fn sed_escaping_for_literals() {
+        assert_eq!(escape_sed_literal_pattern("a.b*[c]/$^\\"), "a\\.b\\*\\[c\\]\\/\\$\\^\\\\");
+        assert_eq!(escape_sed_literal_replacement("a&b/c\\"), "a\\&b\\/c\\\\");
+        assert_eq!(escape_sed_delimiter("a/b\\/c"), "a\\/b\\/c");
     }
 
     #[test]
@@ -2368,10 +7079,19 @@ impl SandboxProvider for TestProvider {
     fn snapshot_message_for_triggers() {
         assert_eq!(
             snapshot_message(&SnapshotTrigger::Write {
-                path: "README.md".to_string()
+                path: "README.md".to_string(),
+                mode: WriteMode::Overwrite,
             }),
             "write: README.md"
         );
+        assert_eq!(
+            snapshot_message(&SnapshotTrigger::Write {
+                path: "app.log".to_string(),
+                mode: WriteMode::Append,
+            }),
+            "write (append): app.log"
+        );
+        assert_eq!(snapshot_message(&SnapshotTrigger::Watch), "watch: files changed");
         assert_eq!(
             snapshot_message(&SnapshotTrigger::Patch {
                 path: "src/lib.rs".to_string()
@@ -2384,6 +7104,32 @@ impl SandboxProvider for TestProvider {
             }),
             "bash: cargo test"
         );
+        assert_eq!(
+            snapshot_message(&SnapshotTrigger::SearchReplace {
+                pattern: "foo".to_string(),
+                replacement: "bar".to_string(),
+                path: "src".to_string(),
+            }),
+            "search-replace: foo -> bar in src"
+        );
+        assert_eq!(
+            snapshot_message(&SnapshotTrigger::Manual {
+                message: Some("before refactor".to_string()),
+            }),
+            "manual: before refactor"
+        );
+        assert_eq!(
+            snapshot_message(&SnapshotTrigger::Manual { message: None }),
+            "manual snapshot"
+        );
+        assert_eq!(
+            snapshot_message(&SnapshotTrigger::Reset),
+            "reset: before restoring HEAD"
+        );
+        assert_eq!(
+            snapshot_message(&SnapshotTrigger::MultiWrite { count: 2 }),
+            "multi-write: 2 files"
+        );
     }
 
     #[test]
@@ -2391,8 +7137,10 @@ impl SandboxProvider for TestProvider {
         let scm = TestScm::new(false);
         snapshot_after_with_scm(
             &scm,
+            DEFAULT_WORKDIR,
             SnapshotTrigger::Write {
                 path: "a".to_string(),
+                mode: WriteMode::Overwrite,
             },
         )
         .expect("snapshot");
@@ -2405,6 +7153,7 @@ impl SandboxProvider for TestProvider {
         let scm = TestScm::new(true);
         snapshot_after_with_scm(
             &scm,
+            DEFAULT_WORKDIR,
             SnapshotTrigger::Patch {
                 path: "b".to_string(),
             },
@@ -2421,8 +7170,10 @@ impl SandboxProvider for TestProvider {
         let scm = ThreadSafeScm::open(tempdir.path()).expect("open scm");
         snapshot_after_with_scm(
             &scm,
+            DEFAULT_WORKDIR,
             SnapshotTrigger::Write {
                 path: "README.md".to_string(),
+                mode: WriteMode::Overwrite,
             },
         )
         .expect("snapshot");
@@ -2449,8 +7200,10 @@ impl SandboxProvider for TestProvider {
         let scm = ThreadSafeScm::open(tempdir.path()).expect("open scm");
         snapshot_after_with_scm(
             &scm,
+            DEFAULT_WORKDIR,
             SnapshotTrigger::Write {
                 path: "README.md".to_string(),
+                mode: WriteMode::Overwrite,
             },
         )
         .expect("snapshot");
@@ -2461,6 +7214,79 @@ impl SandboxProvider for TestProvider {
         }
     }
 
+    #[test]
+    fn snapshot_after_with_scm_ignores_changes_outside_touched_path() {
+        let (tempdir, repo) = init_repo();
+        repo.index().expect("index").write().expect("write index");
+        fs::write(tempdir.path().join("other.txt"), "from another tool").expect("write");
+        let scm = ThreadSafeScm::open(tempdir.path()).expect("open scm");
+        snapshot_after_with_scm(
+            &scm,
+            DEFAULT_WORKDIR,
+            SnapshotTrigger::Write {
+                path: "/src/README.md".to_string(),
+                mode: WriteMode::Overwrite,
+            },
+        )
+        .expect("snapshot");
+
+        assert!(repo.find_reference("refs/heads/litterbox-snapshots").is_err());
+    }
+
+    #[test]
+    fn snapshot_after_with_scm_commits_only_the_touched_path() {
+        let (tempdir, repo) = init_repo();
+        fs::write(tempdir.path().join("README.md"), "written").expect("write");
+        fs::write(tempdir.path().join("other.txt"), "from another tool").expect("write");
+        let scm = ThreadSafeScm::open(tempdir.path()).expect("open scm");
+        snapshot_after_with_scm(
+            &scm,
+            DEFAULT_WORKDIR,
+            SnapshotTrigger::Write {
+                path: "/src/README.md".to_string(),
+                mode: WriteMode::Overwrite,
+            },
+        )
+        .expect("snapshot");
+
+        let snapshot = repo
+            .find_reference("refs/heads/litterbox-snapshots")
+            .expect("snapshot ref")
+            .peel_to_tree()
+            .expect("tree");
+        let readme = snapshot.get_path(Path::new("README.md")).expect("README.md");
+        let blob = repo.find_blob(readme.id()).expect("blob");
+        assert_eq!(blob.content(), b"written");
+        assert!(snapshot.get_path(Path::new("other.txt")).is_err());
+    }
+
+    #[test]
+    fn git_diff_for_path_adds_git_header() {
+        let hunk = "@@ -1 +1 @@\n-original\n+patched\n";
+        let expected = format!("diff --git a/a/b.txt b/a/b.txt\n--- a/a/b.txt\n+++ b/a/b.txt\n{hunk}");
+
+        assert_eq!(git_diff_for_path("a/b.txt", hunk).as_deref(), Some(expected.as_str()));
+        let with_headers = format!("--- b.txt\n+++ b.txt\n{hunk}");
+        assert_eq!(git_diff_for_path("a/b.txt", &with_headers), Some(expected));
+        assert_eq!(git_diff_for_path("a/b.txt", "--- a\n+++ b\n"), None);
+    }
+
+    #[test]
+    fn touched_path_is_relative_to_the_workdir() {
+        let write = |path: &str| SnapshotTrigger::Write {
+            path: path.to_string(),
+            mode: WriteMode::Overwrite,
+        };
+
+        assert_eq!(write("/src/a/b.rs").touched_path(DEFAULT_WORKDIR), Some("a/b.rs"));
+        assert_eq!(write("./a/b.rs").touched_path(DEFAULT_WORKDIR), Some("a/b.rs"));
+        assert_eq!(write("/etc/hosts").touched_path(DEFAULT_WORKDIR), None);
+        let bash = SnapshotTrigger::Bash {
+            command: "true".to_string(),
+        };
+        assert_eq!(bash.touched_path(DEFAULT_WORKDIR), None);
+    }
+
     #[test]
     fn end_to_end_snapshot_workflow() {
         let (tempdir, repo) = init_repo();
@@ -2469,8 +7295,10 @@ impl SandboxProvider for TestProvider {
         fs::write(tempdir.path().join("README.md"), "write").expect("write");
         snapshot_after_with_scm(
             &scm,
+            DEFAULT_WORKDIR,
             SnapshotTrigger::Write {
                 path: "README.md".to_string(),
+                mode: WriteMode::Overwrite,
             },
         )
         .expect("snapshot write");
@@ -2478,6 +7306,7 @@ impl SandboxProvider for TestProvider {
         fs::write(tempdir.path().join("README.md"), "patch").expect("write patch");
         snapshot_after_with_scm(
             &scm,
+            DEFAULT_WORKDIR,
             SnapshotTrigger::Patch {
                 path: "README.md".to_string(),
             },
@@ -2493,6 +7322,7 @@ impl SandboxProvider for TestProvider {
         assert!(status.success());
         snapshot_after_with_scm(
             &scm,
+            DEFAULT_WORKDIR,
             SnapshotTrigger::Bash {
                 command: "printf %s bash >>README.md".to_string(),
             },
@@ -2562,4 +7392,138 @@ impl SandboxProvider for TestProvider {
         let error = glob_entries("[[", dir.path()).expect_err("invalid pattern");
         assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
     }
+
+    #[test]
+    fn sandbox_config_for_create_applies_template() {
+        let config: crate::config::Config = toml::from_str(
+            r#"
+            [docker]
+            image = "base:latest"
+            setup-command = "make setup"
+
+            [[templates]]
+            name = "node"
+            image = "node:20"
+            extra-env = { NODE_ENV = "test" }
+            "#,
+        )
+        .expect("parse");
+        let args = |template: Option<&str>| SandboxCreateArgs {
+            name: "ticket-1".to_string(),
+            from_ref: Some("main".to_string()),
+            if_not_exists: None,
+            template: template.map(str::to_string),
+            network: None,
+        };
+
+        let sandbox_config =
+            sandbox_config_for_create(&config, &args(Some("node"))).expect("config");
+        assert_eq!(sandbox_config.image, "node:20");
+        assert_eq!(sandbox_config.setup_command.as_deref(), Some("make setup"));
+        assert_eq!(sandbox_config.extra_env, vec!["NODE_ENV=test".to_string()]);
+        assert_eq!(sandbox_config.from_ref.as_deref(), Some("main"));
+
+        let sandbox_config = sandbox_config_for_create(&config, &args(None)).expect("config");
+        assert_eq!(sandbox_config.image, "base:latest");
+        assert!(sandbox_config.extra_env.is_empty());
+
+        let error = sandbox_config_for_create(&config, &args(Some("rust"))).expect_err("unknown");
+        assert!(error.message.contains("Unknown template 'rust'. Configured templates: node."));
+    }
+
+    #[test]
+    fn map_error_embeds_error_code() {
+        let error = map_error(SandboxError::SandboxNotFound {
+            name: "feature".to_string(),
+        });
+
+        assert_eq!(
+            error.data,
+            Some(serde_json::json!({ "code": 1001, "kind": "not-found" }))
+        );
+    }
+
+    fn link_provider() -> TestProvider {
+        let result = ExecutionResult {
+            exit_code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration_ms: None,
+            max_rss_bytes: None,
+        };
+        TestProvider::new(Ok(result), Arc::new(Mutex::new(None)))
+    }
+
+    fn linked_metadata(name: &str) -> SandboxMetadata {
+        SandboxMetadata {
+            name: name.to_string(),
+            container_id: format!("{name}-container"),
+            ..stub_metadata()
+        }
+    }
+
+    #[tokio::test]
+    async fn link_sandboxes_joins_both_and_reports_aliases() {
+        let provider = link_provider();
+        let members = [
+            ("Web App".to_string(), linked_metadata("web-app")),
+            ("db".to_string(), linked_metadata("db")),
+        ];
+        let response = link_sandboxes(&provider, members, "shared".to_string(), Some("repo"))
+            .await
+            .expect("link");
+
+        let joined = provider.joined.lock().expect("joined lock");
+        assert_eq!(
+            *joined,
+            vec![
+                (
+                    "web-app-container".to_string(),
+                    "shared".to_string(),
+                    "repo-web-app".to_string()
+                ),
+                ("db-container".to_string(), "shared".to_string(), "repo-db".to_string()),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&response).expect("json"),
+            serde_json::json!({
+                "network": "shared",
+                "sandboxes": [
+                    { "sandbox": "Web App", "alias": "repo-web-app" },
+                    { "sandbox": "db", "alias": "repo-db" },
+                ],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn link_sandboxes_uses_bare_slugs_without_a_prefix() {
+        let provider = link_provider();
+        let members = [
+            ("web".to_string(), linked_metadata("web")),
+            ("db".to_string(), linked_metadata("db")),
+        ];
+        let response = link_sandboxes(&provider, members, "litterbox-repo".to_string(), None)
+            .await
+            .expect("link");
+
+        let aliases: Vec<_> = response.sandboxes.iter().map(|s| s.alias.as_str()).collect();
+        assert_eq!(aliases, ["web", "db"]);
+    }
+
+    #[tokio::test]
+    async fn link_sandboxes_rejects_two_names_for_the_same_sandbox() {
+        let provider = link_provider();
+        let members = [
+            ("Web".to_string(), linked_metadata("web")),
+            ("web".to_string(), linked_metadata("web")),
+        ];
+        let error = link_sandboxes(&provider, members, "shared".to_string(), None)
+            .await
+            .expect_err("same sandbox");
+
+        assert_eq!(error.data, Some(error_data(LitterboxErrorCode::InvalidArgument)));
+        assert!(provider.joined.lock().expect("joined lock").is_empty());
+    }
 }
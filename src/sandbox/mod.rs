@@ -1,34 +1,53 @@
 use std::collections::HashMap;
+use std::fs;
 use std::io::Cursor;
-use std::net::TcpListener;
-use std::path::Path;
+use std::net::{TcpListener, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use futures_util::future::BoxFuture;
+use ignore::WalkBuilder;
 use tar::Archive;
 use tempfile::TempDir;
-use tokio::time::sleep;
+use tokio::sync::watch;
+use tokio::time::{Instant, sleep, timeout};
 
-use crate::compute::{Compute, ContainerInspection, ContainerSpec};
+use crate::compute::{
+    Compute, ContainerInspection, ContainerSpec, ContainerStats, ExecOptions, LABEL_BRANCH,
+    LABEL_REPO_PREFIX, LABEL_SLUG, is_zstd_compressed,
+};
+use crate::config::{DEFAULT_PORT_RANGE_END, DEFAULT_PORT_RANGE_START};
 use crate::domain::{
-    slugify_name,
+    slugify,
+    slugify_new_name,
+    validate_slug_format,
     ComputeError,
     ExecutionResult,
     ForwardedPortMapping,
+    PortProtocol,
     SandboxConfig,
     SandboxError,
     SandboxMetadata,
     SandboxStatus,
+    ScmError,
+    SyncDirection,
+    SyncResult,
 };
-use crate::scm::Scm;
+use crate::scm::{AsyncScm, LITTERBOX_IGNORE_FILE, ThreadSafeScm};
 
-const DEFAULT_WORKDIR: &str = "/src";
-const DEFAULT_PORT_RANGE_START: u16 = 3000;
-const DEFAULT_PORT_RANGE_END: u16 = 8000;
+/// Container directory the project is copied to unless `docker.workdir` says otherwise.
+pub const DEFAULT_WORKDIR: &str = "/src";
 const PORT_ALLOC_BACKOFF_MS: u64 = 25;
 const PORT_ALLOC_MAX_RETRIES: usize = 32;
+const DEFAULT_READY_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_READY_POLL_INTERVAL_MS: u64 = 500;
 
 pub trait SandboxProvider {
+    /// Container directory holding the project, which relative tool paths resolve against.
+    fn workdir(&self) -> &str {
+        DEFAULT_WORKDIR
+    }
     fn create<'a>(
         &'a self,
         name: &'a str,
@@ -38,17 +57,56 @@ pub trait SandboxProvider {
         &'a self,
         container_id: &'a str,
     ) -> BoxFuture<'a, Result<ContainerInspection, SandboxError>>;
+    /// `metadata` with the live status, forwarded ports, and IP address of its container.
+    fn inspect<'a>(
+        &'a self,
+        metadata: &'a SandboxMetadata,
+    ) -> BoxFuture<'a, Result<SandboxMetadata, SandboxError>>
+    where
+        Self: Sync,
+    {
+        Box::pin(async move {
+            let inspection = self.inspect_container(&metadata.container_id).await?;
+            Ok(metadata_with_inspection(metadata.clone(), &inspection))
+        })
+    }
+    fn container_stats<'a>(
+        &'a self,
+        container_id: &'a str,
+    ) -> BoxFuture<'a, Result<ContainerStats, SandboxError>>;
+    fn commit_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        image: &'a str,
+        tag: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>>;
     fn pause<'a>(&'a self, container_id: &'a str)
         -> BoxFuture<'a, Result<(), SandboxError>>;
     fn resume<'a>(&'a self, container_id: &'a str)
         -> BoxFuture<'a, Result<(), SandboxError>>;
     fn delete<'a>(&'a self, metadata: &'a SandboxMetadata)
         -> BoxFuture<'a, Result<(), SandboxError>>;
+    /// Attach the sandbox's container to `network`, created if missing, reachable as `alias`.
+    fn join_network<'a>(
+        &'a self,
+        metadata: &'a SandboxMetadata,
+        network: &'a str,
+        alias: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>>;
     fn shell<'a>(
         &'a self,
         metadata: &'a SandboxMetadata,
         command: &'a [String],
+        options: Option<&'a ExecOptions>,
     ) -> BoxFuture<'a, Result<ExecutionResult, SandboxError>>;
+    /// Run `command` every `poll_interval_ms` until it exits 0, failing after `timeout_secs`.
+    fn wait_for_ready<'a>(
+        &'a self,
+        metadata: &'a SandboxMetadata,
+        command: &'a str,
+        timeout_secs: u64,
+        poll_interval_ms: u64,
+    ) -> BoxFuture<'a, Result<(), SandboxError>>;
     fn upload_path<'a>(
         &'a self,
         metadata: &'a SandboxMetadata,
@@ -61,16 +119,78 @@ pub trait SandboxProvider {
         src_path: &'a str,
         dest_path: &'a Path,
     ) -> BoxFuture<'a, Result<(), SandboxError>>;
+    /// Copy files that differ between the host checkout and the container's `/src`.
+    ///
+    /// Files are only added or overwritten, never deleted, on the receiving side.
+    fn sync<'a>(
+        &'a self,
+        metadata: &'a SandboxMetadata,
+        direction: SyncDirection,
+    ) -> BoxFuture<'a, Result<SyncResult, SandboxError>>;
 }
 
 pub struct DockerSandboxProvider<S, C> {
     scm: S,
     compute: C,
+    host_root: PathBuf,
+    workdir: String,
+    exec_retries: Option<u32>,
+    compress_archives: bool,
 }
 
 impl<S, C> DockerSandboxProvider<S, C> {
     pub fn new(scm: S, compute: C) -> Self {
-        Self { scm, compute }
+        Self {
+            scm,
+            compute,
+            host_root: PathBuf::from("."),
+            workdir: DEFAULT_WORKDIR.to_string(),
+            exec_retries: None,
+            compress_archives: false,
+        }
+    }
+
+    /// Sets the container directory the project is copied to, in place of `/src`.
+    pub fn with_workdir(mut self, workdir: impl Into<String>) -> Self {
+        let workdir = workdir.into();
+        self.workdir = match workdir.trim_end_matches('/') {
+            "" => DEFAULT_WORKDIR.to_string(),
+            trimmed => trimmed.to_string(),
+        };
+        self
+    }
+
+    /// Sets how many times creating a command exec is retried on transient engine errors.
+    pub fn with_exec_retries(mut self, exec_retries: Option<u32>) -> Self {
+        self.exec_retries = exec_retries;
+        self
+    }
+
+    /// Sets whether the project archive a new sandbox is staged from is zstd-compressed.
+    pub fn with_compressed_archives(mut self, compress_archives: bool) -> Self {
+        self.compress_archives = compress_archives;
+        self
+    }
+
+    /// Sets the host directory that `sync` reads from and writes to.
+    pub fn with_host_root(mut self, host_root: PathBuf) -> Self {
+        self.host_root = host_root;
+        self
+    }
+}
+
+impl<S, C: Compute> DockerSandboxProvider<S, C> {
+    /// Adopts the workdir the sandbox's container was created with, so changing
+    /// `docker.workdir` only affects new sandboxes. A missing container keeps the current one.
+    pub async fn for_sandbox(self, metadata: &SandboxMetadata) -> Result<Self, SandboxError> {
+        match self.compute.inspect_container(&metadata.container_id).await {
+            Ok(inspection) => Ok(match inspection.working_dir {
+                Some(working_dir) => self.with_workdir(working_dir),
+                None => self,
+            }),
+            Err(SandboxError::Compute(ComputeError::ContainerNotFound { .. })) => Ok(self),
+            Err(error) => Err(error),
+        }
     }
 }
 
@@ -82,110 +202,393 @@ pub fn branch_name_for_slug(slug: &str) -> String {
     format!("litterbox/{}", slug)
 }
 
-impl<S, C> SandboxProvider for DockerSandboxProvider<S, C>
+/// Updates `metadata` with what the engine reported about its container.
+pub fn metadata_with_inspection(
+    mut metadata: SandboxMetadata,
+    inspection: &ContainerInspection,
+) -> SandboxMetadata {
+    metadata.status = inspection.status.clone();
+    metadata.forwarded_ports = forwarded_ports_from_inspection(inspection);
+    metadata.container_ip = inspection.ip_address.clone();
+    metadata.network_aliases = inspection.network_aliases.clone();
+    metadata.image_digest = inspection.image_digest.clone();
+    metadata.started_at = inspection.started_at.clone();
+    metadata
+}
+
+/// Rebuilds the forwarded port mappings of a container from its environment and port bindings.
+pub fn forwarded_ports_from_inspection(inspection: &ContainerInspection) -> Vec<ForwardedPortMapping> {
+    let mut env_map: HashMap<u16, String> = HashMap::new();
+    for entry in &inspection.env {
+        if let Some((key, value)) = entry.split_once('=')
+            && key.starts_with("LITTERBOX_FWD_PORT_")
+            && let Ok(port) = value.parse::<u16>()
+        {
+            env_map.insert(port, key.to_string());
+        }
+    }
+
+    let mut mappings = Vec::new();
+    for (container_port, bindings) in &inspection.port_bindings {
+        let target = container_port
+            .split('/')
+            .next()
+            .and_then(|value| value.parse::<u16>().ok());
+        let target = match target {
+            Some(target) => target,
+            None => continue,
+        };
+
+        for binding in bindings {
+            let host_port = binding
+                .host_port
+                .as_ref()
+                .and_then(|value| value.parse::<u16>().ok());
+            let host_port = match host_port {
+                Some(host_port) => host_port,
+                None => continue,
+            };
+
+            // A port forwarded for both TCP and UDP has two bindings but one mapping.
+            if mappings.iter().any(|mapping: &ForwardedPortMapping| {
+                mapping.target == target && mapping.host_port == host_port
+            }) {
+                continue;
+            }
+            let env_var = match env_map.get(&host_port) {
+                Some(env) => env.clone(),
+                None => continue,
+            };
+            let name = env_var
+                .strip_prefix("LITTERBOX_FWD_PORT_")
+                .unwrap_or("")
+                .to_ascii_lowercase()
+                .replace('_', "-");
+
+            mappings.push(ForwardedPortMapping {
+                name,
+                target,
+                host_port,
+                env_var,
+            });
+        }
+    }
+
+    mappings
+}
+
+/// Process-wide shutdown state shared with in-flight sandbox creations.
+struct Interrupts {
+    requested: watch::Sender<bool>,
+    in_flight: watch::Sender<usize>,
+}
+
+static INTERRUPTS: LazyLock<Interrupts> = LazyLock::new(|| Interrupts {
+    requested: watch::Sender::new(false),
+    in_flight: watch::Sender::new(0),
+});
+
+/// Counts a sandbox creation as in flight until dropped.
+struct InFlightCreation;
+
+impl InFlightCreation {
+    fn begin() -> Self {
+        INTERRUPTS.in_flight.send_modify(|count| *count += 1);
+        Self
+    }
+}
+
+impl Drop for InFlightCreation {
+    fn drop(&mut self) {
+        INTERRUPTS.in_flight.send_modify(|count| *count -= 1);
+    }
+}
+
+/// Runs `future`, failing with [`SandboxError::Interrupted`] once a shutdown is requested.
+async fn interruptible<T>(
+    future: impl Future<Output = Result<T, SandboxError>>,
+) -> Result<T, SandboxError> {
+    let mut requested = INTERRUPTS.requested.subscribe();
+    tokio::select! {
+        result = future => result,
+        _ = requested.wait_for(|requested| *requested) => Err(SandboxError::Interrupted),
+    }
+}
+
+/// Interrupts every sandbox creation in flight and waits until each has rolled back.
+pub async fn interrupt_creations() {
+    INTERRUPTS.requested.send_replace(true);
+    let mut in_flight = INTERRUPTS.in_flight.subscribe();
+    let _ = in_flight.wait_for(|count| *count == 0).await;
+}
+
+/// A signal asking the process to shut down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShutdownSignal {
+    Interrupt,
+    Terminate,
+}
+
+impl ShutdownSignal {
+    /// The status a shell reports for a process killed by this signal: 128 plus its number.
+    pub fn exit_status(self) -> i32 {
+        match self {
+            Self::Interrupt => 130,
+            Self::Terminate => 143,
+        }
+    }
+}
+
+/// Resolves with the signal once the process receives SIGINT or, on Unix, SIGTERM.
+pub async fn shutdown_signal() -> ShutdownSignal {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            return tokio::select! {
+                _ = tokio::signal::ctrl_c() => ShutdownSignal::Interrupt,
+                _ = terminate.recv() => ShutdownSignal::Terminate,
+            };
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+    ShutdownSignal::Interrupt
+}
+
+impl<S, C> DockerSandboxProvider<S, C>
 where
-    S: Scm + Send + Sync,
+    S: AsyncScm + Send + Sync,
     C: Compute + Send + Sync,
 {
-    fn create<'a>(
-        &'a self,
-        name: &'a str,
-        config: &'a SandboxConfig,
-    ) -> BoxFuture<'a, Result<SandboxMetadata, SandboxError>> {
-        Box::pin(async move {
-            let slug = slugify_name(name)?;
-            let branch_name = self.scm.create_branch(&slug)?;
-            let repo_prefix = self.scm.repo_prefix()?;
-            let archive = match self.scm.make_archive("HEAD") {
-                Ok(archive) => archive,
-                Err(error) => {
-                    let _ = self.scm.delete_branch(&slug);
-                    return Err(error);
-                }
-            };
-            let staged = match stage_archive(&archive) {
-                Ok(staged) => staged,
-                Err(error) => {
-                    let _ = self.scm.delete_branch(&slug);
-                    return Err(error);
-                }
-            };
+    /// Provisions the container for a freshly created sandbox branch, recording the
+    /// container in `container` as soon as it may exist so a failure can remove it.
+    async fn provision(
+        &self,
+        slug: &str,
+        branch_name: String,
+        config: &SandboxConfig,
+        container: &mut Option<String>,
+    ) -> Result<SandboxMetadata, SandboxError> {
+        let repo_prefix = self.scm.repo_prefix().await?;
+        let start = config.from_ref.as_deref().unwrap_or("HEAD");
+        let archive = self.scm.make_archive(start, self.compress_archives).await?;
+        let staged = stage_archive(&archive)?;
+        self.compute.ensure_image(&config.image).await?;
+
+        let (mut env, port_bindings, forwarded_ports) = build_forwarded_ports(config).await?;
+        env.extend(config.extra_env.iter().cloned());
+
+        let spec = ContainerSpec {
+            name: container_name_for_slug(&repo_prefix, slug),
+            image: config.image.clone(),
+            command: vec!["sh".to_string(), "-c".to_string(), "tail -f /dev/null".to_string()],
+            working_dir: Some(self.workdir.clone()),
+            env,
+            port_bindings,
+            labels: HashMap::from([
+                (LABEL_REPO_PREFIX.to_string(), repo_prefix.clone()),
+                (LABEL_SLUG.to_string(), slug.to_string()),
+                (LABEL_BRANCH.to_string(), branch_name.clone()),
+            ]),
+            restart_policy: config.restart_policy,
+        };
 
-            if let Err(error) = self.compute.ensure_image(&config.image).await {
-                let _ = self.scm.delete_branch(&slug);
+        // The name is recorded first: an interrupted request may still create the container.
+        *container = Some(spec.name.clone());
+        let container_id = match self.compute.create_container(&spec).await {
+            Ok(id) => id,
+            Err(error) => {
+                // A name conflict means the container belongs to someone else.
+                *container = None;
+                if is_container_name_conflict(&error) {
+                    return Err(SandboxError::SandboxExists {
+                        name: slug.to_string(),
+                    });
+                }
                 return Err(error);
             }
+        };
+        *container = Some(container_id.clone());
 
-            let (env, port_bindings, forwarded_ports) =
-                build_forwarded_ports(config).await?;
+        if let Some(network) = &config.network {
+            self.attach_network(network, &container_id, slug).await?;
+        }
 
-            let spec = ContainerSpec {
-                name: container_name_for_slug(&repo_prefix, &slug),
-                image: config.image.clone(),
-                command: vec!["sh".to_string(), "-c".to_string(), "tail -f /dev/null".to_string()],
-                working_dir: Some(DEFAULT_WORKDIR.to_string()),
-                env,
-                port_bindings,
-            };
+        self.compute
+            .upload_path(&container_id, staged.path(), &self.workdir)
+            .await?;
 
-            let container_id = match self.compute.create_container(&spec).await {
-                Ok(id) => id,
-                Err(error) => {
-                    let _ = self.scm.delete_branch(&slug);
-                    if is_container_name_conflict(&error) {
-                        return Err(SandboxError::SandboxExists { name: slug.clone() });
+        if let Some(command) = &config.setup_command {
+            let startup_command = vec!["sh".to_string(), "-c".to_string(), command.clone()];
+            let exec = self.compute.exec(
+                &container_id,
+                &startup_command,
+                Some(&self.workdir),
+                None,
+                self.exec_retries,
+            );
+            let result = match config.setup_timeout_secs {
+                Some(secs) => match timeout(Duration::from_secs(secs), exec).await {
+                    Ok(result) => result?,
+                    Err(_) => {
+                        // Stop the command before the rollback removes its container.
+                        let _ = self.compute.kill_container(&container_id, "SIGKILL").await;
+                        return Err(SandboxError::SetupCommandFailed {
+                            exit_code: -1,
+                            stderr: "timed out".to_string(),
+                        });
                     }
-                    return Err(error);
-                }
+                },
+                None => exec.await?,
             };
 
-            if let Err(error) = self
-                .compute
-                .upload_path(&container_id, staged.path(), DEFAULT_WORKDIR)
-                .await
-            {
-                let _ = self.compute.delete_container(&container_id).await;
-                let _ = self.scm.delete_branch(&slug);
-                return Err(error);
+            if result.exit_code != 0 {
+                let stderr = if result.stderr.is_empty() {
+                    result.stdout
+                } else {
+                    result.stderr
+                };
+                return Err(SandboxError::SetupCommandFailed {
+                    exit_code: result.exit_code,
+                    stderr,
+                });
             }
+        }
 
-            if let Some(command) = &config.setup_command {
-                let startup_command = vec!["sh".to_string(), "-c".to_string(), command.clone()];
-                let result = match self
-                    .compute
-                    .exec(&container_id, &startup_command, Some(DEFAULT_WORKDIR))
-                    .await
-                {
-                    Ok(result) => result,
-                    Err(error) => {
-                        let _ = self.compute.delete_container(&container_id).await;
-                        let _ = self.scm.delete_branch(&slug);
-                        return Err(error);
-                    }
-                };
+        let (container_ip, network_aliases) =
+            match self.compute.inspect_container(&container_id).await {
+                Ok(inspection) => (inspection.ip_address, inspection.network_aliases),
+                Err(_) => (None, Vec::new()),
+            };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|elapsed| elapsed.as_secs());
+        let metadata = SandboxMetadata {
+            name: slug.to_string(),
+            branch_name,
+            container_id,
+            status: SandboxStatus::Active,
+            forwarded_ports,
+            created_at: now,
+            last_active_at: now,
+            container_ip,
+            network_aliases,
+            image_digest: None,
+            started_at: None,
+        };
 
-                if result.exit_code != 0 {
-                    let _ = self.compute.delete_container(&container_id).await;
-                    let _ = self.scm.delete_branch(&slug);
-                    let stderr = if result.stderr.is_empty() {
-                        result.stdout
-                    } else {
-                        result.stderr
-                    };
-                    return Err(SandboxError::SetupCommandFailed {
-                        exit_code: result.exit_code,
-                        stderr,
-                    });
-                }
+        if let Some(command) = &config.ready_command {
+            self.wait_for_ready(
+                &metadata,
+                command,
+                DEFAULT_READY_TIMEOUT_SECS,
+                DEFAULT_READY_POLL_INTERVAL_MS,
+            )
+            .await?;
+        }
+
+        Ok(metadata)
+    }
+
+    /// Joins the container to the network `network`, creating it first if needed, with the
+    /// sandbox slug as its alias there.
+    async fn attach_network(
+        &self,
+        network: &str,
+        container_id: &str,
+        slug: &str,
+    ) -> Result<(), SandboxError> {
+        let network_id = self.compute.network_create(network).await?;
+        self.compute
+            .network_connect(&network_id, container_id, &[slug.to_string()])
+            .await
+    }
+
+    /// Removes what a failed creation made. Interrupted creations report what was removed,
+    /// since the caller is about to exit.
+    async fn roll_back_create(&self, slug: &str, container: Option<&str>, error: &SandboxError) {
+        let mut removed = Vec::new();
+        if let Some(container) = container
+            && self.compute.delete_container(container).await.is_ok()
+        {
+            removed.push(format!("container {container}"));
+        }
+        if self.scm.delete_branch(slug).await.is_ok() {
+            removed.push(format!("branch {}", branch_name_for_slug(slug)));
+        }
+
+        if matches!(error, SandboxError::Interrupted) {
+            let removed = if removed.is_empty() {
+                "nothing to clean up".to_string()
+            } else {
+                format!("removed {}", removed.join(" and "))
+            };
+            eprintln!("Interrupted while creating sandbox '{slug}'; {removed}");
+        }
+    }
+
+    /// Downloads the contents of the workdir into `dest_path`.
+    ///
+    /// The engine archives a directory under its own name. The compute layer drops a leading
+    /// `src/`, so any other workdir name is lifted off here.
+    async fn download_workdir(
+        &self,
+        container_id: &str,
+        dest_path: &Path,
+    ) -> Result<(), SandboxError> {
+        self.compute
+            .download_path(container_id, &self.workdir, dest_path)
+            .await?;
+        let root = Path::new(&self.workdir).file_name().unwrap_or_default();
+        if root == "src" || !dest_path.join(root).is_dir() {
+            return Ok(());
+        }
+        // Moved aside first, since the project may have a top-level entry with the same name.
+        let nested = TempDir::new_in(dest_path)?;
+        fs::rename(dest_path.join(root), nested.path().join(root))?;
+        for entry in fs::read_dir(nested.path().join(root))? {
+            let entry = entry?;
+            if entry.file_name() != ".git" {
+                fs::rename(entry.path(), dest_path.join(entry.file_name()))?;
             }
+        }
+        Ok(())
+    }
+}
 
-            Ok(SandboxMetadata {
-                name: slug,
-                branch_name,
-                container_id,
-                status: SandboxStatus::Active,
-                forwarded_ports,
-            })
+impl<S, C> SandboxProvider for DockerSandboxProvider<S, C>
+where
+    S: AsyncScm + Send + Sync,
+    C: Compute + Send + Sync,
+{
+    fn workdir(&self) -> &str {
+        &self.workdir
+    }
+
+    fn create<'a>(
+        &'a self,
+        name: &'a str,
+        config: &'a SandboxConfig,
+    ) -> BoxFuture<'a, Result<SandboxMetadata, SandboxError>> {
+        Box::pin(async move {
+            let slug = slugify_new_name(name)?;
+            let _in_flight = InFlightCreation::begin();
+            // Branch creation is quick and local, so it is not interrupted part-way; a
+            // pre-existing branch is never treated as ours to roll back.
+            let branch_name = match &config.from_ref {
+                Some(from_ref) => self.scm.create_branch_at(&slug, from_ref).await?,
+                None => self.scm.create_branch(&slug).await?,
+            };
+
+            let mut container = None;
+            let result =
+                interruptible(self.provision(&slug, branch_name, config, &mut container)).await;
+            if let Err(error) = &result {
+                self.roll_back_create(&slug, container.as_deref(), error).await;
+            }
+            result
         })
     }
 
@@ -203,6 +606,22 @@ where
         Box::pin(async move { self.compute.inspect_container(container_id).await })
     }
 
+    fn container_stats<'a>(
+        &'a self,
+        container_id: &'a str,
+    ) -> BoxFuture<'a, Result<ContainerStats, SandboxError>> {
+        Box::pin(async move { self.compute.container_stats(container_id).await })
+    }
+
+    fn commit_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        image: &'a str,
+        tag: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move { self.compute.commit_container(container_id, image, tag).await })
+    }
+
     fn resume<'a>(
         &'a self,
         container_id: &'a str,
@@ -216,23 +635,65 @@ where
     ) -> BoxFuture<'a, Result<(), SandboxError>> {
         Box::pin(async move {
             self.compute.delete_container(&metadata.container_id).await?;
-            self.scm.delete_branch(&metadata.name)?;
+            self.scm.delete_branch(&metadata.name).await?;
             Ok(())
         })
     }
 
+    fn join_network<'a>(
+        &'a self,
+        metadata: &'a SandboxMetadata,
+        network: &'a str,
+        alias: &'a str,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move { self.attach_network(network, &metadata.container_id, alias).await })
+    }
+
     fn shell<'a>(
         &'a self,
         metadata: &'a SandboxMetadata,
         command: &'a [String],
+        options: Option<&'a ExecOptions>,
     ) -> BoxFuture<'a, Result<ExecutionResult, SandboxError>> {
         Box::pin(async move {
+            let workdir = Some(self.workdir.as_str());
             self.compute
-                .exec(&metadata.container_id, command, Some(DEFAULT_WORKDIR))
+                .exec(&metadata.container_id, command, workdir, options, self.exec_retries)
                 .await
         })
     }
 
+    fn wait_for_ready<'a>(
+        &'a self,
+        metadata: &'a SandboxMetadata,
+        command: &'a str,
+        timeout_secs: u64,
+        poll_interval_ms: u64,
+    ) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move {
+            let probe = vec!["sh".to_string(), "-c".to_string(), command.to_string()];
+            let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+            let not_ready = || SandboxError::NotReady {
+                command: command.to_string(),
+                timeout_secs,
+            };
+            loop {
+                // A probe that hangs counts against the deadline rather than blocking forever.
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let result = timeout(remaining, self.shell(metadata, &probe, None))
+                    .await
+                    .map_err(|_| not_ready())??;
+                if result.exit_code == 0 {
+                    return Ok(());
+                }
+                if Instant::now() >= deadline {
+                    return Err(not_ready());
+                }
+                sleep(Duration::from_millis(poll_interval_ms)).await;
+            }
+        })
+    }
+
     fn upload_path<'a>(
         &'a self,
         metadata: &'a SandboxMetadata,
@@ -253,20 +714,153 @@ where
         dest_path: &'a Path,
     ) -> BoxFuture<'a, Result<(), SandboxError>> {
         Box::pin(async move {
+            if src_path.trim_end_matches('/') == self.workdir {
+                return self.download_workdir(&metadata.container_id, dest_path).await;
+            }
             self.compute
                 .download_path(&metadata.container_id, src_path, dest_path)
                 .await
         })
     }
+
+    fn sync<'a>(
+        &'a self,
+        metadata: &'a SandboxMetadata,
+        direction: SyncDirection,
+    ) -> BoxFuture<'a, Result<SyncResult, SandboxError>> {
+        Box::pin(async move {
+            let container_tree = TempDir::new()?;
+            self.download_workdir(&metadata.container_id, container_tree.path())
+                .await?;
+
+            match direction {
+                SyncDirection::ContainerToHost => copy_changed_files(
+                    container_tree.path(),
+                    &self.host_root,
+                    &self.host_root,
+                    false,
+                ),
+                SyncDirection::HostToContainer => {
+                    let changed = TempDir::new()?;
+                    let result = copy_changed_files(
+                        &self.host_root,
+                        container_tree.path(),
+                        changed.path(),
+                        true,
+                    )?;
+                    if result.files_transferred > 0 {
+                        self.compute
+                            .upload_path(&metadata.container_id, changed.path(), &self.workdir)
+                            .await?;
+                    }
+                    Ok(result)
+                }
+            }
+        })
+    }
+}
+
+/// Restore a sandbox's working tree to the contents of `reference`.
+///
+/// The restored tree is recorded as a new snapshot on the sandbox branch, so history is
+/// preserved and the restore itself can be undone.
+pub async fn restore_snapshot<P: SandboxProvider>(
+    provider: &P,
+    scm: &ThreadSafeScm,
+    metadata: &SandboxMetadata,
+    reference: &str,
+) -> Result<Option<git2::Oid>, SandboxError> {
+    let archive = scm.make_archive(reference, false).await?;
+    let staged = stage_archive(&archive)?;
+
+    let clear = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        format!(
+            "find {} -mindepth 1 -maxdepth 1 ! -name .git -exec rm -rf {{}} +",
+            provider.workdir()
+        ),
+    ];
+    let result = provider.shell(metadata, &clear, None).await?;
+    if result.exit_code != 0 {
+        return Err(SandboxError::SnapshotRestoreFailed {
+            reference: reference.to_string(),
+            message: result.stderr.trim().to_string(),
+        });
+    }
+
+    provider
+        .upload_path(metadata, staged.path(), provider.workdir())
+        .await?;
+    // Restoring an older tree must always move the branch, so only the tip is compared.
+    scm.commit_snapshot_from_staging_async(staged.path(), &format!("restore: {reference}"), 1)
+        .await
 }
 
 fn stage_archive(archive: &[u8]) -> Result<TempDir, SandboxError> {
     let tempdir = TempDir::new()?;
-    let mut archive = Archive::new(Cursor::new(archive));
-    archive.unpack(tempdir.path())?;
+    if is_zstd_compressed(archive) {
+        let decoder = zstd::Decoder::new(Cursor::new(archive))?;
+        Archive::new(decoder).unpack(tempdir.path())?;
+    } else {
+        Archive::new(Cursor::new(archive)).unpack(tempdir.path())?;
+    }
     Ok(tempdir)
 }
 
+/// Copy every file under `src_root` whose blob hash differs from the same path under
+/// `compare_root` into `dest_root`.
+///
+/// With `respect_ignores`, `src_root` is walked honouring `.gitignore` and
+/// `.litterboxignore`; `.git` is always skipped.
+fn copy_changed_files(
+    src_root: &Path,
+    compare_root: &Path,
+    dest_root: &Path,
+    respect_ignores: bool,
+) -> Result<SyncResult, SandboxError> {
+    let mut result = SyncResult::default();
+    let mut walker = WalkBuilder::new(src_root);
+    walker
+        .standard_filters(respect_ignores)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git");
+    if respect_ignores {
+        walker.add_custom_ignore_filename(LITTERBOX_IGNORE_FILE);
+    }
+
+    for entry in walker.build() {
+        let entry = entry.map_err(|error| SandboxError::Config(error.to_string()))?;
+        if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(src_root)
+            .map_err(|error| SandboxError::Config(error.to_string()))?;
+        let src_hash = blob_hash(entry.path())?;
+        let existing = compare_root.join(relative);
+        if existing.is_file() && blob_hash(&existing)? == src_hash {
+            continue;
+        }
+
+        let dest = dest_root.join(relative);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        result.bytes_transferred += fs::copy(entry.path(), &dest)?;
+        result.files_transferred += 1;
+    }
+
+    Ok(result)
+}
+
+fn blob_hash(path: &Path) -> Result<git2::Oid, SandboxError> {
+    git2::Oid::hash_file(git2::ObjectType::Blob, path)
+        .map_err(|source| SandboxError::Scm(ScmError::Archive { source }))
+}
+
 fn is_container_name_conflict(error: &SandboxError) -> bool {
     matches!(
         error,
@@ -283,22 +877,29 @@ async fn build_forwarded_ports(
         return Ok((Vec::new(), HashMap::new(), Vec::new()));
     }
 
+    let (range_start, range_end) = config
+        .port_range
+        .unwrap_or((DEFAULT_PORT_RANGE_START, DEFAULT_PORT_RANGE_END));
     let mut env = Vec::new();
     let mut port_bindings: HashMap<String, Vec<bollard::models::PortBinding>> = HashMap::new();
     let mut forwarded = Vec::new();
 
     for port in &config.forwarded_ports {
-        let slug = slugify_name(&port.name)?;
+        // Port names only become env var keys, so reserved sandbox names are fine here.
+        let slug = slugify(&port.name);
+        validate_slug_format(&port.name, &slug)?;
         let env_key = env_var_for_slug(&slug);
-        let host_port = allocate_host_port(DEFAULT_PORT_RANGE_START, DEFAULT_PORT_RANGE_END).await?;
+        let host_port = allocate_host_port(range_start, range_end, port.protocol).await?;
         env.push(format!("{env_key}={host_port}"));
-        port_bindings.insert(
-            format!("{}/tcp", port.target),
-            vec![bollard::models::PortBinding {
-                host_ip: Some("0.0.0.0".to_string()),
-                host_port: Some(host_port.to_string()),
-            }],
-        );
+        for protocol in port.protocol.docker_protocols() {
+            port_bindings.insert(
+                format!("{}/{protocol}", port.target),
+                vec![bollard::models::PortBinding {
+                    host_ip: Some("0.0.0.0".to_string()),
+                    host_port: Some(host_port.to_string()),
+                }],
+            );
+        }
         forwarded.push(ForwardedPortMapping {
             name: port.name.clone(),
             target: port.target,
@@ -317,7 +918,11 @@ fn env_var_for_slug(slug: &str) -> String {
     )
 }
 
-async fn allocate_host_port(range_start: u16, range_end: u16) -> Result<u16, SandboxError> {
+async fn allocate_host_port(
+    range_start: u16,
+    range_end: u16,
+    protocol: PortProtocol,
+) -> Result<u16, SandboxError> {
     if range_end < range_start {
         return Err(SandboxError::Config(format!(
             "Invalid port range: {range_start}-{range_end}"
@@ -325,16 +930,14 @@ async fn allocate_host_port(range_start: u16, range_end: u16) -> Result<u16, San
     }
 
     let range = (range_end - range_start + 1) as u64;
-    let seed = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|error| SandboxError::Config(error.to_string()))?
-        .as_nanos() as u64;
+    // A random start keeps sandboxes created at the same instant from probing the same ports.
+    let seed = u64::from(rand::random::<u16>());
     let max_attempts = PORT_ALLOC_MAX_RETRIES.min(range as usize);
 
     for attempt in 0..max_attempts {
         let offset = (seed + attempt as u64) % range;
         let candidate = range_start + offset as u16;
-        if TcpListener::bind(("127.0.0.1", candidate)).is_ok() {
+        if host_port_available(candidate, protocol) {
             return Ok(candidate);
         }
         sleep(Duration::from_millis(PORT_ALLOC_BACKOFF_MS)).await;
@@ -345,6 +948,13 @@ async fn allocate_host_port(range_start: u16, range_end: u16) -> Result<u16, San
     )))
 }
 
+fn host_port_available(port: u16, protocol: PortProtocol) -> bool {
+    protocol.docker_protocols().iter().all(|protocol| match *protocol {
+        "udp" => UdpSocket::bind(("127.0.0.1", port)).is_ok(),
+        _ => TcpListener::bind(("127.0.0.1", port)).is_ok(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -357,8 +967,8 @@ mod tests {
     use git2::{IndexAddOption, Repository, Signature};
     use tempfile::TempDir;
 
-    use crate::compute::DockerCompute;
-    use crate::domain::ForwardedPort;
+    use crate::compute::{DockerCompute, PortBindingSpec};
+    use crate::domain::{ForwardedPort, PortProtocol};
     use crate::scm::ThreadSafeScm;
 
     static UNIQUE_COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -404,9 +1014,43 @@ mod tests {
         assert_eq!(env, "LITTERBOX_FWD_PORT_MY_SERVICE");
     }
 
+    #[test]
+    fn copy_changed_files_copies_only_differing_files() {
+        let src = TempDir::new().expect("src tempdir");
+        let compare = TempDir::new().expect("compare tempdir");
+        let dest = TempDir::new().expect("dest tempdir");
+
+        fs::write(src.path().join("same.txt"), "same").expect("write same");
+        fs::write(compare.path().join("same.txt"), "same").expect("write same");
+        fs::write(src.path().join("changed.txt"), "new").expect("write changed");
+        fs::write(compare.path().join("changed.txt"), "old").expect("write changed");
+        fs::create_dir_all(src.path().join("nested")).expect("create nested");
+        fs::write(src.path().join("nested/added.txt"), "added").expect("write added");
+        fs::create_dir_all(src.path().join(".git")).expect("create .git");
+        fs::write(src.path().join(".git/HEAD"), "ref").expect("write HEAD");
+
+        let result = copy_changed_files(src.path(), compare.path(), dest.path(), true)
+            .expect("copy changed files");
+
+        assert_eq!(result.files_transferred, 2);
+        assert_eq!(result.bytes_transferred, 8);
+        assert!(!dest.path().join("same.txt").exists());
+        assert!(!dest.path().join(".git").exists());
+        assert_eq!(
+            fs::read_to_string(dest.path().join("changed.txt")).expect("read changed"),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(dest.path().join("nested/added.txt")).expect("read added"),
+            "added"
+        );
+    }
+
     #[tokio::test]
     async fn allocate_host_port_returns_in_range() {
-        let port = allocate_host_port(45000, 45010).await.expect("alloc port");
+        let port = allocate_host_port(45000, 45010, PortProtocol::Tcp)
+            .await
+            .expect("alloc port");
 
         assert!((45000..=45010).contains(&port));
     }
@@ -420,7 +1064,7 @@ mod tests {
             return;
         }
 
-        let allocated = allocate_host_port(port, port + 1)
+        let allocated = allocate_host_port(port, port + 1, PortProtocol::Tcp)
             .await
             .expect("alloc port");
 
@@ -429,7 +1073,7 @@ mod tests {
 
     #[tokio::test]
     async fn allocate_host_port_rejects_invalid_range() {
-        let err = allocate_host_port(9000, 8000)
+        let err = allocate_host_port(9000, 8000, PortProtocol::Tcp)
             .await
             .expect_err("invalid range rejected");
 
@@ -441,7 +1085,7 @@ mod tests {
         let listener = TcpListener::bind(("127.0.0.1", 0)).expect("bind port");
         let port = listener.local_addr().expect("local addr").port();
 
-        let err = allocate_host_port(port, port)
+        let err = allocate_host_port(port, port, PortProtocol::Tcp)
             .await
             .expect_err("no available ports");
 
@@ -453,10 +1097,18 @@ mod tests {
         let config = SandboxConfig {
             image: "busybox".to_string(),
             setup_command: None,
+            setup_timeout_secs: None,
             forwarded_ports: vec![ForwardedPort {
                 name: "web".to_string(),
                 target: 8080,
+                protocol: PortProtocol::Tcp,
             }],
+            port_range: None,
+            ready_command: None,
+            from_ref: None,
+            restart_policy: None,
+            extra_env: Vec::new(),
+            network: None,
         };
 
         let (env, port_bindings, forwarded) =
@@ -471,12 +1123,48 @@ mod tests {
         assert!((DEFAULT_PORT_RANGE_START..=DEFAULT_PORT_RANGE_END).contains(&forwarded[0].host_port));
     }
 
+    #[tokio::test]
+    async fn build_forwarded_ports_binds_tcp_and_udp_for_both() {
+        let config = SandboxConfig {
+            image: "busybox".to_string(),
+            setup_command: None,
+            setup_timeout_secs: None,
+            forwarded_ports: vec![ForwardedPort {
+                name: "dns".to_string(),
+                target: 53,
+                protocol: PortProtocol::Both,
+            }],
+            port_range: Some((46000, 46100)),
+            ready_command: None,
+            from_ref: None,
+            restart_policy: None,
+            extra_env: Vec::new(),
+            network: None,
+        };
+
+        let (_, port_bindings, forwarded) =
+            build_forwarded_ports(&config).await.expect("build ports");
+
+        let host_port = Some(forwarded[0].host_port.to_string());
+        assert_eq!(port_bindings.len(), 2);
+        assert_eq!(port_bindings["53/tcp"][0].host_port, host_port);
+        assert_eq!(port_bindings["53/udp"][0].host_port, host_port);
+        assert!((46000..=46100).contains(&forwarded[0].host_port));
+    }
+
     #[tokio::test]
     async fn build_forwarded_ports_allows_empty_config() {
         let config = SandboxConfig {
             image: "busybox".to_string(),
             setup_command: None,
+            setup_timeout_secs: None,
             forwarded_ports: Vec::new(),
+            port_range: None,
+            ready_command: None,
+            from_ref: None,
+            restart_policy: None,
+            extra_env: Vec::new(),
+            network: None,
         };
 
         let (env, port_bindings, forwarded) =
@@ -492,10 +1180,18 @@ mod tests {
         let config = SandboxConfig {
             image: "busybox".to_string(),
             setup_command: None,
+            setup_timeout_secs: None,
             forwarded_ports: vec![ForwardedPort {
                 name: "----".to_string(),
                 target: 8080,
+                protocol: PortProtocol::Tcp,
             }],
+            port_range: None,
+            ready_command: None,
+            from_ref: None,
+            restart_policy: None,
+            extra_env: Vec::new(),
+            network: None,
         };
 
         let err = build_forwarded_ports(&config)
@@ -505,6 +1201,187 @@ mod tests {
         assert!(err.to_string().contains("Invalid sandbox name"));
     }
 
+    #[test]
+    fn with_workdir_trims_trailing_slashes() {
+        let provider = DockerSandboxProvider::new((), ()).with_workdir("/workspace/");
+        assert_eq!(provider.workdir, "/workspace");
+
+        let provider = DockerSandboxProvider::new((), ()).with_workdir("/");
+        assert_eq!(provider.workdir, DEFAULT_WORKDIR);
+    }
+
+    /// Engine stand-in whose execs never finish, recording every container it is asked to
+    /// create, kill or delete.
+    #[derive(Default)]
+    struct HangingCompute {
+        calls: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl HangingCompute {
+        fn record(&self, call: String) {
+            self.calls.lock().expect("calls lock").push(call);
+        }
+    }
+
+    impl Compute for HangingCompute {
+        fn ensure_image<'a>(&'a self, _image: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn build_image<'a>(
+            &'a self,
+            _context_path: &'a Path,
+            _tag: &'a str,
+            _build_args: &'a HashMap<String, String>,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn create_container<'a>(
+            &'a self,
+            spec: &'a ContainerSpec,
+        ) -> BoxFuture<'a, Result<String, SandboxError>> {
+            self.record(format!("create {}", spec.name));
+            Box::pin(async { Ok("hanging".to_string()) })
+        }
+
+        fn inspect_container<'a>(
+            &'a self,
+            container_id: &'a str,
+        ) -> BoxFuture<'a, Result<ContainerInspection, SandboxError>> {
+            Box::pin(async move {
+                Err(SandboxError::Compute(ComputeError::ContainerNotFound {
+                    container_id: container_id.to_string(),
+                }))
+            })
+        }
+
+        fn container_stats<'a>(
+            &'a self,
+            _container_id: &'a str,
+        ) -> BoxFuture<'a, Result<ContainerStats, SandboxError>> {
+            Box::pin(async {
+                Ok(ContainerStats {
+                    cpu_percent: 0.0,
+                    memory_used_bytes: 0,
+                    memory_limit_bytes: 0,
+                })
+            })
+        }
+
+        fn commit_container<'a>(
+            &'a self,
+            _container_id: &'a str,
+            _image: &'a str,
+            _tag: &'a str,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn pause_container<'a>(
+            &'a self,
+            _container_id: &'a str,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn resume_container<'a>(
+            &'a self,
+            _container_id: &'a str,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn delete_container<'a>(
+            &'a self,
+            container_id: &'a str,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            self.record(format!("delete {container_id}"));
+            Box::pin(async { Ok(()) })
+        }
+
+        fn kill_container<'a>(
+            &'a self,
+            container_id: &'a str,
+            signal: &'a str,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            self.record(format!("kill {container_id} {signal}"));
+            Box::pin(async { Ok(()) })
+        }
+
+        fn exec<'a>(
+            &'a self,
+            _container_id: &'a str,
+            _command: &'a [String],
+            _working_dir: Option<&'a str>,
+            _options: Option<&'a ExecOptions>,
+            _retries: Option<u32>,
+        ) -> BoxFuture<'a, Result<ExecutionResult, SandboxError>> {
+            Box::pin(std::future::pending())
+        }
+
+        fn upload_path<'a>(
+            &'a self,
+            _container_id: &'a str,
+            _src_path: &'a Path,
+            _dest_path: &'a str,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn download_path<'a>(
+            &'a self,
+            _container_id: &'a str,
+            _src_path: &'a str,
+            _dest_path: &'a Path,
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn network_create<'a>(
+            &'a self,
+            name: &'a str,
+        ) -> BoxFuture<'a, Result<String, SandboxError>> {
+            Box::pin(async move { Ok(name.to_string()) })
+        }
+
+        fn network_connect<'a>(
+            &'a self,
+            _network_id: &'a str,
+            _container_id: &'a str,
+            _aliases: &'a [String],
+        ) -> BoxFuture<'a, Result<(), SandboxError>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn create_kills_the_container_when_setup_times_out() {
+        let (tempdir, _repo) = init_repo();
+        let scm = ThreadSafeScm::open(tempdir.path()).expect("open scm");
+        let provider = DockerSandboxProvider::new(scm, HangingCompute::default());
+        let config = SandboxConfig {
+            image: "busybox:latest".to_string(),
+            setup_command: Some("sleep 60".to_string()),
+            setup_timeout_secs: Some(1),
+            forwarded_ports: Vec::new(),
+            port_range: None,
+            ready_command: None,
+            from_ref: None,
+            restart_policy: None,
+            extra_env: Vec::new(),
+            network: None,
+        };
+
+        let error = provider.create("slow-setup", &config).await.expect_err("timed out");
+
+        assert!(matches!(error, SandboxError::SetupCommandFailed { exit_code: -1, .. }));
+        let calls = provider.compute.calls.lock().expect("calls lock").clone();
+        assert_eq!(calls[1..], ["kill hanging SIGKILL", "delete hanging"]);
+        // The rollback also removed the sandbox branch.
+        assert!(provider.scm.delete_branch("slow-setup").await.is_err());
+    }
+
     #[tokio::test]
     async fn create_provisions_container() -> Result<(), Box<dyn std::error::Error>> {
         if std::env::var("LITTERBOX_DOCKER_TESTS").is_err() {
@@ -523,7 +1400,14 @@ mod tests {
                 &SandboxConfig {
                     image: "busybox:latest".to_string(),
                     setup_command: None,
+                    setup_timeout_secs: None,
                     forwarded_ports: Vec::new(),
+                    port_range: None,
+                    ready_command: None,
+                    from_ref: None,
+                    restart_policy: None,
+                    extra_env: Vec::new(),
+                    network: None,
                 },
             )
             .await?;
@@ -545,7 +1429,62 @@ mod tests {
                 }),
             )
             .await;
-        let _ = provider.scm.delete_branch(&metadata.name);
+        let _ = provider.scm.delete_branch(&metadata.name).await;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_attaches_container_to_network() -> Result<(), Box<dyn std::error::Error>> {
+        if std::env::var("LITTERBOX_DOCKER_TESTS").is_err() {
+            return Ok(());
+        }
+
+        let (tempdir, _repo) = init_repo();
+        let scm = ThreadSafeScm::open(tempdir.path())?;
+        let compute = DockerCompute::connect()?;
+        let provider = DockerSandboxProvider::new(scm, compute);
+
+        let suffix = unique_suffix();
+        let network = format!("litterbox-test-{suffix}");
+        let name = format!("sandbox-{suffix}");
+        let metadata = provider
+            .create(
+                &name,
+                &SandboxConfig {
+                    image: "busybox:latest".to_string(),
+                    setup_command: None,
+                    setup_timeout_secs: None,
+                    forwarded_ports: Vec::new(),
+                    port_range: None,
+                    ready_command: None,
+                    from_ref: None,
+                    restart_policy: None,
+                    extra_env: Vec::new(),
+                    network: Some(network.clone()),
+                },
+            )
+            .await?;
+
+        let client = provider.compute.client();
+        let container = client.inspect_container(&metadata.container_id, None).await?;
+        let networks = container
+            .network_settings
+            .and_then(|settings| settings.networks)
+            .unwrap_or_default();
+        assert!(networks.contains_key(&network));
+
+        let _ = client
+            .remove_container(
+                &metadata.container_id,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await;
+        let _ = client.remove_network(&network).await;
+        let _ = provider.scm.delete_branch(&metadata.name).await;
 
         Ok(())
     }
@@ -568,10 +1507,18 @@ mod tests {
                 &SandboxConfig {
                     image: "busybox:latest".to_string(),
                     setup_command: None,
+                    setup_timeout_secs: None,
                     forwarded_ports: vec![ForwardedPort {
                         name: "web".to_string(),
                         target: 8080,
+                        protocol: PortProtocol::Tcp,
                     }],
+                    port_range: None,
+                    ready_command: None,
+                    from_ref: None,
+                    restart_policy: None,
+                    extra_env: Vec::new(),
+                    network: None,
                 },
             )
             .await?;
@@ -613,7 +1560,7 @@ mod tests {
                 }),
             )
             .await;
-        let _ = provider.scm.delete_branch(&metadata.name);
+        let _ = provider.scm.delete_branch(&metadata.name).await;
 
         Ok(())
     }
@@ -636,7 +1583,14 @@ mod tests {
                 &SandboxConfig {
                     image: "busybox:latest".to_string(),
                     setup_command: None,
+                    setup_timeout_secs: None,
                     forwarded_ports: Vec::new(),
+                    port_range: None,
+                    ready_command: None,
+                    from_ref: None,
+                    restart_policy: None,
+                    extra_env: Vec::new(),
+                    network: None,
                 },
             )
             .await?;
@@ -687,7 +1641,14 @@ mod tests {
                 &SandboxConfig {
                     image: "busybox:latest".to_string(),
                     setup_command: None,
+                    setup_timeout_secs: None,
                     forwarded_ports: Vec::new(),
+                    port_range: None,
+                    ready_command: Some("test -d /src".to_string()),
+                    from_ref: None,
+                    restart_policy: None,
+                    extra_env: Vec::new(),
+                    network: None,
                 },
             )
             .await?;
@@ -700,10 +1661,12 @@ mod tests {
                     "-c".to_string(),
                     "echo hello".to_string(),
                 ],
+                None,
             )
             .await?;
         assert_eq!(result.exit_code, 0);
         assert!(result.stdout.contains("hello"));
+        assert!(result.duration_ms.is_some());
 
         let failure = provider
             .shell(
@@ -713,6 +1676,7 @@ mod tests {
                     "-c".to_string(),
                     "ls /does-not-exist".to_string(),
                 ],
+                None,
             )
             .await?;
         assert_ne!(failure.exit_code, 0);
@@ -722,4 +1686,152 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn for_sandbox_keeps_the_workdir_the_sandbox_was_created_with()
+    -> Result<(), Box<dyn std::error::Error>> {
+        if std::env::var("LITTERBOX_DOCKER_TESTS").is_err() {
+            return Ok(());
+        }
+
+        let (tempdir, _repo) = init_repo();
+        let scm = ThreadSafeScm::open(tempdir.path())?;
+        let provider =
+            DockerSandboxProvider::new(scm, DockerCompute::connect()?).with_workdir("/workspace");
+
+        let name = format!("sandbox-{}", unique_suffix());
+        let metadata = provider
+            .create(
+                &name,
+                &SandboxConfig {
+                    image: "busybox:latest".to_string(),
+                    setup_command: None,
+                    setup_timeout_secs: None,
+                    forwarded_ports: Vec::new(),
+                    port_range: None,
+                    ready_command: None,
+                    from_ref: None,
+                    restart_policy: None,
+                    extra_env: Vec::new(),
+                    network: None,
+                },
+            )
+            .await?;
+
+        let scm = ThreadSafeScm::open(tempdir.path())?;
+        let reconfigured = DockerSandboxProvider::new(scm, DockerCompute::connect()?)
+            .for_sandbox(&metadata)
+            .await?;
+        assert_eq!(reconfigured.workdir(), "/workspace");
+
+        provider.delete(&metadata).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn wait_for_ready_gives_up_on_a_hanging_probe()
+    -> Result<(), Box<dyn std::error::Error>> {
+        if std::env::var("LITTERBOX_DOCKER_TESTS").is_err() {
+            return Ok(());
+        }
+
+        let (tempdir, _repo) = init_repo();
+        let scm = ThreadSafeScm::open(tempdir.path())?;
+        let provider = DockerSandboxProvider::new(scm, DockerCompute::connect()?);
+
+        let name = format!("sandbox-{}", unique_suffix());
+        let metadata = provider
+            .create(
+                &name,
+                &SandboxConfig {
+                    image: "busybox:latest".to_string(),
+                    setup_command: None,
+                    setup_timeout_secs: None,
+                    forwarded_ports: Vec::new(),
+                    port_range: None,
+                    ready_command: None,
+                    from_ref: None,
+                    restart_policy: None,
+                    extra_env: Vec::new(),
+                    network: None,
+                },
+            )
+            .await?;
+
+        let started = Instant::now();
+        let result = provider.wait_for_ready(&metadata, "sleep 60", 1, 100).await;
+        assert!(matches!(result, Err(SandboxError::NotReady { .. })));
+        assert!(started.elapsed() < Duration::from_secs(30));
+
+        provider.delete(&metadata).await?;
+        Ok(())
+    }
+
+    #[test]
+    fn shutdown_signal_exit_status_is_128_plus_signal_number() {
+        assert_eq!(ShutdownSignal::Interrupt.exit_status(), 130);
+        assert_eq!(ShutdownSignal::Terminate.exit_status(), 143);
+    }
+
+    #[test]
+    fn forwarded_ports_from_inspection_builds_mapping() {
+        let inspection = ContainerInspection {
+            env: vec!["LITTERBOX_FWD_PORT_WEB=3001".to_string()],
+            port_bindings: HashMap::from([(
+                "8080/tcp".to_string(),
+                vec![PortBindingSpec {
+                    host_ip: Some("0.0.0.0".to_string()),
+                    host_port: Some("3001".to_string()),
+                }],
+            )]),
+            ip_address: None,
+            image_digest: None,
+            started_at: None,
+            status: SandboxStatus::Active,
+            network_aliases: Vec::new(),
+            working_dir: None,
+        };
+
+        let mappings = forwarded_ports_from_inspection(&inspection);
+
+        assert_eq!(mappings.len(), 1);
+        assert_eq!(mappings[0].name, "web");
+        assert_eq!(mappings[0].target, 8080);
+        assert_eq!(mappings[0].host_port, 3001);
+        assert_eq!(mappings[0].env_var, "LITTERBOX_FWD_PORT_WEB");
+    }
+
+    #[test]
+    fn metadata_with_inspection_takes_live_status() {
+        let metadata = SandboxMetadata {
+            name: "feature".to_string(),
+            branch_name: branch_name_for_slug("feature"),
+            container_id: "litterbox-repo-feature".to_string(),
+            status: SandboxStatus::Active,
+            forwarded_ports: Vec::new(),
+            created_at: Some(1),
+            last_active_at: None,
+            container_ip: None,
+            network_aliases: Vec::new(),
+            image_digest: None,
+            started_at: None,
+        };
+        let inspection = ContainerInspection {
+            env: Vec::new(),
+            port_bindings: HashMap::new(),
+            ip_address: Some("172.17.0.2".to_string()),
+            image_digest: Some("sha256:abc".to_string()),
+            started_at: None,
+            status: SandboxStatus::Paused,
+            network_aliases: vec!["feature".to_string()],
+            working_dir: Some("/src".to_string()),
+        };
+
+        let metadata = metadata_with_inspection(metadata, &inspection);
+
+        assert_eq!(metadata.status, SandboxStatus::Paused);
+        assert_eq!(metadata.container_ip.as_deref(), Some("172.17.0.2"));
+        assert_eq!(metadata.image_digest.as_deref(), Some("sha256:abc"));
+        assert_eq!(metadata.network_aliases, ["feature"]);
+        assert_eq!(metadata.created_at, Some(1));
+    }
 }
@@ -1,20 +1,160 @@
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
+use futures_util::future::BoxFuture;
 use git2::{BranchType, IndexAddOption, ObjectType, Repository, StatusOptions};
-
-use crate::domain::{SandboxError, ScmError, slugify};
+use glob::Pattern;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::domain::{
+    MergeResult, MergeStrategy, PruneResult, SandboxEntry, SandboxError, ScmError, SnapshotInfo,
+    slugify,
+};
+
+const ARCHIVE_ZSTD_LEVEL: i32 = 3;
+/// How many recent snapshot commits a new snapshot tree is compared against.
+pub const DEFAULT_SNAPSHOT_DEDUP_DEPTH: usize = 5;
+/// How recent an older snapshot must be to count as a repeat of a new one.
+const SNAPSHOT_DEDUP_WINDOW_SECS: i64 = 60;
+pub const LITTERBOX_IGNORE_FILE: &str = ".litterboxignore";
+const SYMLINK_FILEMODE: i32 = 0o120000;
 
 pub trait Scm {
     fn create_branch(&self, slug: &str) -> Result<String, SandboxError>;
+    /// Like [`Scm::create_branch`], but starts the sandbox branch at `from_ref` instead of HEAD.
+    fn create_branch_at(&self, slug: &str, from_ref: &str) -> Result<String, SandboxError>;
     fn delete_branch(&self, slug: &str) -> Result<(), SandboxError>;
-    fn make_archive(&self, reference: &str) -> Result<Vec<u8>, SandboxError>;
+    fn rename_branch(&self, old_slug: &str, new_slug: &str) -> Result<String, SandboxError>;
+    /// Create a plain branch named `branch_name` at the tip of the sandbox branch for `slug`.
+    fn create_branch_from(&self, slug: &str, branch_name: &str) -> Result<String, SandboxError>;
+    /// Push the local branch `branch_name` to the branch of the same name on `origin`.
+    fn push_branch(&self, branch_name: &str) -> Result<(), SandboxError>;
+    /// Fold the sandbox branch for `source_slug` into the local branch `target_ref` names.
+    ///
+    /// A conflicting merge leaves the target untouched and reports `had_conflicts`.
+    fn merge_branch(
+        &self,
+        source_slug: &str,
+        target_ref: &str,
+        strategy: MergeStrategy,
+    ) -> Result<MergeResult, SandboxError>;
+    /// Build a tar of `reference`'s tree, zstd-compressed when `compressed` is set.
+    fn make_archive(&self, reference: &str, compressed: bool) -> Result<Vec<u8>, SandboxError>;
     fn list_sandboxes(&self) -> Result<Vec<String>, SandboxError>;
+    /// Like [`Scm::list_sandboxes`], with each sandbox's snapshot history summarised.
+    fn list_sandboxes_with_metadata(&self) -> Result<Vec<SandboxEntry>, SandboxError>;
     fn repo_prefix(&self) -> Result<String, SandboxError>;
     fn has_changes(&self) -> Result<bool, SandboxError>;
+    /// Like [`Scm::has_changes`], limited to files matching any of the `paths` pathspecs.
+    fn has_changes_in(&self, paths: &[&str]) -> Result<bool, SandboxError>;
     fn stage_all(&self) -> Result<(), SandboxError>;
+    /// Stage all changes except paths matching any of the `exclude` glob patterns.
+    fn stage_all_except(&self, exclude: &[&str]) -> Result<(), SandboxError>;
     fn commit_snapshot(&self, message: &str) -> Result<Option<git2::Oid>, SandboxError>;
+    /// Like [`Scm::commit_snapshot`], taking only `paths` from the working tree.
+    fn commit_snapshot_paths(
+        &self,
+        paths: &[&str],
+        message: &str,
+    ) -> Result<Option<git2::Oid>, SandboxError>;
     fn apply_patch(&self, diff: &str) -> Result<(), SandboxError>;
+    /// Check that `diff` applies cleanly to the snapshot branch, or HEAD before the branch
+    /// exists, without touching the working directory.
+    fn apply_patch_dry_run(&self, diff: &str) -> Result<(), SandboxError>;
+    /// Replay the changes of `commit_oid` as a new commit on the snapshot branch.
+    ///
+    /// Returns `None` when the branch already contains the commit or its changes.
+    fn cherry_pick(&self, commit_oid: &str) -> Result<Option<git2::Oid>, SandboxError>;
+    /// Render a unified diff from `from` to `to`, optionally limited to `path_filter`.
+    ///
+    /// Returns `None` when `to` does not resolve to a commit yet.
+    fn diff_between(
+        &self,
+        from: &str,
+        to: &str,
+        path_filter: Option<&str>,
+    ) -> Result<Option<String>, SandboxError>;
+    /// List snapshot commits on a sandbox branch that are not reachable from HEAD, newest first.
+    fn list_snapshots(
+        &self,
+        slug: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<SnapshotInfo>, SandboxError>;
+    /// Set aside uncommitted changes to tracked files, leaving the working directory at HEAD.
+    fn stash(&self, message: &str) -> Result<git2::Oid, SandboxError>;
+    /// Reapply the most recent stash and drop it.
+    fn stash_pop(&self) -> Result<(), SandboxError>;
+}
+
+/// Async counterpart of [`Scm`] for implementations that can move git work off the runtime threads.
+pub trait AsyncScm {
+    fn create_branch<'a>(&'a self, slug: &'a str) -> BoxFuture<'a, Result<String, SandboxError>>;
+    fn create_branch_at<'a>(
+        &'a self,
+        slug: &'a str,
+        from_ref: &'a str,
+    ) -> BoxFuture<'a, Result<String, SandboxError>>;
+    fn delete_branch<'a>(&'a self, slug: &'a str) -> BoxFuture<'a, Result<(), SandboxError>>;
+    fn rename_branch<'a>(
+        &'a self,
+        old_slug: &'a str,
+        new_slug: &'a str,
+    ) -> BoxFuture<'a, Result<String, SandboxError>>;
+    fn create_branch_from<'a>(
+        &'a self,
+        slug: &'a str,
+        branch_name: &'a str,
+    ) -> BoxFuture<'a, Result<String, SandboxError>>;
+    fn push_branch<'a>(&'a self, branch_name: &'a str) -> BoxFuture<'a, Result<(), SandboxError>>;
+    fn merge_branch<'a>(
+        &'a self,
+        source_slug: &'a str,
+        target_ref: &'a str,
+        strategy: MergeStrategy,
+    ) -> BoxFuture<'a, Result<MergeResult, SandboxError>>;
+    fn make_archive<'a>(
+        &'a self,
+        reference: &'a str,
+        compressed: bool,
+    ) -> BoxFuture<'a, Result<Vec<u8>, SandboxError>>;
+    fn list_sandboxes(&self) -> BoxFuture<'_, Result<Vec<String>, SandboxError>>;
+    fn list_sandboxes_with_metadata(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<SandboxEntry>, SandboxError>>;
+    fn repo_prefix(&self) -> BoxFuture<'_, Result<String, SandboxError>>;
+    fn has_changes(&self) -> BoxFuture<'_, Result<bool, SandboxError>>;
+    fn has_changes_in<'a>(&'a self, paths: &'a [&'a str]) -> BoxFuture<'a, Result<bool, SandboxError>>;
+    fn stage_all(&self) -> BoxFuture<'_, Result<(), SandboxError>>;
+    fn stage_all_except<'a>(&'a self, exclude: &'a [&'a str]) -> BoxFuture<'a, Result<(), SandboxError>>;
+    fn commit_snapshot<'a>(
+        &'a self,
+        message: &'a str,
+    ) -> BoxFuture<'a, Result<Option<git2::Oid>, SandboxError>>;
+    fn commit_snapshot_paths<'a>(
+        &'a self,
+        paths: &'a [&'a str],
+        message: &'a str,
+    ) -> BoxFuture<'a, Result<Option<git2::Oid>, SandboxError>>;
+    fn apply_patch<'a>(&'a self, diff: &'a str) -> BoxFuture<'a, Result<(), SandboxError>>;
+    fn apply_patch_dry_run<'a>(&'a self, diff: &'a str) -> BoxFuture<'a, Result<(), SandboxError>>;
+    fn cherry_pick<'a>(
+        &'a self,
+        commit_oid: &'a str,
+    ) -> BoxFuture<'a, Result<Option<git2::Oid>, SandboxError>>;
+    fn diff_between<'a>(
+        &'a self,
+        from: &'a str,
+        to: &'a str,
+        path_filter: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Option<String>, SandboxError>>;
+    fn list_snapshots<'a>(
+        &'a self,
+        slug: &'a str,
+        limit: Option<usize>,
+    ) -> BoxFuture<'a, Result<Vec<SnapshotInfo>, SandboxError>>;
+    fn stash<'a>(&'a self, message: &'a str) -> BoxFuture<'a, Result<git2::Oid, SandboxError>>;
+    fn stash_pop(&self) -> BoxFuture<'_, Result<(), SandboxError>>;
 }
 
 pub struct GitScm {
@@ -23,8 +163,11 @@ pub struct GitScm {
 }
 
 impl GitScm {
+    /// Opens the repository containing `path`, searching its parent directories like `git`
+    /// does. Bare repositories are accepted: archives and staged snapshots read from the object
+    /// database, but working-tree operations are refused.
     pub fn open(path: &Path) -> Result<Self, SandboxError> {
-        Repository::open(path)
+        Repository::discover(path)
             .map(|repo| Self {
                 repo,
                 snapshot_branch: None,
@@ -51,6 +194,79 @@ impl GitScm {
         repo_prefix_from_path(&self.repo_root())
     }
 
+    /// Returns the working directory, or a configuration error naming `operation` when the
+    /// repository is bare.
+    fn require_workdir(&self, operation: &str) -> Result<&Path, SandboxError> {
+        self.repo.workdir().ok_or_else(|| {
+            SandboxError::Config(format!(
+                "bare repository: {operation} needs a working tree; \
+                 run litterbox from a non-bare clone of {}",
+                self.repo.path().display()
+            ))
+        })
+    }
+
+    /// Paths the index records as conflicted, followed by those whose changes in `diff` do
+    /// not apply to the working tree on their own.
+    fn conflicted_files(&self, diff: &git2::Diff<'_>) -> Vec<String> {
+        let mut conflicted: Vec<String> = self
+            .repo
+            .statuses(None)
+            .map(|statuses| {
+                statuses
+                    .iter()
+                    .filter(|entry| entry.status().is_conflicted())
+                    .filter_map(|entry| entry.path().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for (position, delta) in diff.deltas().enumerate() {
+            let Some(path) = delta.new_file().path().or(delta.old_file().path()) else {
+                continue;
+            };
+            let path = path.to_string_lossy().into_owned();
+            let mut seen = 0;
+            let mut options = git2::ApplyOptions::new();
+            options.check(true).delta_callback(|_| {
+                let only_this_delta = seen == position;
+                seen += 1;
+                only_this_delta
+            });
+            let applies = self
+                .repo
+                .apply(diff, git2::ApplyLocation::WorkDir, Some(&mut options))
+                .is_ok();
+            if !applies && !conflicted.contains(&path) {
+                conflicted.push(path);
+            }
+        }
+        conflicted
+    }
+
+    fn create_sandbox_branch(
+        &self,
+        slug: &str,
+        start: &git2::Commit<'_>,
+    ) -> Result<String, SandboxError> {
+        let branch_name = Self::branch_name(slug);
+        if self
+            .repo
+            .find_branch(&branch_name, BranchType::Local)
+            .is_ok()
+        {
+            return Err(SandboxError::SandboxExists {
+                name: slug.to_string(),
+            });
+        }
+
+        self.repo
+            .branch(&branch_name, start, false)
+            .map_err(|source| SandboxError::Scm(ScmError::BranchCreate { source }))?;
+
+        Ok(branch_name)
+    }
+
     fn head_commit(&self) -> Result<git2::Commit<'_>, SandboxError> {
         let head = self
             .repo
@@ -117,30 +333,61 @@ impl GitScm {
         }
     }
 
-    fn append_tree(
+    /// Loads `.litterboxignore` from the repository root, if present.
+    fn archive_ignore(&self) -> Result<Option<Gitignore>, SandboxError> {
+        let root = self.repo_root();
+        let path = root.join(LITTERBOX_IGNORE_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let mut builder = GitignoreBuilder::new(&root);
+        if let Some(error) = builder.add(&path) {
+            return Err(SandboxError::Config(format!("{LITTERBOX_IGNORE_FILE}: {error}")));
+        }
+        builder
+            .build()
+            .map(Some)
+            .map_err(|error| SandboxError::Config(format!("{LITTERBOX_IGNORE_FILE}: {error}")))
+    }
+
+    fn append_tree<W: Write>(
         repo: &Repository,
-        builder: &mut tar::Builder<Vec<u8>>,
+        builder: &mut tar::Builder<W>,
         tree: &git2::Tree<'_>,
         base: &Path,
+        ignore: &Option<Gitignore>,
     ) -> Result<(), SandboxError> {
         for entry in tree.iter() {
-            Self::append_entry(repo, builder, base, &entry)?;
+            Self::append_entry(repo, builder, base, &entry, ignore)?;
         }
 
         Ok(())
     }
 
-    fn append_entry(
+    fn append_entry<W: Write>(
         repo: &Repository,
-        builder: &mut tar::Builder<Vec<u8>>,
+        builder: &mut tar::Builder<W>,
         base: &Path,
         entry: &git2::TreeEntry<'_>,
+        ignore: &Option<Gitignore>,
     ) -> Result<(), SandboxError> {
         let name = entry
             .name()
             .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid path"))?;
         let path = base.join(PathBuf::from(name));
 
+        // `.git` is never shipped, whatever the ignore file says.
+        if name == ".git" {
+            return Ok(());
+        }
+        let is_dir = entry.kind() == Some(ObjectType::Tree);
+        if let Some(ignore) = ignore
+            && ignore.matched(&path, is_dir).is_ignore()
+        {
+            return Ok(());
+        }
+
         match entry.kind() {
             Some(ObjectType::Tree) => {
                 let subtree = entry
@@ -148,16 +395,46 @@ impl GitScm {
                     .map_err(|source| SandboxError::Scm(ScmError::Archive { source }))?
                     .peel_to_tree()
                     .map_err(|source| SandboxError::Scm(ScmError::Archive { source }))?;
-                Self::append_tree(repo, builder, &subtree, &path)
+                Self::append_tree(repo, builder, &subtree, &path, ignore)
+            }
+            Some(ObjectType::Blob) if entry.filemode() == SYMLINK_FILEMODE => {
+                Self::append_symlink(repo, builder, &path, entry)
             }
             Some(ObjectType::Blob) => Self::append_blob(repo, builder, &path, entry),
+            // Submodules are recorded as commits and have no content in this repository.
+            Some(ObjectType::Commit) => Ok(()),
             _ => Ok(()),
         }
     }
 
-    fn append_blob(
+    fn append_symlink<W: Write>(
+        repo: &Repository,
+        builder: &mut tar::Builder<W>,
+        path: &Path,
+        entry: &git2::TreeEntry<'_>,
+    ) -> Result<(), SandboxError> {
+        let blob = entry
+            .to_object(repo)
+            .map_err(|source| SandboxError::Scm(ScmError::Archive { source }))?
+            .peel_to_blob()
+            .map_err(|source| SandboxError::Scm(ScmError::Archive { source }))?;
+        // Git stores a symlink as a blob whose content is the link target.
+        let target = String::from_utf8_lossy(blob.content()).to_string();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        header.set_link_name(&target)?;
+        header.set_cksum();
+
+        builder.append_data(&mut header, path, std::io::empty())?;
+        Ok(())
+    }
+
+    fn append_blob<W: Write>(
         repo: &Repository,
-        builder: &mut tar::Builder<Vec<u8>>,
+        builder: &mut tar::Builder<W>,
         path: &Path,
         entry: &git2::TreeEntry<'_>,
     ) -> Result<(), SandboxError> {
@@ -183,24 +460,57 @@ impl GitScm {
     }
 }
 
+/// Where a [`ThreadSafeScm`] opens its repository handles from.
+struct ScmLocation {
+    path: PathBuf,
+    snapshot_branch: Option<String>,
+}
+
+impl ScmLocation {
+    fn open(&self) -> Result<GitScm, SandboxError> {
+        let mut scm = GitScm::open(&self.path)?;
+        scm.snapshot_branch = self.snapshot_branch.clone();
+        Ok(scm)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum LockMode {
+    Read,
+    Write,
+}
+
+/// A [`GitScm`] held under its lock guard, so the guard outlives every use of the handle.
+struct LockedScm<G> {
+    scm: GitScm,
+    _guard: G,
+}
+
+impl<G> std::ops::Deref for LockedScm<G> {
+    type Target = GitScm;
+
+    fn deref(&self) -> &GitScm {
+        &self.scm
+    }
+}
+
+/// Shares a repository between threads. Read-only operations run concurrently under a read
+/// lock; operations that move refs or touch the index and working tree take the write lock.
+///
+/// libgit2 repository handles are `Send` but not `Sync`, so each operation opens its own
+/// handle rather than sharing one behind the lock.
 pub struct ThreadSafeScm {
-    inner: Mutex<GitScm>,
+    inner: Arc<RwLock<ScmLocation>>,
     prefix_override: Option<String>,
 }
 
 impl ThreadSafeScm {
     pub fn open(path: &Path) -> Result<Self, SandboxError> {
-        GitScm::open(path).map(|scm| Self {
-            inner: Mutex::new(scm),
-            prefix_override: None,
-        })
+        Self::open_with_prefix(path, None)
     }
 
     pub fn open_with_prefix(path: &Path, prefix: Option<String>) -> Result<Self, SandboxError> {
-        GitScm::open(path).map(|scm| Self {
-            inner: Mutex::new(scm),
-            prefix_override: prefix,
-        })
+        Self::from_location(path, prefix, None)
     }
 
     pub fn for_sandbox(
@@ -208,12 +518,23 @@ impl ThreadSafeScm {
         prefix: Option<String>,
         sandbox_slug: &str,
     ) -> Result<Self, SandboxError> {
-        let mut scm = GitScm::open(path)?;
-        let branch_name = GitScm::branch_name(sandbox_slug);
-        scm.set_snapshot_branch(branch_name);
+        Self::from_location(path, prefix, Some(GitScm::branch_name(sandbox_slug)))
+    }
+
+    fn from_location(
+        path: &Path,
+        prefix: Option<String>,
+        snapshot_branch: Option<String>,
+    ) -> Result<Self, SandboxError> {
+        let location = ScmLocation {
+            path: path.to_path_buf(),
+            snapshot_branch,
+        };
+        // Fail up front on a path that is not a repository.
+        location.open()?;
 
         Ok(Self {
-            inner: Mutex::new(scm),
+            inner: Arc::new(RwLock::new(location)),
             prefix_override: prefix,
         })
     }
@@ -222,410 +543,2170 @@ impl ThreadSafeScm {
         &self,
         staging_path: &Path,
         message: &str,
+        max_dedup_depth: usize,
     ) -> Result<Option<git2::Oid>, SandboxError> {
-        self.lock()?
-            .commit_snapshot_from_staging(staging_path, message)
+        self.write()?
+            .commit_snapshot_from_staging(staging_path, message, max_dedup_depth)
     }
 
-    fn lock(&self) -> Result<std::sync::MutexGuard<'_, GitScm>, SandboxError> {
-        self.inner
-            .lock()
-            .map_err(|_| SandboxError::Config("Mutex poisoned".to_string()))
+    pub fn branch_tip(&self, slug: &str) -> Result<git2::Oid, SandboxError> {
+        self.read()?.branch_tip(slug)
     }
-}
 
-impl Scm for ThreadSafeScm {
-    fn create_branch(&self, slug: &str) -> Result<String, SandboxError> {
-        self.lock()?.create_branch(slug)
+    pub fn branch_times(&self, slug: &str) -> Result<(Option<u64>, Option<u64>), SandboxError> {
+        self.read()?.branch_times(slug)
     }
 
-    fn delete_branch(&self, slug: &str) -> Result<(), SandboxError> {
-        self.lock()?.delete_branch(slug)
+    pub fn base_commit(&self, slug: &str) -> Result<Option<git2::Oid>, SandboxError> {
+        self.read()?.base_commit(slug)
     }
 
-    fn make_archive(&self, reference: &str) -> Result<Vec<u8>, SandboxError> {
-        self.lock()?.make_archive(reference)
+    pub fn has_commit(&self, reference: &str) -> Result<bool, SandboxError> {
+        Ok(self.read()?.has_commit(reference))
     }
 
-    fn list_sandboxes(&self) -> Result<Vec<String>, SandboxError> {
-        self.lock()?.list_sandboxes()
+    pub fn prune_snapshots(
+        &self,
+        slug: &str,
+        max_age_days: Option<u64>,
+        max_count: Option<usize>,
+    ) -> Result<PruneResult, SandboxError> {
+        self.write()?.prune_snapshots(slug, max_age_days, max_count)
     }
 
-    fn repo_prefix(&self) -> Result<String, SandboxError> {
-        if let Some(ref prefix) = self.prefix_override {
-            Ok(prefix.clone())
-        } else {
-            Ok(self.lock()?.repo_prefix())
-        }
+    /// Like [`Self::commit_snapshot_from_staging`], but runs on the blocking thread pool.
+    pub fn commit_snapshot_from_staging_async(
+        &self,
+        staging_path: &Path,
+        message: &str,
+        max_dedup_depth: usize,
+    ) -> BoxFuture<'static, Result<Option<git2::Oid>, SandboxError>> {
+        let staging_path = staging_path.to_path_buf();
+        let message = message.to_string();
+        self.run_blocking(LockMode::Write, move |scm| {
+            scm.commit_snapshot_from_staging(&staging_path, &message, max_dedup_depth)
+        })
     }
 
-    fn has_changes(&self) -> Result<bool, SandboxError> {
-        self.lock()?.has_changes()
+    /// Like [`Self::commit_snapshot_from_staging_async`], taking only `paths` from
+    /// `staging_path`.
+    pub fn commit_snapshot_paths_from_staging_async(
+        &self,
+        staging_path: &Path,
+        paths: &[&str],
+        message: &str,
+        max_dedup_depth: usize,
+    ) -> BoxFuture<'static, Result<Option<git2::Oid>, SandboxError>> {
+        let staging_path = staging_path.to_path_buf();
+        let paths: Vec<String> = paths.iter().map(|path| path.to_string()).collect();
+        let message = message.to_string();
+        self.run_blocking(LockMode::Write, move |scm| {
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+            scm.commit_snapshot_paths_from_staging(&staging_path, &paths, &message, max_dedup_depth)
+        })
     }
 
-    fn stage_all(&self) -> Result<(), SandboxError> {
-        self.lock()?.stage_all()
+    fn read(&self) -> Result<LockedScm<RwLockReadGuard<'_, ScmLocation>>, SandboxError> {
+        let guard = self.inner.read().map_err(|_| lock_poisoned())?;
+        Ok(LockedScm {
+            scm: guard.open()?,
+            _guard: guard,
+        })
     }
 
-    fn commit_snapshot(&self, message: &str) -> Result<Option<git2::Oid>, SandboxError> {
-        self.lock()?.commit_snapshot(message)
+    fn write(&self) -> Result<LockedScm<RwLockWriteGuard<'_, ScmLocation>>, SandboxError> {
+        let guard = self.inner.write().map_err(|_| lock_poisoned())?;
+        Ok(LockedScm {
+            scm: guard.open()?,
+            _guard: guard,
+        })
     }
 
-    fn apply_patch(&self, diff: &str) -> Result<(), SandboxError> {
-        self.lock()?.apply_patch(diff)
+    fn run_blocking<T, F>(
+        &self,
+        mode: LockMode,
+        f: F,
+    ) -> BoxFuture<'static, Result<T, SandboxError>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&GitScm) -> Result<T, SandboxError> + Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || match mode {
+                LockMode::Read => {
+                    let location = inner.read().map_err(|_| lock_poisoned())?;
+                    f(&location.open()?)
+                }
+                LockMode::Write => {
+                    let location = inner.write().map_err(|_| lock_poisoned())?;
+                    f(&location.open()?)
+                }
+            })
+            .await
+            .map_err(|error| SandboxError::Config(format!("SCM task failed: {error}")))?
+        })
     }
 }
 
-impl Scm for GitScm {
-    fn create_branch(&self, slug: &str) -> Result<String, SandboxError> {
-        let branch_name = Self::branch_name(slug);
-        let head = self.head_commit()?;
+fn lock_poisoned() -> SandboxError {
+    SandboxError::Config("SCM lock poisoned".to_string())
+}
 
-        if self
-            .repo
-            .find_branch(&branch_name, BranchType::Local)
-            .is_ok()
-        {
-            return Err(SandboxError::SandboxExists {
-                name: slug.to_string(),
-            });
-        }
+impl AsyncScm for ThreadSafeScm {
+    fn create_branch<'a>(&'a self, slug: &'a str) -> BoxFuture<'a, Result<String, SandboxError>> {
+        let slug = slug.to_string();
+        self.run_blocking(LockMode::Write, move |scm| scm.create_branch(&slug))
+    }
+
+    fn create_branch_at<'a>(
+        &'a self,
+        slug: &'a str,
+        from_ref: &'a str,
+    ) -> BoxFuture<'a, Result<String, SandboxError>> {
+        let slug = slug.to_string();
+        let from_ref = from_ref.to_string();
+        self.run_blocking(LockMode::Write, move |scm| scm.create_branch_at(&slug, &from_ref))
+    }
+
+    fn delete_branch<'a>(&'a self, slug: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        let slug = slug.to_string();
+        self.run_blocking(LockMode::Write, move |scm| scm.delete_branch(&slug))
+    }
+
+    fn rename_branch<'a>(
+        &'a self,
+        old_slug: &'a str,
+        new_slug: &'a str,
+    ) -> BoxFuture<'a, Result<String, SandboxError>> {
+        let old_slug = old_slug.to_string();
+        let new_slug = new_slug.to_string();
+        self.run_blocking(LockMode::Write, move |scm| scm.rename_branch(&old_slug, &new_slug))
+    }
+
+    fn create_branch_from<'a>(
+        &'a self,
+        slug: &'a str,
+        branch_name: &'a str,
+    ) -> BoxFuture<'a, Result<String, SandboxError>> {
+        let slug = slug.to_string();
+        let branch_name = branch_name.to_string();
+        self.run_blocking(LockMode::Write, move |scm| {
+            scm.create_branch_from(&slug, &branch_name)
+        })
+    }
 
-        self.repo
-            .branch(&branch_name, &head, false)
-            .map_err(|source| SandboxError::Scm(ScmError::BranchCreate { source }))?;
+    fn push_branch<'a>(&'a self, branch_name: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        let branch_name = branch_name.to_string();
+        self.run_blocking(LockMode::Write, move |scm| scm.push_branch(&branch_name))
+    }
 
-        Ok(branch_name)
+    fn merge_branch<'a>(
+        &'a self,
+        source_slug: &'a str,
+        target_ref: &'a str,
+        strategy: MergeStrategy,
+    ) -> BoxFuture<'a, Result<MergeResult, SandboxError>> {
+        let source_slug = source_slug.to_string();
+        let target_ref = target_ref.to_string();
+        self.run_blocking(LockMode::Write, move |scm| {
+            scm.merge_branch(&source_slug, &target_ref, strategy)
+        })
     }
 
-    fn delete_branch(&self, slug: &str) -> Result<(), SandboxError> {
-        let branch_name = Self::branch_name(slug);
+    fn make_archive<'a>(
+        &'a self,
+        reference: &'a str,
+        compressed: bool,
+    ) -> BoxFuture<'a, Result<Vec<u8>, SandboxError>> {
+        let reference = reference.to_string();
+        self.run_blocking(LockMode::Read, move |scm| scm.make_archive(&reference, compressed))
+    }
 
-        let mut branch = self
-            .repo
-            .find_branch(&branch_name, BranchType::Local)
-            .map_err(|_| SandboxError::SandboxNotFound {
-                name: slug.to_string(),
-            })?;
+    fn list_sandboxes(&self) -> BoxFuture<'_, Result<Vec<String>, SandboxError>> {
+        self.run_blocking(LockMode::Read, |scm| scm.list_sandboxes())
+    }
 
-        branch
-            .delete()
-            .map_err(|source| SandboxError::Scm(ScmError::BranchDelete { source }))
+    fn list_sandboxes_with_metadata(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<SandboxEntry>, SandboxError>> {
+        self.run_blocking(LockMode::Read, |scm| scm.list_sandboxes_with_metadata())
     }
 
-    fn make_archive(&self, reference: &str) -> Result<Vec<u8>, SandboxError> {
-        let tree = self.tree_from_reference(reference)?;
-        let mut builder = tar::Builder::new(Vec::new());
+    fn repo_prefix(&self) -> BoxFuture<'_, Result<String, SandboxError>> {
+        if let Some(prefix) = self.prefix_override.clone() {
+            return Box::pin(async move { Ok(prefix) });
+        }
+        self.run_blocking(LockMode::Read, |scm| Ok(scm.repo_prefix()))
+    }
 
-        Self::append_tree(&self.repo, &mut builder, &tree, Path::new(""))?;
+    fn has_changes(&self) -> BoxFuture<'_, Result<bool, SandboxError>> {
+        self.run_blocking(LockMode::Read, |scm| scm.has_changes())
+    }
 
-        builder.into_inner().map_err(SandboxError::Io)
+    fn has_changes_in<'a>(&'a self, paths: &'a [&'a str]) -> BoxFuture<'a, Result<bool, SandboxError>> {
+        let paths: Vec<String> = paths.iter().map(|path| path.to_string()).collect();
+        self.run_blocking(LockMode::Read, move |scm| {
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+            scm.has_changes_in(&paths)
+        })
     }
 
-    fn list_sandboxes(&self) -> Result<Vec<String>, SandboxError> {
-        let mut sandboxes = Vec::new();
-        let branches = self
-            .repo
-            .branches(Some(BranchType::Local))
-            .map_err(|source| SandboxError::Scm(ScmError::BranchList { source }))?;
+    fn stage_all(&self) -> BoxFuture<'_, Result<(), SandboxError>> {
+        self.run_blocking(LockMode::Write, |scm| scm.stage_all())
+    }
 
-        for branch in branches {
-            let (branch, _) =
-                branch.map_err(|source| SandboxError::Scm(ScmError::BranchList { source }))?;
-            if let Some(name) = branch.name().ok().flatten()
-                && let Some(slug) = name.strip_prefix("litterbox/")
-            {
-                sandboxes.push(slug.to_string());
-            }
-        }
+    fn stage_all_except<'a>(&'a self, exclude: &'a [&'a str]) -> BoxFuture<'a, Result<(), SandboxError>> {
+        let exclude: Vec<String> = exclude.iter().map(|pattern| pattern.to_string()).collect();
+        self.run_blocking(LockMode::Write, move |scm| {
+            let exclude: Vec<&str> = exclude.iter().map(String::as_str).collect();
+            scm.stage_all_except(&exclude)
+        })
+    }
 
-        Ok(sandboxes)
+    fn commit_snapshot<'a>(
+        &'a self,
+        message: &'a str,
+    ) -> BoxFuture<'a, Result<Option<git2::Oid>, SandboxError>> {
+        let message = message.to_string();
+        self.run_blocking(LockMode::Write, move |scm| scm.commit_snapshot(&message))
+    }
+
+    fn commit_snapshot_paths<'a>(
+        &'a self,
+        paths: &'a [&'a str],
+        message: &'a str,
+    ) -> BoxFuture<'a, Result<Option<git2::Oid>, SandboxError>> {
+        let paths: Vec<String> = paths.iter().map(|path| path.to_string()).collect();
+        let message = message.to_string();
+        self.run_blocking(LockMode::Write, move |scm| {
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+            scm.commit_snapshot_paths(&paths, &message)
+        })
     }
 
-    fn repo_prefix(&self) -> Result<String, SandboxError> {
-        Ok(self.repo_prefix())
+    fn apply_patch<'a>(&'a self, diff: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        let diff = diff.to_string();
+        self.run_blocking(LockMode::Write, move |scm| scm.apply_patch(&diff))
     }
 
-    fn has_changes(&self) -> Result<bool, SandboxError> {
-        let mut status_opts = StatusOptions::new();
-        status_opts.include_untracked(true);
-        status_opts.include_ignored(false);
+    fn apply_patch_dry_run<'a>(&'a self, diff: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        let diff = diff.to_string();
+        self.run_blocking(LockMode::Read, move |scm| scm.apply_patch_dry_run(&diff))
+    }
 
-        let statuses = self
-            .repo
-            .statuses(Some(&mut status_opts))
-            .map_err(|source| SandboxError::Scm(ScmError::Status { source }))?;
+    fn cherry_pick<'a>(
+        &'a self,
+        commit_oid: &'a str,
+    ) -> BoxFuture<'a, Result<Option<git2::Oid>, SandboxError>> {
+        let commit_oid = commit_oid.to_string();
+        self.run_blocking(LockMode::Write, move |scm| scm.cherry_pick(&commit_oid))
+    }
 
-        Ok(!statuses.is_empty())
+    fn diff_between<'a>(
+        &'a self,
+        from: &'a str,
+        to: &'a str,
+        path_filter: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Option<String>, SandboxError>> {
+        let from = from.to_string();
+        let to = to.to_string();
+        let path_filter = path_filter.map(str::to_string);
+        self.run_blocking(LockMode::Read, move |scm| {
+            scm.diff_between(&from, &to, path_filter.as_deref())
+        })
     }
 
-    fn stage_all(&self) -> Result<(), SandboxError> {
-        let mut index = self
-            .repo
-            .index()
-            .map_err(|source| SandboxError::Scm(ScmError::IndexAdd { source }))?;
+    fn list_snapshots<'a>(
+        &'a self,
+        slug: &'a str,
+        limit: Option<usize>,
+    ) -> BoxFuture<'a, Result<Vec<SnapshotInfo>, SandboxError>> {
+        let slug = slug.to_string();
+        self.run_blocking(LockMode::Read, move |scm| scm.list_snapshots(&slug, limit))
+    }
 
-        index
-            .add_all(["*"].iter(), IndexAddOption::DEFAULT, None)
-            .map_err(|source| SandboxError::Scm(ScmError::IndexAdd { source }))?;
+    fn stash<'a>(&'a self, message: &'a str) -> BoxFuture<'a, Result<git2::Oid, SandboxError>> {
+        let message = message.to_string();
+        self.run_blocking(LockMode::Write, move |scm| scm.stash(&message))
+    }
 
-        index
-            .write()
-            .map_err(|source| SandboxError::Scm(ScmError::IndexWrite { source }))
+    fn stash_pop(&self) -> BoxFuture<'_, Result<(), SandboxError>> {
+        self.run_blocking(LockMode::Write, |scm| scm.stash_pop())
     }
+}
 
-    fn commit_snapshot(&self, message: &str) -> Result<Option<git2::Oid>, SandboxError> {
-        let workdir = self.repo.workdir().ok_or_else(|| {
-            SandboxError::Config("Repository has no working directory".to_string())
-        })?;
+/// Exposes a synchronous [`Scm`] through [`AsyncScm`] by running each call inline.
+///
+/// The fallback for implementations that cannot move work onto the blocking pool: each call
+/// still blocks the task that polls it, so prefer [`ThreadSafeScm`] on a shared runtime.
+pub struct BlockingScm<S> {
+    inner: Mutex<S>,
+}
 
-        // Use the same logic as commit_snapshot_from_staging
-        self.commit_snapshot_from_staging(workdir, message)
+impl<S: Scm> BlockingScm<S> {
+    pub fn new(scm: S) -> Self {
+        Self {
+            inner: Mutex::new(scm),
+        }
     }
 
-    fn apply_patch(&self, diff: &str) -> Result<(), SandboxError> {
-        let diff_obj = git2::Diff::from_buffer(diff.as_bytes()).map_err(|e| {
-            SandboxError::Scm(ScmError::ApplyPatch {
-                message: format!("Failed to parse diff: {}", e),
-            })
-        })?;
-
-        self.repo
-            .apply(&diff_obj, git2::ApplyLocation::WorkDir, None)
-            .map_err(|e| {
-                SandboxError::Scm(ScmError::ApplyPatch {
-                    message: format!("Failed to apply patch: {}", e),
-                })
-            })
+    fn call<T>(&self, f: impl FnOnce(&S) -> Result<T, SandboxError>) -> Result<T, SandboxError> {
+        f(&*self.inner.lock().map_err(|_| lock_poisoned())?)
     }
 }
 
-impl GitScm {
-    fn commit_snapshot_from_staging(
-        &self,
-        staging_path: &Path,
-        message: &str,
-    ) -> Result<Option<git2::Oid>, SandboxError> {
-        let parent = self.snapshot_parent()?;
-        let signature = self.signature()?;
+impl<S: Scm + Send> AsyncScm for BlockingScm<S> {
+    fn create_branch<'a>(&'a self, slug: &'a str) -> BoxFuture<'a, Result<String, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.create_branch(slug)) })
+    }
 
-        // Backup snapshot branch ref before modification (for atomic recovery)
-        let backup = self.backup_snapshot_ref()?;
+    fn create_branch_at<'a>(
+        &'a self,
+        slug: &'a str,
+        from_ref: &'a str,
+    ) -> BoxFuture<'a, Result<String, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.create_branch_at(slug, from_ref)) })
+    }
 
-        // Build a new tree from staging directory
-        let mut builder = self
-            .repo
-            .treebuilder(None)
-            .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+    fn delete_branch<'a>(&'a self, slug: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.delete_branch(slug)) })
+    }
 
-        self.add_directory_to_tree(&mut builder, staging_path)?;
+    fn rename_branch<'a>(
+        &'a self,
+        old_slug: &'a str,
+        new_slug: &'a str,
+    ) -> BoxFuture<'a, Result<String, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.rename_branch(old_slug, new_slug)) })
+    }
 
-        let tree_oid = builder.write().map_err(|e| {
-            // Restore backup on failure
-            let _ = self.restore_snapshot_ref(&backup);
-            SandboxError::Scm(ScmError::Commit { source: e })
-        })?;
+    fn create_branch_from<'a>(
+        &'a self,
+        slug: &'a str,
+        branch_name: &'a str,
+    ) -> BoxFuture<'a, Result<String, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.create_branch_from(slug, branch_name)) })
+    }
 
-        // Check if tree changed
-        if let Some(ref parent_commit) = parent {
-            if parent_commit.tree_id() == tree_oid {
-                return Ok(None);
-            }
-        } else if tree_oid == git2::Oid::zero() {
-            return Ok(None);
+    fn push_branch<'a>(&'a self, branch_name: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.push_branch(branch_name)) })
+    }
+
+    fn merge_branch<'a>(
+        &'a self,
+        source_slug: &'a str,
+        target_ref: &'a str,
+        strategy: MergeStrategy,
+    ) -> BoxFuture<'a, Result<MergeResult, SandboxError>> {
+        Box::pin(async move {
+            self.call(|scm| scm.merge_branch(source_slug, target_ref, strategy))
+        })
+    }
+
+    fn make_archive<'a>(
+        &'a self,
+        reference: &'a str,
+        compressed: bool,
+    ) -> BoxFuture<'a, Result<Vec<u8>, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.make_archive(reference, compressed)) })
+    }
+
+    fn list_sandboxes(&self) -> BoxFuture<'_, Result<Vec<String>, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.list_sandboxes()) })
+    }
+
+    fn list_sandboxes_with_metadata(
+        &self,
+    ) -> BoxFuture<'_, Result<Vec<SandboxEntry>, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.list_sandboxes_with_metadata()) })
+    }
+
+    fn repo_prefix(&self) -> BoxFuture<'_, Result<String, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.repo_prefix()) })
+    }
+
+    fn has_changes(&self) -> BoxFuture<'_, Result<bool, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.has_changes()) })
+    }
+
+    fn has_changes_in<'a>(&'a self, paths: &'a [&'a str]) -> BoxFuture<'a, Result<bool, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.has_changes_in(paths)) })
+    }
+
+    fn stage_all(&self) -> BoxFuture<'_, Result<(), SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.stage_all()) })
+    }
+
+    fn stage_all_except<'a>(&'a self, exclude: &'a [&'a str]) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.stage_all_except(exclude)) })
+    }
+
+    fn commit_snapshot<'a>(
+        &'a self,
+        message: &'a str,
+    ) -> BoxFuture<'a, Result<Option<git2::Oid>, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.commit_snapshot(message)) })
+    }
+
+    fn commit_snapshot_paths<'a>(
+        &'a self,
+        paths: &'a [&'a str],
+        message: &'a str,
+    ) -> BoxFuture<'a, Result<Option<git2::Oid>, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.commit_snapshot_paths(paths, message)) })
+    }
+
+    fn apply_patch<'a>(&'a self, diff: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.apply_patch(diff)) })
+    }
+
+    fn apply_patch_dry_run<'a>(&'a self, diff: &'a str) -> BoxFuture<'a, Result<(), SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.apply_patch_dry_run(diff)) })
+    }
+
+    fn cherry_pick<'a>(
+        &'a self,
+        commit_oid: &'a str,
+    ) -> BoxFuture<'a, Result<Option<git2::Oid>, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.cherry_pick(commit_oid)) })
+    }
+
+    fn diff_between<'a>(
+        &'a self,
+        from: &'a str,
+        to: &'a str,
+        path_filter: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Option<String>, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.diff_between(from, to, path_filter)) })
+    }
+
+    fn list_snapshots<'a>(
+        &'a self,
+        slug: &'a str,
+        limit: Option<usize>,
+    ) -> BoxFuture<'a, Result<Vec<SnapshotInfo>, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.list_snapshots(slug, limit)) })
+    }
+
+    fn stash<'a>(&'a self, message: &'a str) -> BoxFuture<'a, Result<git2::Oid, SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.stash(message)) })
+    }
+
+    fn stash_pop(&self) -> BoxFuture<'_, Result<(), SandboxError>> {
+        Box::pin(async move { self.call(|scm| scm.stash_pop()) })
+    }
+}
+
+impl Scm for ThreadSafeScm {
+    fn create_branch(&self, slug: &str) -> Result<String, SandboxError> {
+        self.write()?.create_branch(slug)
+    }
+
+    fn create_branch_at(&self, slug: &str, from_ref: &str) -> Result<String, SandboxError> {
+        self.write()?.create_branch_at(slug, from_ref)
+    }
+
+    fn delete_branch(&self, slug: &str) -> Result<(), SandboxError> {
+        self.write()?.delete_branch(slug)
+    }
+
+    fn rename_branch(&self, old_slug: &str, new_slug: &str) -> Result<String, SandboxError> {
+        self.write()?.rename_branch(old_slug, new_slug)
+    }
+
+    fn create_branch_from(&self, slug: &str, branch_name: &str) -> Result<String, SandboxError> {
+        self.write()?.create_branch_from(slug, branch_name)
+    }
+
+    fn push_branch(&self, branch_name: &str) -> Result<(), SandboxError> {
+        self.write()?.push_branch(branch_name)
+    }
+
+    fn merge_branch(
+        &self,
+        source_slug: &str,
+        target_ref: &str,
+        strategy: MergeStrategy,
+    ) -> Result<MergeResult, SandboxError> {
+        self.write()?.merge_branch(source_slug, target_ref, strategy)
+    }
+
+    fn make_archive(&self, reference: &str, compressed: bool) -> Result<Vec<u8>, SandboxError> {
+        self.read()?.make_archive(reference, compressed)
+    }
+
+    fn list_sandboxes(&self) -> Result<Vec<String>, SandboxError> {
+        self.read()?.list_sandboxes()
+    }
+
+    fn list_sandboxes_with_metadata(&self) -> Result<Vec<SandboxEntry>, SandboxError> {
+        self.read()?.list_sandboxes_with_metadata()
+    }
+
+    fn repo_prefix(&self) -> Result<String, SandboxError> {
+        if let Some(ref prefix) = self.prefix_override {
+            Ok(prefix.clone())
+        } else {
+            Ok(self.read()?.repo_prefix())
         }
+    }
 
-        let tree = self.repo.find_tree(tree_oid).map_err(|e| {
-            let _ = self.restore_snapshot_ref(&backup);
-            SandboxError::Scm(ScmError::Commit { source: e })
-        })?;
+    fn has_changes(&self) -> Result<bool, SandboxError> {
+        self.read()?.has_changes()
+    }
 
-        let parents: Vec<&git2::Commit> = parent.as_ref().map(|c| vec![c]).unwrap_or_default();
+    fn has_changes_in(&self, paths: &[&str]) -> Result<bool, SandboxError> {
+        self.read()?.has_changes_in(paths)
+    }
 
-        // Create commit without updating ref (to avoid "current tip is not first parent" when jj modifies branch)
-        let oid = self
+    fn stage_all(&self) -> Result<(), SandboxError> {
+        self.write()?.stage_all()
+    }
+
+    fn stage_all_except(&self, exclude: &[&str]) -> Result<(), SandboxError> {
+        self.write()?.stage_all_except(exclude)
+    }
+
+    fn commit_snapshot(&self, message: &str) -> Result<Option<git2::Oid>, SandboxError> {
+        self.write()?.commit_snapshot(message)
+    }
+
+    fn commit_snapshot_paths(
+        &self,
+        paths: &[&str],
+        message: &str,
+    ) -> Result<Option<git2::Oid>, SandboxError> {
+        self.write()?.commit_snapshot_paths(paths, message)
+    }
+
+    fn apply_patch(&self, diff: &str) -> Result<(), SandboxError> {
+        self.write()?.apply_patch(diff)
+    }
+
+    fn apply_patch_dry_run(&self, diff: &str) -> Result<(), SandboxError> {
+        self.read()?.apply_patch_dry_run(diff)
+    }
+
+    fn cherry_pick(&self, commit_oid: &str) -> Result<Option<git2::Oid>, SandboxError> {
+        self.write()?.cherry_pick(commit_oid)
+    }
+
+    fn diff_between(
+        &self,
+        from: &str,
+        to: &str,
+        path_filter: Option<&str>,
+    ) -> Result<Option<String>, SandboxError> {
+        self.read()?.diff_between(from, to, path_filter)
+    }
+
+    fn list_snapshots(
+        &self,
+        slug: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<SnapshotInfo>, SandboxError> {
+        self.read()?.list_snapshots(slug, limit)
+    }
+
+    fn stash(&self, message: &str) -> Result<git2::Oid, SandboxError> {
+        self.write()?.stash(message)
+    }
+
+    fn stash_pop(&self) -> Result<(), SandboxError> {
+        self.write()?.stash_pop()
+    }
+}
+
+impl Scm for GitScm {
+    fn create_branch(&self, slug: &str) -> Result<String, SandboxError> {
+        let head = self.head_commit()?;
+        self.create_sandbox_branch(slug, &head)
+    }
+
+    fn create_branch_at(&self, slug: &str, from_ref: &str) -> Result<String, SandboxError> {
+        let commit = self
             .repo
-            .commit(
-                None, // Don't update ref yet
-                &signature, &signature, message, &tree, &parents,
-            )
-            .map_err(|e| {
-                let _ = self.restore_snapshot_ref(&backup);
-                SandboxError::Scm(ScmError::Commit { source: e })
+            .revparse_single(from_ref)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|source| SandboxError::Scm(ScmError::BranchCreate { source }))?;
+        self.create_sandbox_branch(slug, &commit)
+    }
+
+    fn delete_branch(&self, slug: &str) -> Result<(), SandboxError> {
+        let branch_name = Self::branch_name(slug);
+
+        let mut branch = self
+            .repo
+            .find_branch(&branch_name, BranchType::Local)
+            .map_err(|_| SandboxError::SandboxNotFound {
+                name: slug.to_string(),
             })?;
 
-        // Force update the ref to point to our new commit (handles concurrent jj updates)
-        // Retry with backoff if locked (jj may be holding the lock)
-        let mut retries = 0;
-        let max_retries = 5;
-        loop {
-            let result = match self.repo.find_reference(&self.snapshot_branch_ref()) {
-                Ok(mut reference) => reference.set_target(oid, message),
-                Err(e) if e.code() == git2::ErrorCode::NotFound => {
-                    self.repo
-                        .reference(&self.snapshot_branch_ref(), oid, false, message)
-                }
-                Err(e) => Err(e),
-            };
+        branch
+            .delete()
+            .map_err(|source| SandboxError::Scm(ScmError::BranchDelete { source }))
+    }
 
-            match result {
-                Ok(_) => break,
-                Err(e) if e.code() == git2::ErrorCode::Locked && retries < max_retries => {
-                    retries += 1;
-                    std::thread::sleep(std::time::Duration::from_millis(10 * retries as u64));
-                    continue;
-                }
-                Err(e) => {
-                    let _ = self.restore_snapshot_ref(&backup);
-                    return Err(SandboxError::Scm(ScmError::Commit { source: e }));
+    fn rename_branch(&self, old_slug: &str, new_slug: &str) -> Result<String, SandboxError> {
+        let new_branch_name = Self::branch_name(new_slug);
+
+        let mut branch = self
+            .repo
+            .find_branch(&Self::branch_name(old_slug), BranchType::Local)
+            .map_err(|_| SandboxError::SandboxNotFound {
+                name: old_slug.to_string(),
+            })?;
+
+        if self
+            .repo
+            .find_branch(&new_branch_name, BranchType::Local)
+            .is_ok()
+        {
+            return Err(SandboxError::SandboxExists {
+                name: new_slug.to_string(),
+            });
+        }
+
+        branch
+            .rename(&new_branch_name, false)
+            .map_err(|source| SandboxError::Scm(ScmError::BranchRename { source }))?;
+
+        Ok(new_branch_name)
+    }
+
+    fn create_branch_from(&self, slug: &str, branch_name: &str) -> Result<String, SandboxError> {
+        let tip = self
+            .repo
+            .find_branch(&Self::branch_name(slug), BranchType::Local)
+            .map_err(|_| SandboxError::SandboxNotFound {
+                name: slug.to_string(),
+            })?
+            .get()
+            .peel_to_commit()
+            .map_err(|source| SandboxError::Scm(ScmError::BranchCreate { source }))?;
+
+        self.repo
+            .branch(branch_name, &tip, false)
+            .map_err(|source| SandboxError::Scm(ScmError::BranchCreate { source }))?;
+
+        Ok(branch_name.to_string())
+    }
+
+    fn push_branch(&self, branch_name: &str) -> Result<(), SandboxError> {
+        let push_error = |source| SandboxError::Scm(ScmError::Push { source });
+        let mut remote = self.repo.find_remote("origin").map_err(push_error)?;
+        let config = self.repo.config().map_err(push_error)?;
+
+        let mut callbacks = git2::RemoteCallbacks::new();
+        callbacks.credentials(|url, username, allowed| {
+            if allowed.contains(git2::CredentialType::SSH_KEY) {
+                git2::Cred::ssh_key_from_agent(username.unwrap_or("git"))
+            } else if allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+                git2::Cred::credential_helper(&config, url, username)
+            } else {
+                git2::Cred::default()
+            }
+        });
+        // The server reports per-ref rejections here rather than failing the push itself.
+        callbacks.push_update_reference(|reference, status| match status {
+            Some(message) => Err(git2::Error::from_str(&format!(
+                "{reference} rejected: {message}"
+            ))),
+            None => Ok(()),
+        });
+        let mut options = git2::PushOptions::new();
+        options.remote_callbacks(callbacks);
+
+        let refspec = format!("refs/heads/{branch_name}:refs/heads/{branch_name}");
+        remote
+            .push(&[refspec.as_str()], Some(&mut options))
+            .map_err(push_error)
+    }
+
+    fn merge_branch(
+        &self,
+        source_slug: &str,
+        target_ref: &str,
+        strategy: MergeStrategy,
+    ) -> Result<MergeResult, SandboxError> {
+        let merge_error = |source| SandboxError::Scm(ScmError::Merge { source });
+        let source = self
+            .repo
+            .find_branch(&Self::branch_name(source_slug), BranchType::Local)
+            .map_err(|_| SandboxError::SandboxNotFound {
+                name: source_slug.to_string(),
+            })?
+            .get()
+            .peel_to_commit()
+            .map_err(merge_error)?;
+
+        // Resolve through symbolic refs so that "HEAD" names the checked-out branch.
+        let target_reference = self
+            .repo
+            .resolve_reference_from_short_name(target_ref)
+            .and_then(|reference| reference.resolve())
+            .map_err(merge_error)?;
+        let target_name = match target_reference.name() {
+            Some(name) if target_reference.is_branch() => name.to_string(),
+            _ => {
+                return Err(SandboxError::Config(format!(
+                    "Merge target '{target_ref}' is not a local branch"
+                )));
+            }
+        };
+        let target = target_reference.peel_to_commit().map_err(merge_error)?;
+        let target_short = target_name.trim_start_matches("refs/heads/");
+
+        let base = self
+            .repo
+            .merge_base(source.id(), target.id())
+            .map_err(merge_error)?;
+        if base == source.id() {
+            // The target already contains everything on the sandbox branch.
+            return Ok(MergeResult {
+                oid: target.id(),
+                had_conflicts: false,
+            });
+        }
+
+        let merged = match strategy {
+            MergeStrategy::FastForward if base == target.id() => source.clone(),
+            MergeStrategy::FastForward => {
+                return Err(SandboxError::Scm(ScmError::NotFastForward {
+                    target: target_short.to_string(),
+                }));
+            }
+            MergeStrategy::Merge | MergeStrategy::Squash => {
+                let mut index = self
+                    .repo
+                    .merge_commits(&target, &source, Some(&git2::MergeOptions::new()))
+                    .map_err(merge_error)?;
+                if index.has_conflicts() {
+                    return Ok(MergeResult {
+                        oid: target.id(),
+                        had_conflicts: true,
+                    });
                 }
+                let tree_id = index.write_tree_to(&self.repo).map_err(merge_error)?;
+                let tree = self.repo.find_tree(tree_id).map_err(merge_error)?;
+                let signature = self.signature()?;
+                let (message, parents) = if strategy == MergeStrategy::Squash {
+                    (
+                        format!("Squash sandbox '{source_slug}' into {target_short}"),
+                        vec![&target],
+                    )
+                } else {
+                    (
+                        format!("Merge sandbox '{source_slug}' into {target_short}"),
+                        vec![&target, &source],
+                    )
+                };
+                let oid = self
+                    .repo
+                    .commit(None, &signature, &signature, &message, &tree, &parents)
+                    .map_err(merge_error)?;
+                self.repo.find_commit(oid).map_err(merge_error)?
+            }
+        };
+
+        // Keep a checked-out target's working tree in step; a safe checkout refuses to
+        // overwrite local modifications, so the ref is only moved once it succeeds.
+        let head_name = self.repo.head().ok().and_then(|head| head.name().map(str::to_string));
+        if !self.repo.is_bare() && head_name.as_deref() == Some(target_name.as_str()) {
+            let mut checkout = git2::build::CheckoutBuilder::new();
+            checkout.safe();
+            self.repo
+                .checkout_tree(merged.as_object(), Some(&mut checkout))
+                .map_err(merge_error)?;
+        }
+        self.repo
+            .reference_matching(
+                &target_name,
+                merged.id(),
+                true,
+                target.id(),
+                &format!("litterbox: merge sandbox '{source_slug}'"),
+            )
+            .map_err(merge_error)?;
+
+        Ok(MergeResult {
+            oid: merged.id(),
+            had_conflicts: false,
+        })
+    }
+
+    fn make_archive(&self, reference: &str, compressed: bool) -> Result<Vec<u8>, SandboxError> {
+        let tree = self.tree_from_reference(reference)?;
+        let ignore = self.archive_ignore()?;
+
+        if compressed {
+            let encoder = zstd::Encoder::new(Vec::new(), ARCHIVE_ZSTD_LEVEL)?;
+            let mut builder = tar::Builder::new(encoder);
+            Self::append_tree(&self.repo, &mut builder, &tree, Path::new(""), &ignore)?;
+            return builder.into_inner()?.finish().map_err(SandboxError::Io);
+        }
+
+        let mut builder = tar::Builder::new(Vec::new());
+
+        Self::append_tree(&self.repo, &mut builder, &tree, Path::new(""), &ignore)?;
+
+        builder.into_inner().map_err(SandboxError::Io)
+    }
+
+    fn list_sandboxes(&self) -> Result<Vec<String>, SandboxError> {
+        let mut sandboxes = Vec::new();
+        let branches = self
+            .repo
+            .branches(Some(BranchType::Local))
+            .map_err(|source| SandboxError::Scm(ScmError::BranchList { source }))?;
+
+        for branch in branches {
+            let (branch, _) =
+                branch.map_err(|source| SandboxError::Scm(ScmError::BranchList { source }))?;
+            if let Some(name) = branch.name().ok().flatten()
+                && let Some(slug) = name.strip_prefix("litterbox/")
+            {
+                sandboxes.push(slug.to_string());
             }
         }
 
-        Ok(Some(oid))
-    }
+        Ok(sandboxes)
+    }
+
+    fn list_sandboxes_with_metadata(&self) -> Result<Vec<SandboxEntry>, SandboxError> {
+        let log_error = |source| SandboxError::Scm(ScmError::Log { source });
+        let head = self.head_commit_optional()?.map(|commit| commit.id());
+
+        self.list_sandboxes()?
+            .into_iter()
+            .map(|slug| {
+                let tip = self.branch_tip(&slug)?;
+                // Snapshots are the sandbox branch's commits that HEAD does not have yet.
+                let mut revwalk = self.repo.revwalk().map_err(log_error)?;
+                revwalk.push(tip).map_err(log_error)?;
+                if let Some(head) = head {
+                    revwalk.hide(head).map_err(log_error)?;
+                }
+                let snapshot_count = revwalk
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(log_error)?
+                    .len();
+                let (snapshot_oid, last_snapshot_time) = if snapshot_count > 0 {
+                    let commit = self.repo.find_commit(tip).map_err(log_error)?;
+                    (Some(tip), Some(commit.time().seconds()))
+                } else {
+                    (None, None)
+                };
+
+                Ok(SandboxEntry {
+                    branch_name: Self::branch_name(&slug),
+                    slug,
+                    snapshot_oid,
+                    last_snapshot_time,
+                    snapshot_count,
+                })
+            })
+            .collect()
+    }
+
+    fn repo_prefix(&self) -> Result<String, SandboxError> {
+        Ok(self.repo_prefix())
+    }
+
+    fn has_changes(&self) -> Result<bool, SandboxError> {
+        self.has_changes_in(&[])
+    }
+
+    fn has_changes_in(&self, paths: &[&str]) -> Result<bool, SandboxError> {
+        self.require_workdir("checking for changes")?;
+        let mut status_opts = StatusOptions::new();
+        status_opts.include_untracked(true);
+        status_opts.include_ignored(false);
+        // A new file inside an untracked directory is otherwise reported as the directory
+        // alone, which a pathspec naming the file would not match.
+        status_opts.recurse_untracked_dirs(!paths.is_empty());
+        for path in paths {
+            status_opts.pathspec(path);
+        }
+
+        let statuses = self
+            .repo
+            .statuses(Some(&mut status_opts))
+            .map_err(|source| SandboxError::Scm(ScmError::Status { source }))?;
+
+        Ok(!statuses.is_empty())
+    }
+
+    fn stage_all(&self) -> Result<(), SandboxError> {
+        self.stage_all_except(&[])
+    }
+
+    fn stage_all_except(&self, exclude: &[&str]) -> Result<(), SandboxError> {
+        let patterns = exclude
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern).map_err(|error| {
+                    SandboxError::Config(format!("Invalid exclude pattern '{pattern}': {error}"))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.require_workdir("staging changes")?;
+        let mut index = self
+            .repo
+            .index()
+            .map_err(|source| SandboxError::Scm(ScmError::IndexAdd { source }))?;
+
+        // A positive return value tells libgit2 to skip the path.
+        let mut skip_excluded = |path: &Path, _matched: &[u8]| -> i32 {
+            i32::from(patterns.iter().any(|pattern| pattern.matches_path(path)))
+        };
+        index
+            .add_all(
+                ["*"].iter(),
+                IndexAddOption::DEFAULT,
+                Some(&mut skip_excluded as &mut git2::IndexMatchedPath),
+            )
+            .map_err(|source| SandboxError::Scm(ScmError::IndexAdd { source }))?;
+
+        index
+            .write()
+            .map_err(|source| SandboxError::Scm(ScmError::IndexWrite { source }))
+    }
+
+    fn commit_snapshot(&self, message: &str) -> Result<Option<git2::Oid>, SandboxError> {
+        let workdir = self.require_workdir("committing a snapshot")?;
+
+        // Use the same logic as commit_snapshot_from_staging
+        self.commit_snapshot_from_staging(workdir, message, 1)
+    }
+
+    fn commit_snapshot_paths(
+        &self,
+        paths: &[&str],
+        message: &str,
+    ) -> Result<Option<git2::Oid>, SandboxError> {
+        let workdir = self.require_workdir("committing a snapshot")?;
+        self.commit_snapshot_paths_from_staging(workdir, paths, message, 1)
+    }
+
+    fn apply_patch(&self, diff: &str) -> Result<(), SandboxError> {
+        let diff_obj = git2::Diff::from_buffer(diff.as_bytes()).map_err(|e| {
+            SandboxError::Scm(ScmError::ApplyPatch {
+                message: format!("Failed to parse diff: {}", e),
+            })
+        })?;
+
+        self.repo
+            .apply(&diff_obj, git2::ApplyLocation::WorkDir, None)
+            .map_err(|e| {
+                let conflicted_files = self.conflicted_files(&diff_obj);
+                if conflicted_files.is_empty() {
+                    SandboxError::Scm(ScmError::ApplyPatch {
+                        message: format!("Failed to apply patch: {}", e),
+                    })
+                } else {
+                    SandboxError::Scm(ScmError::PatchConflict { conflicted_files })
+                }
+            })
+    }
+
+    fn apply_patch_dry_run(&self, diff: &str) -> Result<(), SandboxError> {
+        let diff_obj = git2::Diff::from_buffer(diff.as_bytes()).map_err(|e| {
+            SandboxError::Scm(ScmError::ApplyPatch {
+                message: format!("Failed to parse diff: {}", e),
+            })
+        })?;
+        let tree = match self.snapshot_parent()? {
+            Some(commit) => commit
+                .tree()
+                .map_err(|source| SandboxError::Scm(ScmError::Reference { source }))?,
+            None => self.tree_from_reference("HEAD")?,
+        };
+
+        // Applying to a tree builds the result in memory and leaves the workdir alone.
+        self.repo
+            .apply_to_tree(&tree, &diff_obj, None)
+            .map(|_| ())
+            .map_err(|e| {
+                SandboxError::Scm(ScmError::ApplyPatch {
+                    message: format!("Patch does not apply: {}", e),
+                })
+            })
+    }
+
+    fn cherry_pick(&self, commit_oid: &str) -> Result<Option<git2::Oid>, SandboxError> {
+        let pick_error = |source| SandboxError::Scm(ScmError::CherryPick { source });
+        let commit = self
+            .repo
+            .revparse_single(commit_oid)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(pick_error)?;
+        let onto = self.snapshot_parent()?.ok_or_else(|| {
+            SandboxError::Config("Cannot cherry-pick onto a branch with no commits".to_string())
+        })?;
+        if onto.id() == commit.id()
+            || self
+                .repo
+                .graph_descendant_of(onto.id(), commit.id())
+                .map_err(pick_error)?
+        {
+            return Ok(None);
+        }
+
+        // Picking onto a commit in memory leaves the host's index and working tree alone.
+        let mut index = self
+            .repo
+            .cherrypick_commit(&commit, &onto, 0, None)
+            .map_err(pick_error)?;
+        if index.has_conflicts() {
+            let mut paths = Vec::new();
+            for conflict in index.conflicts().map_err(pick_error)? {
+                let conflict = conflict.map_err(pick_error)?;
+                if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+                    paths.push(String::from_utf8_lossy(&entry.path).into_owned());
+                }
+            }
+            return Err(SandboxError::Scm(ScmError::CherryPickConflict {
+                commit: commit.id().to_string(),
+                conflicted_files: paths,
+            }));
+        }
+        let tree_id = index.write_tree_to(&self.repo).map_err(pick_error)?;
+        if tree_id == onto.tree_id() {
+            return Ok(None);
+        }
+
+        let tree = self.repo.find_tree(tree_id).map_err(pick_error)?;
+        let committer = self.signature()?;
+        let message = format!(
+            "{}\n\n(cherry picked from commit {})",
+            commit.message().unwrap_or_default().trim_end(),
+            commit.id()
+        );
+        self.repo
+            .commit(
+                Some(&self.snapshot_branch_ref()),
+                &commit.author(),
+                &committer,
+                &message,
+                &tree,
+                &[&onto],
+            )
+            .map(Some)
+            .map_err(pick_error)
+    }
+
+    fn diff_between(
+        &self,
+        from: &str,
+        to: &str,
+        path_filter: Option<&str>,
+    ) -> Result<Option<String>, SandboxError> {
+        let to_tree = match self.repo.revparse_single(to) {
+            Ok(object) => object
+                .peel_to_tree()
+                .map_err(|source| SandboxError::Scm(ScmError::Diff { source }))?,
+            Err(error) if error.code() == git2::ErrorCode::NotFound => return Ok(None),
+            Err(source) => return Err(SandboxError::Scm(ScmError::Diff { source })),
+        };
+        let from_tree = self
+            .repo
+            .revparse_single(from)
+            .and_then(|object| object.peel_to_tree())
+            .map_err(|source| SandboxError::Scm(ScmError::Diff { source }))?;
+
+        let mut options = git2::DiffOptions::new();
+        if let Some(path) = path_filter {
+            options.pathspec(path);
+        }
+        let diff = self
+            .repo
+            .diff_tree_to_tree(Some(&from_tree), Some(&to_tree), Some(&mut options))
+            .map_err(|source| SandboxError::Scm(ScmError::Diff { source }))?;
+
+        let mut output = String::new();
+        diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                output.push(line.origin());
+            }
+            output.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })
+        .map_err(|source| SandboxError::Scm(ScmError::Diff { source }))?;
+
+        Ok(Some(output))
+    }
+
+    fn list_snapshots(
+        &self,
+        slug: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<SnapshotInfo>, SandboxError> {
+        let branch_name = Self::branch_name(slug);
+        let branch = self
+            .repo
+            .find_branch(&branch_name, BranchType::Local)
+            .map_err(|_| SandboxError::SandboxNotFound {
+                name: slug.to_string(),
+            })?;
+        let tip = branch
+            .get()
+            .target()
+            .ok_or_else(|| SandboxError::SandboxNotFound {
+                name: slug.to_string(),
+            })?;
+
+        let mut revwalk = self
+            .repo
+            .revwalk()
+            .map_err(|source| SandboxError::Scm(ScmError::Log { source }))?;
+        revwalk
+            .push(tip)
+            .map_err(|source| SandboxError::Scm(ScmError::Log { source }))?;
+        if let Some(head) = self.head_commit_optional()? {
+            revwalk
+                .hide(head.id())
+                .map_err(|source| SandboxError::Scm(ScmError::Log { source }))?;
+        }
+
+        let mut snapshots = Vec::new();
+        for oid in revwalk.take(limit.unwrap_or(usize::MAX)) {
+            let oid = oid.map_err(|source| SandboxError::Scm(ScmError::Log { source }))?;
+            let commit = self
+                .repo
+                .find_commit(oid)
+                .map_err(|source| SandboxError::Scm(ScmError::Log { source }))?;
+            snapshots.push(SnapshotInfo {
+                oid: oid.to_string(),
+                timestamp: commit.time().seconds(),
+                message: commit.summary().unwrap_or("").to_string(),
+            });
+        }
+
+        Ok(snapshots)
+    }
+
+    fn stash(&self, message: &str) -> Result<git2::Oid, SandboxError> {
+        let signature = self.signature()?;
+        let mut repo = self.stash_handle()?;
+        repo.stash_save(&signature, message, None)
+            .map_err(|source| SandboxError::Scm(ScmError::Stash { source }))
+    }
+
+    fn stash_pop(&self) -> Result<(), SandboxError> {
+        let mut repo = self.stash_handle()?;
+        repo.stash_pop(0, None)
+            .map_err(|source| SandboxError::Scm(ScmError::Stash { source }))
+    }
+}
+
+impl GitScm {
+    pub fn branch_tip(&self, slug: &str) -> Result<git2::Oid, SandboxError> {
+        self.repo
+            .find_branch(&Self::branch_name(slug), BranchType::Local)
+            .ok()
+            .and_then(|branch| branch.get().target())
+            .ok_or_else(|| SandboxError::SandboxNotFound {
+                name: slug.to_string(),
+            })
+    }
+
+    /// The commit the sandbox branch for `slug` started from: the one it was created at, or
+    /// its merge-base with HEAD when the branch has no reflog.
+    pub fn base_commit(&self, slug: &str) -> Result<Option<git2::Oid>, SandboxError> {
+        if let Some(base) = self.branch_base(slug) {
+            return Ok(Some(base));
+        }
+        let tip = self.branch_tip(slug)?;
+        let Some(head) = self.head_commit_optional()? else {
+            return Ok(None);
+        };
+        Ok(self.repo.merge_base(tip, head.id()).ok())
+    }
+
+    /// Whether `reference` names a commit present in this repository.
+    pub fn has_commit(&self, reference: &str) -> bool {
+        self.repo
+            .revparse_single(reference)
+            .and_then(|object| object.peel_to_commit())
+            .is_ok()
+    }
+
+    /// Returns `(created_at, last_active_at)` Unix timestamps for a sandbox branch.
+    ///
+    /// Creation time comes from the branch reflog, falling back to the commit the
+    /// branch was created from; last activity is the branch tip commit time.
+    pub fn branch_times(&self, slug: &str) -> Result<(Option<u64>, Option<u64>), SandboxError> {
+        let tip = self.branch_tip(slug)?;
+        let tip_commit = self
+            .repo
+            .find_commit(tip)
+            .map_err(|source| SandboxError::Scm(ScmError::Log { source }))?;
+
+        let reflog_name = format!("refs/heads/{}", Self::branch_name(slug));
+        let created = match self.repo.reflog(&reflog_name) {
+            Ok(reflog) => reflog
+                .iter()
+                .next_back()
+                .map(|entry| entry.committer().when().seconds()),
+            Err(_) => None,
+        };
+        let created = match created {
+            Some(seconds) => Some(seconds),
+            None => self.base_commit_time(tip)?,
+        };
+
+        Ok((
+            created.and_then(|seconds| u64::try_from(seconds).ok()),
+            u64::try_from(tip_commit.time().seconds()).ok(),
+        ))
+    }
+
+    /// Drops old snapshots from the sandbox branch for `slug`, rewriting the newer ones.
+    ///
+    /// Keeps the newest `max_count` snapshots that are also at most `max_age_days` old; a
+    /// missing limit does not restrict. The oldest kept snapshot is re-parented onto the
+    /// branch's base, so its tree still carries every pruned change. When nothing is kept,
+    /// the branch collapses to one squash commit holding the tip's tree, so a sandbox never
+    /// loses its files to pruning.
+    pub fn prune_snapshots(
+        &self,
+        slug: &str,
+        max_age_days: Option<u64>,
+        max_count: Option<usize>,
+    ) -> Result<PruneResult, SandboxError> {
+        let cutoff = max_age_days.map(|days| {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0);
+            i64::try_from(now.saturating_sub(days.saturating_mul(86_400))).unwrap_or(i64::MAX)
+        });
+        self.prune_snapshots_before(slug, cutoff, max_count)
+    }
+
+    fn prune_snapshots_before(
+        &self,
+        slug: &str,
+        cutoff: Option<i64>,
+        max_count: Option<usize>,
+    ) -> Result<PruneResult, SandboxError> {
+        let log_error = |source| SandboxError::Scm(ScmError::Log { source });
+        let commit_error = |source| SandboxError::Scm(ScmError::Commit { source });
+
+        let tip = self.branch_tip(slug)?;
+        let mut revwalk = self.repo.revwalk().map_err(log_error)?;
+        revwalk
+            .set_sorting(git2::Sort::TOPOLOGICAL)
+            .map_err(log_error)?;
+        revwalk.simplify_first_parent().map_err(log_error)?;
+        revwalk.push(tip).map_err(log_error)?;
+        if let Some(head) = self.head_commit_optional()? {
+            revwalk.hide(head.id()).map_err(log_error)?;
+        }
+        // A branch started at another ref has commits HEAD lacks that are not snapshots.
+        if let Some(base) = self.branch_base(slug) {
+            revwalk.hide(base).map_err(log_error)?;
+        }
+
+        let mut snapshots = Vec::new();
+        for oid in revwalk {
+            let oid = oid.map_err(log_error)?;
+            snapshots.push(self.repo.find_commit(oid).map_err(log_error)?);
+        }
+
+        let keep = snapshots
+            .iter()
+            .enumerate()
+            .take_while(|(index, commit)| {
+                max_count.is_none_or(|max_count| *index < max_count)
+                    && cutoff.is_none_or(|cutoff| commit.time().seconds() >= cutoff)
+            })
+            .count();
+        // Squashing a single snapshot would only rewrite it.
+        if keep == snapshots.len() || (keep == 0 && snapshots.len() == 1) {
+            return Ok(PruneResult {
+                removed: 0,
+                kept: snapshots.len(),
+                squash: None,
+            });
+        }
+
+        let oldest = &snapshots[snapshots.len() - 1];
+        let base = if oldest.parent_count() > 0 {
+            Some(oldest.parent(0).map_err(log_error)?)
+        } else {
+            None
+        };
+
+        let (new_tip, squash) = if keep == 0 {
+            let tree = snapshots[0].tree().map_err(log_error)?;
+            let signature = self.signature()?;
+            let message = format!("Squash {} pruned snapshots", snapshots.len());
+            let parents: Vec<&git2::Commit> = base.iter().collect();
+            let oid = self
+                .repo
+                .commit(None, &signature, &signature, &message, &tree, &parents)
+                .map_err(commit_error)?;
+            (oid, Some(oid.to_string()))
+        } else {
+            let mut parent = base;
+            for commit in snapshots[..keep].iter().rev() {
+                let tree = commit.tree().map_err(log_error)?;
+                let merged = commit.parents().skip(1).collect::<Vec<_>>();
+                let parents: Vec<&git2::Commit> = parent.iter().chain(merged.iter()).collect();
+                let oid = self
+                    .repo
+                    .commit(
+                        None,
+                        &commit.author(),
+                        &commit.committer(),
+                        commit.message_raw().unwrap_or(""),
+                        &tree,
+                        &parents,
+                    )
+                    .map_err(commit_error)?;
+                parent = Some(self.repo.find_commit(oid).map_err(commit_error)?);
+            }
+            let oid = parent.map(|commit| commit.id()).unwrap_or(tip);
+            (oid, None)
+        };
+
+        // Refuse to move the branch if a snapshot landed on it while the history was rewritten.
+        let reflog_message = format!("litterbox: prune {} snapshots", snapshots.len() - keep);
+        self.repo
+            .reference_matching(
+                &format!("refs/heads/{}", Self::branch_name(slug)),
+                new_tip,
+                true,
+                tip,
+                &reflog_message,
+            )
+            .map_err(|source| SandboxError::Scm(ScmError::Reference { source }))?;
+
+        Ok(PruneResult {
+            removed: snapshots.len() - keep,
+            kept: keep.max(usize::from(squash.is_some())),
+            squash,
+        })
+    }
+
+    /// Opens a second handle on the repository, as libgit2's stash calls need exclusive access.
+    fn stash_handle(&self) -> Result<Repository, SandboxError> {
+        Repository::open(self.repo.path())
+            .map_err(|source| SandboxError::Scm(ScmError::Open { source }))
+    }
+
+    /// The commit the sandbox branch for `slug` was created at, from its oldest reflog entry.
+    fn branch_base(&self, slug: &str) -> Option<git2::Oid> {
+        let reflog = self
+            .repo
+            .reflog(&format!("refs/heads/{}", Self::branch_name(slug)))
+            .ok()?;
+        let created = reflog.iter().next_back()?;
+        Some(created.id_new())
+    }
+
+    fn base_commit_time(&self, tip: git2::Oid) -> Result<Option<i64>, SandboxError> {
+        let Some(head) = self.head_commit_optional()? else {
+            return Ok(None);
+        };
+        let Ok(base) = self.repo.merge_base(tip, head.id()) else {
+            return Ok(None);
+        };
+        let commit = self
+            .repo
+            .find_commit(base)
+            .map_err(|source| SandboxError::Scm(ScmError::Log { source }))?;
+        Ok(Some(commit.time().seconds()))
+    }
+
+    /// Commits `staging_path` onto the snapshot branch.
+    ///
+    /// Returns `None` without committing when the tree matches the branch tip, or one of the
+    /// last `max_dedup_depth` commits that repeat `message` within the last minute. Only
+    /// such rapid repeats of one trigger are compared, so returning to an earlier tree for
+    /// any other reason, such as a revert, is still recorded.
+    fn commit_snapshot_from_staging(
+        &self,
+        staging_path: &Path,
+        message: &str,
+        max_dedup_depth: usize,
+    ) -> Result<Option<git2::Oid>, SandboxError> {
+        let parent = self.snapshot_parent()?;
+        let tree_oid = self.tree_from_staging(staging_path)?;
+        self.commit_snapshot_tree(parent, tree_oid, message, max_dedup_depth)
+    }
+
+    /// Like [`Self::commit_snapshot_from_staging`], but only `paths` are taken from
+    /// `staging_path`; everything else keeps its content from the branch tip. A path that is
+    /// missing from `staging_path` is deleted.
+    fn commit_snapshot_paths_from_staging(
+        &self,
+        staging_path: &Path,
+        paths: &[&str],
+        message: &str,
+        max_dedup_depth: usize,
+    ) -> Result<Option<git2::Oid>, SandboxError> {
+        let commit_error = |source| SandboxError::Scm(ScmError::Commit { source });
+        let parent = self.snapshot_parent()?;
+        let staged = self
+            .repo
+            .find_tree(self.tree_from_staging(staging_path)?)
+            .map_err(commit_error)?;
+        let Some(base) = parent.as_ref().map(git2::Commit::tree).transpose().map_err(commit_error)?
+        else {
+            return self.commit_snapshot_tree(parent, staged.id(), message, max_dedup_depth);
+        };
+
+        let mut update = git2::build::TreeUpdateBuilder::new();
+        for path in paths {
+            match staged.get_path(Path::new(path)) {
+                Ok(entry) => {
+                    update.upsert(*path, entry.id(), file_mode(entry.filemode()));
+                }
+                Err(_) if base.get_path(Path::new(path)).is_ok() => {
+                    update.remove(*path);
+                }
+                Err(_) => {}
+            }
+        }
+        let tree_oid = update.create_updated(&self.repo, &base).map_err(commit_error)?;
+        self.commit_snapshot_tree(parent, tree_oid, message, max_dedup_depth)
+    }
+
+    fn tree_from_staging(&self, staging_path: &Path) -> Result<git2::Oid, SandboxError> {
+        let mut builder = self
+            .repo
+            .treebuilder(None)
+            .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+        self.add_directory_to_tree(&mut builder, staging_path)?;
+        builder
+            .write()
+            .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))
+    }
+
+    /// Commits `tree_oid` onto the snapshot branch, unless it repeats a recent snapshot as
+    /// described on [`Self::commit_snapshot_from_staging`].
+    fn commit_snapshot_tree(
+        &self,
+        parent: Option<git2::Commit<'_>>,
+        tree_oid: git2::Oid,
+        message: &str,
+        max_dedup_depth: usize,
+    ) -> Result<Option<git2::Oid>, SandboxError> {
+        let signature = self.signature()?;
+
+        // Backup snapshot branch ref before modification (for atomic recovery)
+        let backup = self.backup_snapshot_ref()?;
+
+        // Check if tree changed
+        if let Some(ref parent_commit) = parent {
+            let since = signature.when().seconds() - SNAPSHOT_DEDUP_WINDOW_SECS;
+            if recent_tree_matches(parent_commit, tree_oid, message, since, max_dedup_depth) {
+                return Ok(None);
+            }
+        } else if tree_oid == git2::Oid::zero() {
+            return Ok(None);
+        }
+
+        let tree = self.repo.find_tree(tree_oid).map_err(|e| {
+            let _ = self.restore_snapshot_ref(&backup);
+            SandboxError::Scm(ScmError::Commit { source: e })
+        })?;
+
+        let parents: Vec<&git2::Commit> = parent.as_ref().map(|c| vec![c]).unwrap_or_default();
+
+        // Create commit without updating ref (to avoid "current tip is not first parent" when jj modifies branch)
+        let oid = self
+            .repo
+            .commit(
+                None, // Don't update ref yet
+                &signature, &signature, message, &tree, &parents,
+            )
+            .map_err(|e| {
+                let _ = self.restore_snapshot_ref(&backup);
+                SandboxError::Scm(ScmError::Commit { source: e })
+            })?;
+
+        // Force update the ref to point to our new commit (handles concurrent jj updates)
+        // Retry with backoff if locked (jj may be holding the lock)
+        let mut retries = 0;
+        let max_retries = 5;
+        loop {
+            let result = match self.repo.find_reference(&self.snapshot_branch_ref()) {
+                Ok(mut reference) => reference.set_target(oid, message),
+                Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                    self.repo
+                        .reference(&self.snapshot_branch_ref(), oid, false, message)
+                }
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(_) => break,
+                Err(e) if e.code() == git2::ErrorCode::Locked && retries < max_retries => {
+                    retries += 1;
+                    std::thread::sleep(std::time::Duration::from_millis(10 * retries as u64));
+                    continue;
+                }
+                Err(e) => {
+                    let _ = self.restore_snapshot_ref(&backup);
+                    return Err(SandboxError::Scm(ScmError::Commit { source: e }));
+                }
+            }
+        }
+
+        Ok(Some(oid))
+    }
+
+    fn backup_snapshot_ref(&self) -> Result<Option<git2::Oid>, SandboxError> {
+        let ref_name = self.snapshot_branch_ref();
+        match self.repo.find_reference(&ref_name) {
+            Ok(reference) => Ok(reference.target()),
+            Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(source) => Err(SandboxError::Scm(ScmError::Commit { source })),
+        }
+    }
+
+    fn restore_snapshot_ref(&self, backup: &Option<git2::Oid>) -> Result<(), SandboxError> {
+        let ref_name = self.snapshot_branch_ref();
+
+        match backup {
+            Some(oid) => {
+                // Restore to previous oid
+                self.repo
+                    .reference(&ref_name, *oid, true, "Restore from backup")
+                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+            }
+            None => {
+                // Ref didn't exist before, delete it
+                match self.repo.find_reference(&ref_name) {
+                    Ok(mut reference) => {
+                        reference
+                            .delete()
+                            .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+                    }
+                    Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                        // Already gone, nothing to do
+                    }
+                    Err(source) => {
+                        return Err(SandboxError::Scm(ScmError::Commit { source }));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_directory_to_tree(
+        &self,
+        builder: &mut git2::TreeBuilder,
+        current_path: &Path,
+    ) -> Result<(), SandboxError> {
+        use std::fs;
+
+        let entries = fs::read_dir(current_path).map_err(SandboxError::Io)?;
+
+        for entry in entries {
+            let entry = entry.map_err(SandboxError::Io)?;
+            let path = entry.path();
+            let file_name = entry.file_name();
+            let name_str = file_name.to_string_lossy();
+
+            // Skip .git directory
+            if name_str == ".git" {
+                continue;
+            }
+
+            let metadata = entry.metadata().map_err(SandboxError::Io)?;
+
+            if metadata.is_dir() {
+                let mut sub_builder = self
+                    .repo
+                    .treebuilder(None)
+                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+
+                self.add_directory_to_tree(&mut sub_builder, &path)?;
+
+                let sub_tree_oid = sub_builder
+                    .write()
+                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+
+                builder
+                    .insert(&*name_str, sub_tree_oid, 0o040000)
+                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+            } else {
+                let content = fs::read(&path).map_err(SandboxError::Io)?;
+
+                let blob_oid = self
+                    .repo
+                    .blob(&content)
+                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+
+                #[cfg(unix)]
+                let filemode = {
+                    use std::os::unix::fs::PermissionsExt;
+                    let mode = metadata.permissions().mode();
+                    if mode & 0o111 != 0 {
+                        0o100755
+                    } else {
+                        0o100644
+                    }
+                };
+
+                #[cfg(not(unix))]
+                let filemode = 0o100644;
+
+                builder
+                    .insert(&*name_str, blob_oid, filemode)
+                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `tree_oid` is the tree of `tip`, or of one of the `depth` newest commits from
+/// `tip` that are a run of `message` snapshots made at or after `since`.
+fn recent_tree_matches(
+    tip: &git2::Commit<'_>,
+    tree_oid: git2::Oid,
+    message: &str,
+    since: i64,
+    depth: usize,
+) -> bool {
+    if tip.tree_id() == tree_oid {
+        return true;
+    }
+    let mut commit = Some(tip.clone());
+    for _ in 0..depth {
+        let Some(current) = commit.take() else {
+            return false;
+        };
+        if current.message() != Some(message) || current.time().seconds() < since {
+            return false;
+        }
+        if current.tree_id() == tree_oid {
+            return true;
+        }
+        commit = current.parent(0).ok();
+    }
+    false
+}
+
+/// The [`git2::FileMode`] of a raw tree entry mode.
+fn file_mode(mode: i32) -> git2::FileMode {
+    match mode {
+        0o040000 => git2::FileMode::Tree,
+        0o100755 => git2::FileMode::BlobExecutable,
+        0o120000 => git2::FileMode::Link,
+        0o160000 => git2::FileMode::Commit,
+        _ => git2::FileMode::Blob,
+    }
+}
+
+fn repo_prefix_from_path(path: &Path) -> String {
+    let base = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("repo");
+    let slug = slugify(base);
+    if slug.is_empty() {
+        "repo".to_string()
+    } else {
+        slug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::io::Cursor;
+
+    use git2::{IndexAddOption, Signature};
+    use tempfile::TempDir;
+
+    use crate::domain::LitterboxErrorCode;
+
+    fn init_repo() -> (TempDir, Repository) {
+        let tempdir = TempDir::new().expect("tempdir");
+        let repo = Repository::init(tempdir.path()).expect("repo init");
+
+        let file_path = tempdir.path().join("README.md");
+        fs::write(&file_path, "hello").expect("write file");
+
+        let gitignore_path = tempdir.path().join(".gitignore");
+        fs::write(&gitignore_path, "ignored.txt\n").expect("write gitignore");
+
+        let mut index = repo.index().expect("index");
+        index
+            .add_all(["*"].iter(), IndexAddOption::DEFAULT, None)
+            .expect("add all");
+        index.write().expect("index write");
+        let tree_id = index.write_tree().expect("write tree");
+
+        let signature = Signature::now("Litterbox", "noreply@example.com").expect("signature");
+        {
+            let tree = repo.find_tree(tree_id).expect("find tree");
+            repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+                .expect("commit");
+        }
+
+        (tempdir, repo)
+    }
+
+    #[test]
+    fn create_branch_creates_litterbox_branch() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        let branch_name = scm.create_branch("my-feature").expect("create branch");
+        assert_eq!(branch_name, "litterbox/my-feature");
+
+        let branch = scm
+            .repo
+            .find_branch(&branch_name, BranchType::Local)
+            .expect("branch exists");
+        let branch_commit = branch.get().peel_to_commit().expect("branch commit");
+        let head_commit = scm
+            .repo
+            .head()
+            .expect("head")
+            .peel_to_commit()
+            .expect("head commit");
+        assert_eq!(branch_commit.id(), head_commit.id());
+    }
+
+    #[test]
+    fn create_branch_rejects_duplicates() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        scm.create_branch("my-feature").expect("create branch");
+        let err = scm
+            .create_branch("my-feature")
+            .expect_err("duplicate branch");
+        assert_eq!(err.to_string(), "Sandbox 'my-feature' already exists.");
+    }
+
+    #[test]
+    fn delete_branch_removes_branch() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        let branch_name = scm.create_branch("cleanup").expect("create branch");
+        scm.delete_branch("cleanup").expect("delete branch");
+
+        assert!(
+            scm.repo
+                .find_branch(&branch_name, BranchType::Local)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn delete_branch_missing_returns_not_found() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        let err = scm.delete_branch("missing").expect_err("missing branch");
+        assert_eq!(err.to_string(), "Sandbox 'missing' not found.");
+    }
+
+    #[test]
+    fn rename_branch_moves_branch() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        scm.create_branch("old-name").expect("create branch");
+        let branch_name = scm.rename_branch("old-name", "new-name").expect("rename branch");
+
+        assert_eq!(branch_name, "litterbox/new-name");
+        assert!(scm.repo.find_branch("litterbox/new-name", BranchType::Local).is_ok());
+        assert!(scm.repo.find_branch("litterbox/old-name", BranchType::Local).is_err());
+    }
+
+    #[test]
+    fn rename_branch_rejects_existing_target() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        scm.create_branch("first").expect("create branch");
+        scm.create_branch("second").expect("create branch");
+        let err = scm.rename_branch("first", "second").expect_err("target exists");
+        assert_eq!(err.to_string(), "Sandbox 'second' already exists.");
+    }
+
+    #[test]
+    fn rename_branch_missing_returns_not_found() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        let err = scm.rename_branch("missing", "other").expect_err("missing branch");
+        assert_eq!(err.to_string(), "Sandbox 'missing' not found.");
+    }
+
+    #[test]
+    fn create_branch_from_targets_sandbox_tip() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        scm.create_branch("feature").expect("create branch");
+        let head = scm.head_commit().expect("head");
+        let signature = scm.signature().expect("signature");
+        let tip = scm
+            .repo
+            .commit(
+                Some("refs/heads/litterbox/feature"),
+                &signature,
+                &signature,
+                "snapshot",
+                &head.tree().expect("tree"),
+                &[&head],
+            )
+            .expect("commit");
+
+        let branch_name = scm
+            .create_branch_from("feature", "feature/done")
+            .expect("create branch from");
+        assert_eq!(branch_name, "feature/done");
+        let branch = scm
+            .repo
+            .find_branch("feature/done", BranchType::Local)
+            .expect("branch exists");
+        assert_eq!(branch.get().target(), Some(tip));
+    }
+
+    #[test]
+    fn create_branch_from_missing_sandbox_returns_not_found() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        let err = scm
+            .create_branch_from("missing", "feature/done")
+            .expect_err("missing sandbox");
+        assert_eq!(err.to_string(), "Sandbox 'missing' not found.");
+    }
+
+    #[test]
+    fn create_branch_at_starts_from_ref() {
+        let (tempdir, repo) = init_repo();
+        let first = repo.head().expect("head").target().expect("oid");
+        repo.tag_lightweight("v1", &repo.find_object(first, None).expect("object"), false)
+            .expect("tag");
+        commit_to_head(&tempdir, &repo, "later.txt", "later");
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        let branch = scm.create_branch_at("tagged", "v1").expect("create branch");
+
+        assert_eq!(branch, "litterbox/tagged");
+        assert_eq!(scm.branch_tip("tagged").expect("tip"), first);
+        assert_eq!(scm.base_commit("tagged").expect("base"), Some(first));
+        assert!(scm.create_branch_at("missing", "no-such-ref").is_err());
+    }
+
+    #[test]
+    fn push_branch_updates_origin() {
+        let (_tempdir, repo) = init_repo();
+        let remote_dir = TempDir::new().expect("remote tempdir");
+        let remote = Repository::init_bare(remote_dir.path()).expect("init bare");
+        repo.remote("origin", remote_dir.path().to_str().expect("remote path"))
+            .expect("add remote");
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        scm.create_branch("pushed").expect("create branch");
+        scm.create_branch_from("pushed", "feature/pushed")
+            .expect("create branch from");
+        scm.push_branch("feature/pushed").expect("push");
+
+        let head = scm.head_commit().expect("head").id();
+        let pushed = remote
+            .find_reference("refs/heads/feature/pushed")
+            .expect("pushed ref");
+        assert_eq!(pushed.target(), Some(head));
+    }
+
+    /// Creates sandbox `slug` with one snapshot holding the repository files plus `files`.
+    fn snapshot_sandbox(
+        tempdir: &TempDir,
+        repo: Repository,
+        slug: &str,
+        files: &[(&str, &str)],
+    ) -> GitScm {
+        let scm = GitScm {
+            repo,
+            snapshot_branch: Some(GitScm::branch_name(slug)),
+        };
+        scm.create_branch(slug).expect("create branch");
 
-    fn backup_snapshot_ref(&self) -> Result<Option<git2::Oid>, SandboxError> {
-        let ref_name = self.snapshot_branch_ref();
-        match self.repo.find_reference(&ref_name) {
-            Ok(reference) => Ok(reference.target()),
-            Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
-            Err(source) => Err(SandboxError::Scm(ScmError::Commit { source })),
+        let staging_dir = TempDir::new().expect("staging dir");
+        for name in ["README.md", ".gitignore"] {
+            fs::copy(tempdir.path().join(name), staging_dir.path().join(name)).expect("copy");
+        }
+        for (name, content) in files {
+            fs::write(staging_dir.path().join(name), content).expect("write staged");
         }
+        scm.commit_snapshot_from_staging(staging_dir.path(), "sandbox work", 1)
+            .expect("snapshot")
+            .expect("oid");
+        scm
     }
 
-    fn restore_snapshot_ref(&self, backup: &Option<git2::Oid>) -> Result<(), SandboxError> {
-        let ref_name = self.snapshot_branch_ref();
+    fn commit_to_head(tempdir: &TempDir, repo: &Repository, name: &str, content: &str) {
+        fs::write(tempdir.path().join(name), content).expect("write file");
+        let mut index = repo.index().expect("index");
+        index.add_path(Path::new(name)).expect("add path");
+        index.write().expect("index write");
+        let tree = repo.find_tree(index.write_tree().expect("write tree")).expect("tree");
+        let parent = repo.head().expect("head").peel_to_commit().expect("parent");
+        let signature = Signature::now("Litterbox", "noreply@example.com").expect("signature");
+        repo.commit(Some("HEAD"), &signature, &signature, "main work", &tree, &[&parent])
+            .expect("commit");
+    }
 
-        match backup {
-            Some(oid) => {
-                // Restore to previous oid
-                self.repo
-                    .reference(&ref_name, *oid, true, "Restore from backup")
-                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
-            }
-            None => {
-                // Ref didn't exist before, delete it
-                match self.repo.find_reference(&ref_name) {
-                    Ok(mut reference) => {
-                        reference
-                            .delete()
-                            .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
-                    }
-                    Err(e) if e.code() == git2::ErrorCode::NotFound => {
-                        // Already gone, nothing to do
-                    }
-                    Err(source) => {
-                        return Err(SandboxError::Scm(ScmError::Commit { source }));
-                    }
-                }
+    #[test]
+    fn list_sandboxes_with_metadata_counts_snapshots() {
+        let (tempdir, repo) = init_repo();
+        let scm = snapshot_sandbox(&tempdir, repo, "busy", &[("sandbox.txt", "sandbox")]);
+        scm.create_branch("idle").expect("create idle");
+        let tip = scm.branch_tip("busy").expect("tip");
+
+        let mut entries = scm.list_sandboxes_with_metadata().expect("list");
+        entries.sort_by(|a, b| a.slug.cmp(&b.slug));
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].slug, "busy");
+        assert_eq!(entries[0].branch_name, "litterbox/busy");
+        assert_eq!(entries[0].snapshot_oid, Some(tip));
+        assert!(entries[0].last_snapshot_time.is_some());
+        assert_eq!(entries[0].snapshot_count, 1);
+        assert_eq!(
+            entries[1],
+            SandboxEntry {
+                slug: "idle".to_string(),
+                branch_name: "litterbox/idle".to_string(),
+                snapshot_oid: None,
+                last_snapshot_time: None,
+                snapshot_count: 0,
             }
-        }
+        );
+    }
 
-        Ok(())
+    #[test]
+    fn cherry_pick_replays_commit_onto_snapshot_branch() {
+        let (tempdir, repo) = init_repo();
+        let source = snapshot_sandbox(&tempdir, repo, "source", &[("fix.txt", "fixed")]);
+        let fix = source.branch_tip("source").expect("tip");
+        source.create_branch("target").expect("create target");
+        let mut target = GitScm::open(tempdir.path()).expect("open");
+        target.set_snapshot_branch(GitScm::branch_name("target"));
+
+        let oid = target
+            .cherry_pick(&fix.to_string())
+            .expect("cherry-pick")
+            .expect("oid");
+
+        assert_eq!(target.branch_tip("target").expect("tip"), oid);
+        let commit = target.repo.find_commit(oid).expect("commit");
+        assert!(commit.message().expect("message").contains(&fix.to_string()));
+        assert!(commit.tree().expect("tree").get_name("fix.txt").is_some());
+        // The same changes again, or a commit the branch already has, are no-ops.
+        assert_eq!(target.cherry_pick(&fix.to_string()).expect("repeat"), None);
+        assert_eq!(target.cherry_pick("HEAD").expect("ancestor"), None);
     }
 
-    fn add_directory_to_tree(
-        &self,
-        builder: &mut git2::TreeBuilder,
-        current_path: &Path,
-    ) -> Result<(), SandboxError> {
-        use std::fs;
+    #[test]
+    fn cherry_pick_reports_conflicting_files() {
+        let (tempdir, repo) = init_repo();
+        let source = snapshot_sandbox(&tempdir, repo, "source", &[("README.md", "source")]);
+        let fix = source.branch_tip("source").expect("tip");
+        let repo = Repository::open(tempdir.path()).expect("reopen");
+        let target = snapshot_sandbox(&tempdir, repo, "target", &[("README.md", "target")]);
+
+        let error = target.cherry_pick(&fix.to_string()).expect_err("conflict");
+
+        assert_eq!(error.error_code(), LitterboxErrorCode::Conflict);
+        let SandboxError::Scm(ScmError::CherryPickConflict {
+            commit,
+            conflicted_files,
+        }) = error
+        else {
+            panic!("expected a cherry-pick conflict");
+        };
+        assert_eq!(commit, fix.to_string());
+        assert_eq!(conflicted_files, ["README.md"]);
+    }
 
-        let entries = fs::read_dir(current_path).map_err(SandboxError::Io)?;
+    #[test]
+    fn merge_branch_fast_forwards_checked_out_target() {
+        let (tempdir, repo) = init_repo();
+        let scm = snapshot_sandbox(&tempdir, repo, "ff", &[("sandbox.txt", "sandbox")]);
+        let tip = scm.branch_tip("ff").expect("tip");
 
-        for entry in entries {
-            let entry = entry.map_err(SandboxError::Io)?;
-            let path = entry.path();
-            let file_name = entry.file_name();
-            let name_str = file_name.to_string_lossy();
+        let result = scm
+            .merge_branch("ff", "HEAD", MergeStrategy::FastForward)
+            .expect("merge");
 
-            // Skip .git directory
-            if name_str == ".git" {
-                continue;
-            }
+        assert_eq!(result, MergeResult { oid: tip, had_conflicts: false });
+        assert_eq!(scm.head_commit().expect("head").id(), tip);
+        assert_eq!(
+            fs::read_to_string(tempdir.path().join("sandbox.txt")).expect("checked out"),
+            "sandbox"
+        );
+    }
 
-            let metadata = entry.metadata().map_err(SandboxError::Io)?;
+    #[test]
+    fn merge_branch_rejects_fast_forward_of_diverged_target() {
+        let (tempdir, repo) = init_repo();
+        let scm = snapshot_sandbox(&tempdir, repo, "diverged", &[("sandbox.txt", "sandbox")]);
+        commit_to_head(&tempdir, &scm.repo, "main.txt", "main");
 
-            if metadata.is_dir() {
-                let mut sub_builder = self
-                    .repo
-                    .treebuilder(None)
-                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+        let error = scm
+            .merge_branch("diverged", "HEAD", MergeStrategy::FastForward)
+            .expect_err("not a fast-forward");
 
-                self.add_directory_to_tree(&mut sub_builder, &path)?;
+        assert!(matches!(error, SandboxError::Scm(ScmError::NotFastForward { .. })));
+    }
 
-                let sub_tree_oid = sub_builder
-                    .write()
-                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+    #[test]
+    fn merge_branch_merge_and_squash_set_parents() {
+        for (strategy, parent_count) in [(MergeStrategy::Merge, 2), (MergeStrategy::Squash, 1)] {
+            let (tempdir, repo) = init_repo();
+            let scm = snapshot_sandbox(&tempdir, repo, "work", &[("sandbox.txt", "sandbox")]);
+            commit_to_head(&tempdir, &scm.repo, "main.txt", "main");
+
+            let result = scm.merge_branch("work", "HEAD", strategy).expect("merge");
+
+            assert!(!result.had_conflicts);
+            let commit = scm.head_commit().expect("head");
+            assert_eq!(commit.id(), result.oid);
+            assert_eq!(commit.parent_count(), parent_count);
+            let tree = commit.tree().expect("tree");
+            assert!(tree.get_name("sandbox.txt").is_some());
+            assert!(tree.get_name("main.txt").is_some());
+        }
+    }
 
-                builder
-                    .insert(&*name_str, sub_tree_oid, 0o040000)
-                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
-            } else {
-                let content = fs::read(&path).map_err(SandboxError::Io)?;
+    #[test]
+    fn merge_branch_reports_conflicts_without_moving_target() {
+        let (tempdir, repo) = init_repo();
+        let scm = snapshot_sandbox(&tempdir, repo, "clash", &[("README.md", "sandbox")]);
+        commit_to_head(&tempdir, &scm.repo, "README.md", "main");
+        let before = scm.head_commit().expect("head").id();
 
-                let blob_oid = self
-                    .repo
-                    .blob(&content)
-                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
+        let result = scm
+            .merge_branch("clash", "HEAD", MergeStrategy::Merge)
+            .expect("merge");
 
-                #[cfg(unix)]
-                let filemode = {
-                    use std::os::unix::fs::PermissionsExt;
-                    let mode = metadata.permissions().mode();
-                    if mode & 0o111 != 0 {
-                        0o100755
-                    } else {
-                        0o100644
-                    }
-                };
+        assert_eq!(result, MergeResult { oid: before, had_conflicts: true });
+        assert_eq!(scm.head_commit().expect("head").id(), before);
+    }
 
-                #[cfg(not(unix))]
-                let filemode = 0o100644;
+    #[test]
+    fn branch_tip_returns_branch_commit() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
 
-                builder
-                    .insert(&*name_str, blob_oid, filemode)
-                    .map_err(|source| SandboxError::Scm(ScmError::Commit { source }))?;
-            }
-        }
+        scm.create_branch("tip").expect("create branch");
+        let head = scm.head_commit().expect("head").id();
 
-        Ok(())
+        assert_eq!(scm.branch_tip("tip").expect("branch tip"), head);
+        assert!(scm.branch_tip("missing").is_err());
     }
-}
 
-fn repo_prefix_from_path(path: &Path) -> String {
-    let base = path
-        .file_name()
-        .and_then(|name| name.to_str())
-        .unwrap_or("repo");
-    let slug = slugify(base);
-    if slug.is_empty() {
-        "repo".to_string()
-    } else {
-        slug
-    }
-}
+    #[test]
+    fn branch_times_reports_creation_and_tip_times() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        scm.create_branch("timed").expect("create branch");
+        let head_time = scm.head_commit().expect("head").time().seconds() as u64;
+        let (created_at, last_active_at) = scm.branch_times("timed").expect("branch times");
 
-    use std::fs;
-    use std::io::Cursor;
+        assert!(created_at.is_some());
+        assert_eq!(last_active_at, Some(head_time));
+        assert!(scm.branch_times("missing").is_err());
+    }
 
-    use git2::{IndexAddOption, Signature};
-    use tempfile::TempDir;
+    #[test]
+    fn archive_contains_tracked_files_only() {
+        let (tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
 
-    fn init_repo() -> (TempDir, Repository) {
-        let tempdir = TempDir::new().expect("tempdir");
-        let repo = Repository::init(tempdir.path()).expect("repo init");
+        let ignored_path = tempdir.path().join("ignored.txt");
+        fs::write(&ignored_path, "ignored").expect("write ignored");
+        let untracked_path = tempdir.path().join("notes.txt");
+        fs::write(&untracked_path, "notes").expect("write untracked");
 
-        let file_path = tempdir.path().join("README.md");
-        fs::write(&file_path, "hello").expect("write file");
+        let archive = scm.make_archive("HEAD", false).expect("archive");
+        let mut entries = Vec::new();
+        let mut reader = tar::Archive::new(Cursor::new(archive));
+        for entry in reader.entries().expect("entries") {
+            let entry = entry.expect("entry");
+            let path = entry.path().expect("path");
+            entries.push(path.to_string_lossy().to_string());
+        }
 
-        let gitignore_path = tempdir.path().join(".gitignore");
-        fs::write(&gitignore_path, "ignored.txt\n").expect("write gitignore");
+        entries.sort();
+        assert_eq!(entries, vec![".gitignore", "README.md"]);
+    }
+
+    #[test]
+    fn archive_skips_litterboxignore_matches() {
+        let (tempdir, repo) = init_repo();
+        fs::create_dir_all(tempdir.path().join("target/debug")).expect("create target");
+        fs::write(tempdir.path().join("target/debug/app"), "binary").expect("write artefact");
+        fs::create_dir_all(tempdir.path().join("src")).expect("create src");
+        fs::write(tempdir.path().join("src/main.rs"), "fn main() {}").expect("write source");
+        fs::write(tempdir.path().join("src/debug.log"), "log").expect("write log");
 
         let mut index = repo.index().expect("index");
         index
@@ -633,111 +2714,195 @@ mod tests {
             .expect("add all");
         index.write().expect("index write");
         let tree_id = index.write_tree().expect("write tree");
+        let signature = Signature::now("Litterbox", "noreply@example.com").expect("signature");
+        {
+            let tree = repo.find_tree(tree_id).expect("find tree");
+            let parent = repo.head().expect("head").peel_to_commit().expect("commit");
+            repo.commit(Some("HEAD"), &signature, &signature, "artefacts", &tree, &[&parent])
+                .expect("commit");
+        }
+        fs::write(tempdir.path().join(".litterboxignore"), "target/\n*.log\n").expect("write ignore");
+
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        let archive = scm.make_archive("HEAD", false).expect("archive");
+        let mut reader = tar::Archive::new(Cursor::new(archive));
+        let mut entries: Vec<String> = reader
+            .entries()
+            .expect("entries")
+            .map(|entry| {
+                let entry = entry.expect("entry");
+                entry.path().expect("path").to_string_lossy().to_string()
+            })
+            .collect();
+
+        entries.sort();
+        assert_eq!(entries, vec![".gitignore", "README.md", "src/main.rs"]);
+    }
 
+    #[cfg(unix)]
+    #[test]
+    fn archive_preserves_tracked_symlinks() {
+        let (tempdir, repo) = init_repo();
+        std::os::unix::fs::symlink("README.md", tempdir.path().join("link.md")).expect("symlink");
+
+        let mut index = repo.index().expect("index");
+        index
+            .add_all(["*"].iter(), IndexAddOption::DEFAULT, None)
+            .expect("add all");
+        index.write().expect("index write");
+        let tree_id = index.write_tree().expect("write tree");
         let signature = Signature::now("Litterbox", "noreply@example.com").expect("signature");
         {
             let tree = repo.find_tree(tree_id).expect("find tree");
-            repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            let parent = repo.head().expect("head").peel_to_commit().expect("commit");
+            repo.commit(Some("HEAD"), &signature, &signature, "symlink", &tree, &[&parent])
                 .expect("commit");
         }
 
-        (tempdir, repo)
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        let archive = scm.make_archive("HEAD", false).expect("archive");
+        let mut reader = tar::Archive::new(Cursor::new(archive));
+        let link = reader
+            .entries()
+            .expect("entries")
+            .map(|entry| entry.expect("entry"))
+            .find(|entry| entry.path().expect("path").to_string_lossy() == "link.md")
+            .expect("symlink entry");
+
+        assert_eq!(link.header().entry_type(), tar::EntryType::Symlink);
+        assert_eq!(
+            link.link_name().expect("link name").expect("target"),
+            Path::new("README.md")
+        );
     }
 
     #[test]
-    fn create_branch_creates_litterbox_branch() {
+    fn compressed_archive_round_trips() {
         let (_tempdir, repo) = init_repo();
         let scm = GitScm {
             repo,
             snapshot_branch: None,
         };
 
-        let branch_name = scm.create_branch("my-feature").expect("create branch");
-        assert_eq!(branch_name, "litterbox/my-feature");
+        let archive = scm.make_archive("HEAD", true).expect("archive");
+        assert!(crate::compute::is_zstd_compressed(&archive));
+
+        let decoder = zstd::Decoder::new(Cursor::new(archive)).expect("decoder");
+        let mut reader = tar::Archive::new(decoder);
+        let mut entries: Vec<String> = reader
+            .entries()
+            .expect("entries")
+            .map(|entry| {
+                let entry = entry.expect("entry");
+                entry.path().expect("path").to_string_lossy().to_string()
+            })
+            .collect();
 
-        let branch = scm
-            .repo
-            .find_branch(&branch_name, BranchType::Local)
-            .expect("branch exists");
-        let branch_commit = branch.get().peel_to_commit().expect("branch commit");
-        let head_commit = scm
-            .repo
-            .head()
-            .expect("head")
-            .peel_to_commit()
-            .expect("head commit");
-        assert_eq!(branch_commit.id(), head_commit.id());
+        entries.sort();
+        assert_eq!(entries, vec![".gitignore", "README.md"]);
+    }
+
+    #[test]
+    fn apply_patch_dry_run_leaves_workdir_untouched() {
+        let (tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        let diff = "diff --git a/README.md b/README.md\n--- a/README.md\n+++ b/README.md\n@@ -1 +1 @@\n-hello\n\\ No newline at end of file\n+patched\n\\ No newline at end of file\n";
+
+        scm.apply_patch_dry_run(diff).expect("patch applies");
+
+        let content = fs::read_to_string(tempdir.path().join("README.md")).expect("read");
+        assert_eq!(content, "hello");
     }
 
     #[test]
-    fn create_branch_rejects_duplicates() {
+    fn apply_patch_dry_run_rejects_conflicting_patch() {
         let (_tempdir, repo) = init_repo();
         let scm = GitScm {
             repo,
             snapshot_branch: None,
         };
+        let diff = "diff --git a/README.md b/README.md\n--- a/README.md\n+++ b/README.md\n@@ -1 +1 @@\n-goodbye\n+patched\n";
 
-        scm.create_branch("my-feature").expect("create branch");
-        let err = scm
-            .create_branch("my-feature")
-            .expect_err("duplicate branch");
-        assert_eq!(err.to_string(), "Sandbox 'my-feature' already exists.");
+        let error = scm.apply_patch_dry_run(diff).expect_err("patch conflicts");
+        assert!(error.to_string().contains("Patch does not apply"));
     }
 
     #[test]
-    fn delete_branch_removes_branch() {
+    fn apply_patch_dry_run_checks_the_snapshot_branch() {
         let (_tempdir, repo) = init_repo();
         let scm = GitScm {
             repo,
-            snapshot_branch: None,
+            snapshot_branch: Some("test-snapshot".to_string()),
         };
+        let staging_dir = TempDir::new().expect("staging dir");
+        fs::write(staging_dir.path().join("README.md"), "snapshot\n").expect("write file");
+        scm.commit_snapshot_from_staging(staging_dir.path(), "Snapshot", 1)
+            .expect("commit")
+            .expect("oid");
+        let diff = "diff --git a/README.md b/README.md\n--- a/README.md\n+++ b/README.md\n@@ -1 +1 @@\n-snapshot\n+patched\n";
 
-        let branch_name = scm.create_branch("cleanup").expect("create branch");
-        scm.delete_branch("cleanup").expect("delete branch");
-
-        assert!(
-            scm.repo
-                .find_branch(&branch_name, BranchType::Local)
-                .is_err()
-        );
+        scm.apply_patch_dry_run(diff).expect("patch applies to the snapshot");
     }
 
     #[test]
-    fn delete_branch_missing_returns_not_found() {
-        let (_tempdir, repo) = init_repo();
+    fn apply_patch_reports_conflicted_files() {
+        let (tempdir, repo) = init_repo();
         let scm = GitScm {
             repo,
             snapshot_branch: None,
         };
+        let diff = "diff --git a/.gitignore b/.gitignore\n--- a/.gitignore\n+++ b/.gitignore\n@@ -1 +1 @@\n-ignored.txt\n+other.txt\ndiff --git a/README.md b/README.md\n--- a/README.md\n+++ b/README.md\n@@ -1 +1 @@\n-goodbye\n+patched\n";
 
-        let err = scm.delete_branch("missing").expect_err("missing branch");
-        assert_eq!(err.to_string(), "Sandbox 'missing' not found.");
+        let error = scm.apply_patch(diff).expect_err("patch conflicts");
+
+        match error {
+            SandboxError::Scm(ScmError::PatchConflict { conflicted_files }) => {
+                assert_eq!(conflicted_files, vec!["README.md"]);
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+        let gitignore = fs::read_to_string(tempdir.path().join(".gitignore")).expect("read");
+        assert_eq!(gitignore, "ignored.txt\n");
     }
 
     #[test]
-    fn archive_contains_tracked_files_only() {
+    fn stage_all_except_skips_excluded_paths() {
         let (tempdir, repo) = init_repo();
         let scm = GitScm {
             repo,
             snapshot_branch: None,
         };
+        fs::write(tempdir.path().join("kept.txt"), "kept").expect("write kept");
+        fs::create_dir_all(tempdir.path().join("gen")).expect("create gen");
+        fs::write(tempdir.path().join("gen/output.rs"), "generated").expect("write generated");
+        fs::write(tempdir.path().join("schema.gen"), "generated").expect("write generated");
 
-        let ignored_path = tempdir.path().join("ignored.txt");
-        fs::write(&ignored_path, "ignored").expect("write ignored");
-        let untracked_path = tempdir.path().join("notes.txt");
-        fs::write(&untracked_path, "notes").expect("write untracked");
+        scm.stage_all_except(&["gen/*", "*.gen"]).expect("stage");
 
-        let archive = scm.make_archive("HEAD").expect("archive");
-        let mut entries = Vec::new();
-        let mut reader = tar::Archive::new(Cursor::new(archive));
-        for entry in reader.entries().expect("entries") {
-            let entry = entry.expect("entry");
-            let path = entry.path().expect("path");
-            entries.push(path.to_string_lossy().to_string());
-        }
+        let index = scm.repo.index().expect("index");
+        assert!(index.get_path(Path::new("kept.txt"), 0).is_some());
+        assert!(index.get_path(Path::new("gen/output.rs"), 0).is_none());
+        assert!(index.get_path(Path::new("schema.gen"), 0).is_none());
+    }
 
-        entries.sort();
-        assert_eq!(entries, vec![".gitignore", "README.md"]);
+    #[test]
+    fn stage_all_except_rejects_invalid_pattern() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        assert!(scm.stage_all_except(&["[unclosed"]).is_err());
     }
 
     #[test]
@@ -763,6 +2928,59 @@ mod tests {
         assert!(!scm.has_changes().expect("has changes"));
     }
 
+    #[test]
+    fn has_changes_in_only_considers_matching_paths() {
+        let (tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        fs::create_dir_all(tempdir.path().join("tools")).expect("create tools");
+        fs::write(tempdir.path().join("tools/cache.txt"), "cache").expect("write");
+
+        assert!(!scm.has_changes_in(&["README.md"]).expect("has changes"));
+        assert!(scm.has_changes_in(&["tools/cache.txt"]).expect("has changes"));
+        assert!(scm.has_changes_in(&["README.md", "tools"]).expect("has changes"));
+    }
+
+    #[test]
+    fn open_discovers_repository_from_subdirectory() {
+        let (tempdir, _repo) = init_repo();
+        let nested = tempdir.path().join("crates/app");
+        fs::create_dir_all(&nested).expect("create nested");
+
+        let scm = GitScm::open(&nested).expect("open from subdirectory");
+        assert_eq!(scm.repo_root(), GitScm::open(tempdir.path()).expect("open").repo_root());
+    }
+
+    #[test]
+    fn bare_repository_archives_but_rejects_worktree_operations() {
+        let (tempdir, _repo) = init_repo();
+        let bare_dir = TempDir::new().expect("bare dir");
+        git2::build::RepoBuilder::new()
+            .bare(true)
+            .clone(tempdir.path().to_str().expect("utf-8 path"), bare_dir.path())
+            .expect("bare clone");
+
+        let scm = GitScm::open(bare_dir.path()).expect("open bare");
+        let archive = scm.make_archive("HEAD", false).expect("archive");
+        assert!(!archive.is_empty());
+
+        for error in [
+            scm.has_changes().map(|_| ()).expect_err("has_changes"),
+            scm.stage_all().expect_err("stage_all"),
+            scm.commit_snapshot("snapshot").map(|_| ()).expect_err("commit"),
+        ] {
+            assert!(
+                matches!(
+                    &error,
+                    SandboxError::Config(message) if message.starts_with("bare repository")
+                ),
+                "unexpected error: {error:?}"
+            );
+        }
+    }
+
     #[test]
     fn commit_snapshot_returns_none_when_clean() {
         let (_tempdir, repo) = init_repo();
@@ -857,6 +3075,266 @@ mod tests {
         assert_eq!(second_commit.parent_id(0).expect("parent"), first_oid);
     }
 
+    #[test]
+    fn diff_between_renders_snapshot_changes() {
+        let (_tempdir, repo) = init_repo();
+        let mut scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        let branch_name = scm.create_branch("feature").expect("create branch");
+        scm.set_snapshot_branch(branch_name.clone());
+
+        let staging_dir = TempDir::new().expect("staging dir");
+        fs::write(staging_dir.path().join("README.md"), "hello\nworld\n").expect("write file");
+        fs::write(staging_dir.path().join("new.txt"), "new\n").expect("write file");
+        scm.commit_snapshot_from_staging(staging_dir.path(), "snapshot", 1)
+            .expect("commit")
+            .expect("oid");
+
+        let diff = scm
+            .diff_between("HEAD", &branch_name, None)
+            .expect("diff")
+            .expect("branch exists");
+        assert!(diff.contains("+world"));
+        assert!(diff.contains("new.txt"));
+
+        let filtered = scm
+            .diff_between("HEAD", &branch_name, Some("new.txt"))
+            .expect("diff")
+            .expect("branch exists");
+        assert!(filtered.contains("+new"));
+        assert!(!filtered.contains("README.md"));
+    }
+
+    #[test]
+    fn diff_between_missing_reference_returns_none() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        let diff = scm
+            .diff_between("HEAD", "litterbox/missing", None)
+            .expect("diff");
+        assert!(diff.is_none());
+    }
+
+    #[test]
+    fn list_snapshots_returns_newest_first() {
+        let (_tempdir, repo) = init_repo();
+        let mut scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        let branch_name = scm.create_branch("feature").expect("create branch");
+        scm.set_snapshot_branch(branch_name);
+
+        let staging_dir = TempDir::new().expect("staging dir");
+        fs::write(staging_dir.path().join("a.txt"), "one").expect("write file");
+        scm.commit_snapshot_from_staging(staging_dir.path(), "first", 1)
+            .expect("commit")
+            .expect("oid");
+        fs::write(staging_dir.path().join("a.txt"), "two").expect("write file");
+        let second = scm
+            .commit_snapshot_from_staging(staging_dir.path(), "second", 1)
+            .expect("commit")
+            .expect("oid");
+
+        let snapshots = scm.list_snapshots("feature", None).expect("list snapshots");
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].oid, second.to_string());
+        assert_eq!(snapshots[0].message, "second");
+        assert_eq!(snapshots[1].message, "first");
+
+        let limited = scm.list_snapshots("feature", Some(1)).expect("list snapshots");
+        assert_eq!(limited.len(), 1);
+    }
+
+    fn commit_numbered_snapshots(scm: &mut GitScm, slug: &str, count: usize) {
+        let branch_name = scm.create_branch(slug).expect("create branch");
+        scm.set_snapshot_branch(branch_name);
+        let staging_dir = TempDir::new().expect("staging dir");
+        for number in 1..=count {
+            fs::write(staging_dir.path().join("a.txt"), number.to_string()).expect("write file");
+            scm.commit_snapshot_from_staging(staging_dir.path(), &format!("snapshot {number}"), 1)
+                .expect("commit")
+                .expect("oid");
+        }
+    }
+
+    #[test]
+    fn prune_snapshots_keeps_newest_count_with_full_tree() {
+        let (_tempdir, repo) = init_repo();
+        let mut scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        commit_numbered_snapshots(&mut scm, "feature", 4);
+        let tip_tree = scm
+            .repo
+            .find_commit(scm.branch_tip("feature").expect("tip"))
+            .expect("tip commit")
+            .tree_id();
+
+        let result = scm.prune_snapshots("feature", None, Some(2)).expect("prune");
+
+        assert_eq!(
+            result,
+            PruneResult {
+                removed: 2,
+                kept: 2,
+                squash: None,
+            }
+        );
+        let snapshots = scm.list_snapshots("feature", None).expect("list snapshots");
+        let messages: Vec<&str> = snapshots.iter().map(|s| s.message.as_str()).collect();
+        assert_eq!(messages, ["snapshot 4", "snapshot 3"]);
+        let tip = scm
+            .repo
+            .find_commit(scm.branch_tip("feature").expect("tip"))
+            .expect("tip commit");
+        assert_eq!(tip.tree_id(), tip_tree);
+        let head = scm.head_commit().expect("head").id();
+        assert_eq!(tip.parent(0).expect("parent").parent_id(0).expect("base"), head);
+    }
+
+    #[test]
+    fn prune_snapshots_stops_at_the_base_of_a_branch_from_another_ref() {
+        let (tempdir, repo) = init_repo();
+        let first = repo.head().expect("head").peel_to_commit().expect("first").id();
+        commit_to_head(&tempdir, &repo, "upstream.txt", "upstream");
+        let upstream = repo.head().expect("head").target().expect("oid");
+        repo.tag_lightweight("upstream", &repo.find_object(upstream, None).expect("object"), false)
+            .expect("tag");
+        repo.reset(&repo.find_object(first, None).expect("object"), git2::ResetType::Hard, None)
+            .expect("reset");
+        let mut scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        let branch_name = scm.create_branch_at("feature", "upstream").expect("create branch");
+        scm.set_snapshot_branch(branch_name);
+        let staging_dir = TempDir::new().expect("staging dir");
+        for number in 1..=2 {
+            fs::write(staging_dir.path().join("a.txt"), number.to_string()).expect("write file");
+            scm.commit_snapshot_from_staging(staging_dir.path(), &format!("snapshot {number}"), 1)
+                .expect("commit")
+                .expect("oid");
+        }
+
+        let result = scm.prune_snapshots("feature", None, Some(1)).expect("prune");
+
+        assert_eq!(
+            result,
+            PruneResult {
+                removed: 1,
+                kept: 1,
+                squash: None,
+            }
+        );
+        let tip = scm
+            .repo
+            .find_commit(scm.branch_tip("feature").expect("tip"))
+            .expect("tip commit");
+        assert_eq!(tip.summary(), Some("snapshot 2"));
+        assert_eq!(tip.parent_id(0).expect("base"), upstream);
+    }
+
+    #[test]
+    fn prune_snapshots_squashes_when_every_snapshot_is_too_old() {
+        let (_tempdir, repo) = init_repo();
+        let mut scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        commit_numbered_snapshots(&mut scm, "feature", 3);
+        let old_tip = scm.branch_tip("feature").expect("tip");
+
+        let result = scm
+            .prune_snapshots_before("feature", Some(i64::MAX), None)
+            .expect("prune");
+
+        assert_eq!(result.removed, 3);
+        assert_eq!(result.kept, 1);
+        let snapshots = scm.list_snapshots("feature", None).expect("list snapshots");
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(Some(&snapshots[0].oid), result.squash.as_ref());
+        assert_eq!(snapshots[0].message, "Squash 3 pruned snapshots");
+        let tip = scm
+            .repo
+            .find_commit(scm.branch_tip("feature").expect("tip"))
+            .expect("tip commit");
+        let old_tip = scm.repo.find_commit(old_tip).expect("old tip commit");
+        assert_eq!(tip.tree_id(), old_tip.tree_id());
+    }
+
+    #[test]
+    fn prune_snapshots_within_limits_leaves_branch_alone() {
+        let (_tempdir, repo) = init_repo();
+        let mut scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        commit_numbered_snapshots(&mut scm, "feature", 2);
+        let tip = scm.branch_tip("feature").expect("tip");
+
+        let result = scm.prune_snapshots("feature", Some(30), Some(5)).expect("prune");
+
+        assert_eq!(result.removed, 0);
+        assert_eq!(result.kept, 2);
+        assert_eq!(scm.branch_tip("feature").expect("tip"), tip);
+    }
+
+    #[test]
+    fn stash_and_pop_round_trip_working_directory_changes() {
+        let (tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+        fs::write(tempdir.path().join("README.md"), "edited").expect("write file");
+
+        scm.stash("agent work").expect("stash");
+        assert_eq!(
+            fs::read_to_string(tempdir.path().join("README.md")).expect("read"),
+            "hello"
+        );
+
+        scm.stash_pop().expect("stash pop");
+        assert_eq!(
+            fs::read_to_string(tempdir.path().join("README.md")).expect("read"),
+            "edited"
+        );
+        let error = scm.stash_pop().expect_err("stash is empty");
+        assert_eq!(error.error_code(), crate::domain::LitterboxErrorCode::NotFound);
+    }
+
+    #[test]
+    fn stash_clean_working_directory_returns_not_found() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        let error = scm.stash("nothing").expect_err("nothing to stash");
+        assert_eq!(error.error_code(), crate::domain::LitterboxErrorCode::NotFound);
+    }
+
+    #[test]
+    fn list_snapshots_missing_branch_returns_not_found() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: None,
+        };
+
+        let err = scm.list_snapshots("missing", None).expect_err("missing branch");
+        assert_eq!(err.to_string(), "Sandbox 'missing' not found.");
+    }
+
     #[test]
     fn commit_snapshot_from_staging_creates_commit_on_snapshot_branch() {
         let (_tempdir, repo) = init_repo();
@@ -869,7 +3347,7 @@ mod tests {
         fs::write(staging_dir.path().join("file.txt"), "content").expect("write file");
 
         let oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Test snapshot")
+            .commit_snapshot_from_staging(staging_dir.path(), "Test snapshot", 1)
             .expect("commit")
             .expect("oid");
 
@@ -907,7 +3385,7 @@ mod tests {
         fs::write(git_dir.join("config"), "fake git config").expect("write git config");
 
         let oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Test snapshot")
+            .commit_snapshot_from_staging(staging_dir.path(), "Test snapshot", 1)
             .expect("commit")
             .expect("oid");
 
@@ -942,7 +3420,7 @@ mod tests {
         fs::write(subdir.join("nested.txt"), "nested").expect("write nested");
 
         let oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Test snapshot")
+            .commit_snapshot_from_staging(staging_dir.path(), "Test snapshot", 1)
             .expect("commit")
             .expect("oid");
 
@@ -981,7 +3459,7 @@ mod tests {
         fs::write(&regular_path, "content").expect("write file");
 
         let oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Test snapshot")
+            .commit_snapshot_from_staging(staging_dir.path(), "Test snapshot", 1)
             .expect("commit")
             .expect("oid");
 
@@ -1008,13 +3486,13 @@ mod tests {
 
         // First commit
         let first_oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "First")
+            .commit_snapshot_from_staging(staging_dir.path(), "First", 1)
             .expect("commit")
             .expect("oid");
 
         // Second commit with same content - should return None
         let second = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Second")
+            .commit_snapshot_from_staging(staging_dir.path(), "Second", 1)
             .expect("commit");
 
         assert_eq!(second, None);
@@ -1027,6 +3505,98 @@ mod tests {
         assert_eq!(snapshot_ref.target().expect("target"), first_oid);
     }
 
+    #[test]
+    fn commit_snapshot_from_staging_records_reverts() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: Some("test-snapshot".to_string()),
+        };
+
+        let depth = DEFAULT_SNAPSHOT_DEDUP_DEPTH;
+        let staging_dir = TempDir::new().expect("staging dir");
+        fs::write(staging_dir.path().join("file.txt"), "first").expect("write file");
+        scm.commit_snapshot_from_staging(staging_dir.path(), "First", depth)
+            .expect("commit")
+            .expect("oid");
+        fs::write(staging_dir.path().join("file.txt"), "second").expect("write file");
+        let second_oid = scm
+            .commit_snapshot_from_staging(staging_dir.path(), "Second", depth)
+            .expect("commit")
+            .expect("oid");
+
+        // Back to the first tree from a different trigger, so the revert is committed.
+        fs::write(staging_dir.path().join("file.txt"), "first").expect("write file");
+        let reverted = scm
+            .commit_snapshot_from_staging(staging_dir.path(), "Third", depth)
+            .expect("commit")
+            .expect("oid");
+        let reverted = scm.repo.find_commit(reverted).expect("commit");
+        assert_eq!(reverted.parent_id(0).expect("parent"), second_oid);
+    }
+
+    #[test]
+    fn commit_snapshot_from_staging_dedups_rapid_repeats_of_one_trigger() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: Some("test-snapshot".to_string()),
+        };
+
+        let staging_dir = TempDir::new().expect("staging dir");
+        for contents in ["first", "second"] {
+            fs::write(staging_dir.path().join("file.txt"), contents).expect("write file");
+            scm.commit_snapshot_from_staging(staging_dir.path(), "Write", 1)
+                .expect("commit")
+                .expect("oid");
+        }
+
+        fs::write(staging_dir.path().join("file.txt"), "first").expect("write file");
+        let deduped = scm
+            .commit_snapshot_from_staging(staging_dir.path(), "Write", DEFAULT_SNAPSHOT_DEDUP_DEPTH)
+            .expect("commit");
+        assert!(deduped.is_none());
+        let tip_only = scm
+            .commit_snapshot_from_staging(staging_dir.path(), "Write", 1)
+            .expect("commit");
+        assert!(tip_only.is_some());
+    }
+
+    #[test]
+    fn commit_snapshot_paths_from_staging_keeps_other_paths() {
+        let (_tempdir, repo) = init_repo();
+        let scm = GitScm {
+            repo,
+            snapshot_branch: Some("test-snapshot".to_string()),
+        };
+
+        let staging_dir = TempDir::new().expect("staging dir");
+        fs::create_dir_all(staging_dir.path().join("src")).expect("create dir");
+        fs::write(staging_dir.path().join("src/lib.rs"), "fn main() {}").expect("write file");
+        fs::write(staging_dir.path().join("other.txt"), "unrelated").expect("write file");
+
+        let oid = scm
+            .commit_snapshot_paths_from_staging(
+                staging_dir.path(),
+                &["src", "README.md"],
+                "Paths",
+                1,
+            )
+            .expect("commit")
+            .expect("oid");
+        let tree = scm.repo.find_commit(oid).expect("commit").tree().expect("tree");
+        assert!(tree.get_path(Path::new("src/lib.rs")).is_ok());
+        // README.md is missing from staging, so it is deleted; other.txt was not touched.
+        assert!(tree.get_path(Path::new("README.md")).is_err());
+        assert!(tree.get_path(Path::new("other.txt")).is_err());
+        assert!(tree.get_path(Path::new(".gitignore")).is_ok());
+
+        let unchanged = scm
+            .commit_snapshot_paths_from_staging(staging_dir.path(), &["src"], "Again", 1)
+            .expect("commit");
+        assert_eq!(unchanged, None);
+    }
+
     #[test]
     fn commit_snapshot_from_staging_chains_commits() {
         let (_tempdir, repo) = init_repo();
@@ -1039,7 +3609,7 @@ mod tests {
         fs::write(staging_dir.path().join("file.txt"), "first").expect("write file");
 
         let first_oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "First")
+            .commit_snapshot_from_staging(staging_dir.path(), "First", 1)
             .expect("commit")
             .expect("oid");
 
@@ -1047,7 +3617,7 @@ mod tests {
         fs::write(staging_dir.path().join("file.txt"), "second").expect("write file");
 
         let second_oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Second")
+            .commit_snapshot_from_staging(staging_dir.path(), "Second", 1)
             .expect("commit")
             .expect("oid");
 
@@ -1078,7 +3648,7 @@ mod tests {
         fs::write(staging_dir.path().join("staged.txt"), "staged content").expect("write staged");
 
         let oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Snapshot")
+            .commit_snapshot_from_staging(staging_dir.path(), "Snapshot", 1)
             .expect("commit")
             .expect("oid");
 
@@ -1131,7 +3701,7 @@ mod tests {
             .expect("write snapshot");
 
         let oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Snapshot")
+            .commit_snapshot_from_staging(staging_dir.path(), "Snapshot", 1)
             .expect("commit")
             .expect("oid");
 
@@ -1163,7 +3733,7 @@ mod tests {
         let staging_dir = TempDir::new().expect("staging dir");
 
         let result = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Empty snapshot")
+            .commit_snapshot_from_staging(staging_dir.path(), "Empty snapshot", 1)
             .expect("commit");
 
         // Empty directory creates empty tree - should still create commit for first snapshot
@@ -1191,7 +3761,7 @@ mod tests {
         fs::write(subdir.join("nested.txt"), "nested content").expect("write nested");
 
         let oid = scm
-            .commit_snapshot_from_staging(&staging_dir, "Test snapshot")
+            .commit_snapshot_from_staging(&staging_dir, "Test snapshot", 1)
             .expect("commit")
             .expect("oid");
 
@@ -1230,7 +3800,7 @@ mod tests {
         fs::write(staging_dir.path().join("initial.txt"), "initial").expect("write initial");
 
         let initial_oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Initial")
+            .commit_snapshot_from_staging(staging_dir.path(), "Initial", 1)
             .expect("commit")
             .expect("oid");
 
@@ -1248,7 +3818,7 @@ mod tests {
         fs::write(staging_dir.path().join("second.txt"), "second").expect("write second");
 
         let second_oid = scm
-            .commit_snapshot_from_staging(staging_dir.path(), "Second")
+            .commit_snapshot_from_staging(staging_dir.path(), "Second", 1)
             .expect("commit")
             .expect("oid");
 
@@ -1261,4 +3831,54 @@ mod tests {
         assert_eq!(second_commit.parent_count(), 1);
         assert_eq!(second_commit.parent_id(0).expect("parent"), initial_oid);
     }
+
+    #[tokio::test]
+    async fn async_scm_runs_on_blocking_pool() {
+        let (tempdir, _repo) = init_repo();
+        let scm = ThreadSafeScm::open_with_prefix(tempdir.path(), Some("demo".to_string()))
+            .expect("open scm");
+
+        let branch = AsyncScm::create_branch(&scm, "async").await.expect("create branch");
+        assert_eq!(branch, "litterbox/async");
+        assert_eq!(
+            AsyncScm::list_sandboxes(&scm).await.expect("list"),
+            vec!["async".to_string()]
+        );
+        assert_eq!(AsyncScm::repo_prefix(&scm).await.expect("prefix"), "demo");
+
+        AsyncScm::delete_branch(&scm, "async").await.expect("delete branch");
+        assert!(Scm::list_sandboxes(&scm).expect("list").is_empty());
+    }
+
+    #[tokio::test]
+    async fn blocking_scm_falls_back_to_the_sync_trait() {
+        let (tempdir, _repo) = init_repo();
+        let scm = BlockingScm::new(GitScm::open(tempdir.path()).expect("open scm"));
+
+        let branch = scm.create_branch("inline").await.expect("create branch");
+        assert_eq!(branch, "litterbox/inline");
+        assert_eq!(scm.list_sandboxes().await.expect("list"), vec!["inline".to_string()]);
+        scm.delete_branch("inline").await.expect("delete branch");
+        assert!(scm.list_sandboxes().await.expect("list").is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_list_sandboxes_share_the_read_lock() {
+        let (tempdir, _repo) = init_repo();
+        let scm = Arc::new(ThreadSafeScm::open(tempdir.path()).expect("open scm"));
+        Scm::create_branch(scm.as_ref(), "one").expect("create one");
+        Scm::create_branch(scm.as_ref(), "two").expect("create two");
+
+        // A held read lock must not block other readers.
+        let _reader = scm.read().expect("read lock");
+        let tasks = (0..10).map(|_| {
+            let scm = Arc::clone(&scm);
+            tokio::spawn(async move { AsyncScm::list_sandboxes(scm.as_ref()).await })
+        });
+        for result in futures_util::future::join_all(tasks).await {
+            let mut sandboxes = result.expect("join").expect("list");
+            sandboxes.sort();
+            assert_eq!(sandboxes, vec!["one".to_string(), "two".to_string()]);
+        }
+    }
 }
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::task::JoinHandle;
+
+/// Seconds between snapshots of a watched sandbox unless the caller picks another interval.
+pub const DEFAULT_WATCH_INTERVAL_SECS: u64 = 30;
+
+/// Sandboxes snapshotted in the background by this server process, keyed by sandbox name.
+pub type Watches = Arc<Mutex<HashMap<String, WatchState>>>;
+
+/// A running watch of one sandbox. Dropping it stops the watch.
+pub struct WatchState {
+    pub interval_secs: u64,
+    task: JoinHandle<()>,
+}
+
+impl WatchState {
+    pub fn new(interval_secs: u64, task: JoinHandle<()>) -> Self {
+        Self {
+            interval_secs,
+            task,
+        }
+    }
+
+    /// Whether the watch has stopped on its own, such as after its sandbox was deleted.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for WatchState {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn dropping_watch_state_stops_the_task() {
+        let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(async move {
+            let _sender = sender;
+            loop {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+        });
+        let watches: Watches = Arc::default();
+        watches
+            .lock()
+            .expect("lock")
+            .insert("feature".to_string(), WatchState::new(DEFAULT_WATCH_INTERVAL_SECS, task));
+
+        watches.lock().expect("lock").remove("feature");
+
+        // The sender is dropped with the aborted task, closing the channel.
+        assert!(receiver.await.is_err());
+    }
+}